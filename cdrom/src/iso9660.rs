@@ -0,0 +1,158 @@
+//! Minimal ISO 9660 file system reader, only capable of reading files from the root directory of
+//! the data track
+
+use crate::cdtime::CdTime;
+use crate::cue::TrackMode;
+use crate::reader::CdRom;
+use crate::{BYTES_PER_SECTOR, CdRomResult};
+
+const USER_DATA_LEN: usize = 2048;
+
+const PRIMARY_VOLUME_DESCRIPTOR_SECTOR: u32 = 16;
+const ROOT_DIRECTORY_RECORD_OFFSET: usize = 156;
+
+/// Read a file from the root directory of the disc's file system. File names are compared
+/// case-insensitively and the `;1` version suffix is optional.
+///
+/// Returns `None` if track 1 does not contain an ISO 9660 file system or if the file does not
+/// exist.
+///
+/// # Errors
+///
+/// This function will propagate any error encountered while reading from the disc.
+pub fn read_root_file(disc: &mut CdRom, file_name: &str) -> CdRomResult<Option<Vec<u8>>> {
+    let mut reader = DataReader::new(disc);
+
+    let Some(pvd) = reader.read_sector(PRIMARY_VOLUME_DESCRIPTOR_SECTOR)? else {
+        return Ok(None);
+    };
+    if pvd[0] != 1 || &pvd[1..6] != b"CD001" {
+        return Ok(None);
+    }
+
+    let (root_lba, root_len) = extent_location(&pvd[ROOT_DIRECTORY_RECORD_OFFSET..]);
+    let Some(root_directory) = reader.read_extent(root_lba, root_len)? else { return Ok(None) };
+
+    let Some((file_lba, file_len)) = find_directory_entry(&root_directory, file_name) else {
+        return Ok(None);
+    };
+
+    reader.read_extent(file_lba, file_len)
+}
+
+fn extent_location(record: &[u8]) -> (u32, u32) {
+    let lba = u32::from_le_bytes(record[2..6].try_into().unwrap());
+    let len = u32::from_le_bytes(record[10..14].try_into().unwrap());
+    (lba, len)
+}
+
+fn find_directory_entry(directory: &[u8], file_name: &str) -> Option<(u32, u32)> {
+    for sector in directory.chunks(USER_DATA_LEN) {
+        let mut i = 0;
+        // Records never cross sector boundaries; a length of 0 pads out the rest of the sector
+        while i + 33 < sector.len() && sector[i] != 0 {
+            let record_len = sector[i] as usize;
+            let name_len = sector[i + 32] as usize;
+            let Some(record) = sector.get(i..i + record_len) else { break };
+            let Some(name) = record.get(33..33 + name_len) else { break };
+
+            let name = String::from_utf8_lossy(name);
+            let name = name.split(';').next().unwrap_or("");
+            if name.eq_ignore_ascii_case(file_name.split(';').next().unwrap_or("")) {
+                return Some(extent_location(record));
+            }
+
+            i += record_len;
+        }
+    }
+
+    None
+}
+
+struct DataReader<'a> {
+    disc: &'a mut CdRom,
+    user_data_offset: usize,
+    track_len_sectors: u32,
+    buffer: Box<[u8; BYTES_PER_SECTOR as usize]>,
+}
+
+impl<'a> DataReader<'a> {
+    fn new(disc: &'a mut CdRom) -> Self {
+        let track = disc.cue().track(1);
+        let user_data_offset = match track.mode {
+            TrackMode::Mode1 => 16,
+            // Sync + header + XA subheader
+            TrackMode::Mode2 | TrackMode::Audio => 24,
+        };
+        let track_len_sectors = (track.end_time - track.start_time).to_sector_number();
+
+        Self {
+            disc,
+            user_data_offset,
+            track_len_sectors,
+            buffer: Box::new([0; BYTES_PER_SECTOR as usize]),
+        }
+    }
+
+    // Returns the 2048 bytes of user data in the given sector, or None if the sector number is
+    // past the end of the track
+    fn read_sector(&mut self, lba: u32) -> CdRomResult<Option<&[u8]>> {
+        // Add 2 seconds for the pregap
+        let relative_sector = lba + CdTime::SECTOR_0_START.to_sector_number();
+        if relative_sector >= self.track_len_sectors {
+            return Ok(None);
+        }
+
+        self.disc.read_sector(
+            1,
+            CdTime::from_sector_number(relative_sector),
+            self.buffer.as_mut_slice(),
+        )?;
+
+        Ok(Some(&self.buffer[self.user_data_offset..self.user_data_offset + USER_DATA_LEN]))
+    }
+
+    fn read_extent(&mut self, lba: u32, len: u32) -> CdRomResult<Option<Vec<u8>>> {
+        let mut data = Vec::with_capacity(len as usize);
+        for sector in 0..len.div_ceil(USER_DATA_LEN as u32) {
+            let Some(sector_data) = self.read_sector(lba + sector)? else { return Ok(None) };
+            data.extend_from_slice(sector_data);
+        }
+        data.truncate(len as usize);
+
+        Ok(Some(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory_record(name: &str, lba: u32, len: u32) -> Vec<u8> {
+        let mut record = vec![0; 33];
+        record[2..6].copy_from_slice(&lba.to_le_bytes());
+        record[10..14].copy_from_slice(&len.to_le_bytes());
+        record[32] = name.len() as u8;
+        record.extend_from_slice(name.as_bytes());
+        if !record.len().is_multiple_of(2) {
+            record.push(0);
+        }
+        record[0] = record.len() as u8;
+        record
+    }
+
+    #[test]
+    fn find_entry_in_directory() {
+        let mut directory = Vec::new();
+        directory.extend(directory_record("\0", 22, 2048));
+        directory.extend(directory_record("\u{1}", 22, 2048));
+        directory.extend(directory_record("MAIN.EXE;1", 30, 100_000));
+        directory.resize(USER_DATA_LEN, 0);
+        directory.extend(directory_record("SYSTEM.CNF;1", 24, 68));
+        directory.resize(2 * USER_DATA_LEN, 0);
+
+        assert_eq!(find_directory_entry(&directory, "system.cnf"), Some((24, 68)));
+        assert_eq!(find_directory_entry(&directory, "MAIN.EXE;1"), Some((30, 100_000)));
+        assert_eq!(find_directory_entry(&directory, "MISSING.DAT"), None);
+    }
+}
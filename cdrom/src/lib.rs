@@ -1,5 +1,6 @@
 pub mod cdtime;
 pub mod cue;
+pub mod iso9660;
 pub mod reader;
 
 use std::io;
@@ -53,6 +54,16 @@ pub enum CdRomError {
     ChdHeaderParseError { metadata_value: String },
     #[error("CHD header contains an invalid CD-ROM track list: {track_numbers:?}")]
     ChdInvalidTrackList { track_numbers: Vec<u8> },
+    #[error("Error opening disc image file '{path}': {source}")]
+    ImageOpen {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Invalid or unsupported disc image: {0}")]
+    InvalidImage(String),
+    #[error("PBP file contains {count} disc(s); disc index {index} is out of range")]
+    PbpDiscIndex { index: usize, count: usize },
     #[error("I/O error reading from disc: {0}")]
     DiscReadIo(#[source] io::Error),
     #[error(
@@ -2,12 +2,14 @@
 
 mod chd;
 mod cuebin;
+mod image;
 mod seekvec;
 
 use crate::cdtime::CdTime;
 use crate::cue::{CueSheet, TrackMode, TrackType};
 use crate::reader::chd::ChdFile;
 use crate::reader::cuebin::CdBinFiles;
+use crate::reader::image::{DiscImage, ImageFormat};
 use crate::reader::seekvec::SeekableVec;
 use crate::{CdRomError, CdRomResult};
 use bincode::de::{BorrowDecoder, Decoder};
@@ -43,12 +45,17 @@ type CdBinMemoryFiles = CdBinFiles<SeekableVec>;
 type ChdFsFile = ChdFile<BufReader<File>>;
 type ChdMemoryFile = ChdFile<SeekableVec>;
 
+type DiscImageFsFile = DiscImage<File>;
+type DiscImageMemoryFile = DiscImage<SeekableVec>;
+
 #[derive(Debug)]
 enum CdRomReader {
     CueBin(CdBinFsFiles),
     CueBinMemory(CdBinMemoryFiles),
     ChdFs(ChdFsFile),
     ChdMemory(ChdMemoryFile),
+    ImageFs(DiscImageFsFile),
+    ImageMemory(DiscImageMemoryFile),
 }
 
 impl Default for CdRomReader {
@@ -77,6 +84,10 @@ impl CdRomReader {
             Self::ChdMemory(chd_file) => {
                 chd_file.read_sector(track_number, relative_sector_number, out)
             }
+            Self::ImageFs(image) => image.read_sector(track_number, relative_sector_number, out),
+            Self::ImageMemory(image) => {
+                image.read_sector(track_number, relative_sector_number, out)
+            }
         }
     }
 }
@@ -105,13 +116,26 @@ pub enum CdRomFileFormat {
     CueBin,
     // CHD files
     Chd,
+    // 2048-byte sector ISO file
+    Iso,
+    // Single BIN file without a CUE file
+    Bin,
+    // ECM-compressed BIN file
+    Ecm,
+    // PSP eboot PBP file; the disc index selects a disc from multi-disc files
+    Pbp { disc_index: usize },
 }
 
 impl CdRomFileFormat {
     pub fn from_file_path<P: AsRef<Path>>(path: P) -> Option<Self> {
-        match path.as_ref().extension().and_then(OsStr::to_str) {
-            Some("cue") => Some(Self::CueBin),
-            Some("chd") => Some(Self::Chd),
+        let extension = path.as_ref().extension().and_then(OsStr::to_str)?.to_ascii_lowercase();
+        match extension.as_str() {
+            "cue" => Some(Self::CueBin),
+            "chd" => Some(Self::Chd),
+            "iso" => Some(Self::Iso),
+            "bin" => Some(Self::Bin),
+            "ecm" => Some(Self::Ecm),
+            "pbp" => Some(Self::Pbp { disc_index: 0 }),
             _ => None,
         }
     }
@@ -149,6 +173,12 @@ impl CdRom {
         match format {
             CdRomFileFormat::CueBin => Self::open_cue_bin(path),
             CdRomFileFormat::Chd => Self::open_chd(path),
+            CdRomFileFormat::Iso => Self::open_image(path, ImageFormat::Iso),
+            CdRomFileFormat::Bin => Self::open_image(path, ImageFormat::Bin),
+            CdRomFileFormat::Ecm => Self::open_image(path, ImageFormat::Ecm),
+            CdRomFileFormat::Pbp { disc_index } => {
+                Self::open_image(path, ImageFormat::Pbp { disc_index })
+            }
         }
     }
 
@@ -170,6 +200,18 @@ impl CdRom {
         Ok(Self { cue_sheet, reader: CdRomReader::ChdFs(chd_file) })
     }
 
+    fn open_image<P: AsRef<Path>>(image_path: P, format: ImageFormat) -> CdRomResult<Self> {
+        let image_path = image_path.as_ref();
+
+        let file = File::open(image_path).map_err(|source| CdRomError::ImageOpen {
+            path: image_path.display().to_string(),
+            source,
+        })?;
+        let (image, cue_sheet) = DiscImage::open(file, format)?;
+
+        Ok(Self { cue_sheet, reader: CdRomReader::ImageFs(image) })
+    }
+
     /// Open a CD-ROM reader that will load the entire disc image into memory.
     ///
     /// # Errors
@@ -188,9 +230,25 @@ impl CdRom {
                 })?;
                 Self::open_chd_in_memory(chd_bytes)
             }
+            CdRomFileFormat::Iso => Self::open_image_in_memory(path, ImageFormat::Iso),
+            CdRomFileFormat::Bin => Self::open_image_in_memory(path, ImageFormat::Bin),
+            CdRomFileFormat::Ecm => Self::open_image_in_memory(path, ImageFormat::Ecm),
+            CdRomFileFormat::Pbp { disc_index } => {
+                Self::open_image_in_memory(path, ImageFormat::Pbp { disc_index })
+            }
         }
     }
 
+    fn open_image_in_memory(image_path: &Path, format: ImageFormat) -> CdRomResult<Self> {
+        let image_bytes = fs::read(image_path).map_err(|source| CdRomError::ImageOpen {
+            path: image_path.display().to_string(),
+            source,
+        })?;
+        let (image, cue_sheet) = DiscImage::open(SeekableVec::new(image_bytes), format)?;
+
+        Ok(Self { cue_sheet, reader: CdRomReader::ImageMemory(image) })
+    }
+
     /// Open a CD-ROM reader that will read from CUE/BIN files that will be read into memory.
     ///
     /// # Errors
@@ -218,6 +276,22 @@ impl CdRom {
         Ok(Self { cue_sheet, reader: CdRomReader::ChdMemory(chd_file) })
     }
 
+    /// Return the number of discs in a PSP eboot PBP file.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if the file cannot be read or is not a PBP file containing
+    /// unencrypted PS1 disc images.
+    pub fn pbp_disc_count<P: AsRef<Path>>(pbp_path: P) -> CdRomResult<usize> {
+        let pbp_path = pbp_path.as_ref();
+
+        let file = File::open(pbp_path).map_err(|source| CdRomError::ImageOpen {
+            path: pbp_path.display().to_string(),
+            source,
+        })?;
+        image::pbp_disc_count(file)
+    }
+
     #[must_use]
     pub fn cue(&self) -> &CueSheet {
         &self.cue_sheet
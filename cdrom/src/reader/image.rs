@@ -0,0 +1,451 @@
+//! Code for reading single-file disc images that do not come with a CUE file: 2048-byte ISO,
+//! CUE-less BIN, ECM-compressed BIN, and PSP eboot PBP
+//!
+//! Each format exposes the disc as a sequence of raw 2352-byte sectors, reconstructing any parts
+//! of a sector that the format leaves out. Sector 0 of every image is the start of track 1, i.e.
+//! absolute time 00:02:00.
+//!
+//! PBP files include a TOC. For the other formats, the track layout is determined by scanning the
+//! image: the data track runs until the first sector without a sync pattern, and everything after
+//! that is treated as a single audio track with a 2-second pregap. A BIN with multiple audio tracks
+//! will play back correctly when seeking by time, but its audio tracks cannot be distinguished.
+
+mod bin;
+mod ecm;
+mod iso;
+mod pbp;
+mod sector;
+
+use crate::cdtime::CdTime;
+use crate::cue::{CueSheet, Track, TrackMode, TrackType};
+use crate::reader::image::bin::BinFile;
+use crate::reader::image::ecm::EcmFile;
+use crate::reader::image::iso::IsoFile;
+use crate::reader::image::pbp::PbpFile;
+use crate::{CdRomError, CdRomResult, cue};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+pub use pbp::disc_count as pbp_disc_count;
+
+const BYTES_PER_SECTOR: usize = crate::BYTES_PER_SECTOR as usize;
+
+// Length of the pregap between the data track and the first audio track when there is no TOC
+const AUDIO_PREGAP_SECTORS: u32 = 150;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Iso,
+    Bin,
+    Ecm,
+    Pbp { disc_index: usize },
+}
+
+/// A file that is read at arbitrary offsets, tracking the current position so that sequential
+/// reads don't seek (and discard the read buffer)
+#[derive(Debug)]
+struct PositionedReader<F: Read + Seek> {
+    file: BufReader<F>,
+    position: u64,
+}
+
+impl<F: Read + Seek> PositionedReader<F> {
+    fn new(file: F) -> Self {
+        Self { file: BufReader::new(file), position: 0 }
+    }
+
+    fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> CdRomResult<()> {
+        if self.position != offset {
+            self.file.seek(SeekFrom::Start(offset)).map_err(CdRomError::DiscReadIo)?;
+        }
+
+        self.file.read_exact(buf).map_err(CdRomError::DiscReadIo)?;
+        self.position = offset + buf.len() as u64;
+
+        Ok(())
+    }
+
+    fn len(&mut self) -> CdRomResult<u64> {
+        let len = self.file.seek(SeekFrom::End(0)).map_err(CdRomError::DiscReadIo)?;
+        self.position = len;
+        Ok(len)
+    }
+}
+
+#[derive(Debug)]
+enum ImageFile<F: Read + Seek> {
+    Iso(IsoFile<F>),
+    Bin(BinFile<F>),
+    Ecm(EcmFile<F>),
+    Pbp(PbpFile<F>),
+}
+
+impl<F: Read + Seek> ImageFile<F> {
+    fn len_sectors(&self) -> u32 {
+        match self {
+            Self::Iso(iso) => iso.len_sectors(),
+            Self::Bin(bin) => bin.len_sectors(),
+            Self::Ecm(ecm) => ecm.len_sectors(),
+            Self::Pbp(pbp) => pbp.len_sectors(),
+        }
+    }
+
+    fn read_raw_sector(&mut self, sector_number: u32, out: &mut [u8]) -> CdRomResult<()> {
+        match self {
+            Self::Iso(iso) => iso.read_raw_sector(sector_number, out),
+            Self::Bin(bin) => bin.read_raw_sector(sector_number, out),
+            Self::Ecm(ecm) => ecm.read_raw_sector(sector_number, out),
+            Self::Pbp(pbp) => pbp.read_raw_sector(sector_number, out),
+        }
+    }
+}
+
+/// A track as laid out in an image, in image sectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ImageTrack {
+    number: u8,
+    track_type: TrackType,
+    // INDEX 00, if the pause before the track is present in the image
+    pause_start: Option<u32>,
+    // INDEX 01
+    start: u32,
+}
+
+#[derive(Debug)]
+pub struct DiscImage<F: Read + Seek> {
+    image: ImageFile<F>,
+    track_start_sectors: Vec<u32>,
+}
+
+impl<F: Read + Seek> DiscImage<F> {
+    pub fn open(file: F, format: ImageFormat) -> CdRomResult<(Self, CueSheet)> {
+        let mut image = match format {
+            ImageFormat::Iso => {
+                let mut reader = PositionedReader::new(file);
+                if iso::is_raw_image(&mut reader)? {
+                    log::info!("ISO file contains raw 2352-byte sectors; reading it as a BIN file");
+                    ImageFile::Bin(BinFile::open(reader)?)
+                } else {
+                    ImageFile::Iso(IsoFile::open(reader)?)
+                }
+            }
+            ImageFormat::Bin => ImageFile::Bin(BinFile::open(PositionedReader::new(file))?),
+            ImageFormat::Ecm => ImageFile::Ecm(EcmFile::open(PositionedReader::new(file))?),
+            ImageFormat::Pbp { disc_index } => {
+                ImageFile::Pbp(PbpFile::open(PositionedReader::new(file), disc_index)?)
+            }
+        };
+
+        let len_sectors = image.len_sectors();
+        if len_sectors == 0 {
+            return Err(CdRomError::InvalidImage("Disc image contains no sectors".into()));
+        }
+
+        // Leave room for the pregap and postgaps that are added to the track list
+        if len_sectors >= CdTime::MAX_SECTORS - 3 * 150 {
+            return Err(CdRomError::InvalidImage(format!(
+                "Disc image is too large: {len_sectors} sectors"
+            )));
+        }
+
+        let image_tracks = match &image {
+            ImageFile::Pbp(pbp) => pbp.tracks().to_vec(),
+            _ => scan_tracks(&mut image)?,
+        };
+
+        let (cue_sheet, track_start_sectors) = to_cue_sheet(&mut image, &image_tracks)?;
+
+        Ok((Self { image, track_start_sectors }, cue_sheet))
+    }
+
+    pub fn read_sector(
+        &mut self,
+        track_number: u8,
+        relative_sector_number: u32,
+        out: &mut [u8],
+    ) -> CdRomResult<()> {
+        let sector_number =
+            self.track_start_sectors[(track_number - 1) as usize] + relative_sector_number;
+        self.image.read_raw_sector(sector_number, &mut out[..BYTES_PER_SECTOR])
+    }
+}
+
+fn has_sync_pattern<F: Read + Seek>(
+    image: &mut ImageFile<F>,
+    sector_number: u32,
+    buffer: &mut [u8; BYTES_PER_SECTOR],
+) -> CdRomResult<bool> {
+    image.read_raw_sector(sector_number, buffer)?;
+    Ok(buffer[..sector::SYNC_PATTERN.len()] == sector::SYNC_PATTERN)
+}
+
+fn scan_tracks<F: Read + Seek>(image: &mut ImageFile<F>) -> CdRomResult<Vec<ImageTrack>> {
+    let len_sectors = image.len_sectors();
+    let mut buffer = [0; BYTES_PER_SECTOR];
+
+    if !has_sync_pattern(image, 0, &mut buffer)? {
+        // Audio CD
+        return Ok(vec![ImageTrack {
+            number: 1,
+            track_type: TrackType::Audio,
+            pause_start: None,
+            start: 0,
+        }]);
+    }
+
+    // Data sectors are contiguous from the start of the image, so binary search for the end of the
+    // data track rather than reading every sector
+    let mut low = 1;
+    let mut high = len_sectors;
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if has_sync_pattern(image, mid, &mut buffer)? {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    let data_end = low;
+
+    let mut tracks =
+        vec![ImageTrack { number: 1, track_type: TrackType::Data, pause_start: None, start: 0 }];
+    if data_end < len_sectors {
+        let audio_track = if len_sectors - data_end > AUDIO_PREGAP_SECTORS {
+            ImageTrack {
+                number: 2,
+                track_type: TrackType::Audio,
+                pause_start: Some(data_end),
+                start: data_end + AUDIO_PREGAP_SECTORS,
+            }
+        } else {
+            ImageTrack {
+                number: 2,
+                track_type: TrackType::Audio,
+                pause_start: None,
+                start: data_end,
+            }
+        };
+        tracks.push(audio_track);
+    }
+
+    log::debug!("Scanned {len_sectors}-sector disc image, data track ends at sector {data_end}");
+
+    Ok(tracks)
+}
+
+fn data_track_mode<F: Read + Seek>(
+    image: &mut ImageFile<F>,
+    sector_number: u32,
+) -> CdRomResult<TrackMode> {
+    let mut buffer = [0; BYTES_PER_SECTOR];
+    image.read_raw_sector(sector_number, &mut buffer)?;
+
+    Ok(match buffer[15] {
+        1 => TrackMode::Mode1,
+        _ => TrackMode::Mode2,
+    })
+}
+
+fn to_cue_sheet<F: Read + Seek>(
+    image: &mut ImageFile<F>,
+    image_tracks: &[ImageTrack],
+) -> CdRomResult<(CueSheet, Vec<u32>)> {
+    let len_sectors = image.len_sectors();
+
+    let mut absolute_start_time = CdTime::ZERO;
+    let mut tracks = Vec::with_capacity(image_tracks.len());
+    let mut track_start_sectors = Vec::with_capacity(image_tracks.len());
+
+    for (i, image_track) in image_tracks.iter().enumerate() {
+        let mode = match image_track.track_type {
+            TrackType::Data => data_track_mode(image, image_track.start)?,
+            TrackType::Audio => TrackMode::Audio,
+        };
+
+        let pregap_len = match image_track.track_type {
+            // Data tracks always have a 2-second pregap
+            TrackType::Data => CdTime::new(0, 2, 0),
+            TrackType::Audio => CdTime::ZERO,
+        };
+        let pause_start = image_track.pause_start.unwrap_or(image_track.start);
+        let pause_len = CdTime::from_sector_number(image_track.start - pause_start);
+
+        let data_end = image_tracks
+            .get(i + 1)
+            .map_or(len_sectors, |next| next.pause_start.unwrap_or(next.start));
+        let postgap_len = image_track.track_type.default_postgap_len();
+
+        let padded_track_len = pregap_len
+            + pause_len
+            + CdTime::from_sector_number(data_end - image_track.start)
+            + postgap_len;
+        tracks.push(Track {
+            number: image_track.number,
+            mode,
+            track_type: image_track.track_type,
+            start_time: absolute_start_time,
+            end_time: absolute_start_time + padded_track_len,
+            pregap_len,
+            pause_len,
+            postgap_len,
+        });
+        track_start_sectors.push(pause_start);
+
+        absolute_start_time += padded_track_len;
+    }
+
+    cue::finalize_track_list(&mut tracks);
+
+    log::trace!("Disc image track list:\n{tracks:#?}");
+
+    assert!(
+        cue::tracks_are_continuous(&tracks),
+        "Tracks in disc image are not continuous; this is a bug"
+    );
+
+    Ok((CueSheet::new(tracks), track_start_sectors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::seekvec::SeekableVec;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    const DATA_SECTORS: u32 = 40;
+    const AUDIO_SECTORS: u32 = 200;
+    const BLOCK_DATA_OFFSET: usize = 0x100000;
+
+    fn open(bytes: Vec<u8>, format: ImageFormat) -> (DiscImage<SeekableVec>, CueSheet) {
+        DiscImage::open(SeekableVec::new(bytes), format).unwrap()
+    }
+
+    fn read_all(image: &mut DiscImage<SeekableVec>, cue_sheet: &CueSheet) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut buffer = [0; BYTES_PER_SECTOR];
+        for track_number in 1..=cue_sheet.last_track().number {
+            let track = cue_sheet.track(track_number);
+            let len = (track.end_time - track.start_time - track.pregap_len - track.postgap_len)
+                .to_sector_number();
+            for sector_number in 0..len {
+                image.read_sector(track_number, sector_number, &mut buffer).unwrap();
+                bytes.extend_from_slice(&buffer);
+            }
+        }
+        bytes
+    }
+
+    fn ecm_record_header(record_type: u8, stored_count: u32, out: &mut Vec<u8>) {
+        let mut byte = record_type | (((stored_count & 0x1F) as u8) << 2);
+        let mut remaining = stored_count >> 5;
+        while remaining != 0 {
+            out.push(byte | 0x80);
+            byte = (remaining & 0x7F) as u8;
+            remaining >>= 7;
+        }
+        out.push(byte);
+    }
+
+    fn bcd_time(sector_number: u32) -> [u8; 3] {
+        let time = CdTime::from_sector_number(sector_number);
+        [time.minutes, time.seconds, time.frames].map(crate::reader::time_component_to_bcd)
+    }
+
+    #[test]
+    fn image_formats_round_trip() {
+        let iso: Vec<u8> = (0..DATA_SECTORS * 2048).map(|i| (i * 13 + i / 2048) as u8).collect();
+        let (mut iso_image, iso_cue) = open(iso, ImageFormat::Iso);
+        assert_eq!(iso_cue.last_track().number, 1);
+        assert_eq!(iso_cue.track(1).mode, TrackMode::Mode2);
+
+        // Append audio to the synthesized sectors to create a BIN with data and audio tracks
+        let mut bin = read_all(&mut iso_image, &iso_cue);
+        assert_eq!(bin.len(), DATA_SECTORS as usize * BYTES_PER_SECTOR);
+        bin.extend((0..AUDIO_SECTORS as usize * BYTES_PER_SECTOR).map(|i| (i % 251) as u8 | 1));
+
+        let (mut bin_image, bin_cue) = open(bin.clone(), ImageFormat::Bin);
+        assert_eq!(bin_cue.last_track().number, 2);
+        assert_eq!(bin_cue.track(2).track_type, TrackType::Audio);
+        assert_eq!(bin_cue.track(2).pause_len, CdTime::from_sector_number(AUDIO_PREGAP_SECTORS));
+        assert_eq!(read_all(&mut bin_image, &bin_cue), bin);
+
+        // ECM: sync/header as raw bytes followed by a Mode 2 Form 1 record for each data sector,
+        // then the audio as a single raw record
+        let mut ecm = b"ECM\0".to_vec();
+        for sector in bin[..DATA_SECTORS as usize * BYTES_PER_SECTOR].chunks_exact(BYTES_PER_SECTOR)
+        {
+            ecm_record_header(0, 16 - 1, &mut ecm);
+            ecm.extend_from_slice(&sector[..16]);
+            ecm_record_header(2, 0, &mut ecm);
+            ecm.extend_from_slice(&sector[0x14..0x818]);
+        }
+        let audio = &bin[DATA_SECTORS as usize * BYTES_PER_SECTOR..];
+        ecm_record_header(0, audio.len() as u32 - 1, &mut ecm);
+        ecm.extend_from_slice(audio);
+        ecm_record_header(0, 0xFFFFFFFF, &mut ecm);
+
+        let (mut ecm_image, ecm_cue) = open(ecm, ImageFormat::Ecm);
+        assert_eq!(format!("{ecm_cue:?}"), format!("{bin_cue:?}"));
+        assert_eq!(read_all(&mut ecm_image, &ecm_cue), bin);
+
+        // PBP with a single disc; the first block is compressed and the rest are stored
+        let psar_offset = 0x28;
+        let mut pbp = vec![0; psar_offset];
+        pbp[..4].copy_from_slice(b"\0PBP");
+        pbp[0x24..0x28].copy_from_slice(&(psar_offset as u32).to_le_bytes());
+
+        let mut disc = vec![0; BLOCK_DATA_OFFSET];
+        disc[..12].copy_from_slice(b"PSISOIMG0000");
+        let data_end = DATA_SECTORS + 150;
+        let toc: [(u8, u8, [u8; 3], [u8; 3]); 5] = [
+            (0x41, 0xA0, [0; 3], [0x01, 0x20, 0x00]),
+            (0x01, 0xA1, [0; 3], [0x02, 0x00, 0x00]),
+            (0x01, 0xA2, [0; 3], bcd_time(bin.len() as u32 / BYTES_PER_SECTOR as u32 + 150)),
+            (0x41, 0x01, [0; 3], bcd_time(150)),
+            (0x01, 0x02, bcd_time(data_end), bcd_time(data_end + AUDIO_PREGAP_SECTORS)),
+        ];
+        for (i, (control, point, index_0, index_1)) in toc.into_iter().enumerate() {
+            let entry = &mut disc[0x800 + 10 * i..0x800 + 10 * (i + 1)];
+            entry[0] = control;
+            entry[2] = point;
+            entry[3..6].copy_from_slice(&index_0);
+            entry[7..10].copy_from_slice(&index_1);
+        }
+
+        for (i, block) in bin.chunks(16 * BYTES_PER_SECTOR).enumerate() {
+            let stored = if i == 0 {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(block).unwrap();
+                encoder.finish().unwrap()
+            } else {
+                block.to_vec()
+            };
+
+            let offset = (disc.len() - BLOCK_DATA_OFFSET) as u32;
+            let entry = &mut disc[0x4000 + 32 * i..0x4000 + 32 * (i + 1)];
+            entry[..4].copy_from_slice(&offset.to_le_bytes());
+            entry[4..6].copy_from_slice(&(stored.len() as u16).to_le_bytes());
+            disc.extend_from_slice(&stored);
+        }
+        pbp.extend_from_slice(&disc);
+
+        assert_eq!(pbp::disc_count(SeekableVec::new(pbp.clone())).unwrap(), 1);
+        assert!(matches!(
+            DiscImage::open(SeekableVec::new(pbp.clone()), ImageFormat::Pbp { disc_index: 1 }),
+            Err(CdRomError::PbpDiscIndex { index: 1, count: 1 })
+        ));
+
+        // A TOC claiming the last track is 00 must be rejected rather than panicking
+        let mut zero_tracks = pbp.clone();
+        zero_tracks[psar_offset + 0x800 + 10 + 7] = 0x00;
+        assert!(matches!(
+            DiscImage::open(SeekableVec::new(zero_tracks), ImageFormat::Pbp { disc_index: 0 }),
+            Err(CdRomError::InvalidImage(_))
+        ));
+
+        let (mut pbp_image, pbp_cue) = open(pbp, ImageFormat::Pbp { disc_index: 0 });
+        assert_eq!(format!("{pbp_cue:?}"), format!("{bin_cue:?}"));
+        assert_eq!(read_all(&mut pbp_image, &pbp_cue), bin);
+    }
+}
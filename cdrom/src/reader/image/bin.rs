@@ -0,0 +1,28 @@
+//! CUE-less BIN files, which contain raw 2352-byte sectors
+
+use crate::CdRomResult;
+use crate::reader::image::{BYTES_PER_SECTOR, PositionedReader};
+use std::io::{Read, Seek};
+
+#[derive(Debug)]
+pub struct BinFile<F: Read + Seek> {
+    reader: PositionedReader<F>,
+    len_sectors: u32,
+}
+
+impl<F: Read + Seek> BinFile<F> {
+    pub(super) fn open(mut reader: PositionedReader<F>) -> CdRomResult<Self> {
+        let len_sectors = (reader.len()? / BYTES_PER_SECTOR as u64) as u32;
+
+        Ok(Self { reader, len_sectors })
+    }
+
+    pub fn len_sectors(&self) -> u32 {
+        self.len_sectors
+    }
+
+    pub fn read_raw_sector(&mut self, sector_number: u32, out: &mut [u8]) -> CdRomResult<()> {
+        let offset = u64::from(sector_number) * BYTES_PER_SECTOR as u64;
+        self.reader.read_exact_at(offset, &mut out[..BYTES_PER_SECTOR])
+    }
+}
@@ -0,0 +1,253 @@
+//! ECM files, which are BIN files with EDC/ECC data stripped out
+//!
+//! An ECM file is the magic bytes `ECM\0` followed by a sequence of records. Each record begins
+//! with a variable-length header that packs a record type and a count:
+//! - Byte 0 bits 0-1: Record type
+//! - Byte 0 bits 2-6: Count bits 0-4
+//! - Each following byte (while bit 7 of the previous byte is set) supplies 7 more count bits
+//!
+//! The stored count is one less than the actual count, and a stored count of `0xFFFFFFFF` marks
+//! the end of the file. Record types:
+//! - 0: `count` bytes copied as-is
+//! - 1: `count` Mode 1 sectors, each stored as 3 address bytes and 2048 bytes of user data
+//! - 2: `count` Mode 2 Form 1 sectors without sync/header, each stored as 4 subheader bytes and
+//!   2048 bytes of user data
+//! - 3: `count` Mode 2 Form 2 sectors without sync/header, each stored as 4 subheader bytes and
+//!   2324 bytes of user data
+//!
+//! To support random access, the file is scanned once on open to build an index of where each
+//! record's output begins.
+
+use crate::reader::image::{BYTES_PER_SECTOR, PositionedReader, sector};
+use crate::{CdRomError, CdRomResult};
+use std::io::{self, Read, Seek};
+
+const MAGIC: &[u8; 4] = b"ECM\0";
+
+// Mode 2 records output sectors without the 12-byte sync and 4-byte header
+const MODE_2_OUTPUT_OFFSET: usize = 16;
+const MODE_2_OUTPUT_LEN: usize = BYTES_PER_SECTOR - MODE_2_OUTPUT_OFFSET;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecordType {
+    Raw,
+    Mode1,
+    Mode2Form1,
+    Mode2Form2,
+}
+
+impl RecordType {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 3 {
+            0 => Self::Raw,
+            1 => Self::Mode1,
+            2 => Self::Mode2Form1,
+            3 => Self::Mode2Form2,
+            _ => unreachable!("value & 3 is always <= 3"),
+        }
+    }
+
+    fn input_unit_len(self) -> u64 {
+        match self {
+            Self::Raw => 1,
+            Self::Mode1 => 3 + 2048,
+            Self::Mode2Form1 => 4 + 2048,
+            Self::Mode2Form2 => 4 + 2324,
+        }
+    }
+
+    fn output_unit_len(self) -> u64 {
+        match self {
+            Self::Raw => 1,
+            Self::Mode1 => BYTES_PER_SECTOR as u64,
+            Self::Mode2Form1 | Self::Mode2Form2 => MODE_2_OUTPUT_LEN as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    record_type: RecordType,
+    count: u32,
+    input_offset: u64,
+    output_offset: u64,
+}
+
+impl Record {
+    fn output_len(&self) -> u64 {
+        u64::from(self.count) * self.record_type.output_unit_len()
+    }
+}
+
+#[derive(Debug)]
+pub struct EcmFile<F: Read + Seek> {
+    reader: PositionedReader<F>,
+    records: Vec<Record>,
+    output_len: u64,
+    sector_buffer: Box<[u8; BYTES_PER_SECTOR]>,
+}
+
+impl<F: Read + Seek> EcmFile<F> {
+    pub(super) fn open(mut reader: PositionedReader<F>) -> CdRomResult<Self> {
+        let file_len = reader.len()?;
+
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact_at(0, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(CdRomError::InvalidImage("ECM file is missing magic bytes".into()));
+        }
+
+        let mut records = Vec::new();
+        let mut input_offset = MAGIC.len() as u64;
+        let mut output_offset = 0;
+        loop {
+            let (record_type, count, header_len) = read_record_header(&mut reader, input_offset)?;
+            input_offset += header_len;
+
+            let Some(count) = count else { break };
+
+            let record = Record { record_type, count, input_offset, output_offset };
+            input_offset += u64::from(count) * record_type.input_unit_len();
+            output_offset += record.output_len();
+
+            if input_offset > file_len {
+                return Err(CdRomError::InvalidImage("ECM file is truncated".into()));
+            }
+
+            records.push(record);
+        }
+
+        log::debug!("Indexed {} ECM records, decoded length {output_offset} bytes", records.len());
+
+        Ok(Self {
+            reader,
+            records,
+            output_len: output_offset,
+            sector_buffer: Box::new([0; BYTES_PER_SECTOR]),
+        })
+    }
+
+    pub fn len_sectors(&self) -> u32 {
+        (self.output_len / BYTES_PER_SECTOR as u64) as u32
+    }
+
+    pub fn read_raw_sector(&mut self, sector_number: u32, out: &mut [u8]) -> CdRomResult<()> {
+        let start = u64::from(sector_number) * BYTES_PER_SECTOR as u64;
+        if start + BYTES_PER_SECTOR as u64 > self.output_len {
+            return Err(CdRomError::DiscReadIo(io::ErrorKind::UnexpectedEof.into()));
+        }
+
+        // Records do not necessarily line up with sector boundaries, so copy from as many records
+        // as necessary to fill the output sector
+        let mut filled = 0;
+        while filled < BYTES_PER_SECTOR {
+            let output_offset = start + filled as u64;
+            let record_idx =
+                self.records.partition_point(|record| record.output_offset <= output_offset) - 1;
+            let record = self.records[record_idx];
+
+            let unit_len = record.record_type.output_unit_len();
+            let offset_in_record = output_offset - record.output_offset;
+            let unit = offset_in_record / unit_len;
+            let offset_in_unit = (offset_in_record % unit_len) as usize;
+
+            let len = match record.record_type {
+                RecordType::Raw => {
+                    let len = (BYTES_PER_SECTOR - filled)
+                        .min((record.output_len() - offset_in_record) as usize);
+                    self.reader.read_exact_at(
+                        record.input_offset + offset_in_record,
+                        &mut out[filled..filled + len],
+                    )?;
+                    len
+                }
+                _ => {
+                    let decoded = self.decode_unit(&record, unit)?;
+                    let len = (BYTES_PER_SECTOR - filled).min(decoded.len() - offset_in_unit);
+                    out[filled..filled + len]
+                        .copy_from_slice(&decoded[offset_in_unit..offset_in_unit + len]);
+                    len
+                }
+            };
+            filled += len;
+        }
+
+        Ok(())
+    }
+
+    // Reconstruct one sector from a Mode 1 or Mode 2 record, returning the bytes that the record
+    // outputs for it
+    fn decode_unit(&mut self, record: &Record, unit: u64) -> CdRomResult<&[u8]> {
+        let input_offset = record.input_offset + unit * record.record_type.input_unit_len();
+        let buffer = self.sector_buffer.as_mut_slice();
+
+        match record.record_type {
+            RecordType::Mode1 => {
+                buffer[..sector::SYNC_PATTERN.len()].copy_from_slice(&sector::SYNC_PATTERN);
+                self.reader.read_exact_at(input_offset, &mut buffer[12..15])?;
+                buffer[15] = 1;
+                self.reader.read_exact_at(input_offset + 3, &mut buffer[16..16 + 2048])?;
+                sector::generate_mode_1(buffer);
+
+                Ok(buffer)
+            }
+            RecordType::Mode2Form1 | RecordType::Mode2Form2 => {
+                let data_len = match record.record_type {
+                    RecordType::Mode2Form1 => 2048,
+                    _ => 2324,
+                };
+                self.reader.read_exact_at(input_offset, &mut buffer[0x14..0x18 + data_len])?;
+
+                // The subheader is stored once but appears twice in the sector
+                buffer.copy_within(0x14..0x18, 0x10);
+
+                if record.record_type == RecordType::Mode2Form1 {
+                    sector::generate_mode_2_form_1(buffer);
+                } else {
+                    sector::generate_mode_2_form_2(buffer);
+                }
+
+                Ok(&buffer[MODE_2_OUTPUT_OFFSET..])
+            }
+            RecordType::Raw => unreachable!("raw records are copied directly"),
+        }
+    }
+}
+
+// Returns the record type, the record count (None for the end-of-file marker), and the length of
+// the header in bytes
+fn read_record_header<F: Read + Seek>(
+    reader: &mut PositionedReader<F>,
+    offset: u64,
+) -> CdRomResult<(RecordType, Option<u32>, u64)> {
+    let mut byte = [0];
+    reader.read_exact_at(offset, &mut byte)?;
+    let mut header_len = 1;
+
+    let record_type = RecordType::from_bits(byte[0]);
+    let mut count = u64::from((byte[0] >> 2) & 0x1F);
+    let mut bits = 5;
+    while byte[0] & 0x80 != 0 {
+        if bits > 31 {
+            return Err(CdRomError::InvalidImage(format!(
+                "Invalid ECM record header at offset {offset}"
+            )));
+        }
+
+        reader.read_exact_at(offset + header_len, &mut byte)?;
+        header_len += 1;
+
+        count |= u64::from(byte[0] & 0x7F) << bits;
+        bits += 7;
+    }
+
+    if count == 0xFFFFFFFF {
+        return Ok((record_type, None, header_len));
+    }
+
+    let count = u32::try_from(count + 1).map_err(|_| {
+        CdRomError::InvalidImage(format!("Invalid ECM record count at offset {offset}"))
+    })?;
+
+    Ok((record_type, Some(count), header_len))
+}
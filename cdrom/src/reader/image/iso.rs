@@ -0,0 +1,62 @@
+//! ISO files, which contain only the 2048 bytes of user data from each sector
+//!
+//! Sectors are reconstructed as Mode 2 Form 1 data sectors, which is what PS1 discs use. Any Form 2
+//! sectors (e.g. XA audio or video streams) were lost when the ISO was created and cannot be
+//! recovered.
+
+use crate::CdRomResult;
+use crate::cdtime::CdTime;
+use crate::reader::image::{PositionedReader, sector};
+use std::io::{Read, Seek};
+
+const BYTES_PER_ISO_SECTOR: usize = 2048;
+
+const USER_DATA_OFFSET: usize = 24;
+
+// File 0, channel 0, submode Data, coding info 0; repeated twice
+const DATA_SUBHEADER: [u8; 8] = [0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x08, 0x00];
+
+/// Returns whether a file with an ISO extension actually contains raw 2352-byte sectors.
+pub fn is_raw_image<F: Read + Seek>(reader: &mut PositionedReader<F>) -> CdRomResult<bool> {
+    if reader.len()? < sector::SYNC_PATTERN.len() as u64 {
+        return Ok(false);
+    }
+
+    let mut sync = [0; sector::SYNC_PATTERN.len()];
+    reader.read_exact_at(0, &mut sync)?;
+
+    Ok(sync == sector::SYNC_PATTERN)
+}
+
+#[derive(Debug)]
+pub struct IsoFile<F: Read + Seek> {
+    reader: PositionedReader<F>,
+    len_sectors: u32,
+}
+
+impl<F: Read + Seek> IsoFile<F> {
+    pub(super) fn open(mut reader: PositionedReader<F>) -> CdRomResult<Self> {
+        let len_sectors = (reader.len()? / BYTES_PER_ISO_SECTOR as u64) as u32;
+
+        Ok(Self { reader, len_sectors })
+    }
+
+    pub fn len_sectors(&self) -> u32 {
+        self.len_sectors
+    }
+
+    pub fn read_raw_sector(&mut self, sector_number: u32, out: &mut [u8]) -> CdRomResult<()> {
+        let offset = u64::from(sector_number) * BYTES_PER_ISO_SECTOR as u64;
+        self.reader.read_exact_at(
+            offset,
+            &mut out[USER_DATA_OFFSET..USER_DATA_OFFSET + BYTES_PER_ISO_SECTOR],
+        )?;
+
+        let time = CdTime::from_sector_number(sector_number) + CdTime::SECTOR_0_START;
+        sector::write_sync_and_header(out, time, 2);
+        out[16..USER_DATA_OFFSET].copy_from_slice(&DATA_SUBHEADER);
+        sector::generate_mode_2_form_1(out);
+
+        Ok(())
+    }
+}
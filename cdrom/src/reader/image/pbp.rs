@@ -0,0 +1,296 @@
+//! PSP eboot PBP files containing PS1 disc images, as created by popstation and similar tools
+//!
+//! The PBP header contains offsets to several embedded files; the disc images are in the last one,
+//! `DATA.PSAR`. `DATA.PSAR` is either a single disc image (magic `PSISOIMG0000`) or a multi-disc
+//! container (magic `PSTITLEIMG000000`) with up to 5 disc image offsets at $200, relative to the
+//! start of `DATA.PSAR`.
+//!
+//! Within a disc image:
+//! - $0800: TOC, 10 bytes per entry in the same format as the Q subchannel in the lead-in area
+//! - $4000: Block index, 32 bytes per entry: 4-byte offset, 2-byte length, and 26 unused bytes
+//! - $100000: Block data; offsets in the block index are relative to this
+//!
+//! Each block is 16 raw 2352-byte sectors. A block whose stored length equals the decompressed
+//! length is uncompressed, and any other block is raw DEFLATE data. Only unencrypted images are
+//! supported; official digital releases store an encrypted disc image instead.
+
+use crate::cdtime::CdTime;
+use crate::cue::TrackType;
+use crate::reader::image::{BYTES_PER_SECTOR, ImageTrack, PositionedReader};
+use crate::{CdRomError, CdRomResult};
+use flate2::read::DeflateDecoder;
+use std::io::{Read, Seek};
+
+const PBP_MAGIC: &[u8; 4] = b"\0PBP";
+const PSAR_OFFSET_LOCATION: u64 = 0x24;
+
+const SINGLE_DISC_MAGIC: &[u8] = b"PSISOIMG0000";
+const MULTI_DISC_MAGIC: &[u8] = b"PSTITLEIMG000000";
+
+const DISC_OFFSETS_LOCATION: u64 = 0x200;
+const MAX_DISCS: usize = 5;
+
+const TOC_LOCATION: u64 = 0x800;
+const TOC_ENTRY_LEN: usize = 10;
+// Lead-in entries A0-A2 plus up to 99 tracks
+const MAX_TOC_ENTRIES: usize = 3 + 99;
+
+const BLOCK_INDEX_LOCATION: u64 = 0x4000;
+const BLOCK_INDEX_ENTRY_LEN: usize = 32;
+const BLOCK_DATA_LOCATION: u64 = 0x100000;
+const MAX_BLOCKS: usize =
+    (BLOCK_DATA_LOCATION - BLOCK_INDEX_LOCATION) as usize / BLOCK_INDEX_ENTRY_LEN;
+
+const SECTORS_PER_BLOCK: u32 = 16;
+const BLOCK_LEN: usize = SECTORS_PER_BLOCK as usize * BYTES_PER_SECTOR;
+
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    offset: u64,
+    len: usize,
+}
+
+#[derive(Debug)]
+pub struct PbpFile<F: Read + Seek> {
+    reader: PositionedReader<F>,
+    tracks: Vec<ImageTrack>,
+    blocks: Vec<Block>,
+    len_sectors: u32,
+    compressed_buffer: Vec<u8>,
+    block_buffer: Box<[u8]>,
+    current_block: Option<usize>,
+}
+
+impl<F: Read + Seek> PbpFile<F> {
+    pub(super) fn open(mut reader: PositionedReader<F>, disc_index: usize) -> CdRomResult<Self> {
+        let disc_offsets = read_disc_offsets(&mut reader)?;
+        let Some(&disc_offset) = disc_offsets.get(disc_index) else {
+            return Err(CdRomError::PbpDiscIndex { index: disc_index, count: disc_offsets.len() });
+        };
+
+        let mut magic = [0; SINGLE_DISC_MAGIC.len()];
+        reader.read_exact_at(disc_offset, &mut magic)?;
+        if magic != SINGLE_DISC_MAGIC {
+            return Err(CdRomError::InvalidImage(format!(
+                "PBP disc {} is not an unencrypted PS1 disc image",
+                disc_index + 1
+            )));
+        }
+
+        let (tracks, len_sectors) = read_toc(&mut reader, disc_offset)?;
+
+        let num_blocks = len_sectors.div_ceil(SECTORS_PER_BLOCK) as usize;
+        if num_blocks > MAX_BLOCKS {
+            return Err(CdRomError::InvalidImage(format!(
+                "PBP disc image is too large: {len_sectors} sectors"
+            )));
+        }
+
+        let mut block_index = vec![0; num_blocks * BLOCK_INDEX_ENTRY_LEN];
+        reader.read_exact_at(disc_offset + BLOCK_INDEX_LOCATION, &mut block_index)?;
+        let blocks = block_index
+            .chunks_exact(BLOCK_INDEX_ENTRY_LEN)
+            .map(|entry| Block {
+                offset: disc_offset
+                    + BLOCK_DATA_LOCATION
+                    + u64::from(u32::from_le_bytes(entry[0..4].try_into().unwrap())),
+                len: u16::from_le_bytes(entry[4..6].try_into().unwrap()).into(),
+            })
+            .collect();
+
+        log::debug!("PBP disc {} tracks: {tracks:?}", disc_index + 1);
+
+        Ok(Self {
+            reader,
+            tracks,
+            blocks,
+            len_sectors,
+            compressed_buffer: Vec::with_capacity(BLOCK_LEN),
+            block_buffer: vec![0; BLOCK_LEN].into_boxed_slice(),
+            current_block: None,
+        })
+    }
+
+    pub(super) fn tracks(&self) -> &[ImageTrack] {
+        &self.tracks
+    }
+
+    pub fn len_sectors(&self) -> u32 {
+        self.len_sectors
+    }
+
+    pub fn read_raw_sector(&mut self, sector_number: u32, out: &mut [u8]) -> CdRomResult<()> {
+        let block_idx = (sector_number / SECTORS_PER_BLOCK) as usize;
+
+        // Only decompress the block if necessary
+        if self.current_block != Some(block_idx) {
+            self.load_block(block_idx)?;
+        }
+
+        let offset = (sector_number % SECTORS_PER_BLOCK) as usize * BYTES_PER_SECTOR;
+        out[..BYTES_PER_SECTOR]
+            .copy_from_slice(&self.block_buffer[offset..offset + BYTES_PER_SECTOR]);
+
+        Ok(())
+    }
+
+    fn load_block(&mut self, block_idx: usize) -> CdRomResult<()> {
+        let Some(&Block { offset, len }) = self.blocks.get(block_idx) else {
+            return Err(CdRomError::DiscReadIo(std::io::ErrorKind::UnexpectedEof.into()));
+        };
+
+        // Invalidate the cached block in case decompression fails partway through
+        self.current_block = None;
+
+        if len == BLOCK_LEN {
+            self.reader.read_exact_at(offset, &mut self.block_buffer)?;
+        } else {
+            self.compressed_buffer.resize(len, 0);
+            self.reader.read_exact_at(offset, &mut self.compressed_buffer)?;
+
+            // The final block may decompress to less than a full block
+            self.block_buffer.fill(0);
+            let mut decoder = DeflateDecoder::new(self.compressed_buffer.as_slice());
+            let mut filled = 0;
+            while filled < BLOCK_LEN {
+                match decoder
+                    .read(&mut self.block_buffer[filled..])
+                    .map_err(CdRomError::DiscReadIo)?
+                {
+                    0 => break,
+                    len => filled += len,
+                }
+            }
+        }
+
+        self.current_block = Some(block_idx);
+
+        Ok(())
+    }
+}
+
+/// Returns the number of discs in a PBP file.
+pub fn disc_count<F: Read + Seek>(file: F) -> CdRomResult<usize> {
+    read_disc_offsets(&mut PositionedReader::new(file)).map(|offsets| offsets.len())
+}
+
+// Returns the absolute file offset of each disc image
+fn read_disc_offsets<F: Read + Seek>(reader: &mut PositionedReader<F>) -> CdRomResult<Vec<u64>> {
+    let mut magic = [0; PBP_MAGIC.len()];
+    reader.read_exact_at(0, &mut magic)?;
+    if &magic != PBP_MAGIC {
+        return Err(CdRomError::InvalidImage("PBP file is missing magic bytes".into()));
+    }
+
+    let mut psar_offset = [0; 4];
+    reader.read_exact_at(PSAR_OFFSET_LOCATION, &mut psar_offset)?;
+    let psar_offset = u64::from(u32::from_le_bytes(psar_offset));
+
+    let mut psar_magic = [0; MULTI_DISC_MAGIC.len()];
+    reader.read_exact_at(psar_offset, &mut psar_magic)?;
+
+    if psar_magic.starts_with(SINGLE_DISC_MAGIC) {
+        return Ok(vec![psar_offset]);
+    }
+
+    if psar_magic != MULTI_DISC_MAGIC {
+        return Err(CdRomError::InvalidImage(
+            "PBP file does not contain an unencrypted PS1 disc image".into(),
+        ));
+    }
+
+    let mut disc_offsets = [0; 4 * MAX_DISCS];
+    reader.read_exact_at(psar_offset + DISC_OFFSETS_LOCATION, &mut disc_offsets)?;
+
+    let disc_offsets: Vec<_> = disc_offsets
+        .chunks_exact(4)
+        .map(|offset| u32::from_le_bytes(offset.try_into().unwrap()))
+        .take_while(|&offset| offset != 0)
+        .map(|offset| psar_offset + u64::from(offset))
+        .collect();
+    if disc_offsets.is_empty() {
+        return Err(CdRomError::InvalidImage("Multi-disc PBP file contains no discs".into()));
+    }
+
+    Ok(disc_offsets)
+}
+
+// Returns the track list and the length of the disc image in sectors
+fn read_toc<F: Read + Seek>(
+    reader: &mut PositionedReader<F>,
+    disc_offset: u64,
+) -> CdRomResult<(Vec<ImageTrack>, u32)> {
+    let mut toc = vec![0; MAX_TOC_ENTRIES * TOC_ENTRY_LEN];
+    reader.read_exact_at(disc_offset + TOC_LOCATION, &mut toc)?;
+
+    let invalid_toc = || CdRomError::InvalidImage("PBP disc image has an invalid TOC".into());
+
+    let mut last_track = None;
+    let mut lead_out = None;
+    let mut track_entries = Vec::new();
+    for entry in toc.chunks_exact(TOC_ENTRY_LEN) {
+        // Byte 0: control/ADR, byte 2: point, bytes 3-5: relative time (INDEX 00 for tracks),
+        // bytes 7-9: absolute time (INDEX 01 for tracks)
+        let point = entry[2];
+        match point {
+            0xA0 => {}
+            0xA1 => last_track = Some(bcd_to_binary(entry[7]).ok_or_else(invalid_toc)?),
+            0xA2 => lead_out = Some(toc_time(&entry[7..10]).ok_or_else(invalid_toc)?),
+            _ => {
+                let Some(track_number) = bcd_to_binary(point) else { break };
+                if track_number == 0 || track_number > 99 {
+                    break;
+                }
+                track_entries.push((track_number, entry));
+            }
+        }
+    }
+
+    let (Some(last_track), Some(lead_out)) = (last_track, lead_out) else {
+        return Err(invalid_toc());
+    };
+    let len_sectors = sector_number(lead_out).ok_or_else(invalid_toc)?;
+
+    let mut tracks = Vec::with_capacity(track_entries.len());
+    for (track_number, entry) in track_entries.into_iter().take(last_track.into()) {
+        let track_type = if entry[0] & 0x40 != 0 { TrackType::Data } else { TrackType::Audio };
+        let start = toc_time(&entry[7..10]).and_then(sector_number).ok_or_else(invalid_toc)?;
+        let pause_start = toc_time(&entry[3..6])
+            .and_then(sector_number)
+            .filter(|&pause_start| pause_start < start);
+
+        let prev_end = tracks.last().map_or(0, |prev: &ImageTrack| prev.start);
+        if track_number != (tracks.len() + 1) as u8
+            || start >= len_sectors
+            || pause_start.unwrap_or(start) < prev_end
+        {
+            return Err(invalid_toc());
+        }
+
+        tracks.push(ImageTrack { number: track_number, track_type, pause_start, start });
+    }
+
+    if tracks.len() != last_track.into() || tracks.first().is_none_or(|track| track.start != 0) {
+        return Err(invalid_toc());
+    }
+
+    Ok((tracks, len_sectors))
+}
+
+fn toc_time(bcd_time: &[u8]) -> Option<CdTime> {
+    CdTime::new_checked(
+        bcd_to_binary(bcd_time[0])?,
+        bcd_to_binary(bcd_time[1])?,
+        bcd_to_binary(bcd_time[2])?,
+    )
+}
+
+// TOC times include the 2-second lead-in before track 1, while image sectors do not
+fn sector_number(time: CdTime) -> Option<u32> {
+    time.to_sector_number().checked_sub(CdTime::SECTOR_0_START.to_sector_number())
+}
+
+fn bcd_to_binary(value: u8) -> Option<u8> {
+    let (msb, lsb) = (value >> 4, value & 0xF);
+    (msb < 10 && lsb < 10).then_some(10 * msb + lsb)
+}
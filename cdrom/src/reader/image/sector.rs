@@ -0,0 +1,192 @@
+//! Reconstruction of the parts of a raw sector that disc image formats leave out: sync pattern,
+//! header, EDC, and ECC
+//!
+//! ECC is computed as Reed-Solomon Product Code (RSPC) parity: 172 bytes of P parity over the
+//! header and user data, then 104 bytes of Q parity over the header, user data, and P parity. In
+//! Mode 2 sectors the header address is treated as all zeros when computing ECC.
+
+use crate::cdtime::CdTime;
+use crate::reader::{CD_ROM_CRC, time_component_to_bcd};
+use std::ops::Range;
+
+pub const SYNC_PATTERN: [u8; 12] =
+    [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+const HEADER_ADDRESS: Range<usize> = 12..15;
+const HEADER_MODE: usize = 15;
+
+const MODE_1_EDC_DIGEST: Range<usize> = 0..0x810;
+const MODE_1_EDC_LOCATION: usize = 0x810;
+const MODE_1_INTERMEDIATE: Range<usize> = 0x814..0x81C;
+
+const MODE_2_FORM_1_EDC_DIGEST: Range<usize> = 0x10..0x818;
+const MODE_2_FORM_1_EDC_LOCATION: usize = 0x818;
+
+const MODE_2_FORM_2_EDC_DIGEST: Range<usize> = 0x10..0x92C;
+const MODE_2_FORM_2_EDC_LOCATION: usize = 0x92C;
+
+const ECC_START: usize = 0x0C;
+const ECC_P_LOCATION: usize = 0x81C;
+const ECC_Q_LOCATION: usize = 0x8C8;
+
+const ECC_F_TABLE: [u8; 256] = ecc_f_table();
+const ECC_B_TABLE: [u8; 256] = ecc_b_table();
+
+// Multiplication by 2 in GF(2^8) with the CD-ROM ECC polynomial x^8 + x^4 + x^3 + x^2 + 1
+const fn ecc_f_table() -> [u8; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = ((i << 1) ^ (if i & 0x80 != 0 { 0x11D } else { 0 })) as u8;
+        i += 1;
+    }
+    table
+}
+
+const fn ecc_b_table() -> [u8; 256] {
+    let f_table = ecc_f_table();
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i ^ f_table[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+/// Write the sync pattern and a header for the given absolute disc time and mode.
+pub fn write_sync_and_header(sector: &mut [u8], time: CdTime, mode: u8) {
+    sector[..SYNC_PATTERN.len()].copy_from_slice(&SYNC_PATTERN);
+    sector[HEADER_ADDRESS].copy_from_slice(&[
+        time_component_to_bcd(time.minutes),
+        time_component_to_bcd(time.seconds),
+        time_component_to_bcd(time.frames),
+    ]);
+    sector[HEADER_MODE] = mode;
+}
+
+/// Generate EDC and ECC for a Mode 1 sector with sync, header, and user data already populated.
+pub fn generate_mode_1(sector: &mut [u8]) {
+    write_edc(sector, MODE_1_EDC_DIGEST, MODE_1_EDC_LOCATION);
+    sector[MODE_1_INTERMEDIATE].fill(0);
+    write_ecc(sector);
+}
+
+/// Generate EDC and ECC for a Mode 2 Form 1 sector with subheader and user data already populated.
+pub fn generate_mode_2_form_1(sector: &mut [u8]) {
+    write_edc(sector, MODE_2_FORM_1_EDC_DIGEST, MODE_2_FORM_1_EDC_LOCATION);
+
+    let address: [u8; 4] = sector[12..16].try_into().unwrap();
+    sector[12..16].fill(0);
+    write_ecc(sector);
+    sector[12..16].copy_from_slice(&address);
+}
+
+/// Generate EDC for a Mode 2 Form 2 sector with subheader and user data already populated. Form 2
+/// sectors have no ECC.
+pub fn generate_mode_2_form_2(sector: &mut [u8]) {
+    write_edc(sector, MODE_2_FORM_2_EDC_DIGEST, MODE_2_FORM_2_EDC_LOCATION);
+}
+
+fn write_edc(sector: &mut [u8], digest_range: Range<usize>, location: usize) {
+    let edc = CD_ROM_CRC.checksum(&sector[digest_range]);
+    sector[location..location + 4].copy_from_slice(&edc.to_le_bytes());
+}
+
+fn write_ecc(sector: &mut [u8]) {
+    // P parity: 86 columns of 24 bytes each
+    let (data, parity) = sector.split_at_mut(ECC_P_LOCATION);
+    compute_ecc_block(&data[ECC_START..], 86, 24, 2, 86, &mut parity[..2 * 86]);
+
+    // Q parity: 52 diagonals of 43 bytes each, covering the P parity bytes as well
+    let (data, parity) = sector.split_at_mut(ECC_Q_LOCATION);
+    compute_ecc_block(&data[ECC_START..], 52, 43, 86, 88, &mut parity[..2 * 52]);
+}
+
+fn compute_ecc_block(
+    source: &[u8],
+    major_count: usize,
+    minor_count: usize,
+    major_mult: usize,
+    minor_inc: usize,
+    dest: &mut [u8],
+) {
+    let size = major_count * minor_count;
+    for major in 0..major_count {
+        let mut index = (major >> 1) * major_mult + (major & 1);
+        let mut ecc_a = 0;
+        let mut ecc_b = 0;
+        for _ in 0..minor_count {
+            let value = source[index];
+            index += minor_inc;
+            if index >= size {
+                index -= size;
+            }
+            ecc_a ^= value;
+            ecc_b ^= value;
+            ecc_a = ECC_F_TABLE[ecc_a as usize];
+        }
+        ecc_a = ECC_B_TABLE[(ECC_F_TABLE[ecc_a as usize] ^ ecc_b) as usize];
+        dest[major] = ecc_a;
+        dest[major + major_count] = ecc_a ^ ecc_b;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every P column and Q diagonal, followed by its two parity bytes, must be a Reed-Solomon
+    // codeword: the sum of all bytes is 0, and evaluating the codeword as a polynomial at 2 is 0
+    fn assert_valid_ecc_block(
+        source: &[u8],
+        (major_count, minor_count, major_mult, minor_inc): (usize, usize, usize, usize),
+        parity: &[u8],
+    ) {
+        let size = major_count * minor_count;
+        for major in 0..major_count {
+            let mut index = (major >> 1) * major_mult + (major & 1);
+            let mut codeword = Vec::with_capacity(minor_count + 2);
+            for _ in 0..minor_count {
+                codeword.push(source[index]);
+                index = (index + minor_inc) % size;
+            }
+            codeword.extend([parity[major], parity[major + major_count]]);
+
+            let sum = codeword.iter().fold(0, |acc, &byte| acc ^ byte);
+            let evaluated = codeword.iter().fold(0, |acc, &byte| ECC_F_TABLE[acc as usize] ^ byte);
+            assert_eq!((sum, evaluated), (0, 0), "invalid ECC for major {major}");
+        }
+    }
+
+    #[test]
+    fn mode_2_form_1_sector() {
+        let mut sector = [0; 2352];
+        write_sync_and_header(&mut sector, CdTime::new(0, 2, 16), 2);
+        sector[16..24].copy_from_slice(&[0, 0, 0x08, 0, 0, 0, 0x08, 0]);
+        for (i, byte) in sector[24..24 + 2048].iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        generate_mode_2_form_1(&mut sector);
+
+        assert_eq!(&sector[12..16], &[0x00, 0x02, 0x16, 0x02]);
+        assert_eq!(
+            u32::from_le_bytes(sector[0x818..0x81C].try_into().unwrap()),
+            CD_ROM_CRC.checksum(&sector[0x10..0x818])
+        );
+
+        // ECC is computed with the address zeroed
+        let mut ecc_input = sector;
+        ecc_input[12..16].fill(0);
+        assert_valid_ecc_block(
+            &ecc_input[ECC_START..ECC_P_LOCATION],
+            (86, 24, 2, 86),
+            &sector[ECC_P_LOCATION..],
+        );
+        assert_valid_ecc_block(
+            &ecc_input[ECC_START..ECC_Q_LOCATION],
+            (52, 43, 86, 88),
+            &sector[ECC_Q_LOCATION..],
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use proc_macro2::Ident;
 use quote::{format_ident, quote};
-use syn::{Attribute, Data, DeriveInput, Fields};
+use syn::{Attribute, Data, DeriveInput, Fields, LitInt};
 
 /// Define a save state struct and a method for converting to the save state struct.
 ///
@@ -9,10 +9,14 @@ use syn::{Attribute, Data, DeriveInput, Fields};
 ///
 /// All fields that are not annotated with a `#[save_state(_)]` attribute must implement `Clone`.
 ///
-/// Struct fields may be annotated with 1 of 2 attributes:
+/// Struct fields may be annotated with 1 of 3 attributes:
 /// - `#[save_state(skip)]`: The field will be left out of the save state struct
 /// - `#[save_state(to = OtherState)]`: The field in the save state struct will be of type `OtherState`,
 ///   and `save_state()` will call the field's `save_state()` method instead of `clone()`
+/// - `#[save_state(since = N)]`: The field was added in save state format version `N`. When
+///   decoding a save state from an older format version, the field will not be read and will be set
+///   to `Default::default()` instead. The version being decoded is read from
+///   `crate::savestate::decoding_format_version()`, which the using crate must define.
 ///
 /// The save state struct will implement the traits `Debug`, `Clone`, `bincode::Encode`, and
 /// `bincode::Decode`.
@@ -80,6 +84,8 @@ pub fn save_state(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 
     let mut save_state_fields = Vec::new();
     let mut to_state_fields = Vec::new();
+    let mut decode_fields = Vec::new();
+    let mut any_versioned_fields = false;
 
     for field in &fields.named {
         let field_ident = field.ident.as_ref().unwrap();
@@ -96,6 +102,20 @@ pub fn save_state(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 to_state_fields.push(quote! {
                     #field_ident: self.#field_ident.save_state()
                 });
+
+                decode_fields.push((field_ident, None));
+            }
+            Some(SaveStateAttribute::Since(version)) => {
+                save_state_fields.push(quote! {
+                    #field_ident: #field_ty
+                });
+
+                to_state_fields.push(quote! {
+                    #field_ident: self.#field_ident.clone()
+                });
+
+                decode_fields.push((field_ident, Some(version)));
+                any_versioned_fields = true;
             }
             None => {
                 save_state_fields.push(quote! {
@@ -105,14 +125,28 @@ pub fn save_state(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                 to_state_fields.push(quote! {
                     #field_ident: self.#field_ident.clone()
                 });
+
+                decode_fields.push((field_ident, None));
             }
         }
     }
 
-    let struct_definition = quote! {
-        #[derive(Debug, Clone, ::bincode::Encode, ::bincode::Decode)]
-        pub struct #save_state_ident {
-            #(#save_state_fields,)*
+    let struct_definition = if any_versioned_fields {
+        let decode_impl = versioned_decode_impl(&save_state_ident, &decode_fields);
+        quote! {
+            #[derive(Debug, Clone, ::bincode::Encode)]
+            pub struct #save_state_ident {
+                #(#save_state_fields,)*
+            }
+
+            #decode_impl
+        }
+    } else {
+        quote! {
+            #[derive(Debug, Clone, ::bincode::Encode, ::bincode::Decode)]
+            pub struct #save_state_ident {
+                #(#save_state_fields,)*
+            }
         }
     };
 
@@ -135,10 +169,62 @@ pub fn save_state(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     gen.into()
 }
 
+fn versioned_decode_impl(
+    save_state_ident: &Ident,
+    fields: &[(&Ident, Option<LitInt>)],
+) -> proc_macro2::TokenStream {
+    let field_decoders = |decode: proc_macro2::TokenStream| {
+        fields
+            .iter()
+            .map(|(field_ident, since)| match since {
+                Some(since) => quote! {
+                    #field_ident: if version >= #since {
+                        #decode
+                    } else {
+                        ::core::default::Default::default()
+                    }
+                },
+                None => quote! {
+                    #field_ident: #decode
+                },
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let decode_fields = field_decoders(quote! { ::bincode::Decode::decode(decoder)? });
+    let borrow_decode_fields =
+        field_decoders(quote! { ::bincode::BorrowDecode::borrow_decode(decoder)? });
+
+    quote! {
+        impl ::bincode::Decode for #save_state_ident {
+            fn decode<D: ::bincode::de::Decoder>(
+                decoder: &mut D,
+            ) -> ::core::result::Result<Self, ::bincode::error::DecodeError> {
+                let version = crate::savestate::decoding_format_version();
+                Ok(Self {
+                    #(#decode_fields,)*
+                })
+            }
+        }
+
+        impl<'de> ::bincode::BorrowDecode<'de> for #save_state_ident {
+            fn borrow_decode<D: ::bincode::de::BorrowDecoder<'de>>(
+                decoder: &mut D,
+            ) -> ::core::result::Result<Self, ::bincode::error::DecodeError> {
+                let version = crate::savestate::decoding_format_version();
+                Ok(Self {
+                    #(#borrow_decode_fields,)*
+                })
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 enum SaveStateAttribute {
     Skip,
     ToState(Ident),
+    Since(LitInt),
 }
 
 fn parse_attribute(attribute: &Attribute) -> Option<SaveStateAttribute> {
@@ -154,6 +240,13 @@ fn parse_attribute(attribute: &Attribute) -> Option<SaveStateAttribute> {
                 return Ok(());
             }
 
+            if meta.path.is_ident("since") {
+                let value = meta.value()?;
+                let version: LitInt = value.parse()?;
+                parsed = Some(SaveStateAttribute::Since(version));
+                return Ok(());
+            }
+
             if meta.path.is_ident("to") {
                 let value = meta.value()?;
                 let field_state_ident: Ident = value.parse()?;
@@ -4,31 +4,36 @@ use crate::bus::Bus;
 use crate::cd::{CdController, CdControllerState};
 use crate::cpu::R3000;
 use crate::dma::{DmaContext, DmaController};
-use crate::gpu::Gpu;
-use crate::gpu::GpuState;
-use crate::input::Ps1Inputs;
+use crate::gpu::{Frame, Gpu, GpuState};
+use crate::input::{DualShockConfig, Ps1Inputs, Ps1Rumble};
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::mdec::MacroblockDecoder;
 use crate::memory::{Memory, MemoryControl};
+use crate::region;
 use crate::scheduler::{Scheduler, SchedulerEvent, SchedulerEventType};
+use crate::sio::link::LinkCable;
 use crate::sio::memcard::MemoryCard;
-use crate::sio::{SerialPort0, SerialPort1};
+use crate::sio::{MultitapMemoryCards, SerialPort0, SerialPort1};
 use crate::spu::Spu;
 use crate::timers::Timers;
 use bincode::{Decode, Encode};
-use cdrom::CdRomError;
 use cdrom::reader::CdRom;
+use cdrom::{CdRomError, CdRomResult};
+use crc::Crc;
 use proc_macros::SaveState;
 use std::fmt::{Display, Formatter};
 use std::num::NonZeroU32;
 use std::sync::Arc;
 use thiserror::Error;
 
-pub use crate::gpu::DisplayConfig;
+pub use crate::gpu::{CpuFrame, DisplayConfig, Screenshot};
 pub use crate::pgxp::PgxpConfig;
+pub use crate::region::Region;
 
 pub const DEFAULT_AUDIO_BUFFER_SIZE: u32 = 64;
 
+const BIOS_CRC: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
 pub enum ColorDepthBits {
     #[default]
@@ -63,6 +68,23 @@ pub trait Renderer {
         frame: &wgpu::Texture,
         pixel_aspect_ratio: f64,
     ) -> Result<(), Self::Err>;
+
+    /// Called instead of [`Renderer::render_frame`] when the emulator was built without a wgpu
+    /// device, in which case frames are rendered into CPU memory by the software rasterizers.
+    ///
+    /// The default implementation discards the frame, so renderers that are only used with a wgpu
+    /// device do not need to implement this.
+    ///
+    /// # Errors
+    ///
+    /// Should propagate any error encountered while rendering the frame.
+    fn render_cpu_frame(
+        &mut self,
+        _frame: CpuFrame<'_>,
+        _pixel_aspect_ratio: f64,
+    ) -> Result<(), Self::Err> {
+        Ok(())
+    }
 }
 
 pub trait AudioOutput {
@@ -81,6 +103,35 @@ pub trait SaveWriter {
     ///
     /// Should propagate any error encountered while persisting the memory card.
     fn save_memory_card_1(&mut self, card_data: &[u8]) -> Result<(), Self::Err>;
+
+    /// # Errors
+    ///
+    /// Should propagate any error encountered while persisting the memory card.
+    fn save_memory_card_2(&mut self, card_data: &[u8]) -> Result<(), Self::Err>;
+
+    /// Persist the memory card in a multitap slot. `port` is 0 for port 1 and 1 for port 2, and
+    /// `slot` indexes slots B-D the same way as [`Ps1Inputs::p1_multitap`].
+    ///
+    /// # Errors
+    ///
+    /// Should propagate any error encountered while persisting the memory card.
+    fn save_multitap_memory_card(
+        &mut self,
+        port: usize,
+        slot: usize,
+        card_data: &[u8],
+    ) -> Result<(), Self::Err>;
+}
+
+pub trait RumbleOutput {
+    type Err;
+
+    /// Called once per rendered frame with the current motor state of every controller.
+    ///
+    /// # Errors
+    ///
+    /// Should propagate any error encountered while updating host force feedback.
+    fn set_rumble(&mut self, rumble: Ps1Rumble) -> Result<(), Self::Err>;
 }
 
 #[derive(Debug, Error)]
@@ -94,13 +145,15 @@ pub enum Ps1Error {
 pub type Ps1Result<T> = Result<T, Ps1Error>;
 
 #[derive(Debug, Error)]
-pub enum TickError<RErr, AErr, SErr> {
+pub enum TickError<RErr, AErr, SErr, VErr> {
     #[error("Error rendering frame: {0}")]
     Render(RErr),
     #[error("Error queueing audio samples: {0}")]
     Audio(AErr),
     #[error("Error saving memory card: {0}")]
     SaveWrite(SErr),
+    #[error("Error updating controller rumble: {0}")]
+    Rumble(VErr),
     #[error("CD-ROM error: {0}")]
     CdRom(#[from] CdRomError),
 }
@@ -111,6 +164,10 @@ pub struct Ps1EmulatorConfig {
     pub pgxp: PgxpConfig,
     pub internal_audio_buffer_size: NonZeroU32,
     pub tty_enabled: bool,
+    pub dualshock: DualShockConfig,
+    /// Console region, or `None` to detect it from the disc and BIOS. This determines the GPU
+    /// clock rate (NTSC or PAL timing) and the region reported for discs without a known region.
+    pub forced_region: Option<Region>,
 }
 
 impl Default for Ps1EmulatorConfig {
@@ -120,15 +177,30 @@ impl Default for Ps1EmulatorConfig {
             pgxp: PgxpConfig::default(),
             internal_audio_buffer_size: NonZeroU32::new(DEFAULT_AUDIO_BUFFER_SIZE).unwrap(),
             tty_enabled: false,
+            dualshock: DualShockConfig::default(),
+            forced_region: None,
         }
     }
 }
 
+/// Identifies the BIOS and disc that the emulator is running, so that recordings made against one
+/// set of content can be checked before they are replayed against another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentIdentity {
+    pub bios_crc32: u32,
+    /// `None` if there is no disc in the drive
+    pub disc_crc32: Option<u32>,
+}
+
 pub struct UnserializedFields {
     disc: Option<CdRom>,
-    memory_card_1: MemoryCard,
-    wgpu_device: Arc<wgpu::Device>,
-    wgpu_queue: Arc<wgpu::Queue>,
+    disc_region: Option<Region>,
+    detected_region: Region,
+    memory_card_1: Option<MemoryCard>,
+    memory_card_2: Option<MemoryCard>,
+    multitap_memory_cards: MultitapMemoryCards,
+    link_cable: Option<LinkCable>,
+    wgpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
     config: Ps1EmulatorConfig,
 }
 
@@ -153,17 +225,23 @@ pub struct Ps1Emulator {
     last_render_cycles: u64,
     #[save_state(skip)]
     config: Ps1EmulatorConfig,
+    // Region that auto mode uses, detected at power on
+    #[save_state(skip)]
+    detected_region: Region,
     tty_buffer: String,
 }
 
 #[derive(Debug)]
 pub struct Ps1EmulatorBuilder {
     bios_rom: Vec<u8>,
-    wgpu_device: Arc<wgpu::Device>,
-    wgpu_queue: Arc<wgpu::Queue>,
+    wgpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
     config: Ps1EmulatorConfig,
     disc: Option<CdRom>,
     memory_card_1: Option<Vec<u8>>,
+    memory_card_2: Option<Vec<u8>>,
+    memory_cards_inserted: [bool; 2],
+    multitap_memory_cards: MultitapMemoryCards,
+    ram_seed: Option<u64>,
 }
 
 impl Ps1EmulatorBuilder {
@@ -173,13 +251,30 @@ impl Ps1EmulatorBuilder {
         wgpu_device: Arc<wgpu::Device>,
         wgpu_queue: Arc<wgpu::Queue>,
     ) -> Self {
+        Self::new_inner(bios_rom, Some((wgpu_device, wgpu_queue)))
+    }
+
+    /// Create a builder for an emulator that does not require a wgpu device.
+    ///
+    /// The hardware rasterizer is not available in this mode; if it is selected in the config,
+    /// the emulator will fall back to a software rasterizer. Frames are output through
+    /// [`Renderer::render_cpu_frame`].
+    #[must_use]
+    pub fn new_software(bios_rom: Vec<u8>) -> Self {
+        Self::new_inner(bios_rom, None)
+    }
+
+    fn new_inner(bios_rom: Vec<u8>, wgpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>) -> Self {
         Self {
             bios_rom,
-            wgpu_device,
-            wgpu_queue,
+            wgpu,
             config: Ps1EmulatorConfig::default(),
             disc: None,
             memory_card_1: None,
+            memory_card_2: None,
+            memory_cards_inserted: [true, false],
+            multitap_memory_cards: Default::default(),
+            ram_seed: None,
         }
     }
 
@@ -192,6 +287,44 @@ impl Ps1EmulatorBuilder {
     #[must_use]
     pub fn with_memory_card_1(mut self, memory_card_1: Vec<u8>) -> Self {
         self.memory_card_1 = Some(memory_card_1);
+        self.memory_cards_inserted[0] = true;
+        self
+    }
+
+    #[must_use]
+    pub fn with_memory_card_2(mut self, memory_card_2: Vec<u8>) -> Self {
+        self.memory_card_2 = Some(memory_card_2);
+        self.memory_cards_inserted[1] = true;
+        self
+    }
+
+    /// Set which memory card slots contain a card. By default, only slot 1 contains a card.
+    ///
+    /// A card that is inserted without data from [`Self::with_memory_card_1`] or
+    /// [`Self::with_memory_card_2`] starts out freshly formatted.
+    #[must_use]
+    pub fn with_memory_cards_inserted(mut self, card_1: bool, card_2: bool) -> Self {
+        self.memory_cards_inserted = [card_1, card_2];
+        self
+    }
+
+    /// Insert a memory card into a multitap slot. `port` is 0 for port 1 and 1 for port 2, and
+    /// `slot` indexes slots B-D the same way as [`Ps1Inputs::p1_multitap`]. A card inserted
+    /// without data starts out freshly formatted.
+    ///
+    /// Software can only access the card while a multitap is connected to the port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `port` or `slot` is out of range.
+    #[must_use]
+    pub fn with_multitap_memory_card(
+        mut self,
+        port: usize,
+        slot: usize,
+        card_data: Option<Vec<u8>>,
+    ) -> Self {
+        self.multitap_memory_cards[port][slot] = Some(MemoryCard::new(card_data));
         self
     }
 
@@ -201,17 +334,27 @@ impl Ps1EmulatorBuilder {
         self
     }
 
+    /// Set the seed used to generate the initial contents of main RAM and the scratchpad. If not
+    /// set, a random seed is used.
+    #[must_use]
+    pub fn with_ram_seed(mut self, ram_seed: u64) -> Self {
+        self.ram_seed = Some(ram_seed);
+        self
+    }
+
     /// # Errors
     ///
     /// Will return an error if the BIOS ROM is invalid.
     pub fn build(self) -> Ps1Result<Ps1Emulator> {
         Ps1Emulator::new(
             self.bios_rom,
-            self.wgpu_device,
-            self.wgpu_queue,
+            self.wgpu,
             self.config,
             self.disc,
-            self.memory_card_1,
+            self.memory_cards_inserted[0].then(|| MemoryCard::new(self.memory_card_1)),
+            self.memory_cards_inserted[1].then(|| MemoryCard::new(self.memory_card_2)),
+            self.multitap_memory_cards,
+            self.ram_seed.unwrap_or_else(rand::random),
         )
     }
 }
@@ -265,33 +408,45 @@ impl Ps1Emulator {
     /// Will return an error if the BIOS ROM is invalid.
     pub fn new(
         bios_rom: Vec<u8>,
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
+        wgpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
         config: Ps1EmulatorConfig,
         disc: Option<CdRom>,
-        memory_card_1: Option<Vec<u8>>,
+        memory_card_1: Option<MemoryCard>,
+        memory_card_2: Option<MemoryCard>,
+        multitap_memory_cards: MultitapMemoryCards,
+        ram_seed: u64,
     ) -> Ps1Result<Self> {
-        let memory = Memory::new(bios_rom)?;
+        let bios_region = Region::from_bios(&bios_rom);
+        let memory = Memory::new(bios_rom, ram_seed)?;
+
+        let cd_controller = CdController::new(disc);
+        let disc_region = cd_controller.disc_region();
+        if config.forced_region.is_none() {
+            region::warn_on_mismatch(bios_region, disc_region);
+        }
 
         let mut emulator = Self {
             cpu: R3000::new(config.pgxp),
-            gpu: Gpu::new(wgpu_device, wgpu_queue, config.display, config.pgxp),
+            gpu: Gpu::new(wgpu, config.display, config.pgxp),
             spu: Spu::new(),
             audio_buffer: Vec::with_capacity(1600),
-            cd_controller: CdController::new(disc),
+            cd_controller,
             mdec: MacroblockDecoder::new(),
             memory,
             memory_control: MemoryControl::new(),
             dma_controller: DmaController::new(config.pgxp),
             interrupt_registers: InterruptRegisters::new(),
-            sio0: SerialPort0::new_sio0(memory_card_1),
-            sio1: SerialPort1::new_sio1(),
+            sio0: SerialPort0::new_sio0(memory_card_1, memory_card_2, multitap_memory_cards),
+            sio1: SerialPort1::new(),
             timers: Timers::new(),
             scheduler: Scheduler::new(),
             last_render_cycles: 0,
             config,
+            detected_region: region::auto_detect(bios_region, disc_region),
             tty_buffer: String::new(),
         };
+        emulator.sio0.set_dualshock_config(config.dualshock);
+        emulator.apply_region();
         emulator.schedule_initial_events();
 
         Ok(emulator)
@@ -308,6 +463,102 @@ impl Ps1Emulator {
         self.cpu.pc()
     }
 
+    /// Simulate turning the console off and back on, keeping the current BIOS, disc, memory cards,
+    /// link cable, and config.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn power_cycle(&mut self, ram_seed: u64) {
+        let bios_rom = self.memory.bios_rom().to_vec();
+        let unserialized = self.take_unserialized_fields();
+
+        *self = Self::new(
+            bios_rom,
+            unserialized.wgpu,
+            unserialized.config,
+            unserialized.disc,
+            unserialized.memory_card_1.map(|card| MemoryCard::new(Some(card.data().to_vec()))),
+            unserialized.memory_card_2.map(|card| MemoryCard::new(Some(card.data().to_vec()))),
+            unserialized.multitap_memory_cards.map(|port| {
+                port.map(|card| card.map(|card| MemoryCard::new(Some(card.data().to_vec()))))
+            }),
+            ram_seed,
+        )
+        .expect("BIOS ROM was validated when the emulator was created");
+        self.set_link_cable(unserialized.link_cable);
+    }
+
+    /// Capture the current display area at native resolution, regardless of which rasterizer is
+    /// active.
+    #[must_use]
+    pub fn screenshot(&mut self) -> Screenshot {
+        self.gpu.screenshot()
+    }
+
+    /// Plug a link cable into the serial port, or unplug it if `None`. Returns the previously
+    /// plugged in cable if there was one.
+    pub fn set_link_cable(&mut self, link_cable: Option<LinkCable>) -> Option<LinkCable> {
+        self.sio1.set_link_cable(link_cable, &mut self.scheduler)
+    }
+
+    #[must_use]
+    pub fn link_cable(&self) -> Option<&LinkCable> {
+        self.sio1.link_cable()
+    }
+
+    /// Open the CD-ROM drive lid. Software sees the drive stop and any disc access fail until the
+    /// lid is closed again.
+    pub fn open_cd_lid(&mut self) {
+        self.cd_controller.open_shell();
+    }
+
+    pub fn close_cd_lid(&mut self) {
+        self.cd_controller.close_shell();
+    }
+
+    #[must_use]
+    pub fn is_cd_lid_open(&self) -> bool {
+        self.cd_controller.is_shell_open()
+    }
+
+    /// Open the lid if it is closed and remove the disc from the drive, returning it if there was
+    /// one. The lid is left open.
+    pub fn eject_disc(&mut self) -> Option<CdRom> {
+        self.cd_controller.eject_disc()
+    }
+
+    /// Open the lid if it is closed and put a new disc in the drive, returning the previous disc if
+    /// there was one. The lid is left open; call [`Self::close_cd_lid`] once software has had time
+    /// to notice that it was opened, typically after a second or so.
+    pub fn insert_disc(&mut self, disc: CdRom) -> Option<CdRom> {
+        self.cd_controller.insert_disc(disc)
+    }
+
+    /// The region that the console is currently emulating, either forced through the config or
+    /// detected when the console was powered on.
+    #[must_use]
+    pub fn console_region(&self) -> Region {
+        self.config.forced_region.unwrap_or(self.detected_region)
+    }
+
+    /// Returns the serial number of the disc in the drive (e.g. `SLUS-00594`), or `None` if there
+    /// is no disc or the serial number could not be determined.
+    ///
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn game_id(&mut self) -> CdRomResult<Option<String>> {
+        self.cd_controller.game_id()
+    }
+
+    /// # Errors
+    ///
+    /// Will propagate any error encountered while reading from the disc.
+    pub fn content_identity(&mut self) -> CdRomResult<ContentIdentity> {
+        Ok(ContentIdentity {
+            bios_crc32: BIOS_CRC.checksum(self.memory.bios_rom()),
+            disc_crc32: self.cd_controller.disc_crc32()?,
+        })
+    }
+
     /// # Errors
     ///
     /// Will return an error if the EXE does not appear to be a PS1 executable based on the header.
@@ -362,14 +613,16 @@ impl Ps1Emulator {
     /// Will propagate any error encountered while rendering a frame.
     #[inline]
     #[allow(clippy::type_complexity)]
-    pub fn tick<R: Renderer, A: AudioOutput, S: SaveWriter>(
+    pub fn tick<R: Renderer, A: AudioOutput, S: SaveWriter, V: RumbleOutput>(
         &mut self,
         inputs: Ps1Inputs,
         renderer: &mut R,
         audio_output: &mut A,
         save_writer: &mut S,
-    ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err>> {
-        self.sio0.set_inputs(inputs);
+        rumble_output: &mut V,
+    ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err, V::Err>> {
+        let gpu = &self.gpu;
+        self.sio0.set_inputs(inputs, |x, y| gpu.beam_position(x, y));
 
         if self.dma_controller.cpu_wait_cycles() != 0 {
             // TODO the CPU can run in parallel to a DMA as long as it doesn't access main RAM
@@ -389,7 +642,7 @@ impl Ps1Emulator {
         }
 
         let tick_effect = if self.scheduler.is_event_ready() {
-            self.process_scheduler_events(renderer, audio_output, save_writer)?
+            self.process_scheduler_events(renderer, audio_output, save_writer, rumble_output)?
         } else {
             TickEffect::None
         };
@@ -398,7 +651,7 @@ impl Ps1Emulator {
             // Force a frame render
             // TODO handle this with the scheduler if the GPU stops generating VBlank IRQs due to
             // invalid Y1/Y2
-            self.render_frame(renderer, audio_output, save_writer)?;
+            self.render_frame(renderer, audio_output, save_writer, rumble_output)?;
             return Ok(TickEffect::FrameRendered);
         }
 
@@ -406,27 +659,55 @@ impl Ps1Emulator {
     }
 
     #[allow(clippy::type_complexity)]
-    fn render_frame<R: Renderer, A: AudioOutput, S: SaveWriter>(
+    fn render_frame<R: Renderer, A: AudioOutput, S: SaveWriter, V: RumbleOutput>(
         &mut self,
         renderer: &mut R,
         audio_output: &mut A,
         save_writer: &mut S,
-    ) -> Result<(), TickError<R::Err, A::Err, S::Err>> {
+        rumble_output: &mut V,
+    ) -> Result<(), TickError<R::Err, A::Err, S::Err, V::Err>> {
         self.last_render_cycles = self.scheduler.cpu_cycle_counter();
 
         let pixel_aspect_ratio = self.gpu.pixel_aspect_ratio();
-        let (frame, command_buffers) = self.gpu.generate_frame_texture();
-        renderer
-            .render_frame(command_buffers, frame, pixel_aspect_ratio)
-            .map_err(TickError::Render)?;
+        match self.gpu.generate_frame() {
+            (Frame::Wgpu(frame), command_buffers) => {
+                renderer.render_frame(command_buffers, frame, pixel_aspect_ratio)
+            }
+            (Frame::Cpu(frame), _) => renderer.render_cpu_frame(frame, pixel_aspect_ratio),
+        }
+        .map_err(TickError::Render)?;
 
         self.drain_audio_samples(audio_output).map_err(TickError::Audio)?;
 
-        let memory_card_1 = self.sio0.memory_card_1();
-        if memory_card_1.get_and_clear_dirty() {
-            save_writer.save_memory_card_1(memory_card_1.data()).map_err(TickError::SaveWrite)?;
+        if let Some(memory_card_1) = self.sio0.memory_card_1() {
+            if memory_card_1.get_and_clear_dirty() {
+                save_writer
+                    .save_memory_card_1(memory_card_1.data())
+                    .map_err(TickError::SaveWrite)?;
+            }
+        }
+
+        if let Some(memory_card_2) = self.sio0.memory_card_2() {
+            if memory_card_2.get_and_clear_dirty() {
+                save_writer
+                    .save_memory_card_2(memory_card_2.data())
+                    .map_err(TickError::SaveWrite)?;
+            }
+        }
+
+        for (port, cards) in self.sio0.multitap_memory_cards().iter_mut().enumerate() {
+            for (slot, card) in cards.iter_mut().enumerate() {
+                let Some(card) = card else { continue };
+                if card.get_and_clear_dirty() {
+                    save_writer
+                        .save_multitap_memory_card(port, slot, card.data())
+                        .map_err(TickError::SaveWrite)?;
+                }
+            }
         }
 
+        rumble_output.set_rumble(self.sio0.rumble()).map_err(TickError::Rumble)?;
+
         Ok(())
     }
 
@@ -439,12 +720,13 @@ impl Ps1Emulator {
 
     #[inline]
     #[allow(clippy::type_complexity)]
-    fn process_scheduler_events<R: Renderer, A: AudioOutput, S: SaveWriter>(
+    fn process_scheduler_events<R: Renderer, A: AudioOutput, S: SaveWriter, V: RumbleOutput>(
         &mut self,
         renderer: &mut R,
         audio_output: &mut A,
         save_writer: &mut S,
-    ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err>> {
+        rumble_output: &mut V,
+    ) -> Result<TickEffect, TickError<R::Err, A::Err, S::Err, V::Err>> {
         let mut tick_effect = TickEffect::None;
 
         while let Some(event) = self.scheduler.pop_ready_event() {
@@ -461,7 +743,9 @@ impl Ps1Emulator {
                     self.sio0.catch_up(&mut self.scheduler, &mut self.interrupt_registers);
                     self.sio1.catch_up(&mut self.scheduler, &mut self.interrupt_registers);
 
-                    self.render_frame(renderer, audio_output, save_writer)?;
+                    self.schedule_light_gun_irq();
+
+                    self.render_frame(renderer, audio_output, save_writer, rumble_output)?;
 
                     tick_effect = TickEffect::FrameRendered;
                 }
@@ -500,28 +784,64 @@ impl Ps1Emulator {
                 SchedulerEventType::Sio1Irq | SchedulerEventType::Sio1Tx => {
                     self.sio1.catch_up(&mut self.scheduler, &mut self.interrupt_registers);
                 }
+                SchedulerEventType::LightGunIrq => {
+                    // Justifier light sensor saw the beam at the position it's aimed at
+                    self.interrupt_registers.set_interrupt_flag(InterruptType::LightGun);
+                }
             }
         }
 
         Ok(tick_effect)
     }
 
+    // Schedule the next frame's light gun IRQ, if a Justifier is connected and aimed at the screen
+    fn schedule_light_gun_irq(&mut self) {
+        let Some(target) = self.sio0.justifier_irq_target() else {
+            self.scheduler.remove_event(SchedulerEventType::LightGunIrq);
+            return;
+        };
+
+        let cycles = self.timers.cpu_cycles_until_beam(
+            target,
+            &mut self.scheduler,
+            &mut self.interrupt_registers,
+        );
+        self.scheduler.update_or_push_event(SchedulerEvent {
+            event_type: SchedulerEventType::LightGunIrq,
+            cpu_cycles: self.scheduler.cpu_cycle_counter() + cycles,
+        });
+    }
+
     pub fn update_config(&mut self, config: Ps1EmulatorConfig) {
         self.cpu.update_pgxp_config(config.pgxp);
         self.dma_controller.update_pgxp_config(config.pgxp);
         self.gpu.update_config(config.display, config.pgxp);
+        self.sio0.set_dualshock_config(config.dualshock);
         self.config = config;
+        self.apply_region();
+    }
+
+    fn apply_region(&mut self) {
+        let region = self.console_region();
+        self.timers.set_gpu_clock_mode(
+            region.video_mode(),
+            &mut self.scheduler,
+            &mut self.interrupt_registers,
+        );
+        self.cd_controller.set_console_region(region);
     }
 
     #[must_use]
     pub fn take_unserialized_fields(&mut self) -> UnserializedFields {
-        let (wgpu_device, wgpu_queue) = self.gpu.get_wgpu_resources();
-
         UnserializedFields {
             disc: self.cd_controller.take_disc(),
+            disc_region: self.cd_controller.disc_region(),
+            detected_region: self.detected_region,
             memory_card_1: self.sio0.memory_card_1().clone(),
-            wgpu_device,
-            wgpu_queue,
+            memory_card_2: self.sio0.memory_card_2().clone(),
+            multitap_memory_cards: self.sio0.multitap_memory_cards().clone(),
+            link_cable: self.sio1.take_link_cable(),
+            wgpu: self.gpu.get_wgpu_resources(),
             config: self.config,
         }
     }
@@ -529,21 +849,30 @@ impl Ps1Emulator {
     pub fn from_state(mut state: Ps1EmulatorState, unserialized: UnserializedFields) -> Self {
         // Don't load memory cards from save states
         *state.sio0.memory_card_1() = unserialized.memory_card_1;
+        *state.sio0.memory_card_2() = unserialized.memory_card_2;
+        *state.sio0.multitap_memory_cards() = unserialized.multitap_memory_cards;
 
         // Important to make the game re-read the memory card header after loading state
-        state.sio0.memory_card_1().clear_written_since_load();
+        if let Some(memory_card) = state.sio0.memory_card_1() {
+            memory_card.clear_written_since_load();
+        }
+        if let Some(memory_card) = state.sio0.memory_card_2() {
+            memory_card.clear_written_since_load();
+        }
+        for memory_card in state.sio0.multitap_memory_cards().iter_mut().flatten().flatten() {
+            memory_card.clear_written_since_load();
+        }
 
         let mut emulator = Self {
             cpu: state.cpu,
-            gpu: Gpu::from_state(
-                state.gpu,
-                unserialized.wgpu_device,
-                unserialized.wgpu_queue,
-                unserialized.config.display,
-            ),
+            gpu: Gpu::from_state(state.gpu, unserialized.wgpu, unserialized.config.display),
             spu: state.spu,
             audio_buffer: state.audio_buffer,
-            cd_controller: CdController::from_state(state.cd_controller, unserialized.disc),
+            cd_controller: CdController::from_state(
+                state.cd_controller,
+                unserialized.disc,
+                unserialized.disc_region,
+            ),
             mdec: state.mdec,
             memory: state.memory,
             memory_control: state.memory_control,
@@ -555,15 +884,25 @@ impl Ps1Emulator {
             scheduler: state.scheduler,
             last_render_cycles: state.last_render_cycles,
             config: unserialized.config,
+            detected_region: unserialized.detected_region,
             tty_buffer: state.tty_buffer,
         };
 
         emulator.update_config(unserialized.config);
+        emulator.set_link_cable(unserialized.link_cable);
 
         emulator
     }
 }
 
+impl Ps1EmulatorState {
+    /// Main RAM, VRAM, and sound RAM, which together make up nearly all of a save state's size.
+    /// Frontends can use these to delta-encode consecutive states against each other.
+    pub fn large_buffers_mut(&mut self) -> [&mut [u8]; 3] {
+        [self.memory.main_ram_mut(), self.gpu.vram_bytes_mut(), self.spu.sound_ram_mut()]
+    }
+}
+
 fn check_for_putchar_call(cpu: &R3000, tty_buffer: &mut String) {
     // BIOS function calls work by jumping to $A0 (A functions), $B0 (B functions), or
     // $C0 (C functions) with the function number specified in R9.
@@ -613,4 +952,25 @@ impl SaveWriter for NullOutput {
     fn save_memory_card_1(&mut self, _card_data: &[u8]) -> Result<(), Self::Err> {
         Ok(())
     }
+
+    fn save_memory_card_2(&mut self, _card_data: &[u8]) -> Result<(), Self::Err> {
+        Ok(())
+    }
+
+    fn save_multitap_memory_card(
+        &mut self,
+        _port: usize,
+        _slot: usize,
+        _card_data: &[u8],
+    ) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+impl RumbleOutput for NullOutput {
+    type Err = String;
+
+    fn set_rumble(&mut self, _rumble: Ps1Rumble) -> Result<(), Self::Err> {
+        Ok(())
+    }
 }
@@ -5,6 +5,7 @@
 
 mod audio;
 mod control;
+mod discinfo;
 mod fifo;
 mod macros;
 mod read;
@@ -12,22 +13,26 @@ mod seek;
 mod status;
 mod xaadpcm;
 
-use crate::cd::audio::PlayState;
+use crate::cd::audio::{AudioScan, PlayState};
 use crate::cd::control::DriveMode;
 use crate::cd::fifo::{DataFifo, ParameterFifo};
 use crate::cd::read::ReadState;
 use crate::cd::xaadpcm::XaAdpcmState;
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::num::U8Ext;
+use crate::region::Region;
 use bincode::{Decode, Encode};
 use cdrom::CdRomResult;
 use cdrom::cdtime::CdTime;
 use cdrom::reader::CdRom;
+use crc::Crc;
 #[allow(clippy::wildcard_imports)]
 use macros::*;
 use proc_macros::SaveState;
 use std::{array, cmp};
 
+const DISC_IDENTITY_CRC: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
 // Roughly 23,796 CPU cycles
 const RECEIVE_COMMAND_CYCLES_STOPPED: u32 = 31;
 
@@ -132,6 +137,15 @@ enum Command {
     SetMode,
     Stop,
     Test,
+    // Added after save states were introduced; kept at the end so that the encoding of the
+    // variants above does not change
+    Backward,
+    Forward,
+    GetParam,
+    GetQ,
+    Reset,
+    SetSession,
+    Invalid(u8),
 }
 
 #[derive(Debug, Clone, Copy, Encode, Decode)]
@@ -142,6 +156,41 @@ enum CommandState {
     GeneratingSecondResponse { command: Command, cycles_remaining: u32 },
 }
 
+impl Command {
+    // Commands that fail with an error while the shell is open because they need to access the disc
+    // or spin up the motor
+    fn requires_closed_shell(self) -> bool {
+        matches!(
+            self,
+            Self::Backward
+                | Self::Forward
+                | Self::GetId
+                | Self::GetLocL
+                | Self::GetLocP
+                | Self::GetQ
+                | Self::GetTD
+                | Self::GetTN
+                | Self::Init
+                | Self::MotorOn
+                | Self::Play
+                | Self::ReadN
+                | Self::ReadS
+                | Self::ReadToc
+                | Self::SeekL
+                | Self::SeekP
+                | Self::SetSession
+        )
+    }
+}
+
+impl Command {
+    // Commands that fail with an error if there is no disc in the drive. GetID instead reports the
+    // missing disc in its second response, and Init succeeds without turning on the motor.
+    fn requires_disc(self) -> bool {
+        self.requires_closed_shell() && !matches!(self, Self::GetId | Self::Init)
+    }
+}
+
 impl Default for CommandState {
     fn default() -> Self {
         Self::Idle
@@ -206,6 +255,11 @@ pub struct CdController {
     index: u8,
     #[save_state(skip)]
     disc: Option<CdRom>,
+    #[save_state(skip)]
+    disc_region: Option<Region>,
+    // Reported by GetID for discs whose region could not be determined
+    #[save_state(skip)]
+    console_region: Region,
     interrupts: CdInterruptRegisters,
     parameter_fifo: ParameterFifo,
     response_fifo: ParameterFifo,
@@ -216,21 +270,41 @@ pub struct CdController {
     drive_mode: DriveMode,
     seek_location: Option<CdTime>,
     scex_read: bool,
+    #[save_state(since = 2)]
+    shell_open: bool,
+    // Status bit 4; stays set after the shell is closed until the next GetStat command
+    #[save_state(since = 2)]
+    shell_opened: bool,
     audio_muted: bool,
     current_audio_sample: (i16, i16),
     cd_to_spu_volume: [[u8; 2]; 2],
     next_cd_to_spu_volume: [[u8; 2]; 2],
     xa_adpcm: XaAdpcmState,
+    #[save_state(since = 12)]
+    audio_scan: AudioScan,
+    // Parameters of the last GetQ command, needed for its second response
+    #[save_state(since = 12)]
+    get_q_params: [u8; 2],
+    // Track of the last audio sector played, used to detect reaching the end of a track when
+    // auto-pause is enabled; 0 if unknown
+    #[save_state(since = 16)]
+    audio_track: u8,
 }
 
 impl CdController {
-    pub fn new(disc: Option<CdRom>) -> Self {
+    pub fn new(mut disc: Option<CdRom>) -> Self {
+        let disc_region = detect_disc_region(disc.as_mut());
+
         // Pretend the SCEx region code was always read if there's a disc in the drive
         let scex_read = disc.is_some();
+        // The drive reports the shell as open when it has never detected a disc
+        let shell_opened = disc.is_none();
 
         Self {
             index: 0,
             disc,
+            disc_region,
+            console_region: Region::NtscU,
             interrupts: CdInterruptRegisters::new(),
             parameter_fifo: ParameterFifo::new(),
             response_fifo: ParameterFifo::new(),
@@ -241,18 +315,29 @@ impl CdController {
             drive_mode: DriveMode::new(),
             seek_location: None,
             scex_read,
+            shell_open: false,
+            shell_opened,
             audio_muted: false,
             current_audio_sample: (0, 0),
             cd_to_spu_volume: [[0; 2]; 2],
             next_cd_to_spu_volume: [[0; 2]; 2],
             xa_adpcm: XaAdpcmState::new(),
+            audio_scan: AudioScan::default(),
+            get_q_params: [0; 2],
+            audio_track: 0,
         }
     }
 
-    pub fn from_state(state: CdControllerState, disc: Option<CdRom>) -> Self {
+    pub fn from_state(
+        state: CdControllerState,
+        disc: Option<CdRom>,
+        disc_region: Option<Region>,
+    ) -> Self {
         Self {
             index: state.index,
             disc,
+            disc_region,
+            console_region: Region::NtscU,
             interrupts: state.interrupts,
             parameter_fifo: state.parameter_fifo,
             response_fifo: state.response_fifo,
@@ -263,11 +348,16 @@ impl CdController {
             drive_mode: state.drive_mode,
             seek_location: state.seek_location,
             scex_read: state.scex_read,
+            shell_open: state.shell_open,
+            shell_opened: state.shell_opened,
             audio_muted: state.audio_muted,
             current_audio_sample: state.current_audio_sample,
             cd_to_spu_volume: state.cd_to_spu_volume,
             next_cd_to_spu_volume: state.next_cd_to_spu_volume,
             xa_adpcm: state.xa_adpcm,
+            audio_scan: state.audio_scan,
+            get_q_params: state.get_q_params,
+            audio_track: state.audio_track,
         }
     }
 
@@ -405,25 +495,39 @@ impl CdController {
     fn execute_command(&mut self, command: Command) -> CommandState {
         log::debug!("Executing command {command:?}");
 
+        if (self.shell_open && command.requires_closed_shell())
+            || (self.disc.is_none() && command.requires_disc())
+        {
+            self.parameter_fifo.reset();
+            return self.execute_not_ready();
+        }
+
         let new_state = match command {
+            Command::Backward => self.execute_scan(AudioScan::Backward),
             Command::Demute => self.execute_demute(),
+            Command::Forward => self.execute_scan(AudioScan::Forward),
             Command::GetId => self.execute_get_id(),
             Command::GetLocL => self.execute_get_loc_l(),
             Command::GetLocP => self.execute_get_loc_p(),
+            Command::GetParam => self.execute_get_param(),
+            Command::GetQ => self.execute_get_q(),
             Command::GetStat => self.execute_get_stat(),
             Command::GetTD => self.execute_get_td(),
             Command::GetTN => self.execute_get_tn(),
             Command::Init => self.execute_init(),
+            Command::Invalid(command_byte) => self.execute_invalid(command_byte),
             Command::MotorOn => self.execute_motor_on(),
             Command::Mute => self.execute_mute(),
             Command::Pause => self.execute_pause(),
             Command::Play => self.execute_play(),
             Command::ReadN | Command::ReadS => self.execute_read(),
             Command::ReadToc => self.execute_read_toc(),
+            Command::Reset => self.execute_reset(),
             Command::SeekL | Command::SeekP => self.execute_seek(),
             Command::SetFilter => self.execute_set_filter(),
             Command::SetLoc => self.execute_set_loc(),
             Command::SetMode => self.execute_set_mode(),
+            Command::SetSession => self.execute_set_session(),
             Command::Stop => self.execute_stop(),
             Command::Test => self.execute_test(),
         };
@@ -440,9 +544,11 @@ impl CdController {
 
         match command {
             Command::GetId => self.get_id_second_response(),
+            Command::GetQ => self.get_q_second_response(),
             Command::Init => self.init_second_response(),
             Command::Pause => self.pause_second_response(),
             Command::ReadToc => self.read_toc_second_response(),
+            Command::SetSession => self.set_session_second_response(),
             Command::Stop => self.stop_second_response(),
             _ => panic!("Invalid state, command {command:?} should not send a second response"),
         }
@@ -483,12 +589,42 @@ impl CdController {
                 // TODO use a different BIOS version?
                 int3!(self, [0x95, 0x07, 0x24, 0xC1]);
             }
-            other => todo!("Test sub-function {other:02X}"),
+            other => {
+                log::warn!("Unsupported Test sub-function {other:02X}");
+                int5!(self, [stat!(self, ERROR), status::INVALID_PARAMETER]);
+            }
         }
 
         CommandState::Idle
     }
 
+    // Error response for commands that need a disc while the shell is open or the drive is empty
+    fn execute_not_ready(&mut self) -> CommandState {
+        int5!(self, [stat!(self, ERROR), status::CANNOT_RESPOND_YET]);
+        CommandState::Idle
+    }
+
+    // Used if the disc is missing while the drive is running, which can only happen if the disc
+    // was removed without opening the shell, e.g. by loading a save state without a disc
+    fn stop_without_disc(&mut self) -> DriveState {
+        log::warn!("No disc in the drive while the drive is running; stopping the motor");
+        int5!(self, [stat!(self, ERROR), status::CANNOT_RESPOND_YET]);
+        DriveState::Stopped
+    }
+
+    // $00, $17-$18, $1F-$FF: Unused or unsupported commands -> INT5(stat | 1, $40)
+    // This includes the VideoCD command ($1F), which only exists on the SCPH-5903, and the secret
+    // unlock commands ($50-$57), which respond with this error even when the unlock succeeds
+    fn execute_invalid(&mut self, command_byte: u8) -> CommandState {
+        match command_byte {
+            0x50..=0x57 => log::debug!("Secret unlock command {command_byte:02X}"),
+            _ => log::warn!("Invalid CD-ROM command {command_byte:02X}"),
+        }
+
+        int5!(self, [stat!(self, ERROR), status::INVALID_COMMAND]);
+        CommandState::Idle
+    }
+
     pub fn read_port(&mut self, address: u32) -> u8 {
         log::trace!("CD-ROM register read: {address:08X}.{}", self.index);
 
@@ -611,6 +747,8 @@ impl CdController {
             0x01 => (Command::GetStat, std_receive_cycles),
             0x02 => (Command::SetLoc, std_receive_cycles),
             0x03 => (Command::Play, std_receive_cycles),
+            0x04 => (Command::Forward, std_receive_cycles),
+            0x05 => (Command::Backward, std_receive_cycles),
             0x06 => (Command::ReadN, std_receive_cycles),
             0x07 => (Command::MotorOn, std_receive_cycles),
             0x08 => (Command::Stop, std_receive_cycles),
@@ -620,8 +758,10 @@ impl CdController {
             0x0C => (Command::Demute, std_receive_cycles),
             0x0D => (Command::SetFilter, std_receive_cycles),
             0x0E => (Command::SetMode, std_receive_cycles),
+            0x0F => (Command::GetParam, std_receive_cycles),
             0x10 => (Command::GetLocL, std_receive_cycles),
             0x11 => (Command::GetLocP, std_receive_cycles),
+            0x12 => (Command::SetSession, std_receive_cycles),
             0x13 => (Command::GetTN, std_receive_cycles),
             0x14 => (Command::GetTD, std_receive_cycles),
             0x15 => (Command::SeekL, std_receive_cycles),
@@ -629,8 +769,10 @@ impl CdController {
             0x19 => (Command::Test, std_receive_cycles),
             0x1A => (Command::GetId, std_receive_cycles),
             0x1B => (Command::ReadS, std_receive_cycles),
+            0x1C => (Command::Reset, std_receive_cycles),
+            0x1D => (Command::GetQ, std_receive_cycles),
             0x1E => (Command::ReadToc, INIT_COMMAND_CYCLES),
-            _ => todo!("Command byte {command_byte:02X}"),
+            _ => (Command::Invalid(command_byte), std_receive_cycles),
         };
         self.command_state = if self.interrupts.int_queued() {
             CommandState::CommandQueued { command, cycles }
@@ -676,15 +818,19 @@ impl CdController {
         log::debug!("  Applied CD-to-SPU volume changes: {}", value.bit(5));
     }
 
+    // Callers handle a missing disc and the end of the disc; if either happens here anyway, the
+    // sector reads as all zeros like the lead-out area
     fn read_sector_atime(&mut self, time: CdTime) -> CdRomResult<()> {
         let Some(disc) = &mut self.disc else {
-            // TODO separate state for no disc?
-            todo!("Read sector with no disc in the drive");
+            log::warn!("Reading sector at {time} with no disc in the drive");
+            self.sector_buffer.fill(0);
+            return Ok(());
         };
 
         let Some(track) = disc.cue().find_track_by_time(time) else {
-            // TODO INT4+pause at disc end
-            todo!("Read to end of disc");
+            log::warn!("Reading sector at {time}, which is past the end of the disc");
+            self.sector_buffer.fill(0);
+            return Ok(());
         };
 
         let track_number = track.number;
@@ -708,6 +854,110 @@ impl CdController {
     pub fn take_disc(&mut self) -> Option<CdRom> {
         self.disc.take()
     }
+
+    /// Open the drive shell. This stops the motor and aborts any seek, read, or play in progress,
+    /// which is reported to software with an INT5 error response.
+    pub fn open_shell(&mut self) {
+        if self.shell_open {
+            return;
+        }
+
+        log::info!("CD-ROM shell opened");
+
+        self.shell_open = true;
+        self.shell_opened = true;
+
+        if self.drive_state != DriveState::Stopped {
+            self.drive_state = DriveState::Stopped;
+            int5!(self, [stat!(self, ERROR), status::SHELL_OPENED]);
+        }
+    }
+
+    /// Close the drive shell. Software detects the change through the shell open status bit, which
+    /// stays set until the next `GetStat` command.
+    pub fn close_shell(&mut self) {
+        if !self.shell_open {
+            return;
+        }
+
+        log::info!("CD-ROM shell closed");
+
+        self.shell_open = false;
+        self.scex_read = self.disc.is_some();
+    }
+
+    pub fn is_shell_open(&self) -> bool {
+        self.shell_open
+    }
+
+    /// Open the shell if it is closed and replace the disc in the drive, returning the previous disc
+    /// if there was one.
+    pub fn insert_disc(&mut self, mut disc: CdRom) -> Option<CdRom> {
+        self.open_shell();
+        self.disc_region = detect_disc_region(Some(&mut disc));
+        self.disc.replace(disc)
+    }
+
+    /// Open the shell if it is closed and remove the disc from the drive.
+    pub fn eject_disc(&mut self) -> Option<CdRom> {
+        self.open_shell();
+        self.disc_region = None;
+        self.disc.take()
+    }
+
+    /// Region of the disc in the drive, if there is a disc and its region could be determined
+    pub fn disc_region(&self) -> Option<Region> {
+        self.disc_region
+    }
+
+    pub fn set_console_region(&mut self, region: Region) {
+        self.console_region = region;
+    }
+
+    /// Compute a CRC-32 that identifies the disc in the drive from its track layout and its ISO 9660
+    /// primary volume descriptor. Returns `None` if there is no disc in the drive.
+    pub fn disc_crc32(&mut self) -> CdRomResult<Option<u32>> {
+        let Some(disc) = &mut self.disc else { return Ok(None) };
+
+        let mut digest = DISC_IDENTITY_CRC.digest();
+        for track_number in 1..=disc.cue().last_track().number {
+            let track = disc.cue().track(track_number);
+            digest.update(&[track.number, track.mode as u8]);
+            digest.update(&track.start_time.to_frames().to_le_bytes());
+            digest.update(&track.end_time.to_frames().to_le_bytes());
+        }
+
+        // The primary volume descriptor is always in sector 16, which is 00:02:16 in track 1 because
+        // of the 2-second pregap
+        let mut sector = [0; BYTES_PER_SECTOR];
+        disc.read_sector(1, CdTime::new(0, 2, 16), &mut sector)?;
+        digest.update(&sector);
+
+        Ok(Some(digest.finalize()))
+    }
+
+    /// Returns the serial number of the disc in the drive (e.g. `SLUS-00594`), if there is a disc
+    /// and its serial number can be determined.
+    pub fn game_id(&mut self) -> CdRomResult<Option<String>> {
+        match &mut self.disc {
+            Some(disc) => discinfo::read_game_id(disc),
+            None => Ok(None),
+        }
+    }
+}
+
+fn detect_disc_region(disc: Option<&mut CdRom>) -> Option<Region> {
+    let disc = disc?;
+    match discinfo::read_region(disc) {
+        Ok(region) => {
+            log::info!("Detected disc region: {region:?}");
+            region
+        }
+        Err(err) => {
+            log::error!("Error detecting disc region: {err}");
+            None
+        }
+    }
 }
 
 fn bcd_to_binary(value: u8) -> u8 {
@@ -717,3 +967,165 @@ fn bcd_to_binary(value: u8) -> u8 {
 fn binary_to_bcd(value: u8) -> u8 {
     ((value / 10) << 4) | (value % 10)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cdrom::reader::CdRomFileFormat;
+    use std::fs;
+
+    // Single 2-second audio track of silence
+    fn test_disc(name: &str) -> CdRom {
+        open_test_disc(
+            name,
+            150,
+            "FILE \"disc.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n",
+        )
+    }
+
+    // Three 2-second audio tracks of silence
+    fn three_track_disc(name: &str) -> CdRom {
+        open_test_disc(
+            name,
+            3 * 150,
+            "FILE \"disc.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    \
+             INDEX 01 00:02:00\n  TRACK 03 AUDIO\n    INDEX 01 00:04:00\n",
+        )
+    }
+
+    fn open_test_disc(name: &str, sectors: usize, cue: &str) -> CdRom {
+        let dir = std::env::temp_dir().join(format!("ps1-cd-test-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("disc.bin"), vec![0; sectors * BYTES_PER_SECTOR]).unwrap();
+        fs::write(dir.join("disc.cue"), cue).unwrap();
+
+        let disc = CdRom::open_in_memory(dir.join("disc.cue"), CdRomFileFormat::CueBin).unwrap();
+        let _ = fs::remove_dir_all(dir);
+        disc
+    }
+
+    fn execute(cd: &mut CdController, command: u8) -> (u8, Vec<u8>) {
+        // Send the command on index 0
+        cd.write_port(0, 0);
+        cd.write_port(1, command);
+
+        next_response(cd)
+    }
+
+    // Acknowledge any previous interrupt, then wait for the next response
+    fn next_response(cd: &mut CdController) -> (u8, Vec<u8>) {
+        let mut interrupt_registers = InterruptRegisters::new();
+
+        cd.write_port(0, 1);
+        cd.write_port(3, 0x1F);
+        cd.write_port(0, 0);
+
+        while !cd.interrupts.int_queued() {
+            cd.clock(&mut interrupt_registers).unwrap();
+        }
+
+        let int = cd.interrupts.flags & 7;
+        let response = (0..cd.response_fifo.len()).map(|_| cd.response_fifo.pop()).collect();
+        (int, response)
+    }
+
+    fn play_track(cd: &mut CdController, track: u8) {
+        cd.write_port(0, 0);
+        cd.write_port(2, track);
+        assert_eq!(execute(cd, 0x03).0, 3);
+
+        let mut interrupt_registers = InterruptRegisters::new();
+        while !matches!(cd.drive_state, DriveState::Playing(..)) {
+            cd.clock(&mut interrupt_registers).unwrap();
+        }
+    }
+
+    #[test]
+    fn shell_open_status() {
+        let mut cd = CdController::new(Some(test_disc("shell")));
+        assert_eq!(execute(&mut cd, 0x01), (3, vec![0x00]));
+
+        cd.open_shell();
+        assert_eq!(execute(&mut cd, 0x01), (3, vec![0x10]));
+        assert_eq!(execute(&mut cd, 0x1A), (5, vec![0x11, status::CANNOT_RESPOND_YET]));
+
+        // Shell open bit stays set until the first GetStat after the shell is closed
+        cd.close_shell();
+        assert_eq!(execute(&mut cd, 0x01), (3, vec![0x10]));
+        assert_eq!(execute(&mut cd, 0x01), (3, vec![0x00]));
+        assert_eq!(execute(&mut cd, 0x13), (3, vec![0x00, 0x01, 0x01]));
+    }
+
+    #[test]
+    fn no_disc() {
+        let mut cd = CdController::new(None);
+        let not_ready = (5, vec![0x11, status::CANNOT_RESPOND_YET]);
+
+        // Shell open bit stays set while there is no disc
+        assert_eq!(execute(&mut cd, 0x01), (3, vec![0x10]));
+        assert_eq!(execute(&mut cd, 0x01), (3, vec![0x10]));
+
+        for command in [0x03, 0x06, 0x07, 0x11, 0x13, 0x14, 0x15, 0x1E] {
+            assert_eq!(execute(&mut cd, command), not_ready, "command {command:02X}");
+        }
+
+        // Init succeeds without turning on the motor
+        assert_eq!(execute(&mut cd, 0x0A), (3, vec![0x10]));
+        assert_eq!(next_response(&mut cd), (2, vec![0x10]));
+
+        assert_eq!(execute(&mut cd, 0x1A), (3, vec![0x10]));
+        assert_eq!(next_response(&mut cd), (5, vec![0x08, 0x40, 0, 0, 0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let mut cd = CdController::new(Some(test_disc("invalid")));
+        let invalid_command = (5, vec![0x01, status::INVALID_COMMAND]);
+
+        for command in [0x00, 0x17, 0x1F, 0x50, 0x57, 0x5F, 0xFF] {
+            assert_eq!(execute(&mut cd, command), invalid_command, "command {command:02X}");
+        }
+
+        // Forward/Backward while audio is not playing
+        assert_eq!(execute(&mut cd, 0x04), (5, vec![0x01, status::CANNOT_RESPOND_YET]));
+
+        // GetParam after Reset, which leaves the motor running
+        assert_eq!(execute(&mut cd, 0x1C), (3, vec![0x00]));
+        assert_eq!(execute(&mut cd, 0x0F), (3, vec![0x02, 0x20, 0x00, 0x00, 0x00]));
+    }
+
+    #[test]
+    fn scan_auto_pauses_at_track_boundaries() {
+        let mut cd = CdController::new(Some(three_track_disc("scan")));
+        let track_start =
+            |cd: &CdController, track| cd.disc.as_ref().unwrap().cue().track(track).start_time;
+
+        // SetMode: auto-pause
+        cd.write_port(0, 0);
+        cd.write_port(2, 0x02);
+        assert_eq!(execute(&mut cd, 0x0E).0, 3);
+
+        // Scanning forward skips most sectors, including the first sector of track 3
+        play_track(&mut cd, 2);
+        assert_eq!(execute(&mut cd, 0x04).0, 3);
+        assert_eq!(next_response(&mut cd).0, 4);
+        let track_3_start = track_start(&cd, 3);
+        assert_eq!(
+            cd.drive_state,
+            DriveState::Paused { time: track_3_start - CdTime::new(0, 0, 1), int2_queued: false }
+        );
+
+        // Scanning backward into track 2 does not pause
+        play_track(&mut cd, 3);
+        assert_eq!(execute(&mut cd, 0x05).0, 3);
+        let track_2_start = track_start(&cd, 2);
+        let mut interrupt_registers = InterruptRegisters::new();
+        cd.write_port(0, 1);
+        cd.write_port(3, 0x1F);
+        while cd.drive_state.current_time() >= track_2_start {
+            cd.clock(&mut interrupt_registers).unwrap();
+            assert!(!cd.interrupts.int_queued());
+        }
+        assert!(matches!(cd.drive_state, DriveState::Playing(..)));
+    }
+}
@@ -3,7 +3,7 @@
 use crate::cd;
 #[allow(clippy::wildcard_imports)]
 use crate::cd::macros::*;
-use crate::cd::{CdController, CommandState, DriveState, SeekNextState, seek};
+use crate::cd::{CdController, CommandState, DriveState, SeekNextState, seek, status};
 use bincode::{Decode, Encode};
 use cdrom::CdRomResult;
 use cdrom::cdtime::CdTime;
@@ -11,6 +11,9 @@ use cdrom::cdtime::CdTime;
 pub const CD_DA_SAMPLES_PER_SECTOR: u16 = 588;
 const SECTORS_BETWEEN_REPORTS: u8 = 16;
 
+// Sectors that the drive moves per sector played while fast forwarding or rewinding
+const SCAN_SECTORS: CdTime = CdTime { minutes: 0, seconds: 0, frames: 8 };
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 pub enum AudioReportType {
     Absolute,
@@ -26,6 +29,14 @@ impl AudioReportType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub enum AudioScan {
+    #[default]
+    Off,
+    Forward,
+    Backward,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 pub struct PlayState {
     pub time: CdTime,
@@ -72,13 +83,14 @@ impl CdController {
     // If track parameter is zero or not present, begins playback from the last SetLoc location, or
     // the current time if there is no unprocessed SetLoc location.
     pub(super) fn execute_play(&mut self) -> CommandState {
-        int3!(self, [stat!(self)]);
-
         let Some(disc) = &self.disc else {
-            // TODO generate error response
-            todo!("Play command issued with no disc");
+            return self.execute_not_ready();
         };
 
+        int3!(self, [stat!(self)]);
+
+        self.audio_scan = AudioScan::Off;
+
         let track_number = if self.parameter_fifo.empty() {
             0
         } else {
@@ -104,14 +116,46 @@ impl CdController {
         CommandState::Idle
     }
 
+    // $04: Forward() -> INT3(stat), INT1(report)*
+    // $05: Backward() -> INT3(stat), INT1(report)*
+    // Fast forwards or rewinds audio playback until the next Play command (or until the start of
+    // the disc when rewinding). Returns an INT5 error response if audio is not playing.
+    pub(super) fn execute_scan(&mut self, scan: AudioScan) -> CommandState {
+        if !matches!(self.drive_state, DriveState::PreparingToPlay { .. } | DriveState::Playing(..))
+        {
+            int5!(self, [stat!(self, ERROR), status::CANNOT_RESPOND_YET]);
+            return CommandState::Idle;
+        }
+
+        int3!(self, [stat!(self)]);
+
+        log::debug!("Executing {scan:?} scan from {}", self.drive_state.current_time());
+        self.audio_scan = scan;
+
+        CommandState::Idle
+    }
+
+    fn next_play_time(&mut self, time: CdTime) -> CdTime {
+        match self.audio_scan {
+            AudioScan::Off => time + CdTime::new(0, 0, 1),
+            AudioScan::Forward => time + SCAN_SECTORS,
+            AudioScan::Backward => {
+                if time <= SCAN_SECTORS {
+                    self.audio_scan = AudioScan::Off;
+                    return CdTime::ZERO;
+                }
+                time - SCAN_SECTORS
+            }
+        }
+    }
+
     pub(super) fn read_audio_sector(
         &mut self,
         PlayState { time, mut sectors_till_report, mut next_report_type, .. }: PlayState,
         first_sector: bool,
     ) -> CdRomResult<DriveState> {
         let Some(disc) = &self.disc else {
-            // TODO generate error INT
-            panic!("Reading audio sector with no disc in the drive");
+            return Ok(self.stop_without_disc());
         };
 
         let num_tracks = disc.cue().last_track().number;
@@ -131,15 +175,22 @@ impl CdController {
             return Ok(self.drive_state);
         };
 
-        // If auto-pause is enabled and the drive moved to a new audio track, pause at the end of
-        // the previous track and generate INT4
+        // If auto-pause is enabled and the drive moved forward into a new audio track, pause at the
+        // end of the previous track and generate INT4. Scanning moves several sectors at a time and
+        // can skip over a track's first sector, so this compares against the track of the previous
+        // sector. Rewinding into an earlier track does not count as reaching the end of a track
+        let previous_track = self.audio_track;
+        self.audio_track = track.number;
         if self.drive_mode.auto_pause_audio
             && !first_sector
             && track.number > 2
-            && time == track.start_time
+            && previous_track != 0
+            && track.number > previous_track
         {
-            self.drive_state =
-                DriveState::Paused { time: time - CdTime::new(0, 0, 1), int2_queued: false };
+            self.drive_state = DriveState::Paused {
+                time: track.start_time - CdTime::new(0, 0, 1),
+                int2_queued: false,
+            };
             int4!(self, [stat!(self)]);
             return Ok(self.drive_state);
         }
@@ -184,7 +235,7 @@ impl CdController {
         self.read_sector_atime(time)?;
 
         Ok(DriveState::Playing(PlayState {
-            time: time + CdTime::new(0, 0, 1),
+            time: self.next_play_time(time),
             sample_idx: 0,
             sectors_till_report: sectors_till_report - 1,
             next_report_type,
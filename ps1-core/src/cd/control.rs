@@ -1,6 +1,7 @@
 //! CD-ROM control commands
 
 use crate::cd;
+use crate::cd::audio::AudioScan;
 #[allow(clippy::wildcard_imports)]
 use crate::cd::macros::*;
 use crate::cd::{
@@ -8,6 +9,7 @@ use crate::cd::{
 };
 use crate::num::U8Ext;
 use bincode::{Decode, Encode};
+use cdrom::cdtime::CdTime;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
 pub enum DriveSpeed {
@@ -48,6 +50,19 @@ impl DriveMode {
     }
 }
 
+impl DriveMode {
+    // Bit 4 is not stored and always reads as 0
+    pub fn to_byte(&self) -> u8 {
+        (u8::from(self.speed == DriveSpeed::Double) << 7)
+            | (u8::from(self.adpcm_enabled) << 6)
+            | (u8::from(self.raw_sectors) << 5)
+            | (u8::from(self.adpcm_filter_enabled) << 3)
+            | (u8::from(self.audio_report_interrupts) << 2)
+            | (u8::from(self.auto_pause_audio) << 1)
+            | u8::from(self.cd_da_enabled)
+    }
+}
+
 impl From<u8> for DriveMode {
     fn from(mode: u8) -> Self {
         let speed = DriveSpeed::from_bit(mode.bit(7));
@@ -93,6 +108,11 @@ impl CdController {
         int3!(self, [stat!(self)]);
 
         match self.drive_state {
+            // With no disc, the motor stops again as soon as the drive fails to find one
+            DriveState::Stopped if self.disc.is_none() => CommandState::GeneratingSecondResponse {
+                command: Command::Init,
+                cycles_remaining: status::GET_ID_SECOND_CYCLES,
+            },
             DriveState::Stopped => {
                 self.drive_state = DriveState::SpinningUp {
                     cycles_remaining: cd::SPIN_UP_CYCLES,
@@ -117,6 +137,31 @@ impl CdController {
         CommandState::Idle
     }
 
+    // $1C: Reset() -> INT3(stat)
+    // Resets the drive controller, which has the same effect as opening and closing the shell.
+    // There is no second response; software is expected to wait roughly 1/8 of a second before
+    // sending another command.
+    pub(super) fn execute_reset(&mut self) -> CommandState {
+        int3!(self, [stat!(self)]);
+
+        self.drive_mode = DriveMode::from(0x20);
+        self.audio_muted = false;
+        self.audio_scan = AudioScan::Off;
+        self.seek_location = None;
+        self.xa_adpcm.clear_buffers();
+
+        // The drive re-reads the lead-in (including the SCEx string) and then pauses at the start
+        // of the disc, which is done immediately here since software has to wait anyway
+        self.scex_read = self.disc.is_some();
+        self.drive_state = if self.disc.is_some() {
+            DriveState::Paused { time: CdTime::ZERO, int2_queued: false }
+        } else {
+            DriveState::Stopped
+        };
+
+        CommandState::Idle
+    }
+
     // $0E: SetMode(mode) -> INT3(stat)
     // Configures drive mode
     pub(super) fn execute_set_mode(&mut self) -> CommandState {
@@ -0,0 +1,107 @@
+//! Disc identification based on the disc's file system
+
+use crate::cd::BYTES_PER_SECTOR;
+use crate::region::Region;
+use cdrom::CdRomResult;
+use cdrom::cdtime::CdTime;
+use cdrom::cue::TrackMode;
+use cdrom::iso9660;
+use cdrom::reader::CdRom;
+
+/// Read the game's serial number (e.g. `SLUS-00594`) from the boot executable path in
+/// `SYSTEM.CNF`. Returns `None` if the disc has no `SYSTEM.CNF` or the boot path does not contain
+/// a serial number.
+pub fn read_game_id(disc: &mut CdRom) -> CdRomResult<Option<String>> {
+    let Some(system_cnf) = iso9660::read_root_file(disc, "SYSTEM.CNF")? else { return Ok(None) };
+
+    Ok(parse_game_id(&String::from_utf8_lossy(&system_cnf)))
+}
+
+/// Determine the disc's region from the license text in sector 4, falling back to the serial number
+/// prefix in `SYSTEM.CNF`. Returns `None` for audio CDs and for discs with neither.
+pub fn read_region(disc: &mut CdRom) -> CdRomResult<Option<Region>> {
+    let user_data_offset = match disc.cue().track(1).mode {
+        TrackMode::Mode1 => 16,
+        TrackMode::Mode2 => 24,
+        TrackMode::Audio => return Ok(None),
+    };
+
+    // Sector 4 is 00:02:04 in track 1 because of the 2-second pregap
+    let mut sector = [0; BYTES_PER_SECTOR];
+    disc.read_sector(1, CdTime::new(0, 2, 4), &mut sector)?;
+    if let Some(region) = parse_license_region(&sector[user_data_offset..]) {
+        return Ok(Some(region));
+    }
+
+    Ok(read_game_id(disc)?.and_then(|game_id| Region::from_game_id(&game_id)))
+}
+
+// "          Licensed  by          Sony Computer Entertainment Amer  ica "
+fn parse_license_region(user_data: &[u8]) -> Option<Region> {
+    const LICENSEE: &[u8] = b"Sony Computer Entertainment ";
+
+    let text = &user_data[..user_data.len().min(128)];
+    let start = text.windows(LICENSEE.len()).position(|w| w == LICENSEE)? + LICENSEE.len();
+    let region = &text[start..];
+
+    if region.starts_with(b"Amer") {
+        Some(Region::NtscU)
+    } else if region.starts_with(b"Euro") {
+        Some(Region::Pal)
+    } else if region.starts_with(b"Inc") {
+        Some(Region::NtscJ)
+    } else {
+        None
+    }
+}
+
+fn parse_game_id(system_cnf: &str) -> Option<String> {
+    let boot_path = system_cnf.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        key.trim().eq_ignore_ascii_case("BOOT").then(|| value.trim())
+    })?;
+
+    // BOOT = cdrom:\SLUS_005.94;1
+    let file_name = boot_path.rsplit(['\\', ':', '/']).next()?;
+    let file_name = file_name.split(';').next()?;
+
+    let (prefix, number) = file_name.split_once(['_', '-'])?;
+    let number = number.replace('.', "");
+    if prefix.len() != 4
+        || !prefix.chars().all(|c| c.is_ascii_alphabetic())
+        || number.is_empty()
+        || !number.chars().all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+
+    Some(format!("{}-{number}", prefix.to_ascii_uppercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_id_from_boot_path() {
+        assert_eq!(
+            parse_game_id("BOOT = cdrom:\\SLUS_005.94;1\r\nTCB = 4\r\nEVENT = 10\r\n"),
+            Some("SLUS-00594".into())
+        );
+        assert_eq!(parse_game_id("BOOT=cdrom:\\scps_100.01;1"), Some("SCPS-10001".into()));
+        assert_eq!(parse_game_id("BOOT = cdrom:\\GAME\\MAIN.EXE;1"), None);
+        assert_eq!(parse_game_id("TCB = 4"), None);
+    }
+
+    #[test]
+    fn region_from_license_text() {
+        let license = |region: &str| {
+            format!("          Licensed  by          Sony Computer Entertainment {region}")
+                .into_bytes()
+        };
+        assert_eq!(parse_license_region(&license("Amer  ica ")), Some(Region::NtscU));
+        assert_eq!(parse_license_region(&license("Euro pe   ")), Some(Region::Pal));
+        assert_eq!(parse_license_region(&license("Inc.")), Some(Region::NtscJ));
+        assert_eq!(parse_license_region(&[0; 2048]), None);
+    }
+}
@@ -79,6 +79,19 @@ impl CdController {
     }
 
     pub(super) fn read_data_sector(&mut self, time: CdTime) -> CdRomResult<DriveState> {
+        let Some(disc) = &self.disc else {
+            return Ok(self.stop_without_disc());
+        };
+
+        if disc.cue().find_track_by_time(time).is_none() {
+            // Reading past the end of the disc pauses at the last sector and generates INT4
+            log::debug!("Data read reached the end of the disc at {time}");
+            self.drive_state =
+                DriveState::Paused { time: time - CdTime::new(0, 0, 1), int2_queued: false };
+            int4!(self, [stat!(self)]);
+            return Ok(self.drive_state);
+        }
+
         self.read_sector_atime(time)?;
 
         log::debug!(
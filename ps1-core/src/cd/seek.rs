@@ -5,13 +5,18 @@ use crate::cd::audio::PlayState;
 #[allow(clippy::wildcard_imports)]
 use crate::cd::macros::*;
 use crate::cd::read::ReadState;
-use crate::cd::{CdController, CommandState, DriveState, SeekNextState, SpinUpNextState, status};
+use crate::cd::{
+    CdController, Command, CommandState, DriveState, SeekNextState, SpinUpNextState, status,
+};
 use cdrom::cdtime::CdTime;
 use std::cmp;
 
 // The BIOS does not like if a seek finishes too quickly
 pub const MIN_SEEK_CYCLES: u32 = 24;
 
+// Roughly a second
+const SET_SESSION_ERROR_CYCLES: u32 = 44_100;
+
 impl CdController {
     // $02: SetLoc(amm, ass, asect) -> INT3(stat)
     // Sets seek location to the specified absolute time
@@ -62,6 +67,44 @@ impl CdController {
 
         CommandState::Idle
     }
+
+    // $12: SetSession(session) -> INT3(stat), INT2(stat)
+    // Seeks to the start of the specified session. Multi-session discs are not supported, so
+    // selecting any session other than 1 fails after the drive searches for it and stops the motor.
+    pub(super) fn execute_set_session(&mut self) -> CommandState {
+        if self.parameter_fifo.len() != 1 {
+            int5!(self, [stat!(self, ERROR), status::WRONG_NUM_PARAMETERS]);
+            return CommandState::Idle;
+        }
+
+        let session = self.parameter_fifo.pop();
+        if session == 0 {
+            int5!(self, [stat!(self, ERROR), status::INVALID_PARAMETER]);
+            return CommandState::Idle;
+        }
+
+        int3!(self, [stat!(self)]);
+
+        if session != 1 || self.disc.is_none() {
+            log::debug!("SetSession to nonexistent session {session}");
+            return CommandState::GeneratingSecondResponse {
+                command: Command::SetSession,
+                cycles_remaining: SET_SESSION_ERROR_CYCLES,
+            };
+        }
+
+        // INT2 is generated when the seek finishes
+        self.drive_state =
+            determine_drive_state(self.drive_state, CdTime::ZERO, SeekNextState::Pause);
+
+        CommandState::Idle
+    }
+
+    pub(super) fn set_session_second_response(&mut self) -> CommandState {
+        int5!(self, [stat!(self, SEEK_ERROR), status::INVALID_COMMAND]);
+        self.drive_state = DriveState::Stopped;
+        CommandState::Idle
+    }
 }
 
 pub(super) fn determine_drive_state(
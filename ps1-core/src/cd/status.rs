@@ -8,6 +8,7 @@ use cdrom::cdtime::CdTime;
 use cdrom::cue::TrackMode;
 use std::ops::BitOr;
 
+pub const SHELL_OPENED: u8 = 0x08;
 pub const INVALID_PARAMETER: u8 = 0x10;
 pub const WRONG_NUM_PARAMETERS: u8 = 0x20;
 pub const INVALID_COMMAND: u8 = 0x40;
@@ -19,12 +20,15 @@ pub const GET_ID_SECOND_CYCLES: u32 = 24;
 // Roughly a second
 const READ_TOC_SECOND_CYCLES: u32 = 44_100;
 
+// Roughly a quarter second
+const GET_Q_SECOND_CYCLES: u32 = 11_025;
+
 pub struct ErrorFlags(u8);
 
 impl ErrorFlags {
     pub const NONE: Self = Self(0);
     pub const ERROR: Self = Self(1);
-    // pub const SEEK_ERROR: Self = Self(1 << 2);
+    pub const SEEK_ERROR: Self = Self(1 << 2);
     // pub const ID_ERROR: Self = Self(1 << 3);
 }
 
@@ -49,18 +53,24 @@ impl CdController {
             DriveState::PreparingToPlay { .. } | DriveState::Playing { .. }
         );
 
-        // TODO Bit 4 (shell open)
         errors.0
             | (u8::from(motor_on) << 1)
+            | (u8::from(self.shell_opened) << 4)
             | (u8::from(reading) << 5)
             | (u8::from(seeking) << 6)
             | (u8::from(playing) << 7)
     }
 
     // $01: GetStat() -> INT3(stat)
-    // Simply returns current status code
+    // Returns current status code, then clears the shell open bit if the shell has been closed.
+    // The bit stays set while there is no disc in the drive.
     pub(super) fn execute_get_stat(&mut self) -> CommandState {
         int3!(self, [stat!(self)]);
+
+        if !self.shell_open && self.disc.is_some() {
+            self.shell_opened = false;
+        }
+
         CommandState::Idle
     }
 
@@ -79,15 +89,19 @@ impl CdController {
 
     pub(super) fn get_id_second_response(&mut self) -> CommandState {
         match &self.disc {
-            // TODO don't hardcode region
+            // Discs with no detectable region report the console region in the SCEx string
             Some(disc) => {
                 let status = stat!(self);
                 let mode_byte = match disc.cue().track(1).mode {
                     TrackMode::Mode2 => 0x20,
                     TrackMode::Mode1 | TrackMode::Audio => 0x00,
                 };
+                let region = self.disc_region.unwrap_or(self.console_region);
 
-                int2!(self, [status, 0x00, mode_byte, 0x00, b'S', b'C', b'E', b'A']);
+                int2!(
+                    self,
+                    [status, 0x00, mode_byte, 0x00, b'S', b'C', b'E', region.scex_letter()]
+                );
             }
             None => {
                 // "No disc" response
@@ -114,18 +128,118 @@ impl CdController {
         CommandState::Idle
     }
 
+    // $0F: GetParam() -> INT3(stat, mode, 0, file, channel)
+    // Returns the current drive mode and XA-ADPCM filter
+    pub(super) fn execute_get_param(&mut self) -> CommandState {
+        int3!(
+            self,
+            [
+                stat!(self),
+                self.drive_mode.to_byte(),
+                0x00,
+                self.xa_adpcm.file,
+                self.xa_adpcm.channel
+            ]
+        );
+
+        CommandState::Idle
+    }
+
+    // $1D: GetQ(adr, point) -> INT3(stat), INT2(adr, 0, point, mm, ss, sect, 0, pmm, pss, psect)
+    // Returns the Subchannel Q data for the specified point in the lead-in area. Only ADR 1 (the
+    // table of contents) is supported.
+    pub(super) fn execute_get_q(&mut self) -> CommandState {
+        if self.parameter_fifo.len() < 2 {
+            int5!(self, [stat!(self, ERROR), WRONG_NUM_PARAMETERS]);
+            return CommandState::Idle;
+        }
+
+        self.get_q_params = [self.parameter_fifo.pop(), self.parameter_fifo.pop()];
+
+        int3!(self, [stat!(self)]);
+        CommandState::GeneratingSecondResponse {
+            command: Command::GetQ,
+            cycles_remaining: GET_Q_SECOND_CYCLES,
+        }
+    }
+
+    pub(super) fn get_q_second_response(&mut self) -> CommandState {
+        let [adr, point] = self.get_q_params;
+        match self.lead_in_q(adr, point) {
+            Some(q) => {
+                generate_response!(
+                    self, 2, q[0], q[1], q[2], q[3], q[4], q[5], q[6], q[7], q[8], q[9]
+                );
+            }
+            None => {
+                log::debug!("GetQ point not found: adr={adr:02X} point={point:02X}");
+                int5!(self, [stat!(self, ERROR), CANNOT_RESPOND_YET]);
+            }
+        }
+
+        CommandState::Idle
+    }
+
+    // Lead-in Q data does not include the running time, so it is always reported as 00:00:00
+    fn lead_in_q(&self, adr: u8, point: u8) -> Option<[u8; 10]> {
+        let disc = self.disc.as_ref()?;
+        if adr != 1 {
+            return None;
+        }
+
+        let cue = disc.cue();
+        let control_adr = |track_number: u8| match cue.track(track_number).mode {
+            TrackMode::Audio => 0x01,
+            TrackMode::Mode1 | TrackMode::Mode2 => 0x41,
+        };
+        let bcd_time = |time: CdTime| {
+            [
+                cd::binary_to_bcd(time.minutes),
+                cd::binary_to_bcd(time.seconds),
+                cd::binary_to_bcd(time.frames),
+            ]
+        };
+        let last_track = cue.last_track().number;
+
+        let (control_adr, [pmin, psec, psect]) = match point {
+            // First track number and disc type
+            0xA0 => {
+                let disc_type = match cue.track(1).mode {
+                    TrackMode::Mode2 => 0x20,
+                    TrackMode::Mode1 | TrackMode::Audio => 0x00,
+                };
+                (control_adr(1), [0x01, disc_type, 0x00])
+            }
+            // Last track number
+            0xA1 => (control_adr(last_track), [cd::binary_to_bcd(last_track), 0x00, 0x00]),
+            // Lead-out start time
+            0xA2 => {
+                let lead_out = cue.last_track().end_time + CdTime::new(0, 0, 1);
+                (control_adr(last_track), bcd_time(lead_out))
+            }
+            // Track start time
+            _ => {
+                let track_number = cd::bcd_to_binary(point);
+                if track_number == 0 || track_number > last_track {
+                    return None;
+                }
+                let start_time = cue.track(track_number).effective_start_time();
+                (control_adr(track_number), bcd_time(start_time))
+            }
+        };
+
+        Some([control_adr, 0x00, point, 0x00, 0x00, 0x00, 0x00, pmin, psec, psect])
+    }
+
     // $13: GetTN() -> INT3(stat, first, last)
     // Returns the first and last track numbers on the disc
     pub(super) fn execute_get_tn(&mut self) -> CommandState {
-        let (first, last) = match &self.disc {
-            Some(disc) => (1_u8, cd::binary_to_bcd(disc.cue().last_track().number)),
-            None => {
-                // TODO this should be an INT5 error?
-                (1, 1)
-            }
+        let Some(disc) = &self.disc else {
+            return self.execute_not_ready();
         };
 
-        int3!(self, [stat!(self), first, last]);
+        let last = cd::binary_to_bcd(disc.cue().last_track().number);
+        int3!(self, [stat!(self), 0x01, last]);
 
         CommandState::Idle
     }
@@ -139,8 +253,7 @@ impl CdController {
         }
 
         let Some(disc) = &self.disc else {
-            // TODO this should be an INT5 error?
-            todo!("GetTD commmand with no disc in the drive");
+            return self.execute_not_ready();
         };
 
         let last_track = disc.cue().last_track().number;
@@ -186,7 +299,7 @@ impl CdController {
     // Returns position data from Subchannel Q
     pub(super) fn execute_get_loc_p(&mut self) -> CommandState {
         let Some(disc) = &self.disc else {
-            todo!("GetLocP executed with no disc in the drive");
+            return self.execute_not_ready();
         };
 
         // TODO better handle if this is executed while seeking
@@ -7,7 +7,10 @@
 //! - Mono or Stereo
 //! - 37800 Hz or 18900 Hz
 //! - 4-bit or 8-bit samples
-//! - Emphasis flag (apparently not used by any released games)
+//! - Emphasis flag (apparently not used by any released games); emphasized audio is played back
+//!   through the standard 50/15 µs de-emphasis filter
+//!
+//! Sectors with reserved coding info values are muted.
 //!
 //! Each sector contains 18 data blocks that are each 128 bytes. The number of samples in each data
 //! block depends on coding info and is one of the following:
@@ -17,7 +20,8 @@
 //!
 //! In total, that means each ADPCM sector contains one of the following:
 //! - 4032 4-bit Mono samples (equivalent to 8 CD-DA sectors at 37800 Hz or 16 at 18900 Hz)
-//! - 2016 4-bit Stereo samples (equivalent to 4 CD-DA sectors at 37800 Hz or 8 at 18900 Hz)
+//! - 2016 4-bit Stereo samples or 8-bit Mono samples (equivalent to 4 CD-DA sectors at 37800 Hz
+//!   or 8 at 18900 Hz)
 //! - 1008 8-bit Stereo samples (equivalent to 2 CD-DA sectors at 37800 Hz or 4 at 18900 Hz)
 //!
 //! Each 128-byte data block is split into either 8 audio blocks (4-bit samples) or 4 audio blocks
@@ -28,12 +32,16 @@
 //! Data blocks begin with a 16-byte header that specifies the ADPCM shift and filter values for each
 //! audio block. The remaining 112 bytes contain interleaved ADPCM sample values: the first sample
 //! from each block, then the second sample from each block, then the third, etc. The final 4 bytes
-//! contain the 28th sample from each block.
+//! contain the 28th sample from each block. With 4-bit samples each byte holds samples from two
+//! consecutive audio blocks, low nibble first; with 8-bit samples each byte is one sample.
 
 mod tables;
 
 use crate::num::U8Ext;
+use crate::savestate;
 use crate::spu::adpcm;
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 
 const FILTER_0_TABLE: [i32; 5] = adpcm::FILTER_0_TABLE;
@@ -81,7 +89,88 @@ enum SampleRate {
     Half,
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BitsPerSample {
+    Four,
+    Eight,
+}
+
+impl BitsPerSample {
+    fn audio_blocks_per_data_block(self) -> usize {
+        match self {
+            Self::Four => 8,
+            Self::Eight => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct CodingInfo {
+    channel_mode: ChannelMode,
+    sample_rate: SampleRate,
+    bits_per_sample: BitsPerSample,
+    emphasis: bool,
+}
+
+impl CodingInfo {
+    // Returns None if any field has a reserved value
+    fn from_byte(byte: u8) -> Option<Self> {
+        let channel_mode = match byte & 3 {
+            0 => ChannelMode::Mono,
+            1 => ChannelMode::Stereo,
+            _ => return None,
+        };
+
+        let sample_rate = match (byte >> 2) & 3 {
+            0 => SampleRate::Normal,
+            1 => SampleRate::Half,
+            _ => return None,
+        };
+
+        let bits_per_sample = match (byte >> 4) & 3 {
+            0 => BitsPerSample::Four,
+            1 => BitsPerSample::Eight,
+            _ => return None,
+        };
+
+        Some(Self { channel_mode, sample_rate, bits_per_sample, emphasis: byte.bit(6) })
+    }
+}
+
+// First-order IIR de-emphasis filter for the 50/15 µs emphasis curve at 44100 Hz, derived using the
+// bilinear transform of H(s) = (1 + 15µs * s) / (1 + 50µs * s). Coefficients are 1.15 fixed point
+const DEEMPHASIS_B0: i32 = 14070;
+const DEEMPHASIS_B1: i32 = -1956;
+const DEEMPHASIS_A1: i32 = 20654;
+
+#[derive(Debug, Clone, Default, Encode, Decode)]
+struct DeemphasisFilter {
+    prev_input: i32,
+    prev_output: i32,
+}
+
+impl DeemphasisFilter {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn apply(&mut self, samples: &mut [i16]) {
+        for sample in samples {
+            let input = i32::from(*sample);
+            let output = (DEEMPHASIS_B0 * input
+                + DEEMPHASIS_B1 * self.prev_input
+                + DEEMPHASIS_A1 * self.prev_output
+                + 0x4000)
+                >> 15;
+
+            self.prev_input = input;
+            self.prev_output = output;
+            *sample = output.clamp(i16::MIN.into(), i16::MAX.into()) as i16;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
 pub struct XaAdpcmState {
     pub file: u8,
     pub channel: u8,
@@ -97,8 +186,43 @@ pub struct XaAdpcmState {
     resample_ring_buffer_r: ResampleRingBuffer,
     channel_mode: ChannelMode,
     sample_rate: SampleRate,
+    deemphasis_l: DeemphasisFilter,
+    deemphasis_r: DeemphasisFilter,
+}
+
+impl Decode for XaAdpcmState {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let mut state = Self {
+            file: Decode::decode(decoder)?,
+            channel: Decode::decode(decoder)?,
+            muted: Decode::decode(decoder)?,
+            adpcm_buffer_l: Decode::decode(decoder)?,
+            adpcm_buffer_r: Decode::decode(decoder)?,
+            output_buffer_l: Decode::decode(decoder)?,
+            output_buffer_r: Decode::decode(decoder)?,
+            output_idx: Decode::decode(decoder)?,
+            block_buffer_l: Decode::decode(decoder)?,
+            block_buffer_r: Decode::decode(decoder)?,
+            resample_ring_buffer_l: Decode::decode(decoder)?,
+            resample_ring_buffer_r: Decode::decode(decoder)?,
+            channel_mode: Decode::decode(decoder)?,
+            sample_rate: Decode::decode(decoder)?,
+            deemphasis_l: DeemphasisFilter::default(),
+            deemphasis_r: DeemphasisFilter::default(),
+        };
+
+        // De-emphasis was added in version 14
+        if savestate::decoding_format_version() >= 14 {
+            state.deemphasis_l = Decode::decode(decoder)?;
+            state.deemphasis_r = Decode::decode(decoder)?;
+        }
+
+        Ok(state)
+    }
 }
 
+bincode::impl_borrow_decode!(XaAdpcmState);
+
 impl XaAdpcmState {
     pub fn new() -> Self {
         Self {
@@ -116,6 +240,8 @@ impl XaAdpcmState {
             resample_ring_buffer_r: ResampleRingBuffer::new(),
             channel_mode: ChannelMode::Stereo,
             sample_rate: SampleRate::Normal,
+            deemphasis_l: DeemphasisFilter::default(),
+            deemphasis_r: DeemphasisFilter::default(),
         }
     }
 
@@ -129,42 +255,45 @@ impl XaAdpcmState {
         self.block_buffer_r.fill(0);
         self.resample_ring_buffer_l.clear();
         self.resample_ring_buffer_r.clear();
+        self.deemphasis_l.reset();
+        self.deemphasis_r.reset();
     }
 
     pub fn decode_sector(&mut self, sector: &[u8]) {
-        let coding_info = sector[19];
-        if coding_info != 0x00 && coding_info != 0x01 && coding_info != 0x04 && coding_info != 0x05
-        {
-            todo!("CD-XA ADPCM sector with coding info {coding_info:02X}");
-        }
-
-        self.channel_mode =
-            if coding_info.bit(0) { ChannelMode::Stereo } else { ChannelMode::Mono };
-
-        self.sample_rate = if coding_info.bit(2) { SampleRate::Half } else { SampleRate::Normal };
-
         self.adpcm_buffer_l.clear();
         self.adpcm_buffer_r.clear();
         self.output_buffer_l.clear();
         self.output_buffer_r.clear();
         self.output_idx = 0;
 
+        let Some(coding_info) = CodingInfo::from_byte(sector[19]) else {
+            // Leave the output buffers empty so that nothing plays for this sector
+            log::warn!("Muting CD-XA ADPCM sector with invalid coding info {:02X}", sector[19]);
+            return;
+        };
+
+        self.channel_mode = coding_info.channel_mode;
+        self.sample_rate = coding_info.sample_rate;
+        let bits_per_sample = coding_info.bits_per_sample;
+
         // At beginning, skip 12 sync bytes + 4 header bytes + 8 subheader bytes
         // At end, skip 20 padding bytes + 4 EDC bytes
         for data_block in sector[24..2352 - 24].chunks_exact(128) {
-            for audio_block_idx in 0..4 {
+            for audio_block_idx in (0..bits_per_sample.audio_blocks_per_data_block()).step_by(2) {
                 match self.channel_mode {
                     ChannelMode::Stereo => {
                         // Stereo: Block N is the next L block and block N+1 is the next R block
                         decode_audio_block(
                             data_block,
-                            2 * audio_block_idx,
+                            audio_block_idx,
+                            bits_per_sample,
                             &mut self.block_buffer_l,
                             &mut self.adpcm_buffer_l,
                         );
                         decode_audio_block(
                             data_block,
-                            2 * audio_block_idx + 1,
+                            audio_block_idx + 1,
+                            bits_per_sample,
                             &mut self.block_buffer_r,
                             &mut self.adpcm_buffer_r,
                         );
@@ -173,13 +302,15 @@ impl XaAdpcmState {
                         // Mono: Decode the next 2 blocks in sequence using the same buffers
                         decode_audio_block(
                             data_block,
-                            2 * audio_block_idx,
+                            audio_block_idx,
+                            bits_per_sample,
                             &mut self.block_buffer_l,
                             &mut self.adpcm_buffer_l,
                         );
                         decode_audio_block(
                             data_block,
-                            2 * audio_block_idx + 1,
+                            audio_block_idx + 1,
+                            bits_per_sample,
                             &mut self.block_buffer_l,
                             &mut self.adpcm_buffer_l,
                         );
@@ -215,6 +346,14 @@ impl XaAdpcmState {
                 );
             }
         }
+
+        if coding_info.emphasis {
+            self.deemphasis_l.apply(&mut self.output_buffer_l);
+            self.deemphasis_r.apply(&mut self.output_buffer_r);
+        } else {
+            self.deemphasis_l.reset();
+            self.deemphasis_r.reset();
+        }
     }
 
     pub fn maybe_output_sample(&mut self) -> Option<(i16, i16)> {
@@ -233,6 +372,7 @@ impl XaAdpcmState {
 fn decode_audio_block(
     data_block: &[u8],
     audio_block_idx: usize,
+    bits_per_sample: BitsPerSample,
     block_buffer: &mut [i16; 32],
     adpcm_buffer: &mut Vec<i16>,
 ) {
@@ -245,15 +385,22 @@ fn decode_audio_block(
     let shift = header_byte & 0xF;
     let filter = (header_byte >> 4) & 0x3;
 
-    let effective_shift = adpcm::compute_effective_shift(shift);
+    // Shift values of 13-15 function the same as 9
+    let shift = if shift > 12 { 9 } else { shift };
     let filter_0 = FILTER_0_TABLE[filter as usize];
     let filter_1 = FILTER_1_TABLE[filter as usize];
 
     for i in 0..28 {
-        let sample_byte = data_block[16 + 4 * i + audio_block_idx / 2];
-        let sample = i4_sample(sample_byte >> (4 * (audio_block_idx & 1)));
+        // Place the sample in the highest bits of a 16-bit value before applying the shift
+        let sample = match bits_per_sample {
+            BitsPerSample::Four => {
+                let sample_byte = data_block[16 + 4 * i + audio_block_idx / 2];
+                i4_sample(sample_byte >> (4 * (audio_block_idx & 1))) << 12
+            }
+            BitsPerSample::Eight => i32::from(data_block[16 + 4 * i + audio_block_idx] as i8) << 8,
+        };
 
-        let shifted = sample << effective_shift;
+        let shifted = sample >> shift;
 
         let older: i32 = block_buffer[2 + i].into();
         let old: i32 = block_buffer[3 + i].into();
@@ -304,3 +451,38 @@ fn resample_to_44100hz(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sector(coding_info: u8, sample: u8) -> Vec<u8> {
+        let mut sector = vec![0; 2352];
+        sector[19] = coding_info;
+        for data_block in sector[24..2352 - 24].chunks_exact_mut(128) {
+            data_block[16..].fill(sample);
+        }
+        sector
+    }
+
+    #[test]
+    fn eight_bit_and_invalid_coding_info() {
+        let mut state = XaAdpcmState::new();
+        state.muted = false;
+
+        // 8-bit Stereo, shift 0, filter 0: each sample byte is the high byte of the output sample
+        state.decode_sector(&sector(0x11, 0x40));
+        assert_eq!(state.adpcm_buffer_l.len(), 1008);
+        assert_eq!(state.adpcm_buffer_r.len(), 1008);
+        assert!(state.adpcm_buffer_l.iter().all(|&sample| sample == 0x4000));
+
+        // 8-bit Mono
+        state.decode_sector(&sector(0x10, 0xC0));
+        assert_eq!(state.adpcm_buffer_l.len(), 2016);
+        assert!(state.adpcm_buffer_l.iter().all(|&sample| sample == -0x4000));
+
+        // Reserved bits per sample value
+        state.decode_sector(&sector(0x21, 0x40));
+        assert_eq!(state.maybe_output_sample(), None);
+    }
+}
@@ -77,7 +77,46 @@ pub struct WgpuResources {
     pub device: Arc<wgpu::Device>,
     pub queue: Arc<wgpu::Queue>,
     pub queued_command_buffers: Vec<wgpu::CommandBuffer>,
-    pub display_config: DisplayConfig,
+}
+
+impl WgpuResources {
+    fn new(device: Arc<wgpu::Device>, queue: Arc<wgpu::Queue>) -> Self {
+        Self { device, queue, queued_command_buffers: Vec::with_capacity(64) }
+    }
+}
+
+/// A frame rendered into CPU memory by one of the software rasterizers.
+///
+/// Pixels are stored in row-major order as RGBA8 (sRGB), with no padding between rows.
+#[derive(Debug, Clone, Copy)]
+pub struct CpuFrame<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: &'a [u8],
+}
+
+/// An owned copy of the current display area, in the same pixel format as [`CpuFrame`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+/// Position of the CRT beam within a frame, in the same units as the display range registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct BeamPosition {
+    /// Scanlines since the start of the frame
+    pub line: u16,
+    /// Video clock cycles since the start of the scanline
+    pub line_cycle: u16,
+    pub video_mode: VideoMode,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'a> {
+    Wgpu(&'a wgpu::Texture),
+    Cpu(CpuFrame<'a>),
 }
 
 #[derive(SaveState)]
@@ -86,14 +125,23 @@ pub struct Gpu {
     gp0: Gp0State,
     gpu_read_buffer: u32,
     #[save_state(skip)]
-    wgpu_resources: WgpuResources,
+    wgpu_resources: Option<WgpuResources>,
+    #[save_state(skip)]
+    display_config: DisplayConfig,
     #[save_state(to = RasterizerState)]
     rasterizer: Rasterizer,
     pgxp_config: PgxpConfig,
 }
 
 #[must_use]
-fn check_rasterizer_type(rasterizer_type: RasterizerType) -> RasterizerType {
+fn check_rasterizer_type(rasterizer_type: RasterizerType, wgpu_available: bool) -> RasterizerType {
+    if rasterizer_type == RasterizerType::WgpuHardware && !wgpu_available {
+        log::error!(
+            "Emulator was created without a wgpu device; hardware rasterizer will not work, using a software rasterizer"
+        );
+        return check_rasterizer_type(RasterizerType::default(), wgpu_available);
+    }
+
     if rasterizer_type != RasterizerType::SimdSoftware {
         return rasterizer_type;
     }
@@ -116,29 +164,31 @@ fn check_rasterizer_type(rasterizer_type: RasterizerType) -> RasterizerType {
     rasterizer_type
 }
 
+impl GpuState {
+    pub fn vram_bytes_mut(&mut self) -> &mut [u8] {
+        bytemuck::cast_slice_mut(self.rasterizer.vram.as_mut_slice())
+    }
+}
+
 impl Gpu {
     pub fn new(
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
+        wgpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
         mut display_config: DisplayConfig,
         pgxp_config: PgxpConfig,
     ) -> Self {
-        display_config.rasterizer_type = check_rasterizer_type(display_config.rasterizer_type);
+        let wgpu_resources = wgpu.map(|(device, queue)| WgpuResources::new(device, queue));
 
-        let rasterizer = Rasterizer::new(&wgpu_device, &wgpu_queue, display_config, pgxp_config);
+        display_config.rasterizer_type =
+            check_rasterizer_type(display_config.rasterizer_type, wgpu_resources.is_some());
 
-        let wgpu_resources = WgpuResources {
-            device: wgpu_device,
-            queue: wgpu_queue,
-            queued_command_buffers: Vec::with_capacity(64),
-            display_config,
-        };
+        let rasterizer = Rasterizer::new(wgpu_resources.as_ref(), display_config, pgxp_config);
 
         Self {
             registers: Registers::new(),
             gp0: Gp0State::new(),
             gpu_read_buffer: 0,
             wgpu_resources,
+            display_config,
             rasterizer,
             pgxp_config,
         }
@@ -183,18 +233,34 @@ impl Gpu {
         self.handle_gp1_write(value, timers, scheduler, interrupt_registers);
     }
 
-    pub fn generate_frame_texture(
+    pub fn generate_frame(
         &mut self,
-    ) -> (&wgpu::Texture, impl Iterator<Item = wgpu::CommandBuffer> + '_) {
-        let frame =
-            self.rasterizer.generate_frame_texture(&self.registers, &mut self.wgpu_resources);
-        let command_buffers = self.wgpu_resources.queued_command_buffers.drain(..);
+    ) -> (Frame<'_>, impl Iterator<Item = wgpu::CommandBuffer> + '_) {
+        let frame = self.rasterizer.generate_frame(
+            &self.registers,
+            self.display_config,
+            self.wgpu_resources.as_mut(),
+        );
+        let command_buffers = self
+            .wgpu_resources
+            .iter_mut()
+            .flat_map(|wgpu_resources| wgpu_resources.queued_command_buffers.drain(..));
 
         (frame, command_buffers)
     }
 
+    pub fn screenshot(&mut self) -> Screenshot {
+        self.rasterizer.screenshot(&self.registers, self.display_config)
+    }
+
+    /// Beam position that draws the given position within the output frame, where (0, 0) is the
+    /// top left corner of the frame and (0xFFFF, 0xFFFF) is the bottom right corner
+    pub fn beam_position(&self, frame_x: u16, frame_y: u16) -> BeamPosition {
+        rasterizer::frame_position_to_beam(&self.registers, self.display_config, frame_x, frame_y)
+    }
+
     pub fn pixel_aspect_ratio(&self) -> f64 {
-        if self.wgpu_resources.display_config.dump_vram {
+        if self.display_config.dump_vram {
             return 1.0;
         }
 
@@ -213,13 +279,13 @@ impl Gpu {
     }
 
     pub fn update_config(&mut self, mut display_config: DisplayConfig, pgxp_config: PgxpConfig) {
-        display_config.rasterizer_type = check_rasterizer_type(display_config.rasterizer_type);
+        display_config.rasterizer_type =
+            check_rasterizer_type(display_config.rasterizer_type, self.wgpu_resources.is_some());
 
-        let prev_rasterizer_type = self.wgpu_resources.display_config.rasterizer_type;
-        let prev_wgpu_rasterizer_config =
-            self.wgpu_resources.display_config.to_wgpu_rasterizer_config();
+        let prev_rasterizer_type = self.display_config.rasterizer_type;
+        let prev_wgpu_rasterizer_config = self.display_config.to_wgpu_rasterizer_config();
         let prev_pgxp_config = self.pgxp_config;
-        self.wgpu_resources.display_config = display_config;
+        self.display_config = display_config;
         self.pgxp_config = pgxp_config;
 
         if prev_rasterizer_type != display_config.rasterizer_type
@@ -230,28 +296,32 @@ impl Gpu {
             let vram = self.rasterizer.clone_vram();
             self.rasterizer = Rasterizer::from_state(
                 RasterizerState { vram },
-                &self.wgpu_resources.device,
-                &self.wgpu_resources.queue,
+                self.wgpu_resources.as_ref(),
                 display_config,
                 pgxp_config,
             );
         }
     }
 
-    pub fn get_wgpu_resources(&self) -> (Arc<wgpu::Device>, Arc<wgpu::Queue>) {
-        (Arc::clone(&self.wgpu_resources.device), Arc::clone(&self.wgpu_resources.queue))
+    pub fn get_wgpu_resources(&self) -> Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)> {
+        self.wgpu_resources.as_ref().map(|wgpu_resources| {
+            (Arc::clone(&wgpu_resources.device), Arc::clone(&wgpu_resources.queue))
+        })
     }
 
     pub fn from_state(
         state: GpuState,
-        wgpu_device: Arc<wgpu::Device>,
-        wgpu_queue: Arc<wgpu::Queue>,
-        display_config: DisplayConfig,
+        wgpu: Option<(Arc<wgpu::Device>, Arc<wgpu::Queue>)>,
+        mut display_config: DisplayConfig,
     ) -> Self {
+        let wgpu_resources = wgpu.map(|(device, queue)| WgpuResources::new(device, queue));
+
+        display_config.rasterizer_type =
+            check_rasterizer_type(display_config.rasterizer_type, wgpu_resources.is_some());
+
         let rasterizer = Rasterizer::from_state(
             state.rasterizer,
-            &wgpu_device,
-            &wgpu_queue,
+            wgpu_resources.as_ref(),
             display_config,
             state.pgxp_config,
         );
@@ -260,12 +330,8 @@ impl Gpu {
             registers: state.registers,
             gp0: state.gp0,
             gpu_read_buffer: state.gpu_read_buffer,
-            wgpu_resources: WgpuResources {
-                device: wgpu_device,
-                queue: wgpu_queue,
-                queued_command_buffers: Vec::with_capacity(64),
-                display_config,
-            },
+            wgpu_resources,
+            display_config,
             rasterizer,
             pgxp_config: state.pgxp_config,
         }
@@ -4,9 +4,10 @@ use crate::api::{DisplayConfig, PgxpConfig};
 use crate::gpu::gp0::{DrawSettings, SemiTransparencyMode, TexturePage, TextureWindow};
 use crate::gpu::rasterizer::naive::NaiveSoftwareRasterizer;
 use crate::gpu::rasterizer::simd::SimdSoftwareRasterizer;
+use crate::gpu::rasterizer::software::SoftwareRenderer;
 use crate::gpu::rasterizer::wgpuhardware::WgpuRasterizer;
 use crate::gpu::registers::{Registers, VerticalResolution};
-use crate::gpu::{Color, Vertex, VideoMode, Vram, WgpuResources};
+use crate::gpu::{BeamPosition, Color, Frame, Screenshot, Vertex, VideoMode, Vram, WgpuResources};
 use crate::pgxp::PreciseVertex;
 use bincode::{Decode, Encode};
 use std::cmp;
@@ -121,11 +122,12 @@ pub trait RasterizerInterface {
 
     fn vram_to_vram_blit(&mut self, args: VramVramBlitArgs);
 
-    fn generate_frame_texture(
+    fn generate_frame(
         &mut self,
         registers: &Registers,
-        wgpu_resources: &mut WgpuResources,
-    ) -> &wgpu::Texture;
+        display_config: DisplayConfig,
+        wgpu_resources: Option<&mut WgpuResources>,
+    ) -> Frame<'_>;
 
     fn clone_vram(&mut self) -> Vram;
 
@@ -152,6 +154,9 @@ impl Default for RasterizerType {
     }
 }
 
+const HARDWARE_REQUIRES_WGPU: &str =
+    "Hardware rasterizer should never be selected when wgpu resources are not available";
+
 pub struct Rasterizer(pub Box<dyn RasterizerInterface + Send + Sync>);
 
 impl Deref for Rasterizer {
@@ -170,11 +175,12 @@ impl DerefMut for Rasterizer {
 
 impl Rasterizer {
     pub fn new(
-        wgpu_device: &Arc<wgpu::Device>,
-        wgpu_queue: &Arc<wgpu::Queue>,
+        wgpu_resources: Option<&WgpuResources>,
         display_config: DisplayConfig,
         pgxp_config: PgxpConfig,
     ) -> Self {
+        let wgpu_device = wgpu_resources.map(|wgpu_resources| wgpu_resources.device.as_ref());
+
         match display_config.rasterizer_type {
             RasterizerType::NaiveSoftware => {
                 Self(Box::new(NaiveSoftwareRasterizer::new(wgpu_device)))
@@ -182,12 +188,38 @@ impl Rasterizer {
             RasterizerType::SimdSoftware => {
                 Self(Box::new(SimdSoftwareRasterizer::new(wgpu_device)))
             }
-            RasterizerType::WgpuHardware => Self(Box::new(WgpuRasterizer::new(
-                Arc::clone(wgpu_device),
-                Arc::clone(wgpu_queue),
-                display_config.to_wgpu_rasterizer_config(),
-                pgxp_config,
-            ))),
+            RasterizerType::WgpuHardware => {
+                let wgpu_resources = wgpu_resources.expect(HARDWARE_REQUIRES_WGPU);
+                Self(Box::new(WgpuRasterizer::new(
+                    Arc::clone(&wgpu_resources.device),
+                    Arc::clone(&wgpu_resources.queue),
+                    display_config.to_wgpu_rasterizer_config(),
+                    pgxp_config,
+                )))
+            }
+        }
+    }
+
+    /// Render the current display area into CPU memory at native resolution. This always uses the
+    /// software renderer so that it works the same regardless of which rasterizer is active.
+    pub fn screenshot(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+    ) -> Screenshot {
+        let vram = self.clone_vram();
+        let display_config = DisplayConfig { dump_vram: false, ..display_config };
+
+        let mut renderer = SoftwareRenderer::new(None);
+        match renderer.generate_frame(registers, display_config, None, &vram) {
+            Frame::Cpu(frame) => {
+                Screenshot { width: frame.width, height: frame.height, rgba: frame.rgba.to_vec() }
+            }
+            Frame::Wgpu(_) => {
+                unreachable!(
+                    "software renderer always outputs CPU frames when there is no wgpu device"
+                )
+            }
         }
     }
 
@@ -198,11 +230,12 @@ impl Rasterizer {
 
     pub fn from_state(
         state: RasterizerState,
-        wgpu_device: &Arc<wgpu::Device>,
-        wgpu_queue: &Arc<wgpu::Queue>,
+        wgpu_resources: Option<&WgpuResources>,
         display_config: DisplayConfig,
         pgxp_config: PgxpConfig,
     ) -> Self {
+        let wgpu_device = wgpu_resources.map(|wgpu_resources| wgpu_resources.device.as_ref());
+
         match display_config.rasterizer_type {
             RasterizerType::NaiveSoftware => {
                 Self(Box::new(NaiveSoftwareRasterizer::from_vram(wgpu_device, &state.vram)))
@@ -211,9 +244,10 @@ impl Rasterizer {
                 Self(Box::new(SimdSoftwareRasterizer::from_vram(wgpu_device, &state.vram)))
             }
             RasterizerType::WgpuHardware => {
+                let wgpu_resources = wgpu_resources.expect(HARDWARE_REQUIRES_WGPU);
                 let rasterizer = WgpuRasterizer::new(
-                    Arc::clone(wgpu_device),
-                    Arc::clone(wgpu_queue),
+                    Arc::clone(&wgpu_resources.device),
+                    Arc::clone(&wgpu_resources.queue),
                     display_config.to_wgpu_rasterizer_config(),
                     pgxp_config,
                 );
@@ -281,6 +315,22 @@ impl ScreenSize {
     const NTSC: Self = Self { left: 0x260, right: 0xC60, top: 16, bottom: 256, v_overscan_rows: 8 };
 
     const PAL: Self = Self { left: 0x274, right: 0xC74, top: 20, bottom: 308, v_overscan_rows: 10 };
+
+    fn for_mode(video_mode: VideoMode) -> Self {
+        match video_mode {
+            VideoMode::Ntsc => Self::NTSC,
+            VideoMode::Pal => Self::PAL,
+        }
+    }
+
+    // First and last+1 lines included in the output frame
+    fn visible_lines(&self, crop_v_overscan: bool) -> (i32, i32) {
+        if crop_v_overscan {
+            (self.top + self.v_overscan_rows, self.bottom - self.v_overscan_rows)
+        } else {
+            (self.top, self.bottom)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -316,21 +366,8 @@ fn compute_frame_location(
     display_config: DisplayConfig,
 ) -> (Option<FrameCoords>, FrameSize) {
     let crop_v_overscan = display_config.crop_vertical_overscan;
-    let screen_size = match registers.video_mode {
-        VideoMode::Ntsc => ScreenSize::NTSC,
-        VideoMode::Pal => ScreenSize::PAL,
-    };
-
-    let screen_top = if crop_v_overscan {
-        screen_size.top + screen_size.v_overscan_rows
-    } else {
-        screen_size.top
-    };
-    let screen_bottom = if crop_v_overscan {
-        screen_size.bottom - screen_size.v_overscan_rows
-    } else {
-        screen_size.bottom
-    };
+    let screen_size = ScreenSize::for_mode(registers.video_mode);
+    let (screen_top, screen_bottom) = screen_size.visible_lines(crop_v_overscan);
 
     let dot_clock_divider: i32 = registers.dot_clock_divider().into();
     let frame_width = (screen_size.right - screen_size.left) / dot_clock_divider;
@@ -400,6 +437,30 @@ fn compute_frame_location(
     )
 }
 
+// Inverse of the frame mapping in compute_frame_location: the output frame always covers the same
+// area of the screen regardless of the display range, so a position within the frame maps directly
+// to a beam position
+pub fn frame_position_to_beam(
+    registers: &Registers,
+    display_config: DisplayConfig,
+    x: u16,
+    y: u16,
+) -> BeamPosition {
+    let screen_size = ScreenSize::for_mode(registers.video_mode);
+    let (screen_top, screen_bottom) =
+        screen_size.visible_lines(display_config.crop_vertical_overscan);
+
+    let line_cycle =
+        screen_size.left + (((screen_size.right - screen_size.left) * i32::from(x)) >> 16);
+    let line = screen_top + (((screen_bottom - screen_top) * i32::from(y)) >> 16);
+
+    BeamPosition {
+        line: line as u16,
+        line_cycle: line_cycle as u16,
+        video_mode: registers.video_mode,
+    }
+}
+
 #[derive(Debug)]
 struct ClearPipeline {
     pipeline: wgpu::RenderPipeline,
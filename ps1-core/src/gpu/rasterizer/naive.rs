@@ -2,6 +2,7 @@
 
 #![allow(clippy::many_single_char_names)]
 
+use crate::api::DisplayConfig;
 use crate::gpu::gp0::{
     DrawSettings, SemiTransparencyMode, TextureColorDepthBits, TexturePage, TextureWindow,
 };
@@ -13,9 +14,8 @@ use crate::gpu::rasterizer::{
     vertices_valid,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, Vertex, Vram, VramArray, WgpuResources};
+use crate::gpu::{Color, Frame, Vertex, Vram, VramArray, WgpuResources};
 use std::cmp;
-use wgpu::Texture;
 
 const DITHER_TABLE: &[[i8; 4]; 4] =
     &[[-4, 0, -3, 1], [2, -2, 3, -1], [-3, 1, -4, 0], [3, -1, 2, -2]];
@@ -77,11 +77,11 @@ pub struct NaiveSoftwareRasterizer {
 }
 
 impl NaiveSoftwareRasterizer {
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: Option<&wgpu::Device>) -> Self {
         Self { vram: Vram::new(), renderer: SoftwareRenderer::new(device) }
     }
 
-    pub fn from_vram(device: &wgpu::Device, vram: &Vram) -> Self {
+    pub fn from_vram(device: Option<&wgpu::Device>, vram: &Vram) -> Self {
         let vram_array: Box<VramArray> = vram.to_vec().into_boxed_slice().try_into().unwrap();
         Self { vram: vram_array.into(), renderer: SoftwareRenderer::new(device) }
     }
@@ -469,12 +469,13 @@ impl RasterizerInterface for NaiveSoftwareRasterizer {
         software::vram_to_vram_blit(&mut self.vram, args);
     }
 
-    fn generate_frame_texture(
+    fn generate_frame(
         &mut self,
         registers: &Registers,
-        wgpu_resources: &mut WgpuResources,
-    ) -> &Texture {
-        self.renderer.generate_frame_texture(registers, wgpu_resources, &self.vram)
+        display_config: DisplayConfig,
+        wgpu_resources: Option<&mut WgpuResources>,
+    ) -> Frame<'_> {
+        self.renderer.generate_frame(registers, display_config, wgpu_resources, &self.vram)
     }
 
     fn clone_vram(&mut self) -> Vram {
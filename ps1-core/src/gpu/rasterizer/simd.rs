@@ -4,6 +4,7 @@
 
 mod avx2;
 
+use crate::api::DisplayConfig;
 use crate::gpu::gp0::DrawSettings;
 use crate::gpu::rasterizer::software::SoftwareRenderer;
 use crate::gpu::rasterizer::{
@@ -11,7 +12,7 @@ use crate::gpu::rasterizer::{
     VramVramBlitArgs, cross_product_z, software, swap_vertices, vertices_valid,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, Vertex, Vram, VramArray, WgpuResources};
+use crate::gpu::{Color, Frame, Vertex, Vram, VramArray, WgpuResources};
 use std::alloc::Layout;
 use std::ops::{Deref, DerefMut};
 use std::{alloc, cmp};
@@ -63,12 +64,12 @@ pub struct SimdSoftwareRasterizer {
 
 impl SimdSoftwareRasterizer {
     #[allow(clippy::large_stack_arrays)]
-    pub fn new(device: &wgpu::Device) -> Self {
+    pub fn new(device: Option<&wgpu::Device>) -> Self {
         Self { vram: AlignedVram::new_on_heap(), renderer: SoftwareRenderer::new(device) }
     }
 
     #[allow(clippy::large_stack_arrays)]
-    pub fn from_vram(device: &wgpu::Device, vram: &Vram) -> Self {
+    pub fn from_vram(device: Option<&wgpu::Device>, vram: &Vram) -> Self {
         let mut aligned_vram = AlignedVram::new_on_heap();
         aligned_vram.0.copy_from_slice(vram.as_ref());
 
@@ -232,12 +233,13 @@ impl RasterizerInterface for SimdSoftwareRasterizer {
         software::vram_to_vram_blit(&mut self.vram, args);
     }
 
-    fn generate_frame_texture(
+    fn generate_frame(
         &mut self,
         registers: &Registers,
-        wgpu_resources: &mut WgpuResources,
-    ) -> &wgpu::Texture {
-        self.renderer.generate_frame_texture(registers, wgpu_resources, &self.vram)
+        display_config: DisplayConfig,
+        wgpu_resources: Option<&mut WgpuResources>,
+    ) -> Frame<'_> {
+        self.renderer.generate_frame(registers, display_config, wgpu_resources, &self.vram)
     }
 
     fn clone_vram(&mut self) -> Vram {
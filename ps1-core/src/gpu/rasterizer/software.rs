@@ -1,9 +1,9 @@
-use crate::api::ColorDepthBits;
+use crate::api::{ColorDepthBits, DisplayConfig};
 use crate::gpu::rasterizer::{
     ClearPipeline, CpuVramBlitArgs, FrameCoords, FrameSize, ScreenSize, VramVramBlitArgs,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, VramArray, WgpuResources, rasterizer};
+use crate::gpu::{Color, CpuFrame, Frame, VramArray, WgpuResources, rasterizer};
 use bytemuck::{Pod, Zeroable};
 use std::collections::HashMap;
 use wgpu::CommandBuffer;
@@ -31,89 +31,16 @@ const FRAME_BUFFER_LEN: usize =
 type FrameBuffer = [RgbaColor; FRAME_BUFFER_LEN];
 
 #[derive(Debug)]
-pub struct SoftwareRenderer {
-    frame_buffer: Box<FrameBuffer>,
+struct WgpuFrameOutput {
     frame_textures: HashMap<FrameSize, wgpu::Texture>,
     clear_pipeline: ClearPipeline,
 }
 
-impl SoftwareRenderer {
-    pub fn new(device: &wgpu::Device) -> Self {
+impl WgpuFrameOutput {
+    fn new(device: &wgpu::Device) -> Self {
         let clear_pipeline = ClearPipeline::new(device, wgpu::TextureFormat::Rgba8UnormSrgb);
 
-        Self {
-            frame_buffer: vec![RgbaColor::BLACK; FRAME_BUFFER_LEN]
-                .into_boxed_slice()
-                .try_into()
-                .unwrap(),
-            frame_textures: HashMap::new(),
-            clear_pipeline,
-        }
-    }
-
-    pub fn generate_frame_texture(
-        &mut self,
-        registers: &Registers,
-        wgpu_resources: &mut WgpuResources,
-        vram: &VramArray,
-    ) -> &wgpu::Texture {
-        if wgpu_resources.display_config.dump_vram {
-            return self.write_frame(
-                &wgpu_resources.device,
-                &wgpu_resources.queue,
-                FrameSize { width: 1024, height: 512 },
-                FrameCoords {
-                    frame_x: 0,
-                    frame_y: 0,
-                    display_x_offset: 0,
-                    display_y_offset: 0,
-                    display_x_start: 0,
-                    display_y_start: 0,
-                    display_width: 1024,
-                    display_height: 512,
-                },
-                ColorDepthBits::Fifteen,
-                vram,
-            );
-        }
-
-        let (frame_coords, frame_size) =
-            rasterizer::compute_frame_location(registers, wgpu_resources.display_config);
-        let Some(frame_coords) = frame_coords else {
-            return self.clear_frame(
-                &wgpu_resources.device,
-                &mut wgpu_resources.queued_command_buffers,
-                frame_size,
-            );
-        };
-
-        log::debug!(
-            "Computed frame coords {frame_coords:?} and frame_size {frame_size:?} from video_mode={}, X1={}, X2={}, Y1={}, Y2={}, dot_clock_divider={}, v_resolution={:?}",
-            registers.video_mode,
-            registers.x_display_range.0,
-            registers.x_display_range.1,
-            registers.y_display_range.0,
-            registers.y_display_range.1,
-            registers.dot_clock_divider(),
-            registers.v_resolution
-        );
-
-        if !registers.display_enabled {
-            return self.clear_frame(
-                &wgpu_resources.device,
-                &mut wgpu_resources.queued_command_buffers,
-                frame_size,
-            );
-        }
-
-        return self.write_frame(
-            &wgpu_resources.device,
-            &wgpu_resources.queue,
-            frame_size,
-            frame_coords,
-            registers.display_area_color_depth,
-            vram,
-        );
+        Self { frame_textures: HashMap::new(), clear_pipeline }
     }
 
     fn clear_frame(
@@ -140,18 +67,14 @@ impl SoftwareRenderer {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         frame_size: FrameSize,
-        frame_coords: FrameCoords,
-        color_depth: ColorDepthBits,
-        vram: &VramArray,
+        frame_buffer: &FrameBuffer,
     ) -> &wgpu::Texture {
-        populate_frame_buffer(frame_size, frame_coords, color_depth, vram, &mut self.frame_buffer);
-
         let frame_texture =
             get_or_create_frame_texture(device, frame_size, &mut self.frame_textures);
 
         queue.write_texture(
             frame_texture.as_image_copy(),
-            bytemuck::cast_slice(self.frame_buffer.as_ref()),
+            bytemuck::cast_slice(frame_buffer.as_ref()),
             wgpu::ImageDataLayout {
                 offset: 0,
                 bytes_per_row: Some(1024 * 4),
@@ -164,6 +87,115 @@ impl SoftwareRenderer {
     }
 }
 
+#[derive(Debug)]
+pub struct SoftwareRenderer {
+    frame_buffer: Box<FrameBuffer>,
+    // None if the emulator was created without a wgpu device, in which case frames are output
+    // directly from the frame buffer
+    wgpu_output: Option<WgpuFrameOutput>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(device: Option<&wgpu::Device>) -> Self {
+        Self {
+            frame_buffer: vec![RgbaColor::BLACK; FRAME_BUFFER_LEN]
+                .into_boxed_slice()
+                .try_into()
+                .unwrap(),
+            wgpu_output: device.map(WgpuFrameOutput::new),
+        }
+    }
+
+    pub fn generate_frame(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+        wgpu_resources: Option<&mut WgpuResources>,
+        vram: &VramArray,
+    ) -> Frame<'_> {
+        let (frame_size, frame_coords, color_depth) = if display_config.dump_vram {
+            (
+                FrameSize { width: 1024, height: 512 },
+                Some(FrameCoords {
+                    frame_x: 0,
+                    frame_y: 0,
+                    display_x_offset: 0,
+                    display_y_offset: 0,
+                    display_x_start: 0,
+                    display_y_start: 0,
+                    display_width: 1024,
+                    display_height: 512,
+                }),
+                ColorDepthBits::Fifteen,
+            )
+        } else {
+            let (frame_coords, frame_size) =
+                rasterizer::compute_frame_location(registers, display_config);
+
+            if let Some(frame_coords) = frame_coords {
+                log::debug!(
+                    "Computed frame coords {frame_coords:?} and frame_size {frame_size:?} from video_mode={}, X1={}, X2={}, Y1={}, Y2={}, dot_clock_divider={}, v_resolution={:?}",
+                    registers.video_mode,
+                    registers.x_display_range.0,
+                    registers.x_display_range.1,
+                    registers.y_display_range.0,
+                    registers.y_display_range.1,
+                    registers.dot_clock_divider(),
+                    registers.v_resolution
+                );
+            }
+
+            (
+                frame_size,
+                frame_coords.filter(|_| registers.display_enabled),
+                registers.display_area_color_depth,
+            )
+        };
+
+        let Self { frame_buffer, wgpu_output } = self;
+
+        if let (Some(wgpu_output), Some(wgpu_resources)) = (wgpu_output, wgpu_resources) {
+            let Some(frame_coords) = frame_coords else {
+                return Frame::Wgpu(wgpu_output.clear_frame(
+                    &wgpu_resources.device,
+                    &mut wgpu_resources.queued_command_buffers,
+                    frame_size,
+                ));
+            };
+
+            populate_frame_buffer(frame_size, frame_coords, color_depth, vram, 1024, frame_buffer);
+
+            return Frame::Wgpu(wgpu_output.write_frame(
+                &wgpu_resources.device,
+                &wgpu_resources.queue,
+                frame_size,
+                frame_buffer,
+            ));
+        }
+
+        // No wgpu device; pack rows tightly so the frame buffer can be handed out as-is
+        let row_len = frame_size.width as usize;
+        let frame_len = row_len * frame_size.height as usize;
+        match frame_coords {
+            Some(frame_coords) => populate_frame_buffer(
+                frame_size,
+                frame_coords,
+                color_depth,
+                vram,
+                row_len,
+                frame_buffer,
+            ),
+            None => frame_buffer[..frame_len].fill(RgbaColor::BLACK),
+        }
+
+        Frame::Cpu(CpuFrame {
+            width: frame_size.width,
+            height: frame_size.height,
+            rgba: bytemuck::cast_slice(&frame_buffer[..frame_len]),
+        })
+    }
+}
+
 fn get_or_create_frame_texture<'a>(
     device: &wgpu::Device,
     frame_size: FrameSize,
@@ -202,6 +234,7 @@ fn populate_frame_buffer(
     frame_coords: FrameCoords,
     color_depth: ColorDepthBits,
     vram: &VramArray,
+    row_len: usize,
     frame_buffer: &mut FrameBuffer,
 ) {
     let x_range =
@@ -211,7 +244,7 @@ fn populate_frame_buffer(
 
     for y in 0..frame_size.height {
         if !y_range.contains(&y) {
-            frame_buffer[1024 * y as usize..1024 * (y + 1) as usize].fill(RgbaColor::BLACK);
+            frame_buffer[row_len * y as usize..row_len * (y + 1) as usize].fill(RgbaColor::BLACK);
             continue;
         }
 
@@ -221,7 +254,7 @@ fn populate_frame_buffer(
         let vram_row_addr = (1024 * vram_y) as usize;
 
         // Fill pixels outside of the horizontal display range with solid black
-        let fb_row_addr = 1024 * y as usize;
+        let fb_row_addr = row_len * y as usize;
         frame_buffer[fb_row_addr..fb_row_addr + x_range.start as usize].fill(RgbaColor::BLACK);
         frame_buffer[fb_row_addr + x_range.end as usize..fb_row_addr + frame_size.width as usize]
             .fill(RgbaColor::BLACK);
@@ -4,7 +4,7 @@ mod hazards;
 mod sync;
 mod twentyfour;
 
-use crate::api::ColorDepthBits;
+use crate::api::{ColorDepthBits, DisplayConfig};
 use crate::gpu::gp0::{DrawSettings, SemiTransparencyMode, TextureColorDepthBits, TexturePage};
 use crate::gpu::rasterizer::wgpuhardware::blit::{
     CpuVramBlitPipeline, VramCopyPipeline, VramCpuBlitter, VramFillPipeline,
@@ -21,7 +21,7 @@ use crate::gpu::rasterizer::{
     vertices_valid,
 };
 use crate::gpu::registers::Registers;
-use crate::gpu::{Color, Vertex, Vram, WgpuResources, rasterizer};
+use crate::gpu::{Color, Frame, Vertex, Vram, WgpuResources, rasterizer};
 use std::collections::HashMap;
 use std::ops::{BitOr, BitOrAssign, Range};
 use std::sync::Arc;
@@ -1002,6 +1002,91 @@ impl WgpuRasterizer {
             );
         }
     }
+
+    fn generate_frame_texture(
+        &mut self,
+        registers: &Registers,
+        display_config: DisplayConfig,
+        wgpu_resources: &mut WgpuResources,
+    ) -> &Texture {
+        log::debug!("Rendering frame to display");
+
+        if let Some(command_buffer) = self.flush_draw_commands() {
+            wgpu_resources.queued_command_buffers.push(command_buffer);
+        }
+
+        if display_config.dump_vram {
+            return &self.scaled_vram;
+        }
+
+        let (frame_coords, frame_size) =
+            rasterizer::compute_frame_location(registers, display_config);
+        let Some(frame_coords) = frame_coords else {
+            return self
+                .get_and_clear_frame(frame_size, &mut wgpu_resources.queued_command_buffers);
+        };
+
+        if !registers.display_enabled {
+            return self
+                .get_and_clear_frame(frame_size, &mut wgpu_resources.queued_command_buffers);
+        }
+
+        log::debug!("  Frame size {frame_size:?}, frame coords {frame_coords:?}");
+
+        if registers.display_area_color_depth == ColorDepthBits::TwentyFour {
+            return self.render_24bpp(
+                frame_coords,
+                frame_size,
+                &mut wgpu_resources.queued_command_buffers,
+            );
+        }
+
+        let resolution_scale = self.config.resolution_scale;
+        let frame = get_or_create_frame_texture(
+            &self.device,
+            frame_size,
+            resolution_scale,
+            &mut self.frame_textures,
+        );
+
+        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor::default());
+        self.clear_pipeline.draw(frame, &mut encoder);
+
+        // TODO bounds check
+        let source_x = frame_coords.frame_x + frame_coords.display_x_offset;
+        let source_y = frame_coords.frame_y + frame_coords.display_y_offset;
+        encoder.copy_texture_to_texture(
+            ImageCopyTexture {
+                texture: &self.scaled_vram,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: resolution_scale * source_x,
+                    y: resolution_scale * source_y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            ImageCopyTexture {
+                texture: frame,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: resolution_scale * frame_coords.display_x_start,
+                    y: resolution_scale * frame_coords.display_y_start,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            Extent3d {
+                width: resolution_scale * frame_coords.display_width,
+                height: resolution_scale * frame_coords.display_height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        wgpu_resources.queued_command_buffers.push(encoder.finish());
+
+        frame
+    }
 }
 
 fn must_use_mask_bit_pipeline(
@@ -1156,88 +1241,15 @@ impl RasterizerInterface for WgpuRasterizer {
         self.draw_commands.push(DrawCommand::VramCopy { args });
     }
 
-    fn generate_frame_texture(
+    fn generate_frame(
         &mut self,
         registers: &Registers,
-        wgpu_resources: &mut WgpuResources,
-    ) -> &Texture {
-        log::debug!("Rendering frame to display");
-
-        if let Some(command_buffer) = self.flush_draw_commands() {
-            wgpu_resources.queued_command_buffers.push(command_buffer);
-        }
-
-        if wgpu_resources.display_config.dump_vram {
-            return &self.scaled_vram;
-        }
-
-        let (frame_coords, frame_size) =
-            rasterizer::compute_frame_location(registers, wgpu_resources.display_config);
-        let Some(frame_coords) = frame_coords else {
-            return self
-                .get_and_clear_frame(frame_size, &mut wgpu_resources.queued_command_buffers);
-        };
-
-        if !registers.display_enabled {
-            return self
-                .get_and_clear_frame(frame_size, &mut wgpu_resources.queued_command_buffers);
-        }
-
-        log::debug!("  Frame size {frame_size:?}, frame coords {frame_coords:?}");
-
-        if registers.display_area_color_depth == ColorDepthBits::TwentyFour {
-            return self.render_24bpp(
-                frame_coords,
-                frame_size,
-                &mut wgpu_resources.queued_command_buffers,
-            );
-        }
-
-        let resolution_scale = self.config.resolution_scale;
-        let frame = get_or_create_frame_texture(
-            &self.device,
-            frame_size,
-            resolution_scale,
-            &mut self.frame_textures,
-        );
-
-        let mut encoder = self.device.create_command_encoder(&CommandEncoderDescriptor::default());
-        self.clear_pipeline.draw(frame, &mut encoder);
-
-        // TODO bounds check
-        let source_x = frame_coords.frame_x + frame_coords.display_x_offset;
-        let source_y = frame_coords.frame_y + frame_coords.display_y_offset;
-        encoder.copy_texture_to_texture(
-            ImageCopyTexture {
-                texture: &self.scaled_vram,
-                mip_level: 0,
-                origin: Origin3d {
-                    x: resolution_scale * source_x,
-                    y: resolution_scale * source_y,
-                    z: 0,
-                },
-                aspect: TextureAspect::All,
-            },
-            ImageCopyTexture {
-                texture: frame,
-                mip_level: 0,
-                origin: Origin3d {
-                    x: resolution_scale * frame_coords.display_x_start,
-                    y: resolution_scale * frame_coords.display_y_start,
-                    z: 0,
-                },
-                aspect: TextureAspect::All,
-            },
-            Extent3d {
-                width: resolution_scale * frame_coords.display_width,
-                height: resolution_scale * frame_coords.display_height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        wgpu_resources.queued_command_buffers.push(encoder.finish());
-
-        frame
+        display_config: DisplayConfig,
+        wgpu_resources: Option<&mut WgpuResources>,
+    ) -> Frame<'_> {
+        let wgpu_resources = wgpu_resources
+            .expect("Hardware rasterizer should always be created with wgpu resources");
+        Frame::Wgpu(self.generate_frame_texture(registers, display_config, wgpu_resources))
     }
 
     fn clone_vram(&mut self) -> Vram {
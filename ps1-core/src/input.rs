@@ -1,9 +1,12 @@
+use crate::savestate;
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 use proc_bitfield::bitfield;
 
 bitfield! {
     #[derive(Clone, Copy, PartialEq, Eq, Default, bincode::Encode, bincode::Decode)]
-    pub struct DigitalJoypadState(u16): Debug, IntoStorage {
+    pub struct DigitalJoypadState(u16): Debug, FromStorage, IntoStorage {
         pub select: bool @ 0,
         pub start: bool @ 3,
         pub up: bool @ 4,
@@ -38,6 +41,20 @@ impl AnalogMode {
     }
 }
 
+/// Overrides for how a `DualShock` switches between digital and analog mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct DualShockConfig {
+    /// Start in analog mode when the controller is connected, for games that require analog mode
+    /// but never enable it themselves
+    pub start_in_analog: bool,
+    /// Ignore software requests to lock or unlock the Analog button (command $44)
+    pub ignore_mode_lock: bool,
+    /// Stay in digital mode; the Analog button and software requests to switch modes are ignored
+    pub force_digital: bool,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
 pub struct AnalogJoypadState {
     pub analog_button: bool,
@@ -66,21 +83,123 @@ pub enum ControllerType {
     None,
     Digital,
     DualShock,
+    Mouse,
+    GunCon,
+    Justifier,
+    NeGcon,
+    /// SCPH-1110 analog joystick (flight stick)
+    AnalogJoystick,
+}
+
+impl ControllerType {
+    #[must_use]
+    pub fn is_light_gun(self) -> bool {
+        matches!(self, Self::GunCon | Self::Justifier)
+    }
+}
+
+/// Inputs for a PS1 mouse.
+///
+/// The position is the total distance that the host mouse has moved, not a position on screen.
+/// Each time the PS1 polls the mouse, it receives the distance moved since the previous poll. The
+/// counters wrap on overflow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub struct MouseState {
+    pub x: i16,
+    pub y: i16,
+    pub left_button: bool,
+    pub right_button: bool,
+}
+
+impl MouseState {
+    pub fn add_motion(&mut self, dx: i16, dy: i16) {
+        self.x = self.x.wrapping_add(dx);
+        self.y = self.y.wrapping_add(dy);
+    }
+}
+
+/// Inputs for a light gun (`GunCon` or Justifier).
+///
+/// The aim point is a position within the emulator's output frame, from (0, 0) at the top left
+/// corner to (0xFFFF, 0xFFFF) at the bottom right corner. It is `None` while the gun is pointed away
+/// from the screen, which games use for reloading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub struct LightGunState {
+    pub aim: Option<(u16, u16)>,
+    pub trigger: bool,
+    /// A on the `GunCon`, Start on the Justifier
+    pub button_a: bool,
+    /// B on the `GunCon`, Aux on the Justifier
+    pub button_b: bool,
 }
 
+/// Analog inputs for a Namco `NeGcon`. The digital buttons (Start, D-pad, R, A, B) are read from
+/// [`DigitalJoypadState`] as Start, D-pad, R1, Circle, and Triangle.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct NeGconState {
+    /// Twist between the two halves of the controller; 0x00 = left, 0x80 = center, 0xFF = right
+    pub twist: u8,
+    /// 0x00 = released, 0xFF = fully pressed
+    pub button_i: u8,
+    pub button_ii: u8,
+    pub button_l: u8,
+}
+
+impl Default for NeGconState {
+    fn default() -> Self {
+        Self { twist: 0x80, button_i: 0x00, button_ii: 0x00, button_l: 0x00 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode)]
 pub struct ControllerState {
     pub controller_type: ControllerType,
     pub digital: DigitalJoypadState,
     pub analog: AnalogJoypadState,
+    pub mouse: MouseState,
+    pub light_gun: LightGunState,
+    pub negcon: NeGconState,
+}
+
+impl Decode for ControllerState {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            controller_type: Decode::decode(decoder)?,
+            digital: Decode::decode(decoder)?,
+            analog: Decode::decode(decoder)?,
+            // Mouse was added in version 6
+            mouse: if savestate::decoding_format_version() < 6 {
+                MouseState::default()
+            } else {
+                Decode::decode(decoder)?
+            },
+            // Light guns were added in version 7
+            light_gun: if savestate::decoding_format_version() < 7 {
+                LightGunState::default()
+            } else {
+                Decode::decode(decoder)?
+            },
+            // NeGcon was added in version 8
+            negcon: if savestate::decoding_format_version() < 8 {
+                NeGconState::default()
+            } else {
+                Decode::decode(decoder)?
+            },
+        })
+    }
 }
 
+bincode::impl_borrow_decode!(ControllerState);
+
 impl ControllerState {
     pub(crate) fn default_p1() -> Self {
         ControllerState {
             controller_type: ControllerType::Digital,
             digital: DigitalJoypadState::default(),
             analog: AnalogJoypadState::default(),
+            mouse: MouseState::default(),
+            light_gun: LightGunState::default(),
+            negcon: NeGconState::default(),
         }
     }
 
@@ -89,18 +208,85 @@ impl ControllerState {
             controller_type: ControllerType::None,
             digital: DigitalJoypadState::default(),
             analog: AnalogJoypadState::default(),
+            mouse: MouseState::default(),
+            light_gun: LightGunState::default(),
+            negcon: NeGconState::default(),
+        }
+    }
+}
+
+/// Force feedback motor state of a controller. Controllers without motors always report both
+/// motors as off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub struct RumbleState {
+    /// The small motor only has on and off states
+    pub small_motor: bool,
+    /// 0x00 = off, 0xFF = full strength
+    pub large_motor: u8,
+}
+
+impl RumbleState {
+    #[must_use]
+    pub fn is_active(self) -> bool {
+        self.small_motor || self.large_motor != 0
+    }
+}
+
+/// Number of controllers that a multitap connects to one port
+pub const MULTITAP_SLOTS: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Ps1Rumble {
+    pub p1: RumbleState,
+    pub p2: RumbleState,
+    /// Controllers in multitap slots B-D of port 1; all off if no multitap is connected
+    pub p1_multitap: [RumbleState; MULTITAP_SLOTS - 1],
+    /// Controllers in multitap slots B-D of port 2; all off if no multitap is connected
+    pub p2_multitap: [RumbleState; MULTITAP_SLOTS - 1],
+}
+
+impl Ps1Rumble {
+    /// Motor state of the controller in multitap slot `slot` (0 = slot A, or the only controller
+    /// if no multitap is connected) of port `port` (0 = port 1)
+    #[must_use]
+    pub fn slot(self, port: usize, slot: usize) -> RumbleState {
+        match (port, slot) {
+            (0, 0) => self.p1,
+            (1, 0) => self.p2,
+            (0, _) => self.p1_multitap.get(slot - 1).copied().unwrap_or_default(),
+            (1, _) => self.p2_multitap.get(slot - 1).copied().unwrap_or_default(),
+            _ => RumbleState::default(),
         }
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Ps1Inputs {
+    /// Controller in port 1, or in multitap slot A if a multitap is connected to port 1
     pub p1: ControllerState,
+    /// Controller in port 2, or in multitap slot A if a multitap is connected to port 2
     pub p2: ControllerState,
+    /// Controllers in multitap slots B-D of port 1, or `None` if no multitap is connected
+    pub p1_multitap: Option<[ControllerState; MULTITAP_SLOTS - 1]>,
+    /// Controllers in multitap slots B-D of port 2, or `None` if no multitap is connected
+    pub p2_multitap: Option<[ControllerState; MULTITAP_SLOTS - 1]>,
 }
 
 impl Default for Ps1Inputs {
     fn default() -> Self {
-        Self { p1: ControllerState::default_p1(), p2: ControllerState::default_p2() }
+        Self {
+            p1: ControllerState::default_p1(),
+            p2: ControllerState::default_p2(),
+            p1_multitap: None,
+            p2_multitap: None,
+        }
+    }
+}
+
+impl Ps1Inputs {
+    /// Controllers in multitap slots B-D filled with nothing, for connecting a multitap
+    #[must_use]
+    pub fn empty_multitap() -> [ControllerState; MULTITAP_SLOTS - 1] {
+        [ControllerState::default_p2(); MULTITAP_SLOTS - 1]
     }
 }
@@ -12,6 +12,7 @@ pub enum InterruptType {
     Timer2,
     Sio,
     Spu,
+    LightGun,
 }
 
 impl InterruptType {
@@ -25,6 +26,7 @@ impl InterruptType {
             Self::Timer2 => 1 << 6,
             Self::Sio => 1 << 7,
             Self::Spu => 1 << 9,
+            Self::LightGun => 1 << 10,
         }
     }
 }
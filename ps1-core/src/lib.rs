@@ -9,14 +9,18 @@ pub mod input;
 mod interrupts;
 mod mdec;
 mod memory;
+pub mod movie;
 mod num;
 mod pgxp;
+pub mod region;
+pub mod savestate;
 mod scheduler;
 mod sio;
 mod spu;
 mod timers;
 
 pub use gpu::RasterizerType;
+pub use sio::{link, memcard};
 
 #[must_use]
 pub fn required_wgpu_features() -> wgpu::Features {
@@ -5,6 +5,8 @@ use crate::boxedarray::BoxedArray;
 use crate::num::U32Ext;
 use crate::pgxp::{PgxpMemory, PreciseVertex};
 use bincode::{Decode, Encode};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 const BIOS_ROM_LEN: usize = 512 * 1024;
 pub const MAIN_RAM_LEN: usize = 2 * 1024 * 1024;
@@ -77,18 +79,22 @@ macro_rules! impl_write_u32 {
 }
 
 impl Memory {
-    pub fn new(bios_rom: Vec<u8>) -> Ps1Result<Self> {
+    /// Main RAM and the scratchpad are filled with pseudorandom values generated from `ram_seed`,
+    /// so power-on state is reproducible given the same seed.
+    pub fn new(bios_rom: Vec<u8>, ram_seed: u64) -> Ps1Result<Self> {
         if bios_rom.len() != BIOS_ROM_LEN {
             return Err(Ps1Error::IncorrectBiosSize { bios_len: bios_rom.len() });
         }
 
         let bios_rom: Box<[u8; BIOS_ROM_LEN]> = bios_rom.into_boxed_slice().try_into().unwrap();
 
+        let mut rng = ChaCha8Rng::seed_from_u64(ram_seed);
+
         let mut main_ram = MainRam::new();
-        main_ram.fill_with(rand::random);
+        main_ram.fill_with(|| rng.gen());
 
         let mut scratchpad = Scratchpad::new();
-        scratchpad.fill_with(rand::random);
+        scratchpad.fill_with(|| rng.gen());
 
         Ok(Self {
             bios_rom: BiosRom::from(bios_rom),
@@ -98,6 +104,14 @@ impl Memory {
         })
     }
 
+    pub fn bios_rom(&self) -> &[u8] {
+        self.bios_rom.as_slice()
+    }
+
+    pub fn main_ram_mut(&mut self) -> &mut [u8] {
+        self.main_ram.as_mut_slice()
+    }
+
     pub fn read_bios_u8(&self, address: u32) -> u8 {
         impl_read_u8!(self.bios_rom, BIOS_ROM_MASK, address)
     }
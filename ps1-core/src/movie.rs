@@ -0,0 +1,514 @@
+//! Input movies, which record the inputs passed to [`Ps1Emulator::tick`] on every frame so that a
+//! session can be replayed exactly
+//!
+//! A movie starts either from power-on or from an embedded save state. Power-on movies must be
+//! replayed on an emulator created with [`Ps1EmulatorBuilder::with_ram_seed`] (or reset with
+//! [`Ps1Emulator::power_cycle`]) using the movie's RAM seed, since the initial contents of main RAM
+//! are otherwise random. Embedded save states are stored as opaque bytes in whatever encoding the
+//! frontend uses for save state files.
+//!
+//! # File format
+//!
+//! All integers are little-endian.
+//!
+//! | Offset  | Size     | Contents                                                          |
+//! |---------|----------|-------------------------------------------------------------------|
+//! | 0x00    | 4        | Magic bytes `PS1M`                                                |
+//! | 0x04    | 2        | Format version, currently 2                                       |
+//! | 0x06    | 1        | Start type: 0 = power-on, 1 = embedded save state                 |
+//! | 0x07    | 1        | 1 if a disc was in the drive, 0 otherwise                         |
+//! | 0x08    | 4        | BIOS ROM CRC-32                                                   |
+//! | 0x0C    | 4        | Disc CRC-32 (see [`Ps1Emulator::content_identity`]), 0 if no disc |
+//! | 0x10    | 8        | Power-on: RAM seed. Save state: embedded save state length N      |
+//! | 0x18    | 4        | Frame count F                                                     |
+//! | 0x1C    | N        | Embedded save state (save state start only, otherwise N = 0)      |
+//! | 0x1C+N  | Variable | F frame input records                                             |
+//!
+//! Each frame input record starts with a byte of multitap flags (bit 0 = port 1, bit 1 = port 2),
+//! followed by an 8-byte port 1 record and an 8-byte port 2 record. These are followed by three more
+//! port records for multitap slots B-D of each port with a multitap connected, port 1 first. Port 1
+//! and port 2 records are for the controller in slot A when a multitap is connected. Version 1
+//! movies have no multitap flags byte and no multitap slot records.
+//!
+//! Each port record has the following layout:
+//!
+//! | Offset | Size | Contents                                                          |
+//! |--------|------|-------------------------------------------------------------------|
+//! | 0      | 1    | Controller type: 0 = none, 1 = digital, 2 = `DualShock`           |
+//! | 1      | 1    | Analog button: 1 if pressed, 0 otherwise                          |
+//! | 2      | 2    | Digital buttons, in the bit layout of [`DigitalJoypadState`]      |
+//! | 4      | 4    | Left stick X, left stick Y, right stick X, right stick Y (1 each) |
+//!
+//! Mouse records have controller type 3 and use the remaining bytes differently: byte 1 holds the
+//! buttons (bit 0 = left, bit 1 = right), bytes 2-3 are always 0, and bytes 4-7 hold the X and Y
+//! positions from [`MouseState`] (2 bytes each).
+//!
+//! Light gun records have controller type 4 (`GunCon`) or 5 (Justifier): byte 1 holds the buttons
+//! (bit 0 = trigger, bit 1 = A, bit 2 = B), byte 2 is 1 if the gun is aimed at the screen and 0
+//! otherwise, byte 3 is always 0, and bytes 4-7 hold the X and Y aim coordinates from
+//! [`LightGunState`] (2 bytes each).
+//!
+//! Analog joystick records have controller type 7 and the same layout as `DualShock` records.
+//! `NeGcon` records have controller type 6: byte 1 is always 0, bytes 2-3 hold the
+//! digital buttons, and bytes 4-7 hold the twist, I, II, and L values from [`NeGconState`].
+//!
+//! [`Ps1Emulator::tick`]: crate::api::Ps1Emulator::tick
+//! [`Ps1Emulator::power_cycle`]: crate::api::Ps1Emulator::power_cycle
+//! [`Ps1Emulator::content_identity`]: crate::api::Ps1Emulator::content_identity
+//! [`Ps1EmulatorBuilder::with_ram_seed`]: crate::api::Ps1EmulatorBuilder::with_ram_seed
+
+use crate::api::ContentIdentity;
+use crate::input::{
+    AnalogJoypadState, ControllerState, ControllerType, DigitalJoypadState, LightGunState,
+    MULTITAP_SLOTS, MouseState, NeGconState, Ps1Inputs,
+};
+use std::io;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+const MAGIC: [u8; 4] = *b"PS1M";
+
+pub const MOVIE_FORMAT_VERSION: u16 = 2;
+
+const START_POWER_ON: u8 = 0;
+const START_SAVE_STATE: u8 = 1;
+
+const PORT_RECORD_LEN: usize = 8;
+
+// Upper bound on the frame capacity reserved up front; the frame count comes from the file header
+// and cannot be trusted until that many records have actually been read
+const MAX_PREALLOCATED_FRAMES: u32 = 60 * 60 * 60;
+
+const MOUSE_TYPE: u8 = 3;
+const GUNCON_TYPE: u8 = 4;
+const JUSTIFIER_TYPE: u8 = 5;
+const NEGCON_TYPE: u8 = 6;
+const ANALOG_JOYSTICK_TYPE: u8 = 7;
+
+#[derive(Debug, Error)]
+pub enum MovieError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("File is not a movie (invalid magic bytes)")]
+    InvalidMagic,
+    #[error(
+        "Unsupported movie format version {0}; latest supported version is {MOVIE_FORMAT_VERSION}"
+    )]
+    UnsupportedVersion(u16),
+    #[error("Invalid movie start type {0}")]
+    InvalidStartType(u8),
+    #[error("Invalid controller type {value} in frame {frame}")]
+    InvalidControllerType { frame: u32, value: u8 },
+    #[error(
+        "Movie was recorded with a different BIOS (CRC-32 {expected:08X}, current BIOS is {actual:08X})"
+    )]
+    BiosMismatch { expected: u32, actual: u32 },
+    #[error("Movie was recorded with a different disc")]
+    DiscMismatch,
+}
+
+pub type MovieResult<T> = Result<T, MovieError>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    /// Recording started at power-on, with main RAM and the scratchpad initialized from this seed
+    PowerOn { ram_seed: u64 },
+    /// Recording started from this save state
+    SaveState(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    identity: ContentIdentity,
+    start: MovieStart,
+    frames: Vec<Ps1Inputs>,
+}
+
+impl Movie {
+    /// Create an empty movie that will start from the given point.
+    #[must_use]
+    pub fn new(identity: ContentIdentity, start: MovieStart) -> Self {
+        Self { identity, start, frames: Vec::new() }
+    }
+
+    #[must_use]
+    pub fn identity(&self) -> ContentIdentity {
+        self.identity
+    }
+
+    #[must_use]
+    pub fn start(&self) -> &MovieStart {
+        &self.start
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the inputs for the given frame, or `None` if the frame is past the end of the movie.
+    #[must_use]
+    pub fn frame_inputs(&self, frame: usize) -> Option<Ps1Inputs> {
+        self.frames.get(frame).copied()
+    }
+
+    pub fn push_frame(&mut self, inputs: Ps1Inputs) {
+        self.frames.push(inputs);
+    }
+
+    /// Check that the movie was recorded with the same BIOS and disc as the given identity.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if either the BIOS or the disc does not match.
+    pub fn check_identity(&self, current: ContentIdentity) -> MovieResult<()> {
+        if self.identity.bios_crc32 != current.bios_crc32 {
+            return Err(MovieError::BiosMismatch {
+                expected: self.identity.bios_crc32,
+                actual: current.bios_crc32,
+            });
+        }
+
+        if self.identity.disc_crc32 != current.disc_crc32 {
+            return Err(MovieError::DiscMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Will propagate any I/O error encountered while writing.
+    pub fn write<W: Write>(&self, writer: &mut W) -> MovieResult<()> {
+        let (start_type, start_value, save_state): (_, _, &[u8]) = match &self.start {
+            MovieStart::PowerOn { ram_seed } => (START_POWER_ON, *ram_seed, &[]),
+            MovieStart::SaveState(state) => (START_SAVE_STATE, state.len() as u64, state),
+        };
+
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&MOVIE_FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&[start_type, self.identity.disc_crc32.is_some().into()])?;
+        writer.write_all(&self.identity.bios_crc32.to_le_bytes())?;
+        writer.write_all(&self.identity.disc_crc32.unwrap_or(0).to_le_bytes())?;
+        writer.write_all(&start_value.to_le_bytes())?;
+        writer.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+        writer.write_all(save_state)?;
+
+        for inputs in &self.frames {
+            let multitap_flags = u8::from(inputs.p1_multitap.is_some())
+                | (u8::from(inputs.p2_multitap.is_some()) << 1);
+            writer.write_all(&[multitap_flags])?;
+            writer.write_all(&encode_port(inputs.p1))?;
+            writer.write_all(&encode_port(inputs.p2))?;
+
+            for slots in [inputs.p1_multitap, inputs.p2_multitap].into_iter().flatten() {
+                for state in slots {
+                    writer.write_all(&encode_port(state))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Will return an error if the data is not a valid movie in a supported format version, or
+    /// will propagate any I/O error encountered while reading.
+    #[allow(clippy::missing_panics_doc)]
+    pub fn read<R: Read>(reader: &mut R) -> MovieResult<Self> {
+        let mut header = [0; 0x1C];
+        reader.read_exact(&mut header)?;
+
+        if header[0x00..0x04] != MAGIC {
+            return Err(MovieError::InvalidMagic);
+        }
+
+        let version = u16::from_le_bytes(header[0x04..0x06].try_into().unwrap());
+        if version == 0 || version > MOVIE_FORMAT_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+
+        let start_type = header[0x06];
+        let has_disc = header[0x07] != 0;
+        let bios_crc32 = u32::from_le_bytes(header[0x08..0x0C].try_into().unwrap());
+        let disc_crc32 = u32::from_le_bytes(header[0x0C..0x10].try_into().unwrap());
+        let start_value = u64::from_le_bytes(header[0x10..0x18].try_into().unwrap());
+        let frame_count = u32::from_le_bytes(header[0x18..0x1C].try_into().unwrap());
+
+        let start = match start_type {
+            START_POWER_ON => MovieStart::PowerOn { ram_seed: start_value },
+            START_SAVE_STATE => {
+                let mut state = Vec::new();
+                reader.take(start_value).read_to_end(&mut state)?;
+                if state.len() as u64 != start_value {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                MovieStart::SaveState(state)
+            }
+            _ => return Err(MovieError::InvalidStartType(start_type)),
+        };
+
+        let mut frames = Vec::with_capacity(frame_count.min(MAX_PREALLOCATED_FRAMES) as usize);
+        for frame in 0..frame_count {
+            // Version 1 had no multitap support
+            let mut multitap_flags = [0];
+            if version >= 2 {
+                reader.read_exact(&mut multitap_flags)?;
+            }
+
+            let p1 = read_port(reader, frame)?;
+            let p2 = read_port(reader, frame)?;
+            let p1_multitap = read_multitap(reader, frame, multitap_flags[0] & 0x01 != 0)?;
+            let p2_multitap = read_multitap(reader, frame, multitap_flags[0] & 0x02 != 0)?;
+
+            frames.push(Ps1Inputs { p1, p2, p1_multitap, p2_multitap });
+        }
+
+        let identity = ContentIdentity { bios_crc32, disc_crc32: has_disc.then_some(disc_crc32) };
+
+        Ok(Self { identity, start, frames })
+    }
+}
+
+fn read_port<R: Read>(reader: &mut R, frame: u32) -> MovieResult<ControllerState> {
+    let mut record = [0; PORT_RECORD_LEN];
+    reader.read_exact(&mut record)?;
+
+    decode_port(frame, &record)
+}
+
+fn read_multitap<R: Read>(
+    reader: &mut R,
+    frame: u32,
+    connected: bool,
+) -> MovieResult<Option<[ControllerState; MULTITAP_SLOTS - 1]>> {
+    if !connected {
+        return Ok(None);
+    }
+
+    let mut slots = Ps1Inputs::empty_multitap();
+    for state in &mut slots {
+        *state = read_port(reader, frame)?;
+    }
+
+    Ok(Some(slots))
+}
+
+fn encode_port(state: ControllerState) -> [u8; PORT_RECORD_LEN] {
+    let controller_type = match state.controller_type {
+        ControllerType::None => 0,
+        ControllerType::Digital => 1,
+        ControllerType::DualShock => 2,
+        ControllerType::Mouse => return encode_mouse(state.mouse),
+        ControllerType::GunCon => return encode_light_gun(GUNCON_TYPE, state.light_gun),
+        ControllerType::Justifier => return encode_light_gun(JUSTIFIER_TYPE, state.light_gun),
+        ControllerType::NeGcon => return encode_negcon(state.digital, state.negcon),
+        ControllerType::AnalogJoystick => ANALOG_JOYSTICK_TYPE,
+    };
+    let [digital_lsb, digital_msb] = u16::from(state.digital).to_le_bytes();
+
+    [
+        controller_type,
+        state.analog.analog_button.into(),
+        digital_lsb,
+        digital_msb,
+        state.analog.left_x,
+        state.analog.left_y,
+        state.analog.right_x,
+        state.analog.right_y,
+    ]
+}
+
+fn encode_mouse(mouse: MouseState) -> [u8; PORT_RECORD_LEN] {
+    let buttons = u8::from(mouse.left_button) | (u8::from(mouse.right_button) << 1);
+    let [x_lsb, x_msb] = mouse.x.to_le_bytes();
+    let [y_lsb, y_msb] = mouse.y.to_le_bytes();
+
+    [MOUSE_TYPE, buttons, 0, 0, x_lsb, x_msb, y_lsb, y_msb]
+}
+
+fn encode_light_gun(controller_type: u8, light_gun: LightGunState) -> [u8; PORT_RECORD_LEN] {
+    let buttons = u8::from(light_gun.trigger)
+        | (u8::from(light_gun.button_a) << 1)
+        | (u8::from(light_gun.button_b) << 2);
+    let (x, y) = light_gun.aim.unwrap_or((0, 0));
+    let [x_lsb, x_msb] = x.to_le_bytes();
+    let [y_lsb, y_msb] = y.to_le_bytes();
+
+    [controller_type, buttons, light_gun.aim.is_some().into(), 0, x_lsb, x_msb, y_lsb, y_msb]
+}
+
+fn encode_negcon(digital: DigitalJoypadState, negcon: NeGconState) -> [u8; PORT_RECORD_LEN] {
+    let [digital_lsb, digital_msb] = u16::from(digital).to_le_bytes();
+
+    [
+        NEGCON_TYPE,
+        0,
+        digital_lsb,
+        digital_msb,
+        negcon.twist,
+        negcon.button_i,
+        negcon.button_ii,
+        negcon.button_l,
+    ]
+}
+
+fn decode_port(frame: u32, record: &[u8]) -> MovieResult<ControllerState> {
+    let controller_type = match record[0] {
+        0 => ControllerType::None,
+        1 => ControllerType::Digital,
+        2 => ControllerType::DualShock,
+        MOUSE_TYPE => return Ok(decode_mouse(record)),
+        GUNCON_TYPE => return Ok(decode_light_gun(ControllerType::GunCon, record)),
+        JUSTIFIER_TYPE => return Ok(decode_light_gun(ControllerType::Justifier, record)),
+        NEGCON_TYPE => return Ok(decode_negcon(record)),
+        ANALOG_JOYSTICK_TYPE => ControllerType::AnalogJoystick,
+        value => return Err(MovieError::InvalidControllerType { frame, value }),
+    };
+
+    Ok(ControllerState {
+        controller_type,
+        digital: DigitalJoypadState::from(u16::from_le_bytes([record[2], record[3]])),
+        analog: AnalogJoypadState {
+            analog_button: record[1] != 0,
+            left_x: record[4],
+            left_y: record[5],
+            right_x: record[6],
+            right_y: record[7],
+        },
+        mouse: MouseState::default(),
+        light_gun: LightGunState::default(),
+        negcon: NeGconState::default(),
+    })
+}
+
+fn decode_negcon(record: &[u8]) -> ControllerState {
+    ControllerState {
+        controller_type: ControllerType::NeGcon,
+        digital: DigitalJoypadState::from(u16::from_le_bytes([record[2], record[3]])),
+        negcon: NeGconState {
+            twist: record[4],
+            button_i: record[5],
+            button_ii: record[6],
+            button_l: record[7],
+        },
+        ..ControllerState::default_p2()
+    }
+}
+
+fn decode_mouse(record: &[u8]) -> ControllerState {
+    ControllerState {
+        controller_type: ControllerType::Mouse,
+        mouse: MouseState {
+            x: i16::from_le_bytes([record[4], record[5]]),
+            y: i16::from_le_bytes([record[6], record[7]]),
+            left_button: record[1] & 0x01 != 0,
+            right_button: record[1] & 0x02 != 0,
+        },
+        ..ControllerState::default_p2()
+    }
+}
+
+fn decode_light_gun(controller_type: ControllerType, record: &[u8]) -> ControllerState {
+    let aim = (record[2] != 0).then(|| {
+        (u16::from_le_bytes([record[4], record[5]]), u16::from_le_bytes([record[6], record[7]]))
+    });
+
+    ControllerState {
+        controller_type,
+        light_gun: LightGunState {
+            aim,
+            trigger: record[1] & 0x01 != 0,
+            button_a: record[1] & 0x02 != 0,
+            button_b: record[1] & 0x04 != 0,
+        },
+        ..ControllerState::default_p2()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let identity = ContentIdentity { bios_crc32: 0x12345678, disc_crc32: Some(0x9ABCDEF0) };
+        let mut movie = Movie::new(identity, MovieStart::SaveState(vec![1, 2, 3, 4, 5]));
+
+        let mut inputs = Ps1Inputs::default();
+        movie.push_frame(inputs);
+        inputs.p1.digital.set_cross(true);
+        inputs.p1.analog.left_x = 0x20;
+        inputs.p2.controller_type = ControllerType::DualShock;
+        inputs.p2.analog.analog_button = true;
+        movie.push_frame(inputs);
+        inputs.p2.controller_type = ControllerType::Mouse;
+        inputs.p2.analog = AnalogJoypadState::default();
+        inputs.p2.mouse = MouseState { x: -300, y: 5, left_button: false, right_button: true };
+        movie.push_frame(inputs);
+        inputs.p1.controller_type = ControllerType::GunCon;
+        inputs.p1.digital = DigitalJoypadState::default();
+        inputs.p1.analog = AnalogJoypadState::default();
+        inputs.p1.light_gun.aim = Some((0x8000, 0x1234));
+        inputs.p1.light_gun.trigger = true;
+        movie.push_frame(inputs);
+        inputs.p1 = ControllerState::default_p1();
+        inputs.p1.controller_type = ControllerType::NeGcon;
+        inputs.p1.negcon.twist = 0x30;
+        inputs.p1.negcon.button_ii = 0xC0;
+        inputs.p2 = ControllerState::default_p2();
+        inputs.p2.controller_type = ControllerType::AnalogJoystick;
+        inputs.p2.analog.right_y = 0xFF;
+        movie.push_frame(inputs);
+        let mut slots = Ps1Inputs::empty_multitap();
+        slots[2].controller_type = ControllerType::DualShock;
+        slots[2].digital.set_start(true);
+        inputs.p2_multitap = Some(slots);
+        movie.push_frame(inputs);
+
+        let mut bytes = Vec::new();
+        movie.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 0x1C + 5 + 6 * 17 + 3 * 8);
+
+        let read_movie = Movie::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read_movie, movie);
+        assert!(read_movie.check_identity(identity).is_ok());
+        assert!(matches!(
+            read_movie.check_identity(ContentIdentity { disc_crc32: None, ..identity }),
+            Err(MovieError::DiscMismatch)
+        ));
+    }
+
+    #[test]
+    fn invalid_header() {
+        let movie = Movie::new(
+            ContentIdentity { bios_crc32: 0, disc_crc32: None },
+            MovieStart::PowerOn { ram_seed: 5 },
+        );
+        let mut bytes = Vec::new();
+        movie.write(&mut bytes).unwrap();
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(Movie::read(&mut bad_magic.as_slice()), Err(MovieError::InvalidMagic)));
+
+        let mut bad_version = bytes.clone();
+        bad_version[0x04] = 0xFF;
+        assert!(matches!(
+            Movie::read(&mut bad_version.as_slice()),
+            Err(MovieError::UnsupportedVersion(0x00FF))
+        ));
+
+        assert!(matches!(Movie::read(&mut &bytes[..0x10]), Err(MovieError::Io(_))));
+
+        let mut huge_frame_count = bytes.clone();
+        huge_frame_count[0x18..0x1C].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(Movie::read(&mut huge_frame_count.as_slice()), Err(MovieError::Io(_))));
+    }
+}
@@ -0,0 +1,112 @@
+//! Console and disc regions
+//!
+//! The console region determines the GPU clock rate (the video crystal differs between NTSC and PAL
+//! consoles) and the region that the CD controller reports for discs without a detectable region.
+//! It is either forced through the config or detected from the disc, falling back to the BIOS.
+
+use crate::gpu::VideoMode;
+use bincode::{Decode, Encode};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Region {
+    /// North America
+    NtscU,
+    /// Japan and Asia
+    NtscJ,
+    /// Europe and Australia
+    Pal,
+}
+
+impl Region {
+    pub const ALL: [Self; 3] = [Self::NtscU, Self::NtscJ, Self::Pal];
+
+    /// Determine the region of a game from its serial number prefix (e.g. `SLUS` in `SLUS-00594`).
+    #[must_use]
+    pub fn from_game_id(game_id: &str) -> Option<Self> {
+        let prefix = game_id.get(..4)?.to_ascii_uppercase();
+        match prefix.as_str() {
+            "SCUS" | "SLUS" => Some(Self::NtscU),
+            "SCPS" | "SLPS" | "SCPM" | "SLPM" | "SIPS" | "SCAJ" | "SLKA" => Some(Self::NtscJ),
+            "SCES" | "SLES" | "SCED" | "SLED" => Some(Self::Pal),
+            _ => None,
+        }
+    }
+
+    /// Determine the region of a BIOS ROM from the region letter at the end of its version string,
+    /// e.g. `System ROM Version 4.1 12/16/97 E`.
+    #[must_use]
+    pub fn from_bios(bios_rom: &[u8]) -> Option<Self> {
+        const VERSION_PREFIX: &[u8] = b"System ROM Version";
+
+        let start = bios_rom.windows(VERSION_PREFIX.len()).position(|w| w == VERSION_PREFIX)?;
+        let version = bios_rom[start..].split(|&b| b == 0).next()?;
+        match version.trim_ascii_end().last()? {
+            b'A' => Some(Self::NtscU),
+            b'J' => Some(Self::NtscJ),
+            b'E' => Some(Self::Pal),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn video_mode(self) -> VideoMode {
+        match self {
+            Self::NtscU | Self::NtscJ => VideoMode::Ntsc,
+            Self::Pal => VideoMode::Pal,
+        }
+    }
+
+    // Last character of the "SCEx" license string reported by the GetID command
+    pub(crate) fn scex_letter(self) -> u8 {
+        match self {
+            Self::NtscU => b'A',
+            Self::NtscJ => b'I',
+            Self::Pal => b'E',
+        }
+    }
+}
+
+impl Display for Region {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NtscU => write!(f, "NTSC-U"),
+            Self::NtscJ => write!(f, "NTSC-J"),
+            Self::Pal => write!(f, "PAL"),
+        }
+    }
+}
+
+/// Pick the console region to use in auto mode: the disc's region if it is known, otherwise the
+/// BIOS region, otherwise NTSC-U.
+pub(crate) fn auto_detect(bios_region: Option<Region>, disc_region: Option<Region>) -> Region {
+    disc_region.or(bios_region).unwrap_or(Region::NtscU)
+}
+
+pub(crate) fn warn_on_mismatch(bios_region: Option<Region>, disc_region: Option<Region>) {
+    if let (Some(bios_region), Some(disc_region)) = (bios_region, disc_region) {
+        if bios_region != disc_region {
+            log::warn!(
+                "BIOS region ({bios_region}) does not match disc region ({disc_region}); the game may not boot or may run at the wrong speed"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_from_bios_version_string() {
+        let mut bios_rom = vec![0; 0x80000];
+        let version = b"System ROM Version 4.1 12/16/97 E\0";
+        bios_rom[0x7FF32..0x7FF32 + version.len()].copy_from_slice(version);
+        assert_eq!(Region::from_bios(&bios_rom), Some(Region::Pal));
+
+        assert_eq!(Region::from_bios(&vec![0; 0x80000]), None);
+        assert_eq!(Region::from_game_id("SLUS-00594"), Some(Region::NtscU));
+        assert_eq!(Region::from_game_id("SCPS-10001"), Some(Region::NtscJ));
+        assert_eq!(Region::from_game_id("HOMEBREW"), None);
+    }
+}
@@ -0,0 +1,312 @@
+//! Versioned save state container
+//!
+//! [`Ps1EmulatorState`] is encoded with bincode, whose output depends on the exact layout of every
+//! state struct in the emulator. The container wraps that encoding with enough information to
+//! reject states that cannot be loaded with a clear error instead of failing somewhere inside
+//! bincode (or silently loading garbage), and to upgrade states written by older versions.
+//!
+//! # File format
+//!
+//! All integers are little-endian.
+//!
+//! | Offset | Size | Contents                                                          |
+//! |--------|------|-------------------------------------------------------------------|
+//! | 0x00   | 8    | Magic bytes `PS1STATE`                                            |
+//! | 0x08   | 2    | Format version, currently 16                                      |
+//! | 0x0A   | 1    | 1 if a disc was in the drive, 0 otherwise                         |
+//! | 0x0B   | 1    | Reserved, always 0                                                |
+//! | 0x0C   | 4    | BIOS ROM CRC-32                                                   |
+//! | 0x10   | 4    | Disc CRC-32 (see [`Ps1Emulator::content_identity`]), 0 if no disc |
+//! | 0x14   | 4    | CRC-32 of the payload                                             |
+//! | 0x18   | 8    | Payload length N                                                  |
+//! | 0x20   | N    | Payload: bincode-encoded [`Ps1EmulatorState`]                     |
+//!
+//! # Versioning
+//!
+//! The format version must be incremented whenever a change to any state struct changes the
+//! bincode encoding, and states from every older version must remain readable. Fields added to a
+//! struct that derives `SaveState` should be annotated with `#[save_state(since = N)]`, where N is
+//! the new format version; they are then set to their default values when older states are
+//! decoded. Any other kind of change needs a manual `Decode` implementation that checks
+//! [`decoding_format_version`].
+//!
+//! | Version | Changes                                         |
+//! |---------|-------------------------------------------------|
+//! | 1       | Initial version                                 |
+//! | 2       | Added CD-ROM shell (lid) state                  |
+//! | 3       | Added memory card slot 2; slots can be empty    |
+//! | 4       | Added DualShock rumble motor state              |
+//! | 5       | DualShock state is tracked separately per port  |
+//! | 6       | Added PlayStation Mouse                         |
+//! | 7       | Added GunCon and Justifier light guns           |
+//! | 8       | Added NeGcon and analog joystick                |
+//! | 9       | Added multitap                                  |
+//! | 10      | SIO1 serial port reimplemented for link cable   |
+//! | 11      | Added DualShock analog mode config              |
+//! | 12      | Added CD-ROM audio scan and GetQ command state  |
+//! | 13      | GPU clock follows the console region            |
+//! | 14      | Added CD-XA de-emphasis filter state            |
+//! | 15      | Added memory cards in multitap slots B-D        |
+//! | 16      | Added CD-ROM audio track for scan auto-pause    |
+//!
+//! [`Ps1Emulator::content_identity`]: crate::api::Ps1Emulator::content_identity
+
+use crate::api::{ContentIdentity, Ps1EmulatorState};
+use bincode::Decode;
+use bincode::error::{DecodeError, EncodeError};
+use crc::Crc;
+use std::cell::Cell;
+use std::io;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+const MAGIC: [u8; 8] = *b"PS1STATE";
+
+pub const SAVE_STATE_FORMAT_VERSION: u16 = 16;
+
+const HEADER_LEN: usize = 0x20;
+
+const PAYLOAD_CRC: Crc<u32> = Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+macro_rules! bincode_config {
+    () => {
+        bincode::config::standard()
+            .with_little_endian()
+            .with_fixed_int_encoding()
+            .with_limit::<1_000_000_000>()
+    };
+}
+
+thread_local! {
+    static DECODING_FORMAT_VERSION: Cell<u16> = const { Cell::new(SAVE_STATE_FORMAT_VERSION) };
+}
+
+/// Returns the format version of the save state that is currently being decoded on this thread, or
+/// the current format version if no save state is being decoded.
+#[must_use]
+pub fn decoding_format_version() -> u16 {
+    DECODING_FORMAT_VERSION.get()
+}
+
+#[derive(Debug, Error)]
+pub enum SaveStateError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("File is not a save state (invalid magic bytes)")]
+    InvalidMagic,
+    #[error(
+        "Save state format version {0} is newer than the latest supported version {SAVE_STATE_FORMAT_VERSION}"
+    )]
+    UnsupportedVersion(u16),
+    #[error("Save state is corrupt (CRC-32 {actual:08X}, expected {expected:08X})")]
+    ChecksumMismatch { expected: u32, actual: u32 },
+    #[error(
+        "Save state was created with a different BIOS (CRC-32 {expected:08X}, current BIOS is {actual:08X})"
+    )]
+    BiosMismatch { expected: u32, actual: u32 },
+    #[error("Save state was created with a different disc")]
+    DiscMismatch,
+    #[error("Error encoding save state: {0}")]
+    Encode(#[from] EncodeError),
+    #[error("Error decoding save state: {0}")]
+    Decode(#[from] DecodeError),
+}
+
+pub type SaveStateResult<T> = Result<T, SaveStateError>;
+
+/// Write a save state in the container format.
+///
+/// # Errors
+///
+/// Will propagate any encoding error or any I/O error encountered while writing.
+pub fn write<W: Write>(
+    writer: &mut W,
+    identity: ContentIdentity,
+    state: &Ps1EmulatorState,
+) -> SaveStateResult<()> {
+    let payload = bincode::encode_to_vec(state, bincode_config!())?;
+    write_payload(writer, identity, &payload)
+}
+
+/// Read a save state in the container format, upgrading it from an older format version if
+/// necessary.
+///
+/// # Errors
+///
+/// Will return an error if the data is not a valid save state, if it is from an unsupported format
+/// version, if it is corrupt, or if it was created with a different BIOS or disc than `identity`.
+pub fn read<R: Read>(
+    reader: &mut R,
+    identity: ContentIdentity,
+) -> SaveStateResult<Ps1EmulatorState> {
+    let (version, payload) = read_payload(reader, identity)?;
+    decode_payload(&payload, version)
+}
+
+fn decode_payload<T: Decode>(payload: &[u8], version: u16) -> SaveStateResult<T> {
+    let previous_version = DECODING_FORMAT_VERSION.replace(version);
+    let result = bincode::decode_from_slice(payload, bincode_config!());
+    DECODING_FORMAT_VERSION.set(previous_version);
+
+    let (state, _) = result?;
+    Ok(state)
+}
+
+fn write_payload<W: Write>(
+    writer: &mut W,
+    identity: ContentIdentity,
+    payload: &[u8],
+) -> SaveStateResult<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&SAVE_STATE_FORMAT_VERSION.to_le_bytes())?;
+    writer.write_all(&[identity.disc_crc32.is_some().into(), 0])?;
+    writer.write_all(&identity.bios_crc32.to_le_bytes())?;
+    writer.write_all(&identity.disc_crc32.unwrap_or(0).to_le_bytes())?;
+    writer.write_all(&PAYLOAD_CRC.checksum(payload).to_le_bytes())?;
+    writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+    writer.write_all(payload)?;
+
+    Ok(())
+}
+
+fn read_payload<R: Read>(
+    reader: &mut R,
+    identity: ContentIdentity,
+) -> SaveStateResult<(u16, Vec<u8>)> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    if header[0x00..0x08] != MAGIC {
+        return Err(SaveStateError::InvalidMagic);
+    }
+
+    let version = u16::from_le_bytes(header[0x08..0x0A].try_into().unwrap());
+    if version == 0 || version > SAVE_STATE_FORMAT_VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+
+    let has_disc = header[0x0A] != 0;
+    let bios_crc32 = u32::from_le_bytes(header[0x0C..0x10].try_into().unwrap());
+    let disc_crc32 = u32::from_le_bytes(header[0x10..0x14].try_into().unwrap());
+    let payload_crc32 = u32::from_le_bytes(header[0x14..0x18].try_into().unwrap());
+    let payload_len = u64::from_le_bytes(header[0x18..0x20].try_into().unwrap());
+
+    if bios_crc32 != identity.bios_crc32 {
+        return Err(SaveStateError::BiosMismatch {
+            expected: bios_crc32,
+            actual: identity.bios_crc32,
+        });
+    }
+
+    if has_disc.then_some(disc_crc32) != identity.disc_crc32 {
+        return Err(SaveStateError::DiscMismatch);
+    }
+
+    let mut payload = Vec::new();
+    reader.take(payload_len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != payload_len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+
+    let actual_crc32 = PAYLOAD_CRC.checksum(&payload);
+    if actual_crc32 != payload_crc32 {
+        return Err(SaveStateError::ChecksumMismatch {
+            expected: payload_crc32,
+            actual: actual_crc32,
+        });
+    }
+
+    Ok((version, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proc_macros::SaveState;
+
+    const IDENTITY: ContentIdentity =
+        ContentIdentity { bios_crc32: 0x12345678, disc_crc32: Some(0x9ABCDEF0) };
+
+    fn container(payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_payload(&mut bytes, IDENTITY, payload).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = container(b"payload");
+        assert_eq!(bytes.len(), HEADER_LEN + 7);
+        assert_eq!(
+            read_payload(&mut bytes.as_slice(), IDENTITY).unwrap(),
+            (SAVE_STATE_FORMAT_VERSION, b"payload".to_vec())
+        );
+    }
+
+    #[derive(SaveState)]
+    struct Versioned {
+        a: u32,
+        #[save_state(since = 2)]
+        b: u32,
+    }
+
+    #[test]
+    fn fields_added_in_later_versions_are_defaulted() {
+        let mut versioned = Versioned { a: 1, b: 2 };
+        let payload = bincode::encode_to_vec(versioned.save_state(), bincode_config!()).unwrap();
+
+        let state: VersionedState = decode_payload(&payload, 2).unwrap();
+        assert_eq!((state.a, state.b), (1, 2));
+
+        let state: VersionedState = decode_payload(&payload[..4], 1).unwrap();
+        assert_eq!((state.a, state.b), (1, 0));
+        assert_eq!(decoding_format_version(), SAVE_STATE_FORMAT_VERSION);
+    }
+
+    #[test]
+    fn invalid_containers_are_rejected() {
+        let mut bytes = container(b"payload");
+        bytes[0] = b'X';
+        assert!(matches!(
+            read_payload(&mut bytes.as_slice(), IDENTITY),
+            Err(SaveStateError::InvalidMagic)
+        ));
+
+        let mut bytes = container(b"payload");
+        bytes[0x08] = 0xFF;
+        assert!(matches!(
+            read_payload(&mut bytes.as_slice(), IDENTITY),
+            Err(SaveStateError::UnsupportedVersion(0xFF))
+        ));
+
+        let mut bytes = container(b"payload");
+        *bytes.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            read_payload(&mut bytes.as_slice(), IDENTITY),
+            Err(SaveStateError::ChecksumMismatch { .. })
+        ));
+
+        let bytes = container(b"payload");
+        assert!(matches!(
+            read_payload(&mut &bytes[..bytes.len() - 1], IDENTITY),
+            Err(SaveStateError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn identity_must_match() {
+        let bytes = container(b"payload");
+
+        let other_bios = ContentIdentity { bios_crc32: 0, ..IDENTITY };
+        assert!(matches!(
+            read_payload(&mut bytes.as_slice(), other_bios),
+            Err(SaveStateError::BiosMismatch { expected: 0x12345678, actual: 0 })
+        ));
+
+        let no_disc = ContentIdentity { disc_crc32: None, ..IDENTITY };
+        assert!(matches!(
+            read_payload(&mut bytes.as_slice(), no_disc),
+            Err(SaveStateError::DiscMismatch)
+        ));
+    }
+}
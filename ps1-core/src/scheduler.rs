@@ -16,6 +16,7 @@ pub enum SchedulerEventType {
     Sio0Tx,
     Sio1Irq,
     Sio1Tx,
+    LightGunIrq,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
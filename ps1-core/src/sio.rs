@@ -2,21 +2,38 @@
 //!
 //! SIO0 is used to communicate with controllers and memory cards
 //!
-//! SIO1 is mostly unused, but some games used it for link cable functionality (not emulated)
+//! SIO1 is mostly unused, but some games used it for link cable functionality, which is emulated by
+//! connecting two emulator instances over a local socket
 
 mod controllers;
+pub mod link;
 pub mod memcard;
+mod multitap;
 mod rxfifo;
+mod sio1;
 
-use crate::input::{ControllerState, ControllerType, Ps1Inputs};
+use crate::gpu::BeamPosition;
+use crate::input::{
+    ControllerState, ControllerType, DualShockConfig, MULTITAP_SLOTS, Ps1Inputs, Ps1Rumble,
+    RumbleState,
+};
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::num::U32Ext;
+use crate::savestate;
 use crate::scheduler::{Scheduler, SchedulerEvent, SchedulerEventType};
-use crate::sio::controllers::{DigitalController, DualShock, DualShockControllerState};
+use crate::sio::controllers::{
+    AnalogJoystick, DigitalController, DualShock, DualShockControllerState, DualShockMotors,
+    GunCon, Justifier, Mouse, MousePosition, NeGcon,
+};
 use crate::sio::memcard::{ConnectedMemoryCard, MemoryCard};
+use crate::sio::multitap::{Multitap, MultitapTransfer};
 use crate::sio::rxfifo::RxFifo;
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{BorrowDecode, Decode, Encode};
-use std::cmp;
+use std::{array, cmp};
+
+pub use sio1::SerialPort1;
 
 #[derive(Debug, Clone, Copy, Encode, Decode)]
 struct BaudrateTimer {
@@ -30,17 +47,15 @@ impl BaudrateTimer {
         Self { timer: 0x0088, raw_reload_value: 0x0088, reload_factor: 2 }
     }
 
-    fn tick(&mut self, mut cpu_cycles: u32) {
-        // TODO this is terribly inefficient; improve this
-        while cpu_cycles != 0 {
-            let elapsed = cmp::min(self.timer, cpu_cycles);
-            self.timer -= elapsed;
-            cpu_cycles -= elapsed;
-
-            if self.timer == 0 {
-                self.timer = self.reload_value();
-            }
+    fn tick(&mut self, cpu_cycles: u32) {
+        if cpu_cycles < self.timer {
+            self.timer -= cpu_cycles;
+            return;
         }
+
+        // Timer reloads every time it reaches 0
+        let reload_value = self.reload_value();
+        self.timer = reload_value - (cpu_cycles - self.timer) % reload_value;
     }
 
     fn reload_value(&mut self) -> u32 {
@@ -122,38 +137,267 @@ pub trait SerialDevices {
         &mut self,
         device: Self::Device,
         tx: u8,
+        port: Port,
         rx: &mut RxFifo,
     ) -> Option<Self::Device>;
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+/// Host inputs and persistent controller-side state for the controller in one port
+#[derive(Debug, Clone, Encode)]
+struct ControllerPort {
+    joypad_state: ControllerState,
+    last_analog_button: bool,
+    last_controller_type: ControllerType,
+    dualshock_state: DualShockControllerState,
+    dualshock_motors: DualShockMotors,
+    mouse_position: MousePosition,
+    // Beam position that a light gun in this port is aimed at, if any
+    light_gun_target: Option<BeamPosition>,
+    justifier_irq_enabled: bool,
+}
+
+impl Decode for ControllerPort {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let joypad_state: ControllerState = Decode::decode(decoder)?;
+        Ok(Self {
+            joypad_state,
+            last_analog_button: Decode::decode(decoder)?,
+            last_controller_type: Decode::decode(decoder)?,
+            dualshock_state: Decode::decode(decoder)?,
+            dualshock_motors: Decode::decode(decoder)?,
+            // Mouse was added in version 6
+            mouse_position: if savestate::decoding_format_version() < 6 {
+                MousePosition::of(joypad_state.mouse)
+            } else {
+                Decode::decode(decoder)?
+            },
+            light_gun_target: if savestate::decoding_format_version() < 7 {
+                None
+            } else {
+                Decode::decode(decoder)?
+            },
+            justifier_irq_enabled: if savestate::decoding_format_version() < 7 {
+                false
+            } else {
+                Decode::decode(decoder)?
+            },
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(ControllerPort);
+
+impl ControllerPort {
+    fn new(joypad_state: ControllerState, dualshock_config: DualShockConfig) -> Self {
+        Self {
+            joypad_state,
+            last_analog_button: false,
+            last_controller_type: joypad_state.controller_type,
+            dualshock_state: DualShockControllerState::new(dualshock_config),
+            dualshock_motors: DualShockMotors::default(),
+            mouse_position: MousePosition::of(joypad_state.mouse),
+            light_gun_target: None,
+            justifier_irq_enabled: false,
+        }
+    }
+
+    fn set_inputs(
+        &mut self,
+        inputs: ControllerState,
+        beam_position: impl Fn(u16, u16) -> BeamPosition,
+    ) {
+        self.joypad_state = inputs;
+        self.light_gun_target = inputs.light_gun.aim.map(|(x, y)| beam_position(x, y));
+
+        if self.last_controller_type != inputs.controller_type {
+            self.dualshock_state = DualShockControllerState::new(self.dualshock_state.config);
+            self.dualshock_motors = DualShockMotors::default();
+            // Don't report motion that happened while a mouse wasn't connected
+            self.mouse_position = MousePosition::of(inputs.mouse);
+            self.justifier_irq_enabled = false;
+        }
+        self.last_controller_type = inputs.controller_type;
+
+        if !self.last_analog_button
+            && inputs.analog.analog_button
+            && inputs.controller_type == ControllerType::DualShock
+        {
+            self.dualshock_state.toggle_analog_mode();
+        }
+        self.last_analog_button = inputs.analog.analog_button;
+    }
+
+    fn rumble(&self) -> RumbleState {
+        match self.joypad_state.controller_type {
+            ControllerType::DualShock => self.dualshock_motors.current,
+            ControllerType::None
+            | ControllerType::Digital
+            | ControllerType::Mouse
+            | ControllerType::GunCon
+            | ControllerType::Justifier
+            | ControllerType::NeGcon
+            | ControllerType::AnalogJoystick => RumbleState::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode)]
 pub struct Sio0Devices {
-    p1_joypad_state: ControllerState,
-    p2_joypad_state: ControllerState,
-    last_p1_analog_button: bool,
-    last_p1_controller_type: ControllerType,
-    p1_dualshock_state: DualShockControllerState,
-    memory_card_1: MemoryCard,
+    controllers: [ControllerPort; 2],
+    memory_card_1: Option<MemoryCard>,
+    memory_card_2: Option<MemoryCard>,
+    multitaps: [Option<Multitap>; 2],
+    // Cards in multitap slots B-D of each port, only reachable while a multitap is connected
+    multitap_memory_cards: MultitapMemoryCards,
 }
 
+/// Memory cards in multitap slots B-D, indexed by port and then by slot (0 = slot B)
+pub type MultitapMemoryCards = [[Option<MemoryCard>; MULTITAP_SLOTS - 1]; 2];
+
 impl Sio0Devices {
-    fn new(memory_card_1: Option<Vec<u8>>) -> Self {
+    fn new(
+        memory_card_1: Option<MemoryCard>,
+        memory_card_2: Option<MemoryCard>,
+        multitap_memory_cards: MultitapMemoryCards,
+    ) -> Self {
         Self {
-            p1_joypad_state: ControllerState::default_p1(),
-            p1_dualshock_state: DualShockControllerState::default(),
-            last_p1_analog_button: false,
-            last_p1_controller_type: ControllerState::default_p1().controller_type,
-            p2_joypad_state: ControllerState::default_p2(),
-            memory_card_1: MemoryCard::new(memory_card_1),
+            controllers: [
+                ControllerPort::new(ControllerState::default_p1(), DualShockConfig::default()),
+                ControllerPort::new(ControllerState::default_p2(), DualShockConfig::default()),
+            ],
+            memory_card_1,
+            memory_card_2,
+            multitaps: [None, None],
+            multitap_memory_cards,
+        }
+    }
+
+    fn set_multitap_inputs(
+        &mut self,
+        port: Port,
+        inputs: Option<[ControllerState; MULTITAP_SLOTS - 1]>,
+        beam_position: impl Fn(u16, u16) -> BeamPosition,
+    ) {
+        let multitap = &mut self.multitaps[port as usize];
+        match inputs {
+            Some(inputs) => {
+                let dualshock_config = self.controllers[port as usize].dualshock_state.config;
+                multitap
+                    .get_or_insert_with(|| Multitap::new(inputs, dualshock_config))
+                    .set_inputs(inputs, beam_position);
+            }
+            None => *multitap = None,
+        }
+    }
+
+    fn set_dualshock_config(&mut self, config: DualShockConfig) {
+        let multitap_slots =
+            self.multitaps.iter_mut().flatten().flat_map(|multitap| multitap.slots.iter_mut());
+        for controller in self.controllers.iter_mut().chain(multitap_slots) {
+            controller.dualshock_state.set_config(config);
+        }
+    }
+
+    // Every connected controller, in port then multitap slot order
+    fn all_controllers(&self) -> impl Iterator<Item = &ControllerPort> {
+        [Port::One, Port::Two].into_iter().flat_map(move |port| {
+            (0..MULTITAP_SLOTS as u8).filter_map(move |slot| self.slot_controller(port, slot))
+        })
+    }
+
+    fn memory_card(&mut self, port: Port) -> Option<&mut MemoryCard> {
+        match port {
+            Port::One => self.memory_card_1.as_mut(),
+            Port::Two => self.memory_card_2.as_mut(),
         }
     }
+
+    // Before version 5, only port 1 had DualShock state and the fields were laid out individually
+    fn decode_pre_v5<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let p1_joypad_state = Decode::decode(decoder)?;
+        let p2_joypad_state: ControllerState = Decode::decode(decoder)?;
+        let last_p1_analog_button = Decode::decode(decoder)?;
+        let last_p1_controller_type = Decode::decode(decoder)?;
+        let p1_dualshock_state = Decode::decode(decoder)?;
+
+        // Before version 3, there was always a card in port 1 and never a card in port 2
+        let (memory_card_1, memory_card_2) = if savestate::decoding_format_version() < 3 {
+            (Some(Decode::decode(decoder)?), None)
+        } else {
+            (Decode::decode(decoder)?, Decode::decode(decoder)?)
+        };
+
+        let [p1_motors, p2_motors]: [DualShockMotors; 2] =
+            if savestate::decoding_format_version() < 4 {
+                [DualShockMotors::default(); 2]
+            } else {
+                Decode::decode(decoder)?
+            };
+
+        let p1 = ControllerPort {
+            joypad_state: p1_joypad_state,
+            last_analog_button: last_p1_analog_button,
+            last_controller_type: last_p1_controller_type,
+            dualshock_state: p1_dualshock_state,
+            dualshock_motors: p1_motors,
+            mouse_position: MousePosition::default(),
+            light_gun_target: None,
+            justifier_irq_enabled: false,
+        };
+        let p2 = ControllerPort {
+            dualshock_motors: p2_motors,
+            ..ControllerPort::new(p2_joypad_state, DualShockConfig::default())
+        };
+
+        Ok(Self {
+            controllers: [p1, p2],
+            memory_card_1,
+            memory_card_2,
+            multitaps: [None, None],
+            multitap_memory_cards: Default::default(),
+        })
+    }
 }
 
+impl Decode for Sio0Devices {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        if savestate::decoding_format_version() < 5 {
+            return Self::decode_pre_v5(decoder);
+        }
+
+        Ok(Self {
+            controllers: Decode::decode(decoder)?,
+            memory_card_1: Decode::decode(decoder)?,
+            memory_card_2: Decode::decode(decoder)?,
+            // Multitap was added in version 9
+            multitaps: if savestate::decoding_format_version() < 9 {
+                [None, None]
+            } else {
+                Decode::decode(decoder)?
+            },
+            // Multitap memory cards were added in version 15
+            multitap_memory_cards: if savestate::decoding_format_version() < 15 {
+                Default::default()
+            } else {
+                Decode::decode(decoder)?
+            },
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(Sio0Devices);
+
 #[derive(Debug, Clone, Encode, Decode)]
 pub enum Sio0Device {
     DigitalController(DigitalController),
     DualShock(DualShock),
     MemoryCard(ConnectedMemoryCard),
+    Mouse(Mouse),
+    GunCon(GunCon),
+    Justifier(Justifier),
+    NeGcon(NeGcon),
+    AnalogJoystick(AnalogJoystick),
+    Multitap(MultitapTransfer),
 }
 
 const CONTROLLER_ADDRESS: u8 = 0x01;
@@ -163,11 +407,17 @@ impl SerialDevices for Sio0Devices {
     type Device = Sio0Device;
 
     fn connect(&self, tx: u8, port: Port) -> Option<Self::Device> {
+        if self.multitaps[port as usize].is_some() {
+            return self.connect_multitap(tx, port);
+        }
+
         match (tx, port) {
-            (CONTROLLER_ADDRESS, Port::One) => initial_controller_state(self.p1_joypad_state),
-            (CONTROLLER_ADDRESS, Port::Two) => initial_controller_state(self.p2_joypad_state),
+            (CONTROLLER_ADDRESS, _) => initial_controller_state(&self.controllers[port as usize]),
             (MEMORY_CARD_ADDRESS, Port::One) => {
-                Some(Sio0Device::MemoryCard(ConnectedMemoryCard::initial()))
+                self.memory_card_1.as_ref().map(|_| connected_memory_card())
+            }
+            (MEMORY_CARD_ADDRESS, Port::Two) => {
+                self.memory_card_2.as_ref().map(|_| connected_memory_card())
             }
             _ => None,
         }
@@ -177,23 +427,60 @@ impl SerialDevices for Sio0Devices {
         &mut self,
         device: Self::Device,
         tx: u8,
+        port: Port,
         rx: &mut RxFifo,
     ) -> Option<Self::Device> {
         match device {
-            Sio0Device::DigitalController(controller) => {
-                controller.process(tx, rx).map(Sio0Device::DigitalController)
+            Sio0Device::MemoryCard(connected_memory_card) => {
+                // Cards can't be removed mid-transfer, so the card in the port should still exist
+                let card = self.memory_card(port)?;
+                connected_memory_card.process(tx, rx, card).map(Sio0Device::MemoryCard)
             }
-            Sio0Device::DualShock(dual_shock) => {
-                dual_shock.process(tx, rx, &mut self.p1_dualshock_state).map(Sio0Device::DualShock)
+            Sio0Device::Multitap(transfer) => {
+                self.process_multitap(transfer, tx, port, rx).map(Sio0Device::Multitap)
             }
-            Sio0Device::MemoryCard(connected_memory_card) => connected_memory_card
-                .process(tx, rx, &mut self.memory_card_1)
-                .map(Sio0Device::MemoryCard),
+            device => process_controller(&mut self.controllers[port as usize], device, tx, rx),
+        }
+    }
+}
+
+fn process_controller(
+    controller: &mut ControllerPort,
+    device: Sio0Device,
+    tx: u8,
+    rx: &mut RxFifo,
+) -> Option<Sio0Device> {
+    match device {
+        Sio0Device::DigitalController(digital) => {
+            digital.process(tx, rx).map(Sio0Device::DigitalController)
+        }
+        Sio0Device::DualShock(dual_shock) => dual_shock
+            .process(tx, rx, &mut controller.dualshock_state, &mut controller.dualshock_motors)
+            .map(Sio0Device::DualShock),
+        Sio0Device::Mouse(mouse) => {
+            mouse.process(tx, rx, &mut controller.mouse_position).map(Sio0Device::Mouse)
+        }
+        Sio0Device::GunCon(gun_con) => gun_con.process(tx, rx).map(Sio0Device::GunCon),
+        Sio0Device::Justifier(justifier) => justifier
+            .process(tx, rx, &mut controller.justifier_irq_enabled)
+            .map(Sio0Device::Justifier),
+        Sio0Device::NeGcon(negcon) => negcon.process(tx, rx).map(Sio0Device::NeGcon),
+        Sio0Device::AnalogJoystick(joystick) => {
+            joystick.process(tx, rx).map(Sio0Device::AnalogJoystick)
+        }
+        Sio0Device::MemoryCard(_) | Sio0Device::Multitap(_) => {
+            log::error!("Device is not a controller: {device:?}");
+            None
         }
     }
 }
 
-fn initial_controller_state(state: ControllerState) -> Option<Sio0Device> {
+fn connected_memory_card() -> Sio0Device {
+    Sio0Device::MemoryCard(ConnectedMemoryCard::initial())
+}
+
+fn initial_controller_state(controller: &ControllerPort) -> Option<Sio0Device> {
+    let state = controller.joypad_state;
     match state.controller_type {
         ControllerType::None => None,
         ControllerType::Digital => {
@@ -202,26 +489,19 @@ fn initial_controller_state(state: ControllerState) -> Option<Sio0Device> {
         ControllerType::DualShock => {
             Some(Sio0Device::DualShock(DualShock::initial(state.digital, state.analog)))
         }
-    }
-}
-
-#[derive(Debug, Clone, Encode, Decode)]
-pub struct Sio1Devices;
-
-impl SerialDevices for Sio1Devices {
-    type Device = ();
-
-    fn connect(&self, _tx: u8, _port: Port) -> Option<Self::Device> {
-        None
-    }
-
-    fn process_tx_write(
-        &mut self,
-        _device: Self::Device,
-        _tx: u8,
-        _rx: &mut RxFifo,
-    ) -> Option<Self::Device> {
-        None
+        ControllerType::Mouse => Some(Sio0Device::Mouse(Mouse::initial(state.mouse))),
+        ControllerType::GunCon => {
+            Some(Sio0Device::GunCon(GunCon::initial(state.light_gun, controller.light_gun_target)))
+        }
+        ControllerType::Justifier => {
+            Some(Sio0Device::Justifier(Justifier::initial(state.light_gun)))
+        }
+        ControllerType::NeGcon => {
+            Some(Sio0Device::NeGcon(NeGcon::initial(state.digital, state.negcon)))
+        }
+        ControllerType::AnalogJoystick => {
+            Some(Sio0Device::AnalogJoystick(AnalogJoystick::initial(state.digital, state.analog)))
+        }
     }
 }
 
@@ -250,43 +530,77 @@ pub struct SerialPort<Devices: SerialDevices> {
 }
 
 pub type SerialPort0 = SerialPort<Sio0Devices>;
-pub type SerialPort1 = SerialPort<Sio1Devices>;
 
 impl SerialPort0 {
-    pub fn new_sio0(memory_card_1: Option<Vec<u8>>) -> Self {
+    pub fn new_sio0(
+        memory_card_1: Option<MemoryCard>,
+        memory_card_2: Option<MemoryCard>,
+        multitap_memory_cards: MultitapMemoryCards,
+    ) -> Self {
         Self::new(
-            Sio0Devices::new(memory_card_1),
+            Sio0Devices::new(memory_card_1, memory_card_2, multitap_memory_cards),
             SchedulerEventType::Sio0Irq,
             SchedulerEventType::Sio0Tx,
         )
     }
 
-    pub fn set_inputs(&mut self, inputs: Ps1Inputs) {
-        self.devices.p1_joypad_state = inputs.p1;
-        self.devices.p2_joypad_state = inputs.p2;
+    /// Update host inputs. `beam_position` converts a light gun aim point to the beam position that
+    /// the gun would see.
+    pub fn set_inputs(
+        &mut self,
+        inputs: Ps1Inputs,
+        beam_position: impl Fn(u16, u16) -> BeamPosition,
+    ) {
+        self.devices.controllers[0].set_inputs(inputs.p1, &beam_position);
+        self.devices.controllers[1].set_inputs(inputs.p2, &beam_position);
+        self.devices.set_multitap_inputs(Port::One, inputs.p1_multitap, &beam_position);
+        self.devices.set_multitap_inputs(Port::Two, inputs.p2_multitap, &beam_position);
+    }
+
+    /// Beam position at which a Justifier should raise IRQ10 during the next frame, if any.
+    /// Only one IRQ can be pending per frame, so if multiple Justifiers are armed, the first one in
+    /// port then multitap slot order takes priority.
+    pub fn justifier_irq_target(&self) -> Option<BeamPosition> {
+        self.devices.all_controllers().find_map(|controller| {
+            (controller.joypad_state.controller_type == ControllerType::Justifier
+                && controller.justifier_irq_enabled)
+                .then_some(controller.light_gun_target)
+                .flatten()
+        })
+    }
 
-        if self.devices.last_p1_controller_type != inputs.p1.controller_type {
-            self.devices.p1_dualshock_state = DualShockControllerState::default();
-        }
-        self.devices.last_p1_controller_type = inputs.p1.controller_type;
+    pub fn set_dualshock_config(&mut self, config: DualShockConfig) {
+        self.devices.set_dualshock_config(config);
+    }
 
-        if !self.devices.last_p1_analog_button
-            && inputs.p1.analog.analog_button
-            && inputs.p1.controller_type == ControllerType::DualShock
-        {
-            self.devices.p1_dualshock_state.toggle_analog_mode();
+    /// Motor state of the controller in each port and multitap slot, as of the last completed poll
+    pub fn rumble(&self) -> Ps1Rumble {
+        let multitap_rumble = |port: Port| {
+            array::from_fn(|i| {
+                self.devices
+                    .slot_controller(port, i as u8 + 1)
+                    .map_or_else(RumbleState::default, ControllerPort::rumble)
+            })
+        };
+
+        Ps1Rumble {
+            p1: self.devices.controllers[0].rumble(),
+            p2: self.devices.controllers[1].rumble(),
+            p1_multitap: multitap_rumble(Port::One),
+            p2_multitap: multitap_rumble(Port::Two),
         }
-        self.devices.last_p1_analog_button = inputs.p1.analog.analog_button;
     }
 
-    pub fn memory_card_1(&mut self) -> &mut MemoryCard {
+    pub fn memory_card_1(&mut self) -> &mut Option<MemoryCard> {
         &mut self.devices.memory_card_1
     }
-}
 
-impl SerialPort1 {
-    pub fn new_sio1() -> Self {
-        Self::new(Sio1Devices, SchedulerEventType::Sio1Irq, SchedulerEventType::Sio1Tx)
+    pub fn memory_card_2(&mut self) -> &mut Option<MemoryCard> {
+        &mut self.devices.memory_card_2
+    }
+
+    pub fn multitap_memory_cards(&mut self) -> &mut MultitapMemoryCards {
+        &mut self.devices.multitap_memory_cards
     }
 }
 
@@ -404,7 +718,7 @@ impl<Devices: SerialDevices> SerialPort<Devices> {
             Some(SerialDevice::Disconnected) => Some(SerialDevice::Disconnected),
             Some(SerialDevice::Connected(device)) => Some(
                 self.devices
-                    .process_tx_write(device, value, &mut self.rx_fifo)
+                    .process_tx_write(device, value, self.selected_port, &mut self.rx_fifo)
                     .map_or(SerialDevice::Disconnected, SerialDevice::Connected),
             ),
             None => {
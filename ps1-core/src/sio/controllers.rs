@@ -1,7 +1,14 @@
 //! SIO0 controller code
 
-use crate::input::{AnalogJoypadState, AnalogMode, DigitalJoypadState};
+use crate::gpu::BeamPosition;
+use crate::input::{
+    AnalogJoypadState, AnalogMode, DigitalJoypadState, DualShockConfig, LightGunState, MouseState,
+    NeGconState, RumbleState,
+};
+use crate::savestate;
 use crate::sio::rxfifo::RxFifo;
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 
 #[allow(clippy::enum_variant_names)]
@@ -60,6 +67,434 @@ impl DigitalController {
     }
 }
 
+/// Mouse position as of the last completed poll, used to compute the motion reported by the next
+/// poll
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub struct MousePosition {
+    pub x: i16,
+    pub y: i16,
+}
+
+impl MousePosition {
+    pub fn of(mouse: MouseState) -> Self {
+        Self { x: mouse.x, y: mouse.y }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+enum MouseSioState {
+    SendingIdLow,
+    SendingIdHigh,
+    SendingButtonsLow,
+    SendingButtonsHigh,
+    SendingDeltaX,
+    SendingDeltaY,
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Mouse {
+    state: MouseSioState,
+    mouse: MouseState,
+}
+
+impl Mouse {
+    pub fn initial(mouse: MouseState) -> Self {
+        Self { state: MouseSioState::SendingIdLow, mouse }
+    }
+
+    pub fn process(self, tx: u8, rx: &mut RxFifo, position: &mut MousePosition) -> Option<Self> {
+        match self.state {
+            MouseSioState::SendingIdLow => {
+                // High nibble $1 = mouse
+                // Low nibble $2 = 2 halfwords for buttons and motion
+                rx.push(0x12);
+
+                // Mouse only responds to the read command
+                (tx == 0x42).then_some(self.with_state(MouseSioState::SendingIdHigh))
+            }
+            MouseSioState::SendingIdHigh => {
+                rx.push(0x5A);
+
+                Some(self.with_state(MouseSioState::SendingButtonsLow))
+            }
+            MouseSioState::SendingButtonsLow => {
+                // Low byte is unused and always reads $FF
+                rx.push(0xFF);
+
+                Some(self.with_state(MouseSioState::SendingButtonsHigh))
+            }
+            MouseSioState::SendingButtonsHigh => {
+                // Bit 2 = right button, bit 3 = left button (0 = pressed)
+                // Bits 0-1 always read 0 and bits 4-7 always read 1
+                rx.push(
+                    0xF0 | (u8::from(!self.mouse.left_button) << 3)
+                        | (u8::from(!self.mouse.right_button) << 2),
+                );
+
+                Some(self.with_state(MouseSioState::SendingDeltaX))
+            }
+            MouseSioState::SendingDeltaX => {
+                let delta = mouse_delta(self.mouse.x, position.x);
+                rx.push(delta as u8);
+                position.x = position.x.wrapping_add(delta.into());
+
+                Some(self.with_state(MouseSioState::SendingDeltaY))
+            }
+            MouseSioState::SendingDeltaY => {
+                let delta = mouse_delta(self.mouse.y, position.y);
+                rx.push(delta as u8);
+                position.y = position.y.wrapping_add(delta.into());
+
+                None
+            }
+        }
+    }
+
+    fn with_state(self, state: MouseSioState) -> Self {
+        Self { state, mouse: self.mouse }
+    }
+}
+
+// Motion is reported as a signed 8-bit value; anything beyond that range carries over to the next
+// poll
+fn mouse_delta(current: i16, last_reported: i16) -> i8 {
+    current.wrapping_sub(last_reported).clamp(i8::MIN.into(), i8::MAX.into()) as i8
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+enum GunConSioState {
+    SendingIdLow,
+    SendingIdHigh,
+    SendingButtonsLow,
+    SendingButtonsHigh,
+    SendingXLow,
+    SendingXHigh,
+    SendingYLow,
+    SendingYHigh,
+}
+
+/// Namco `GunCon`. The gun reads the beam position from the composite video signal when it sees the
+/// screen, and reports it as part of the normal input poll.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct GunCon {
+    state: GunConSioState,
+    light_gun: LightGunState,
+    x: u16,
+    y: u16,
+}
+
+impl GunCon {
+    pub fn initial(light_gun: LightGunState, target: Option<BeamPosition>) -> Self {
+        let (x, y) = match target {
+            Some(target) => {
+                // X is reported in 8 MHz clocks since the start of the line
+                let x = u64::from(target.line_cycle) * 8_000_000 / target.video_mode.gpu_clock();
+                (x as u16, target.line)
+            }
+            // Values reported when the gun doesn't see the screen
+            None => (0x0001, 0x000A),
+        };
+
+        Self { state: GunConSioState::SendingIdLow, light_gun, x, y }
+    }
+
+    pub fn process(self, tx: u8, rx: &mut RxFifo) -> Option<Self> {
+        match self.state {
+            GunConSioState::SendingIdLow => {
+                // High nibble $6 = GunCon
+                // Low nibble $3 = 3 halfwords for buttons and position
+                rx.push(0x63);
+
+                (tx == 0x42).then_some(self.with_state(GunConSioState::SendingIdHigh))
+            }
+            GunConSioState::SendingIdHigh => {
+                rx.push(0x5A);
+
+                Some(self.with_state(GunConSioState::SendingButtonsLow))
+            }
+            GunConSioState::SendingButtonsLow => {
+                rx.push(self.buttons() as u8);
+
+                Some(self.with_state(GunConSioState::SendingButtonsHigh))
+            }
+            GunConSioState::SendingButtonsHigh => {
+                rx.push((self.buttons() >> 8) as u8);
+
+                Some(self.with_state(GunConSioState::SendingXLow))
+            }
+            GunConSioState::SendingXLow => {
+                rx.push(self.x as u8);
+
+                Some(self.with_state(GunConSioState::SendingXHigh))
+            }
+            GunConSioState::SendingXHigh => {
+                rx.push((self.x >> 8) as u8);
+
+                Some(self.with_state(GunConSioState::SendingYLow))
+            }
+            GunConSioState::SendingYLow => {
+                rx.push(self.y as u8);
+
+                Some(self.with_state(GunConSioState::SendingYHigh))
+            }
+            GunConSioState::SendingYHigh => {
+                rx.push((self.y >> 8) as u8);
+
+                None
+            }
+        }
+    }
+
+    fn buttons(&self) -> u16 {
+        // Bit 3 = A, bit 13 = trigger, bit 14 = B (0 = pressed); all other bits always read 1
+        !((u16::from(self.light_gun.button_a) << 3)
+            | (u16::from(self.light_gun.trigger) << 13)
+            | (u16::from(self.light_gun.button_b) << 14))
+    }
+
+    fn with_state(mut self, state: GunConSioState) -> Self {
+        self.state = state;
+        self
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+enum JustifierSioState {
+    SendingIdLow,
+    SendingIdHigh,
+    SendingButtonsLow,
+    SendingButtonsHigh,
+}
+
+/// Konami Justifier. Unlike the `GunCon`, the Justifier does not report a position. It raises IRQ10
+/// when its light sensor sees the beam, and games determine the position by reading the timer 0
+/// (dot clock) and timer 1 (scanline) counters from the interrupt handler.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Justifier {
+    state: JustifierSioState,
+    light_gun: LightGunState,
+}
+
+impl Justifier {
+    pub fn initial(light_gun: LightGunState) -> Self {
+        Self { state: JustifierSioState::SendingIdLow, light_gun }
+    }
+
+    pub fn process(self, tx: u8, rx: &mut RxFifo, irq_enabled: &mut bool) -> Option<Self> {
+        match self.state {
+            JustifierSioState::SendingIdLow => {
+                // High nibble $3 = Justifier
+                // Low nibble $1 = 1 halfword for buttons
+                rx.push(0x31);
+
+                (tx == 0x42).then_some(self.with_state(JustifierSioState::SendingIdHigh))
+            }
+            JustifierSioState::SendingIdHigh => {
+                rx.push(0x5A);
+
+                Some(self.with_state(JustifierSioState::SendingButtonsLow))
+            }
+            JustifierSioState::SendingButtonsLow => {
+                rx.push(self.buttons() as u8);
+
+                // Games enable the light sensor IRQ by setting bit 4 of the byte sent alongside
+                // the first button byte
+                *irq_enabled = tx & 0x10 != 0;
+
+                Some(self.with_state(JustifierSioState::SendingButtonsHigh))
+            }
+            JustifierSioState::SendingButtonsHigh => {
+                rx.push((self.buttons() >> 8) as u8);
+
+                None
+            }
+        }
+    }
+
+    fn buttons(&self) -> u16 {
+        // Bit 3 = Start, bit 14 = Aux, bit 15 = trigger (0 = pressed); all other bits always read 1
+        !((u16::from(self.light_gun.button_a) << 3)
+            | (u16::from(self.light_gun.button_b) << 14)
+            | (u16::from(self.light_gun.trigger) << 15))
+    }
+
+    fn with_state(mut self, state: JustifierSioState) -> Self {
+        self.state = state;
+        self
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+enum NeGconSioState {
+    SendingIdLow,
+    SendingIdHigh,
+    SendingButtonsLow,
+    SendingButtonsHigh,
+    SendingTwist,
+    SendingI,
+    SendingII,
+    SendingL,
+}
+
+/// Namco `NeGcon`. The controller twists in the middle for steering and has pressure-sensitive
+/// I, II, and L buttons.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct NeGcon {
+    state: NeGconSioState,
+    digital: DigitalJoypadState,
+    negcon: NeGconState,
+}
+
+impl NeGcon {
+    pub fn initial(digital: DigitalJoypadState, negcon: NeGconState) -> Self {
+        Self { state: NeGconSioState::SendingIdLow, digital, negcon }
+    }
+
+    pub fn process(self, tx: u8, rx: &mut RxFifo) -> Option<Self> {
+        match self.state {
+            NeGconSioState::SendingIdLow => {
+                // High nibble $2 = NeGcon
+                // Low nibble $3 = 3 halfwords for buttons and analog inputs
+                rx.push(0x23);
+
+                (tx == 0x42).then_some(self.with_state(NeGconSioState::SendingIdHigh))
+            }
+            NeGconSioState::SendingIdHigh => {
+                rx.push(0x5A);
+
+                Some(self.with_state(NeGconSioState::SendingButtonsLow))
+            }
+            NeGconSioState::SendingButtonsLow => {
+                rx.push(self.buttons() as u8);
+
+                Some(self.with_state(NeGconSioState::SendingButtonsHigh))
+            }
+            NeGconSioState::SendingButtonsHigh => {
+                rx.push((self.buttons() >> 8) as u8);
+
+                Some(self.with_state(NeGconSioState::SendingTwist))
+            }
+            NeGconSioState::SendingTwist => {
+                rx.push(self.negcon.twist);
+
+                Some(self.with_state(NeGconSioState::SendingI))
+            }
+            NeGconSioState::SendingI => {
+                rx.push(self.negcon.button_i);
+
+                Some(self.with_state(NeGconSioState::SendingII))
+            }
+            NeGconSioState::SendingII => {
+                rx.push(self.negcon.button_ii);
+
+                Some(self.with_state(NeGconSioState::SendingL))
+            }
+            NeGconSioState::SendingL => {
+                rx.push(self.negcon.button_l);
+
+                None
+            }
+        }
+    }
+
+    fn buttons(&self) -> u16 {
+        // The NeGcon only has Start, the D-pad, R (R1 bit), A (Circle bit), and B (Triangle bit);
+        // all other bits always read 1
+        const MASK: u16 = 0x38F8;
+
+        !(u16::from(self.digital) & MASK)
+    }
+
+    fn with_state(mut self, state: NeGconSioState) -> Self {
+        self.state = state;
+        self
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+enum AnalogJoystickSioState {
+    SendingIdLow,
+    SendingIdHigh,
+    SendingButtonsLow,
+    SendingButtonsHigh,
+    SendingRightX,
+    SendingRightY,
+    SendingLeftX,
+    SendingLeftY,
+}
+
+/// SCPH-1110 analog joystick. The response is the same as a `DualShock` in analog mode, but with a
+/// different ID and no configuration commands.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct AnalogJoystick {
+    state: AnalogJoystickSioState,
+    digital: DigitalJoypadState,
+    analog: AnalogJoypadState,
+}
+
+impl AnalogJoystick {
+    pub fn initial(digital: DigitalJoypadState, analog: AnalogJoypadState) -> Self {
+        Self { state: AnalogJoystickSioState::SendingIdLow, digital, analog }
+    }
+
+    pub fn process(self, tx: u8, rx: &mut RxFifo) -> Option<Self> {
+        match self.state {
+            AnalogJoystickSioState::SendingIdLow => {
+                // High nibble $5 = analog joystick
+                // Low nibble $3 = 3 halfwords for buttons and stick positions
+                rx.push(0x53);
+
+                (tx == 0x42).then_some(self.with_state(AnalogJoystickSioState::SendingIdHigh))
+            }
+            AnalogJoystickSioState::SendingIdHigh => {
+                rx.push(0x5A);
+
+                Some(self.with_state(AnalogJoystickSioState::SendingButtonsLow))
+            }
+            AnalogJoystickSioState::SendingButtonsLow => {
+                rx.push(!u16::from(self.digital) as u8);
+
+                Some(self.with_state(AnalogJoystickSioState::SendingButtonsHigh))
+            }
+            AnalogJoystickSioState::SendingButtonsHigh => {
+                rx.push((!u16::from(self.digital) >> 8) as u8);
+
+                Some(self.with_state(AnalogJoystickSioState::SendingRightX))
+            }
+            AnalogJoystickSioState::SendingRightX => {
+                rx.push(self.analog.right_x);
+
+                Some(self.with_state(AnalogJoystickSioState::SendingRightY))
+            }
+            AnalogJoystickSioState::SendingRightY => {
+                rx.push(self.analog.right_y);
+
+                Some(self.with_state(AnalogJoystickSioState::SendingLeftX))
+            }
+            AnalogJoystickSioState::SendingLeftX => {
+                rx.push(self.analog.left_x);
+
+                Some(self.with_state(AnalogJoystickSioState::SendingLeftY))
+            }
+            AnalogJoystickSioState::SendingLeftY => {
+                rx.push(self.analog.left_y);
+
+                None
+            }
+        }
+    }
+
+    fn with_state(mut self, state: AnalogJoystickSioState) -> Self {
+        self.state = state;
+        self
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
 pub enum DualShockMode {
     #[default]
@@ -67,7 +502,7 @@ pub enum DualShockMode {
     Config,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+#[derive(Debug, Clone, PartialEq, Eq, Encode)]
 pub struct DualShockControllerState {
     pub mode: DualShockMode,
     pub analog_mode: AnalogMode,
@@ -75,27 +510,135 @@ pub struct DualShockControllerState {
     pub analog_mode_locked: bool,
     pub config_mode_entered: bool,
     pub analog_mode_changed: bool,
+    pub config: DualShockConfig,
 }
 
-impl Default for DualShockControllerState {
-    fn default() -> Self {
+impl Decode for DualShockControllerState {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self {
+            mode: Decode::decode(decoder)?,
+            analog_mode: Decode::decode(decoder)?,
+            rumble_config: Decode::decode(decoder)?,
+            analog_mode_locked: Decode::decode(decoder)?,
+            config_mode_entered: Decode::decode(decoder)?,
+            analog_mode_changed: Decode::decode(decoder)?,
+            // Config was added in version 11
+            config: if savestate::decoding_format_version() < 11 {
+                DualShockConfig::default()
+            } else {
+                Decode::decode(decoder)?
+            },
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(DualShockControllerState);
+
+impl DualShockControllerState {
+    pub fn new(config: DualShockConfig) -> Self {
+        let analog_mode = if config.start_in_analog && !config.force_digital {
+            AnalogMode::Analog
+        } else {
+            AnalogMode::Digital
+        };
+
         Self {
             mode: DualShockMode::default(),
-            analog_mode: AnalogMode::default(),
+            analog_mode,
             rumble_config: [0xFF; 6],
             analog_mode_locked: false,
             config_mode_entered: false,
             analog_mode_changed: false,
+            config,
+        }
+    }
+
+    /// Apply a config change to an already connected controller. Starting in analog mode only
+    /// takes effect the next time the controller is connected.
+    pub fn set_config(&mut self, config: DualShockConfig) {
+        self.config = config;
+
+        if config.ignore_mode_lock {
+            self.analog_mode_locked = false;
+        }
+
+        if config.force_digital && self.analog_mode == AnalogMode::Analog {
+            self.set_analog_mode(AnalogMode::Digital);
         }
     }
-}
 
-impl DualShockControllerState {
     pub fn toggle_analog_mode(&mut self) {
-        if !self.analog_mode_locked {
-            self.analog_mode = self.analog_mode.toggle();
-            self.analog_mode_changed = true;
-            self.rumble_config.fill(0xFF);
+        if !self.analog_mode_locked && !self.config.force_digital {
+            self.set_analog_mode(self.analog_mode.toggle());
+        }
+    }
+
+    fn set_analog_mode(&mut self, analog_mode: AnalogMode) {
+        self.analog_mode = analog_mode;
+        self.analog_mode_changed = true;
+        self.rumble_config.fill(0xFF);
+    }
+
+    // Mode byte of command $44; software can switch modes even while the Analog button is locked
+    fn set_led_state(&mut self, analog_mode: AnalogMode) {
+        if analog_mode == AnalogMode::Analog && self.config.force_digital {
+            log::debug!("Ignoring request to enable analog mode because digital mode is forced");
+            return;
+        }
+
+        self.analog_mode = analog_mode;
+        log::debug!("Analog mode forcibly set to {analog_mode:?}");
+    }
+
+    // Lock byte of command $44
+    fn set_led_lock_state(&mut self, locked: bool) {
+        if self.config.ignore_mode_lock {
+            log::debug!("Ignoring request to set analog mode lock to {locked}");
+            return;
+        }
+
+        self.analog_mode_locked = locked;
+        log::debug!("Analog mode locked: {locked}");
+    }
+}
+
+/// Rumble motor state for the controller in one port.
+///
+/// Motor values are sent in the 6 bytes following the ID during a read command (0x42), and which
+/// byte drives which motor is set by the rumble configuration. Values are buffered until the
+/// read command completes so that a frame ending partway through a poll doesn't briefly switch the
+/// motors off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub struct DualShockMotors {
+    receiving: bool,
+    pending: RumbleState,
+    pub current: RumbleState,
+}
+
+impl DualShockMotors {
+    fn begin_command(&mut self, command: u8) {
+        self.receiving = command == 0x42;
+        self.pending = RumbleState::default();
+    }
+
+    fn receive(&mut self, idx: usize, tx: u8, rumble_config: [u8; 6]) {
+        if !self.receiving {
+            return;
+        }
+
+        match rumble_config[idx] {
+            // Only bit 0 matters for the small motor
+            0x00 => self.pending.small_motor = tx & 0x01 != 0,
+            // Large motor speed is the full byte
+            0x01 => self.pending.large_motor = tx,
+            _ => {}
+        }
+    }
+
+    fn end_command(&mut self) {
+        if self.receiving {
+            self.current = self.pending;
+            self.receiving = false;
         }
     }
 }
@@ -160,6 +703,7 @@ impl DualShock {
         tx: u8,
         rx: &mut RxFifo,
         state: &mut DualShockControllerState,
+        motors: &mut DualShockMotors,
     ) -> Option<Self> {
         use DualShockConfigCommand as ConfigCommand;
         use DualShockSioState as SioState;
@@ -175,6 +719,8 @@ impl DualShock {
                 };
                 rx.push(id_low);
 
+                motors.begin_command(tx);
+
                 match (state.mode, tx) {
                     (_, 0x42) => Some(
                         self.with_state(SioState::NormalSendingIdHigh { entering_config: false }),
@@ -249,6 +795,7 @@ impl DualShock {
             }
             SioState::SendingDigitalInputsLow { entering_config } => {
                 rx.push(!u16::from(self.digital) as u8);
+                motors.receive(0, tx, state.rumble_config);
 
                 let send_analog =
                     state.mode == DualShockMode::Config || state.analog_mode == AnalogMode::Analog;
@@ -263,23 +810,33 @@ impl DualShock {
             }
             SioState::SendingDigitalInputsHigh { send_analog } => {
                 rx.push((!u16::from(self.digital) >> 8) as u8);
+                motors.receive(1, tx, state.rumble_config);
+
+                if !send_analog {
+                    motors.end_command();
+                }
 
                 send_analog.then_some(self.with_state(SioState::SendingAnalogInputsRightX))
             }
             SioState::SendingAnalogInputsRightX => {
                 rx.push(self.analog.right_x);
+                motors.receive(2, tx, state.rumble_config);
                 Some(self.with_state(SioState::SendingAnalogInputsRightY))
             }
             SioState::SendingAnalogInputsRightY => {
                 rx.push(self.analog.right_y);
+                motors.receive(3, tx, state.rumble_config);
                 Some(self.with_state(SioState::SendingAnalogInputsLeftX))
             }
             SioState::SendingAnalogInputsLeftX => {
                 rx.push(self.analog.left_x);
+                motors.receive(4, tx, state.rumble_config);
                 Some(self.with_state(SioState::SendingAnalogInputsLeftY))
             }
             SioState::SendingAnalogInputsLeftY => {
                 rx.push(self.analog.left_y);
+                motors.receive(5, tx, state.rumble_config);
+                motors.end_command();
                 None
             }
             SioState::ConfigReceivingModeChange => {
@@ -295,14 +852,8 @@ impl DualShock {
                 rx.push(0x00);
 
                 match tx {
-                    0x00 => {
-                        state.analog_mode = AnalogMode::Digital;
-                        log::debug!("Analog mode forcibly disabled");
-                    }
-                    0x01 => {
-                        state.analog_mode = AnalogMode::Analog;
-                        log::debug!("Analog mode forcibly enabled");
-                    }
+                    0x00 => state.set_led_state(AnalogMode::Digital),
+                    0x01 => state.set_led_state(AnalogMode::Analog),
                     _ => {}
                 }
 
@@ -311,8 +862,7 @@ impl DualShock {
             SioState::ReceivingLedLockState => {
                 rx.push(0x00);
 
-                state.analog_mode_locked = tx & 0x03 == 0x03;
-                log::debug!("Analog mode locked: {}", state.analog_mode_locked);
+                state.set_led_lock_state(tx & 0x03 == 0x03);
 
                 Some(self.with_state(SioState::SendingZeroes { remaining: 4 }))
             }
@@ -428,3 +978,54 @@ impl DualShock {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poll_mouse(mouse: MouseState, position: &mut MousePosition) -> Vec<u8> {
+        let mut rx = RxFifo::new();
+        let mut device = Some(Mouse::initial(mouse));
+        let mut responses = Vec::new();
+        for tx in [0x42, 0, 0, 0, 0, 0] {
+            device = device.unwrap().process(tx, &mut rx, position);
+            responses.push(rx.pop());
+        }
+        assert!(device.is_none());
+
+        responses
+    }
+
+    #[test]
+    fn mouse_reports_motion_since_last_poll() {
+        let mut position = MousePosition::default();
+        let mut mouse = MouseState { x: 10, y: -5, left_button: true, ..MouseState::default() };
+        assert_eq!(poll_mouse(mouse, &mut position), [0x12, 0x5A, 0xFF, 0xF4, 10, (-5_i8) as u8]);
+
+        mouse.add_motion(200, 0);
+        assert_eq!(poll_mouse(mouse, &mut position), [0x12, 0x5A, 0xFF, 0xF4, 127, 0]);
+        assert_eq!(poll_mouse(mouse, &mut position)[4..], [73, 0]);
+        assert_eq!(poll_mouse(mouse, &mut position)[4..], [0, 0]);
+    }
+
+    #[test]
+    fn negcon_reports_only_its_own_buttons() {
+        let mut digital = DigitalJoypadState::default();
+        digital.set_start(true);
+        digital.set_circle(true);
+        digital.set_l1(true);
+        digital.set_cross(true);
+        let negcon = NeGconState { twist: 0x20, button_i: 0xFF, button_ii: 0x40, button_l: 0x00 };
+
+        let mut rx = RxFifo::new();
+        let mut device = Some(NeGcon::initial(digital, negcon));
+        let mut responses = Vec::new();
+        for _ in 0..8 {
+            device = device.unwrap().process(0x42, &mut rx);
+            responses.push(rx.pop());
+        }
+        assert!(device.is_none());
+
+        assert_eq!(responses, [0x23, 0x5A, 0xF7, 0xDF, 0x20, 0xFF, 0x40, 0x00]);
+    }
+}
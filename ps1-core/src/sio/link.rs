@@ -0,0 +1,430 @@
+//! Link cable transport for SIO1
+//!
+//! Two emulator instances are connected over a TCP or Unix domain socket. One side listens and the
+//! other connects; once connected, both sides behave identically. The listening side goes back to
+//! waiting for a connection if the other side disconnects.
+//!
+//! Every message on the socket is 2 bytes, a message type followed by a value:
+//!
+//! | Type | Value                                            |
+//! |------|--------------------------------------------------|
+//! | 0    | Serial data byte                                 |
+//! | 1    | Output lines: bit 0 = DTR, bit 1 = RTS (1 = on)  |
+//!
+//! The cable crosses the sender's DTR and RTS outputs over to the receiver's DSR and CTS inputs.
+//! Output lines are sent again whenever a connection is established.
+
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::{fmt, io};
+
+const DATA_MESSAGE: u8 = 0;
+const LINES_MESSAGE: u8 = 1;
+
+// If this much unsent data builds up, the other end has stopped reading
+const MAX_PENDING_OUTPUT: usize = 1024 * 1024;
+
+/// Where the two ends of a link cable meet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkAddress {
+    /// TCP address in `host:port` form
+    Tcp(String),
+    /// Unix domain socket path
+    Unix(PathBuf),
+}
+
+impl LinkAddress {
+    /// Parse an address. `unix:<path>` is a Unix domain socket, and anything else is a TCP address.
+    #[must_use]
+    pub fn parse(address: &str) -> Self {
+        match address.strip_prefix("unix:") {
+            Some(path) => Self::Unix(path.into()),
+            None => Self::Tcp(address.into()),
+        }
+    }
+}
+
+impl fmt::Display for LinkAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(address) => write!(f, "{address}"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Input line states received from the other end of the cable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct RemoteLines {
+    pub dsr: bool,
+    pub cts: bool,
+}
+
+trait LinkStream: Read + Write + Send + Debug {}
+
+impl<T: Read + Write + Send + Debug> LinkStream for T {}
+
+#[derive(Debug)]
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    fn accept(&self) -> io::Result<Option<Box<dyn LinkStream>>> {
+        let result = match self {
+            Self::Tcp(listener) => listener.accept().and_then(|(stream, _)| tcp_stream(stream)),
+            #[cfg(unix)]
+            Self::Unix(listener) => listener.accept().and_then(|(stream, _)| unix_stream(stream)),
+        };
+
+        match result {
+            Ok(stream) => Ok(Some(stream)),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn tcp_stream(stream: TcpStream) -> io::Result<Box<dyn LinkStream>> {
+    stream.set_nonblocking(true)?;
+    stream.set_nodelay(true)?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(unix)]
+fn unix_stream(stream: UnixStream) -> io::Result<Box<dyn LinkStream>> {
+    stream.set_nonblocking(true)?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+fn unsupported_address(address: &LinkAddress) -> io::Error {
+    io::Error::new(
+        ErrorKind::Unsupported,
+        format!("Unix domain sockets are not supported on this platform: {address}"),
+    )
+}
+
+/// One end of a link cable.
+///
+/// All socket operations are non-blocking except for the initial connect, and I/O errors after the
+/// connection is established are logged and treated as the cable being unplugged. Messages that
+/// the socket is not ready to accept are buffered and sent on a later poll.
+#[derive(Debug)]
+pub struct LinkCable {
+    address: LinkAddress,
+    listener: Option<Listener>,
+    stream: Option<Box<dyn LinkStream>>,
+    received: VecDeque<u8>,
+    pending_output: VecDeque<u8>,
+    remote_lines: RemoteLines,
+    output_lines: u8,
+}
+
+impl LinkCable {
+    /// Listen for the other emulator instance to connect.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if unable to bind to the given address.
+    pub fn listen(address: LinkAddress) -> io::Result<Self> {
+        let listener = match &address {
+            LinkAddress::Tcp(tcp_address) => {
+                let listener = TcpListener::bind(tcp_address)?;
+                listener.set_nonblocking(true)?;
+                Listener::Tcp(listener)
+            }
+            #[cfg(unix)]
+            LinkAddress::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = UnixListener::bind(path)?;
+                listener.set_nonblocking(true)?;
+                Listener::Unix(listener)
+            }
+            #[cfg(not(unix))]
+            LinkAddress::Unix(_) => return Err(unsupported_address(&address)),
+        };
+
+        log::info!("Listening for link cable connection on {address}");
+
+        Ok(Self::new(address, Some(listener), None))
+    }
+
+    /// Connect to another emulator instance that is listening.
+    ///
+    /// # Errors
+    ///
+    /// Will return an error if unable to connect to the given address.
+    pub fn connect(address: LinkAddress) -> io::Result<Self> {
+        let stream = match &address {
+            LinkAddress::Tcp(tcp_address) => tcp_stream(TcpStream::connect(tcp_address)?)?,
+            #[cfg(unix)]
+            LinkAddress::Unix(path) => unix_stream(UnixStream::connect(path)?)?,
+            #[cfg(not(unix))]
+            LinkAddress::Unix(_) => return Err(unsupported_address(&address)),
+        };
+
+        log::info!("Connected link cable to {address}");
+
+        Ok(Self::new(address, None, Some(stream)))
+    }
+
+    fn new(
+        address: LinkAddress,
+        listener: Option<Listener>,
+        stream: Option<Box<dyn LinkStream>>,
+    ) -> Self {
+        Self {
+            address,
+            listener,
+            stream,
+            received: VecDeque::new(),
+            pending_output: VecDeque::new(),
+            remote_lines: RemoteLines::default(),
+            output_lines: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn address(&self) -> &LinkAddress {
+        &self.address
+    }
+
+    /// Whether the other end of the cable is currently connected
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub(crate) fn remote_lines(&self) -> RemoteLines {
+        self.remote_lines
+    }
+
+    /// Whether there are messages that have not been written to the socket yet
+    pub(crate) fn has_pending_output(&self) -> bool {
+        !self.pending_output.is_empty()
+    }
+
+    pub(crate) fn send_byte(&mut self, value: u8) {
+        self.send([DATA_MESSAGE, value]);
+    }
+
+    pub(crate) fn set_output_lines(&mut self, dtr: bool, rts: bool) {
+        let lines = u8::from(dtr) | (u8::from(rts) << 1);
+        if lines != self.output_lines {
+            self.output_lines = lines;
+            self.send([LINES_MESSAGE, lines]);
+        }
+    }
+
+    /// Accept a pending connection, send any buffered messages, and read everything that the other
+    /// end has sent so far. Line changes are applied up to the next data byte, which is returned if
+    /// there is one.
+    pub(crate) fn receive(&mut self) -> Option<u8> {
+        self.accept();
+        self.flush();
+        self.read_available();
+
+        while self.received.len() >= 2 {
+            let message_type = self.received.pop_front().unwrap();
+            let value = self.received.pop_front().unwrap();
+            match message_type {
+                DATA_MESSAGE => return Some(value),
+                LINES_MESSAGE => {
+                    self.remote_lines =
+                        RemoteLines { dsr: value & 0x01 != 0, cts: value & 0x02 != 0 };
+                }
+                _ => {
+                    log::error!("Invalid link cable message type {message_type:02X}");
+                    self.disconnect();
+                    return None;
+                }
+            }
+        }
+
+        None
+    }
+
+    fn accept(&mut self) {
+        if self.stream.is_some() {
+            return;
+        }
+        let Some(listener) = &self.listener else { return };
+
+        match listener.accept() {
+            Ok(Some(stream)) => {
+                log::info!("Link cable connection accepted on {}", self.address);
+                self.stream = Some(stream);
+                self.send([LINES_MESSAGE, self.output_lines]);
+            }
+            Ok(None) => {}
+            Err(err) => log::error!("Error accepting link cable connection: {err}"),
+        }
+    }
+
+    fn read_available(&mut self) {
+        let Some(stream) = &mut self.stream else { return };
+
+        let mut buffer = [0; 256];
+        loop {
+            match stream.read(&mut buffer) {
+                Ok(0) => {
+                    log::info!("Link cable disconnected");
+                    self.disconnect();
+                    return;
+                }
+                Ok(len) => self.received.extend(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => {
+                    log::error!("Error reading from link cable: {err}");
+                    self.disconnect();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn send(&mut self, message: [u8; 2]) {
+        if self.stream.is_none() {
+            return;
+        }
+
+        if self.pending_output.len() + message.len() > MAX_PENDING_OUTPUT {
+            log::error!("Link cable peer is not reading; disconnecting");
+            self.disconnect();
+            return;
+        }
+
+        // Queue the whole message so that a partial write never splits it
+        self.pending_output.extend(message);
+        self.flush();
+    }
+
+    fn flush(&mut self) {
+        let Some(stream) = &mut self.stream else { return };
+
+        while !self.pending_output.is_empty() {
+            let (pending, _) = self.pending_output.as_slices();
+            match stream.write(pending) {
+                Ok(0) => {
+                    log::info!("Link cable disconnected");
+                    self.disconnect();
+                    return;
+                }
+                Ok(len) => {
+                    self.pending_output.drain(..len);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => {
+                    log::error!("Error writing to link cable: {err}");
+                    self.disconnect();
+                    return;
+                }
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.received.clear();
+        self.pending_output.clear();
+        self.remote_lines = RemoteLines::default();
+    }
+}
+
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket file left behind by a previous session would make bind() fail; only remove the
+    // path if it really is a socket
+    match std::fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    fn receive_blocking(cable: &mut LinkCable) -> Option<u8> {
+        for _ in 0..100 {
+            if let Some(value) = cable.receive() {
+                return Some(value);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        None
+    }
+
+    #[test]
+    fn bytes_and_lines_cross_over() {
+        let path = std::env::temp_dir().join(format!("ps1-link-test-{}.sock", std::process::id()));
+        let address = LinkAddress::parse(&format!("unix:{}", path.display()));
+
+        let mut listener = LinkCable::listen(address.clone()).unwrap();
+        let mut connector = LinkCable::connect(address).unwrap();
+
+        connector.set_output_lines(true, false);
+        connector.send_byte(0x42);
+        assert_eq!(receive_blocking(&mut listener), Some(0x42));
+        assert_eq!(listener.remote_lines(), RemoteLines { dsr: true, cts: false });
+
+        listener.set_output_lines(false, true);
+        listener.send_byte(0x99);
+        assert_eq!(receive_blocking(&mut connector), Some(0x99));
+        assert_eq!(connector.remote_lines(), RemoteLines { dsr: false, cts: true });
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn buffers_output_while_socket_is_full() {
+        const LEN: usize = 200_000;
+
+        let path =
+            std::env::temp_dir().join(format!("ps1-link-buffer-test-{}.sock", std::process::id()));
+        let address = LinkAddress::parse(&format!("unix:{}", path.display()));
+
+        let mut listener = LinkCable::listen(address.clone()).unwrap();
+        let mut connector = LinkCable::connect(address).unwrap();
+        listener.receive();
+        assert!(listener.is_connected());
+
+        // More than the socket buffer can hold without the other end reading
+        for i in 0..LEN {
+            connector.send_byte(i as u8);
+        }
+        assert!(connector.is_connected());
+        assert!(connector.has_pending_output());
+
+        let mut received = 0;
+        for _ in 0..100 * LEN {
+            if received == LEN {
+                break;
+            }
+
+            connector.receive();
+            if let Some(value) = listener.receive() {
+                assert_eq!(value, received as u8);
+                received += 1;
+            }
+        }
+        assert_eq!(received, LEN);
+        assert!(!connector.has_pending_output());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
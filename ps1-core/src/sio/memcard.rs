@@ -1,3 +1,6 @@
+pub mod filesystem;
+pub mod formats;
+
 use crate::sio::rxfifo::RxFifo;
 use bincode::{Decode, Encode};
 
@@ -13,16 +16,23 @@ pub struct MemoryCard {
 }
 
 impl MemoryCard {
+    /// Create a card from an image in any format supported by [`formats::read_card`], or a newly
+    /// formatted card if no image is given or the image is invalid.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
     pub fn new(data: Option<Vec<u8>>) -> Self {
         let data = match data {
             Some(data) if data.len() == MEMORY_CARD_LEN => data,
-            Some(data) => {
-                log::error!(
-                    "Expected memory card data of size {MEMORY_CARD_LEN}, was {}; formatting card",
-                    data.len()
-                );
-                new_formatted_memory_card()
-            }
+            Some(data) => match formats::read_card(&data) {
+                Ok((format, card)) => {
+                    log::info!("Loaded memory card image in {format:?} format");
+                    card.to_vec()
+                }
+                Err(err) => {
+                    log::error!("Invalid memory card image: {err}; formatting card");
+                    new_formatted_memory_card()
+                }
+            },
             None => new_formatted_memory_card(),
         };
 
@@ -43,6 +53,7 @@ impl MemoryCard {
         self.written_since_load = false;
     }
 
+    #[must_use]
     pub fn data(&self) -> &MemoryCardData {
         &self.data
     }
@@ -86,7 +97,7 @@ enum MemoryCardState {
 }
 
 #[derive(Debug, Clone, Encode, Decode)]
-pub struct ConnectedMemoryCard {
+pub(crate) struct ConnectedMemoryCard {
     state: MemoryCardState,
     sector: u16,
     checksum: u8,
@@ -94,11 +105,16 @@ pub struct ConnectedMemoryCard {
 }
 
 impl ConnectedMemoryCard {
-    pub fn initial() -> Self {
+    pub(crate) fn initial() -> Self {
         Self { state: MemoryCardState::AwaitingCommand, sector: 0, checksum: 0, last_tx: 0 }
     }
 
-    pub fn process(mut self, tx: u8, rx: &mut RxFifo, card: &mut MemoryCard) -> Option<Self> {
+    pub(crate) fn process(
+        mut self,
+        tx: u8,
+        rx: &mut RxFifo,
+        card: &mut MemoryCard,
+    ) -> Option<Self> {
         self.last_tx = tx;
 
         self.state = match self.state {
@@ -0,0 +1,461 @@
+//! Memory card file system: directory parsing and save file management
+//!
+//! A card is 16 blocks of 8KB, each block 64 frames of 128 bytes. Block 0 is the header block;
+//! frame 0 contains the "MC" magic, and frames 1-15 are the directory entries for data blocks
+//! 1-15. A save occupies one or more data blocks, linked together through the next block pointers
+//! in their directory entries.
+//!
+//! The first frame of a save's first block is the title frame, containing the icon palette and the
+//! Shift-JIS save title. The next 1-3 frames contain the icon animation frames.
+
+use crate::sio::memcard::{MemoryCardData, new_formatted_memory_card};
+use encoding_rs::SHIFT_JIS;
+use thiserror::Error;
+
+pub const FRAME_LEN: usize = 128;
+pub const BLOCK_LEN: usize = 64 * FRAME_LEN;
+pub const DATA_BLOCKS: usize = 15;
+
+pub const ICON_WIDTH: usize = 16;
+pub const ICON_HEIGHT: usize = 16;
+
+/// Length of the file name in a directory entry, not including the null terminator
+pub const FILE_NAME_LEN: usize = 20;
+
+const ALLOCATION_MASK: u8 = 0xF0;
+pub(super) const IN_USE: u8 = 0x50;
+const FREE: u8 = 0xA0;
+
+pub(super) const FIRST_BLOCK: u8 = 0x01;
+const MIDDLE_BLOCK: u8 = 0x02;
+const LAST_BLOCK: u8 = 0x03;
+
+pub(super) const NO_NEXT_BLOCK: u16 = 0xFFFF;
+
+#[derive(Debug, Error)]
+pub enum MemoryCardFsError {
+    #[error("Memory card is not formatted")]
+    NotFormatted,
+    #[error("No save starts at block {0}")]
+    NoSaveAtBlock(usize),
+    #[error("Not enough free blocks on memory card; {required} required, {available} available")]
+    NotEnoughSpace { required: usize, available: usize },
+    #[error("Memory card already contains a save named '{0}'")]
+    DuplicateName(String),
+    #[error("Invalid save file name '{0}'; must be 1-20 ASCII characters")]
+    InvalidName(String),
+    #[error("Invalid save size {0}; must be a non-zero multiple of 8192 bytes, at most 15 blocks")]
+    InvalidSize(usize),
+}
+
+pub type MemoryCardFsResult<T> = Result<T, MemoryCardFsError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Japan,
+    America,
+    Europe,
+    Unknown([u8; 2]),
+}
+
+impl Region {
+    fn from_prefix(prefix: [u8; 2]) -> Self {
+        match &prefix {
+            b"BI" => Self::Japan,
+            b"BA" => Self::America,
+            b"BE" => Self::Europe,
+            _ => Self::Unknown(prefix),
+        }
+    }
+}
+
+/// Save icon, with each animation frame converted to 16x16 RGBA8. Always has 1-3 frames.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveIcon {
+    pub frames: Vec<Box<[u8; 4 * ICON_WIDTH * ICON_HEIGHT]>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SaveEntry {
+    /// Data block (1-15) containing the start of the save
+    pub first_block: usize,
+    /// Every data block in the save, in order
+    pub blocks: Vec<usize>,
+    /// Directory file name, e.g. `BASLUS-00594FF7_00`
+    pub file_name: String,
+    pub region: Region,
+    /// Product code from the file name, e.g. `SLUS-00594`
+    pub product_code: String,
+    pub title: String,
+    pub icon: SaveIcon,
+}
+
+/// A single save copied out of a card, without any container header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawSave {
+    pub file_name: String,
+    /// Contents of every block in the save, in order
+    pub data: Vec<u8>,
+}
+
+impl RawSave {
+    #[must_use]
+    pub fn blocks(&self) -> usize {
+        self.data.len() / BLOCK_LEN
+    }
+}
+
+#[must_use]
+pub fn is_formatted(card: &MemoryCardData) -> bool {
+    &card[0..2] == b"MC"
+}
+
+/// Erase every save on the card.
+pub fn format(card: &mut MemoryCardData) {
+    card.copy_from_slice(&new_formatted_memory_card());
+}
+
+/// Returns the number of data blocks that are not in use by any save.
+#[must_use]
+pub fn free_blocks(card: &MemoryCardData) -> usize {
+    (1..=DATA_BLOCKS).filter(|&block| is_free(card, block)).count()
+}
+
+/// List every save on the card, ordered by first block.
+///
+/// # Errors
+///
+/// Returns an error if the card is not formatted.
+pub fn list_saves(card: &MemoryCardData) -> MemoryCardFsResult<Vec<SaveEntry>> {
+    if !is_formatted(card) {
+        return Err(MemoryCardFsError::NotFormatted);
+    }
+
+    Ok((1..=DATA_BLOCKS)
+        .filter(|&block| directory_entry(card, block)[0] == IN_USE | FIRST_BLOCK)
+        .map(|block| read_entry(card, block))
+        .collect())
+}
+
+/// Copy a save out of the card.
+///
+/// # Errors
+///
+/// Returns an error if no save starts at the given block.
+pub fn export_save(card: &MemoryCardData, first_block: usize) -> MemoryCardFsResult<RawSave> {
+    let blocks = save_blocks(card, first_block)?;
+
+    let mut data = Vec::with_capacity(blocks.len() * BLOCK_LEN);
+    for &block in &blocks {
+        data.extend_from_slice(block_data(card, block));
+    }
+
+    Ok(RawSave { file_name: file_name(directory_entry(card, first_block)), data })
+}
+
+/// Write a save into the first free blocks on the card. Returns the save's first block.
+///
+/// # Errors
+///
+/// Returns an error if the card is not formatted, if the save is invalid, if the card does not
+/// have enough free blocks, or if the card already contains a save with the same file name.
+pub fn import_save(card: &mut MemoryCardData, save: &RawSave) -> MemoryCardFsResult<usize> {
+    if !is_formatted(card) {
+        return Err(MemoryCardFsError::NotFormatted);
+    }
+
+    validate_save(save)?;
+
+    if list_saves(card)?.iter().any(|entry| entry.file_name == save.file_name) {
+        return Err(MemoryCardFsError::DuplicateName(save.file_name.clone()));
+    }
+
+    let required = save.blocks();
+    let free: Vec<_> = (1..=DATA_BLOCKS).filter(|&block| is_free(card, block)).collect();
+    if free.len() < required {
+        return Err(MemoryCardFsError::NotEnoughSpace { required, available: free.len() });
+    }
+
+    let blocks = &free[..required];
+    for (i, (&block, block_contents)) in blocks.iter().zip(save.data.chunks(BLOCK_LEN)).enumerate()
+    {
+        let state = if i == 0 {
+            FIRST_BLOCK
+        } else if i == required - 1 {
+            LAST_BLOCK
+        } else {
+            MIDDLE_BLOCK
+        };
+        let next = blocks.get(i + 1).map_or(NO_NEXT_BLOCK, |&next| (next - 1) as u16);
+
+        let frame = directory_frame(state, next, (i == 0).then_some(save));
+        directory_entry_mut(card, block).copy_from_slice(&frame);
+
+        block_data_mut(card, block).copy_from_slice(block_contents);
+    }
+
+    Ok(blocks[0])
+}
+
+/// Delete a save the same way the BIOS does, by marking its blocks as deleted. The save data is
+/// left in place until the blocks are reused.
+///
+/// # Errors
+///
+/// Returns an error if no save starts at the given block.
+pub fn delete_save(card: &mut MemoryCardData, first_block: usize) -> MemoryCardFsResult<()> {
+    for block in save_blocks(card, first_block)? {
+        let entry = directory_entry_mut(card, block);
+        entry[0] = FREE | (entry[0] & !ALLOCATION_MASK);
+        update_checksum(entry);
+    }
+
+    Ok(())
+}
+
+/// Copy a save from one card to another. Returns the save's first block on the destination card.
+///
+/// # Errors
+///
+/// Returns an error if no save starts at the given block on the source card, or if the save
+/// cannot be imported into the destination card.
+pub fn copy_save(
+    source: &MemoryCardData,
+    first_block: usize,
+    dest: &mut MemoryCardData,
+) -> MemoryCardFsResult<usize> {
+    let save = export_save(source, first_block)?;
+    import_save(dest, &save)
+}
+
+pub(super) fn validate_save(save: &RawSave) -> MemoryCardFsResult<()> {
+    if save.file_name.is_empty()
+        || save.file_name.len() > FILE_NAME_LEN
+        || !save.file_name.bytes().all(|b| b.is_ascii_graphic())
+    {
+        return Err(MemoryCardFsError::InvalidName(save.file_name.clone()));
+    }
+
+    let blocks = save.blocks();
+    if blocks == 0 || blocks > DATA_BLOCKS || !save.data.len().is_multiple_of(BLOCK_LEN) {
+        return Err(MemoryCardFsError::InvalidSize(save.data.len()));
+    }
+
+    Ok(())
+}
+
+fn read_entry(card: &MemoryCardData, first_block: usize) -> SaveEntry {
+    let entry = directory_entry(card, first_block);
+    let file_name = file_name(entry);
+    let product_code = file_name.get(2..12).unwrap_or("").to_string();
+
+    let title_frame = &block_data(card, first_block)[..FRAME_LEN];
+
+    SaveEntry {
+        first_block,
+        blocks: save_blocks(card, first_block).unwrap_or_else(|_| vec![first_block]),
+        region: Region::from_prefix([entry[0x0A], entry[0x0B]]),
+        file_name,
+        product_code,
+        title: decode_title(&title_frame[0x04..0x44]),
+        icon: decode_icon(card, first_block),
+    }
+}
+
+// Follow the next block pointers from the first block. Stops at the first block that is not
+// marked as part of a save, so a corrupt directory can't cause an infinite loop
+fn save_blocks(card: &MemoryCardData, first_block: usize) -> MemoryCardFsResult<Vec<usize>> {
+    if !(1..=DATA_BLOCKS).contains(&first_block)
+        || directory_entry(card, first_block)[0] != IN_USE | FIRST_BLOCK
+    {
+        return Err(MemoryCardFsError::NoSaveAtBlock(first_block));
+    }
+
+    let mut blocks = vec![first_block];
+    let mut block = first_block;
+    loop {
+        let entry = directory_entry(card, block);
+        let next = u16::from_le_bytes([entry[8], entry[9]]);
+        if next == NO_NEXT_BLOCK || entry[0] == IN_USE | LAST_BLOCK {
+            break;
+        }
+
+        let next_block = usize::from(next) + 1;
+        if !(1..=DATA_BLOCKS).contains(&next_block)
+            || blocks.contains(&next_block)
+            || ![IN_USE | MIDDLE_BLOCK, IN_USE | LAST_BLOCK]
+                .contains(&directory_entry(card, next_block)[0])
+        {
+            log::warn!("Memory card save at block {first_block} has an invalid block chain");
+            break;
+        }
+
+        blocks.push(next_block);
+        block = next_block;
+    }
+
+    Ok(blocks)
+}
+
+fn is_free(card: &MemoryCardData, block: usize) -> bool {
+    directory_entry(card, block)[0] & ALLOCATION_MASK == FREE
+}
+
+fn directory_entry(card: &MemoryCardData, block: usize) -> &[u8] {
+    &card[block * FRAME_LEN..(block + 1) * FRAME_LEN]
+}
+
+fn directory_entry_mut(card: &mut MemoryCardData, block: usize) -> &mut [u8] {
+    &mut card[block * FRAME_LEN..(block + 1) * FRAME_LEN]
+}
+
+fn block_data(card: &MemoryCardData, block: usize) -> &[u8] {
+    &card[block * BLOCK_LEN..(block + 1) * BLOCK_LEN]
+}
+
+fn block_data_mut(card: &mut MemoryCardData, block: usize) -> &mut [u8] {
+    &mut card[block * BLOCK_LEN..(block + 1) * BLOCK_LEN]
+}
+
+// Only the first block's directory entry contains the save's size and file name
+pub(super) fn directory_frame(state: u8, next: u16, save: Option<&RawSave>) -> [u8; FRAME_LEN] {
+    let mut frame = [0; FRAME_LEN];
+    frame[0] = IN_USE | state;
+    frame[8..10].copy_from_slice(&next.to_le_bytes());
+    if let Some(save) = save {
+        frame[4..8].copy_from_slice(&(save.data.len() as u32).to_le_bytes());
+        frame[0x0A..0x0A + save.file_name.len()].copy_from_slice(save.file_name.as_bytes());
+    }
+    update_checksum(&mut frame);
+    frame
+}
+
+fn update_checksum(frame: &mut [u8]) {
+    frame[FRAME_LEN - 1] = frame[..FRAME_LEN - 1].iter().fold(0, |checksum, &b| checksum ^ b);
+}
+
+pub(super) fn file_name(entry: &[u8]) -> String {
+    let name = &entry[0x0A..0x0A + FILE_NAME_LEN];
+    let len = name.iter().position(|&b| b == 0).unwrap_or(FILE_NAME_LEN);
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+// Titles are Shift-JIS, usually made up entirely of full-width characters. Convert full-width
+// ASCII to regular ASCII so that titles display normally
+fn decode_title(title: &[u8]) -> String {
+    let len = title.iter().position(|&b| b == 0).unwrap_or(title.len());
+    let (decoded, _, _) = SHIFT_JIS.decode(&title[..len]);
+
+    decoded
+        .chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFF01 + 0x21).unwrap_or(c),
+            _ => c,
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+fn decode_icon(card: &MemoryCardData, first_block: usize) -> SaveIcon {
+    let block = block_data(card, first_block);
+    let title_frame = &block[..FRAME_LEN];
+
+    let frame_count = match title_frame[0x02] {
+        0x12 => 2,
+        0x13 => 3,
+        _ => 1,
+    };
+
+    let palette: [[u8; 4]; 16] = std::array::from_fn(|i| {
+        let color = u16::from_le_bytes([title_frame[0x60 + 2 * i], title_frame[0x60 + 2 * i + 1]]);
+        rgba_from_bgr555(color)
+    });
+
+    let frames = (1..=frame_count)
+        .map(|frame| {
+            let bitmap = &block[frame * FRAME_LEN..(frame + 1) * FRAME_LEN];
+
+            let mut rgba = Box::new([0; 4 * ICON_WIDTH * ICON_HEIGHT]);
+            for (i, &byte) in bitmap.iter().enumerate() {
+                // Each byte contains two pixels, low nibble first
+                rgba[8 * i..8 * i + 4].copy_from_slice(&palette[usize::from(byte & 0xF)]);
+                rgba[8 * i + 4..8 * i + 8].copy_from_slice(&palette[usize::from(byte >> 4)]);
+            }
+            rgba
+        })
+        .collect();
+
+    SaveIcon { frames }
+}
+
+// Like textures, color 0x0000 is fully transparent
+fn rgba_from_bgr555(color: u16) -> [u8; 4] {
+    if color == 0 {
+        return [0; 4];
+    }
+
+    let [r, g, b] = [0, 5, 10].map(|shift| {
+        let component = (color >> shift) & 0x1F;
+        ((component << 3) | (component >> 2)) as u8
+    });
+    [r, g, b, 255]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted_card() -> Box<MemoryCardData> {
+        new_formatted_memory_card().into_boxed_slice().try_into().unwrap()
+    }
+
+    fn test_save(file_name: &str, blocks: usize) -> RawSave {
+        let mut data = vec![0; blocks * BLOCK_LEN];
+        data[0..2].copy_from_slice(b"SC");
+        data[2] = 0x11;
+        // "ＡＢＣ" in Shift-JIS
+        data[4..10].copy_from_slice(&[0x82, 0x60, 0x82, 0x61, 0x82, 0x62]);
+        for (i, chunk) in data.chunks_mut(BLOCK_LEN).enumerate() {
+            chunk[FRAME_LEN * 2] = i as u8;
+        }
+        RawSave { file_name: file_name.into(), data }
+    }
+
+    #[test]
+    fn import_list_export_delete() {
+        let mut card = formatted_card();
+        assert_eq!(free_blocks(&card), 15);
+
+        let save = test_save("BASLUS-00594FF7_00", 3);
+        assert_eq!(import_save(&mut card, &save).unwrap(), 1);
+        assert_eq!(import_save(&mut card, &test_save("BESLES-12345SAVE", 1)).unwrap(), 4);
+        assert_eq!(free_blocks(&card), 11);
+
+        let saves = list_saves(&card).unwrap();
+        assert_eq!(saves.len(), 2);
+        assert_eq!(saves[0].blocks, vec![1, 2, 3]);
+        assert_eq!(saves[0].region, Region::America);
+        assert_eq!(saves[0].product_code, "SLUS-00594");
+        assert_eq!(saves[0].title, "ABC");
+        assert_eq!(saves[0].icon.frames.len(), 1);
+        assert_eq!(saves[1].region, Region::Europe);
+
+        assert_eq!(export_save(&card, 1).unwrap(), save);
+        assert!(matches!(import_save(&mut card, &save), Err(MemoryCardFsError::DuplicateName(_))));
+
+        delete_save(&mut card, 1).unwrap();
+        assert_eq!(free_blocks(&card), 14);
+        assert_eq!(list_saves(&card).unwrap().len(), 1);
+        assert!(matches!(export_save(&card, 1), Err(MemoryCardFsError::NoSaveAtBlock(1))));
+
+        // Deleted blocks are reused
+        assert_eq!(import_save(&mut card, &test_save("BISLPS-00001", 4)).unwrap(), 1);
+        assert_eq!(list_saves(&card).unwrap()[0].blocks, vec![1, 2, 3, 5]);
+        assert!(matches!(
+            import_save(&mut card, &test_save("BISLPS-00002", 11)),
+            Err(MemoryCardFsError::NotEnoughSpace { required: 11, available: 10 })
+        ));
+    }
+}
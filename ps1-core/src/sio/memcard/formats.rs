@@ -0,0 +1,340 @@
+//! Memory card image and single save file formats used by other emulators and tools
+//!
+//! Whole-card formats wrap the raw 128KB card image in a header:
+//! * Raw (`.mcd`, `.mcr`, `.mc`): No header
+//! * Dex Drive (`.gme`): 3904-byte header containing a copy of part of the directory and a comment
+//!   for each block
+//! * Connectix Virtual Game Station (`.vgs`, `.mem`): 64-byte header
+//! * PSP/Vita (`.vmp`): 128-byte header containing a keyed SHA-1 signature of the file, which the
+//!   PSP and Vita check before using the card
+//!
+//! Single save formats contain the blocks of one save:
+//! * `.mcs`: The save's first directory frame, followed by the save's blocks
+//! * Action Replay / Game Shark (`.psx`): 54-byte header with the save's file name
+
+use crate::sio::memcard::filesystem::{
+    self, FIRST_BLOCK, FRAME_LEN, IN_USE, MemoryCardFsError, NO_NEXT_BLOCK, RawSave,
+};
+use crate::sio::memcard::{MEMORY_CARD_LEN, MemoryCardData};
+use aes::Aes128;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+const GME_MAGIC: &[u8] = b"123-456-STD";
+const GME_HEADER_LEN: usize = 3904;
+
+const VGS_MAGIC: &[u8] = b"VgsM";
+const VGS_HEADER_LEN: usize = 64;
+
+const VMP_MAGIC: &[u8] = b"\0PMV";
+const VMP_HEADER_LEN: usize = 0x80;
+const VMP_SEED_OFFSET: usize = 0x0C;
+const VMP_HASH_OFFSET: usize = 0x20;
+
+const VMP_KEY: [u8; 16] = [
+    0xAB, 0x5A, 0xBC, 0x9F, 0xC1, 0xF4, 0x9D, 0xE6, 0xA0, 0x51, 0xDB, 0xAE, 0xFA, 0x51, 0x88, 0x59,
+];
+const VMP_IV: [u8; 16] = [
+    0xB3, 0x0F, 0xFE, 0xED, 0xB7, 0xDC, 0x5E, 0xB7, 0x13, 0x3D, 0xA6, 0x0D, 0x1B, 0x6B, 0x2C, 0xDC,
+];
+
+const PSX_HEADER_LEN: usize = 54;
+
+#[derive(Debug, Error)]
+pub enum MemoryCardFormatError {
+    #[error("Unrecognized memory card image format (size {0})")]
+    UnrecognizedCard(usize),
+    #[error("Invalid {format} save file: {reason}")]
+    InvalidSave { format: &'static str, reason: &'static str },
+    #[error(transparent)]
+    Fs(#[from] MemoryCardFsError),
+}
+
+pub type MemoryCardFormatResult<T> = Result<T, MemoryCardFormatError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardFormat {
+    Raw,
+    Gme,
+    Vgs,
+    Vmp,
+}
+
+impl CardFormat {
+    pub const ALL: [Self; 4] = [Self::Raw, Self::Gme, Self::Vgs, Self::Vmp];
+
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mcd" | "mcr" | "mc" => Some(Self::Raw),
+            "gme" => Some(Self::Gme),
+            "vgs" | "mem" => Some(Self::Vgs),
+            "vmp" => Some(Self::Vmp),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Self::Raw => &["mcd", "mcr", "mc"],
+            Self::Gme => &["gme"],
+            Self::Vgs => &["vgs", "mem"],
+            Self::Vmp => &["vmp"],
+        }
+    }
+
+    fn header_len(self) -> usize {
+        match self {
+            Self::Raw => 0,
+            Self::Gme => GME_HEADER_LEN,
+            Self::Vgs => VGS_HEADER_LEN,
+            Self::Vmp => VMP_HEADER_LEN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    Mcs,
+    Psx,
+}
+
+impl SaveFormat {
+    #[must_use]
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mcs" => Some(Self::Mcs),
+            "psx" => Some(Self::Psx),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Mcs => "MCS",
+            Self::Psx => "PSX",
+        }
+    }
+}
+
+/// Read a card image in any supported format, detected from the image's header and size.
+///
+/// # Errors
+///
+/// Returns an error if the image is not in a supported format.
+#[allow(clippy::missing_panics_doc)]
+pub fn read_card(bytes: &[u8]) -> MemoryCardFormatResult<(CardFormat, Box<MemoryCardData>)> {
+    let format =
+        detect_card_format(bytes).ok_or(MemoryCardFormatError::UnrecognizedCard(bytes.len()))?;
+
+    let card = bytes[format.header_len()..].to_vec().into_boxed_slice().try_into().unwrap();
+    Ok((format, card))
+}
+
+fn detect_card_format(bytes: &[u8]) -> Option<CardFormat> {
+    CardFormat::ALL.into_iter().find(|&format| {
+        let magic: &[u8] = match format {
+            CardFormat::Raw => b"",
+            CardFormat::Gme => GME_MAGIC,
+            CardFormat::Vgs => VGS_MAGIC,
+            CardFormat::Vmp => VMP_MAGIC,
+        };
+        bytes.len() == format.header_len() + MEMORY_CARD_LEN && bytes.starts_with(magic)
+    })
+}
+
+/// Write a card image in the given format.
+#[must_use]
+pub fn write_card(card: &MemoryCardData, format: CardFormat) -> Vec<u8> {
+    let mut bytes = match format {
+        CardFormat::Raw => Vec::new(),
+        CardFormat::Gme => gme_header(card),
+        CardFormat::Vgs => vgs_header(),
+        CardFormat::Vmp => vmp_header(),
+    };
+    bytes.extend_from_slice(card);
+
+    if format == CardFormat::Vmp {
+        let hash = vmp_hash(&bytes);
+        bytes[VMP_HASH_OFFSET..VMP_HASH_OFFSET + hash.len()].copy_from_slice(&hash);
+    }
+
+    bytes
+}
+
+fn gme_header(card: &MemoryCardData) -> Vec<u8> {
+    let mut header = vec![0; GME_HEADER_LEN];
+    header[..GME_MAGIC.len()].copy_from_slice(GME_MAGIC);
+    header[0x12] = 0x01;
+    header[0x14] = 0x01;
+    header[0x15] = b'M';
+
+    // Allocation state and next block pointer (low byte) of each directory entry. Comments at
+    // 0x40 are left empty
+    for block in 0..filesystem::DATA_BLOCKS {
+        let entry = &card[(block + 1) * FRAME_LEN..];
+        header[0x16 + block] = entry[0x00];
+        header[0x26 + block] = entry[0x08];
+    }
+
+    header
+}
+
+fn vgs_header() -> Vec<u8> {
+    let mut header = vec![0; VGS_HEADER_LEN];
+    header[..VGS_MAGIC.len()].copy_from_slice(VGS_MAGIC);
+    header[0x04] = 0x01;
+    header[0x08] = 0x01;
+    header[0x0C] = 0x01;
+    header[0x11] = 0x02;
+    header
+}
+
+// The signature seed can be anything; leave it as all zeroes
+fn vmp_header() -> Vec<u8> {
+    let mut header = vec![0; VMP_HEADER_LEN];
+    header[..VMP_MAGIC.len()].copy_from_slice(VMP_MAGIC);
+    header[0x04..0x08].copy_from_slice(&(VMP_HEADER_LEN as u32).to_le_bytes());
+    header
+}
+
+// HMAC-SHA1 over the whole file with the hash field zeroed, using a key derived from the seed
+// in the header
+fn vmp_hash(file: &[u8]) -> [u8; 20] {
+    let seed = &file[VMP_SEED_OFFSET..VMP_SEED_OFFSET + 0x14];
+    let cipher = Aes128::new(&VMP_KEY.into());
+
+    let mut salt = [0; 0x40];
+
+    let mut block = <[u8; 16]>::try_from(&seed[..0x10]).unwrap().into();
+    cipher.decrypt_block(&mut block);
+    for (salt, (decrypted, iv)) in salt[..0x10].iter_mut().zip(block.iter().zip(VMP_IV)) {
+        *salt = decrypted ^ iv;
+    }
+
+    let mut block = <[u8; 16]>::try_from(&seed[..0x10]).unwrap().into();
+    cipher.encrypt_block(&mut block);
+    let mut mask = [0xFF; 0x10];
+    mask[..4].copy_from_slice(&seed[0x10..0x14]);
+    for (salt, (encrypted, mask)) in salt[0x10..0x20].iter_mut().zip(block.iter().zip(mask)) {
+        *salt = encrypted ^ mask;
+    }
+
+    salt[0x14..].fill(0);
+
+    let mut inner = Sha1::new();
+    inner.update(salt.map(|b| b ^ 0x36));
+    inner.update(&file[..VMP_HASH_OFFSET]);
+    inner.update([0; 0x14]);
+    inner.update(&file[VMP_HASH_OFFSET + 0x14..]);
+    let inner_hash = inner.finalize();
+
+    let mut outer = Sha1::new();
+    outer.update(salt.map(|b| b ^ 0x5C));
+    outer.update(inner_hash);
+    outer.finalize().into()
+}
+
+/// Read a single save file.
+///
+/// # Errors
+///
+/// Returns an error if the file is truncated or does not contain a valid save.
+pub fn read_save(bytes: &[u8], format: SaveFormat) -> MemoryCardFormatResult<RawSave> {
+    let invalid = |reason| MemoryCardFormatError::InvalidSave { format: format.name(), reason };
+
+    let save = match format {
+        SaveFormat::Mcs => {
+            let frame = bytes.get(..FRAME_LEN).ok_or_else(|| invalid("file is too short"))?;
+            if frame[0] != IN_USE | FIRST_BLOCK {
+                return Err(invalid("directory frame is not the first block of a save"));
+            }
+
+            RawSave { file_name: filesystem::file_name(frame), data: bytes[FRAME_LEN..].to_vec() }
+        }
+        SaveFormat::Psx => {
+            let header = bytes.get(..PSX_HEADER_LEN).ok_or_else(|| invalid("file is too short"))?;
+            let name_len = header.iter().position(|&b| b == 0).unwrap_or(filesystem::FILE_NAME_LEN);
+            let file_name = String::from_utf8_lossy(&header[..name_len]).into_owned();
+
+            RawSave { file_name, data: bytes[PSX_HEADER_LEN..].to_vec() }
+        }
+    };
+
+    filesystem::validate_save(&save)?;
+    Ok(save)
+}
+
+/// Write a single save file.
+///
+/// # Errors
+///
+/// Returns an error if the save has an invalid file name or size.
+pub fn write_save(save: &RawSave, format: SaveFormat) -> MemoryCardFormatResult<Vec<u8>> {
+    filesystem::validate_save(save)?;
+
+    let mut bytes = match format {
+        SaveFormat::Mcs => {
+            filesystem::directory_frame(FIRST_BLOCK, NO_NEXT_BLOCK, Some(save)).to_vec()
+        }
+        SaveFormat::Psx => {
+            let mut header = vec![0; PSX_HEADER_LEN];
+            header[..save.file_name.len()].copy_from_slice(save.file_name.as_bytes());
+            header
+        }
+    };
+    bytes.extend_from_slice(&save.data);
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sio::memcard::new_formatted_memory_card;
+
+    #[test]
+    fn card_formats_round_trip() {
+        let mut card: Box<MemoryCardData> =
+            new_formatted_memory_card().into_boxed_slice().try_into().unwrap();
+        card[0x2000..0x4000].fill(0x55);
+
+        for format in CardFormat::ALL {
+            let bytes = write_card(&card, format);
+            let (read_format, read_card) = read_card(&bytes).unwrap();
+            assert_eq!(read_format, format);
+            assert_eq!(read_card, card);
+        }
+
+        assert!(matches!(
+            read_card(&[0; 1000]),
+            Err(MemoryCardFormatError::UnrecognizedCard(1000))
+        ));
+    }
+
+    #[test]
+    fn vmp_signature_covers_contents() {
+        let mut card: Box<MemoryCardData> = vec![0; MEMORY_CARD_LEN].try_into().unwrap();
+        let bytes = write_card(&card, CardFormat::Vmp);
+        let signature = &bytes[VMP_HASH_OFFSET..VMP_HASH_OFFSET + 0x14];
+        assert_ne!(signature, &[0; 0x14]);
+        assert_eq!(signature, &vmp_hash(&bytes));
+
+        card[0x1000] = 1;
+        let modified = write_card(&card, CardFormat::Vmp);
+        assert_ne!(signature, &modified[VMP_HASH_OFFSET..VMP_HASH_OFFSET + 0x14]);
+    }
+
+    #[test]
+    fn save_formats_round_trip() {
+        let save = RawSave { file_name: "BASLUS-00594FF7_00".into(), data: vec![0xAA; 2 * 8192] };
+
+        for format in [SaveFormat::Mcs, SaveFormat::Psx] {
+            let bytes = write_save(&save, format).unwrap();
+            assert_eq!(read_save(&bytes, format).unwrap(), save);
+            assert!(read_save(&bytes[..bytes.len() - 1], format).is_err());
+        }
+    }
+}
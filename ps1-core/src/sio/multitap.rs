@@ -0,0 +1,304 @@
+//! SIO0 multitap (SCPH-1070) code
+//!
+//! The multitap connects up to 4 controllers and 4 memory cards to one port. Slot A is the port's
+//! regular controller and memory card, and slots B-D are addressed as $02-$04 (controllers) and
+//! $82-$84 (memory cards) instead of $01 and $81.
+//!
+//! Transfers are normally passed through to the addressed slot. Sending $01 as the third byte of a
+//! controller read ($01 $42 $01) makes the next transfer from address $01 return the multitap ID
+//! followed by 8 bytes from each of the 4 controllers, in slot order. That transfer can use either
+//! the $42 or $43 command; each controller is then sent its own command as the first of its 8
+//! bytes.
+
+use crate::gpu::BeamPosition;
+use crate::input::{ControllerState, DualShockConfig, MULTITAP_SLOTS};
+use crate::sio::memcard::{ConnectedMemoryCard, MemoryCard};
+use crate::sio::rxfifo::RxFifo;
+use crate::sio::{
+    ControllerPort, MEMORY_CARD_ADDRESS, Port, Sio0Device, Sio0Devices, initial_controller_state,
+    process_controller,
+};
+use bincode::{Decode, Encode};
+
+// Controllers with shorter responses are padded with $FF
+const BYTES_PER_SLOT: u8 = 8;
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Multitap {
+    // Slots B-D; slot A is the port's regular controller
+    pub(super) slots: [ControllerPort; MULTITAP_SLOTS - 1],
+    // Set by the third byte of each controller read, and applies to the next read
+    transfer_all: bool,
+}
+
+impl Multitap {
+    pub fn new(
+        inputs: [ControllerState; MULTITAP_SLOTS - 1],
+        dualshock_config: DualShockConfig,
+    ) -> Self {
+        Self {
+            slots: inputs.map(|inputs| ControllerPort::new(inputs, dualshock_config)),
+            transfer_all: false,
+        }
+    }
+
+    pub fn set_inputs(
+        &mut self,
+        inputs: [ControllerState; MULTITAP_SLOTS - 1],
+        beam_position: impl Fn(u16, u16) -> BeamPosition,
+    ) {
+        for (slot, inputs) in self.slots.iter_mut().zip(inputs) {
+            slot.set_inputs(inputs, &beam_position);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub enum MultitapTransfer {
+    /// Passing a controller transfer through to the controller in one slot
+    Single {
+        slot: u8,
+        byte: u8,
+        command: u8,
+        device: Box<Sio0Device>,
+    },
+    SendingIdLow,
+    SendingIdHigh,
+    /// Transferring byte `byte` of the response for the controller in `slot`. `device` is `None`
+    /// if the slot is empty or the controller has finished responding.
+    AllSlots {
+        slot: u8,
+        byte: u8,
+        device: Option<Box<Sio0Device>>,
+    },
+    /// Passing a memory card transfer through to the card in one slot
+    MemoryCard {
+        slot: u8,
+        card: ConnectedMemoryCard,
+    },
+}
+
+impl Sio0Devices {
+    pub(super) fn connect_multitap(&self, tx: u8, port: Port) -> Option<Sio0Device> {
+        let multitap = self.multitaps[port as usize].as_ref()?;
+
+        match tx {
+            0x01 if multitap.transfer_all => {
+                Some(Sio0Device::Multitap(MultitapTransfer::SendingIdLow))
+            }
+            0x01..=0x04 => {
+                let slot = tx - 0x01;
+                let device = initial_controller_state(self.slot_controller(port, slot)?)?;
+                Some(Sio0Device::Multitap(MultitapTransfer::Single {
+                    slot,
+                    byte: 0,
+                    command: 0,
+                    device: Box::new(device),
+                }))
+            }
+            MEMORY_CARD_ADDRESS..=0x84 => {
+                let slot = tx - MEMORY_CARD_ADDRESS;
+                self.slot_memory_card(port, slot)?;
+                Some(Sio0Device::Multitap(MultitapTransfer::MemoryCard {
+                    slot,
+                    card: ConnectedMemoryCard::initial(),
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    pub(super) fn process_multitap(
+        &mut self,
+        transfer: MultitapTransfer,
+        tx: u8,
+        port: Port,
+        rx: &mut RxFifo,
+    ) -> Option<MultitapTransfer> {
+        match transfer {
+            MultitapTransfer::Single { slot, byte, command, device } => {
+                let command = if byte == 0 { tx } else { command };
+                if byte == 1 && command == 0x42 {
+                    self.multitaps[port as usize].as_mut()?.transfer_all = tx == 0x01;
+                }
+
+                let controller = self.slot_controller_mut(port, slot)?;
+                let device = process_controller(controller, *device, tx, rx)?;
+
+                Some(MultitapTransfer::Single {
+                    slot,
+                    byte: byte.saturating_add(1),
+                    command,
+                    device: Box::new(device),
+                })
+            }
+            MultitapTransfer::SendingIdLow => {
+                // $80 = multitap
+                rx.push(0x80);
+
+                matches!(tx, 0x42 | 0x43).then_some(MultitapTransfer::SendingIdHigh)
+            }
+            MultitapTransfer::SendingIdHigh => {
+                rx.push(0x5A);
+                self.multitaps[port as usize].as_mut()?.transfer_all = tx == 0x01;
+
+                Some(MultitapTransfer::AllSlots { slot: 0, byte: 0, device: None })
+            }
+            MultitapTransfer::AllSlots { slot, byte, device } => {
+                // The first byte sent for each slot is the command for that slot's controller
+                let device = if byte == 0 {
+                    self.slot_controller(port, slot).and_then(initial_controller_state)
+                } else {
+                    device.map(|device| *device)
+                };
+
+                let device = match device {
+                    Some(device) => {
+                        let controller = self.slot_controller_mut(port, slot)?;
+                        process_controller(controller, device, tx, rx)
+                    }
+                    None => {
+                        rx.push(0xFF);
+                        None
+                    }
+                };
+
+                let (slot, byte) =
+                    if byte + 1 == BYTES_PER_SLOT { (slot + 1, 0) } else { (slot, byte + 1) };
+                (usize::from(slot) < MULTITAP_SLOTS).then(|| MultitapTransfer::AllSlots {
+                    slot,
+                    byte,
+                    device: device.map(Box::new),
+                })
+            }
+            MultitapTransfer::MemoryCard { slot, card } => {
+                // Cards can't be removed mid-transfer, so the card in the slot should still exist
+                let memory_card = self.slot_memory_card_mut(port, slot)?;
+                card.process(tx, rx, memory_card)
+                    .map(|card| MultitapTransfer::MemoryCard { slot, card })
+            }
+        }
+    }
+
+    pub(super) fn slot_controller(&self, port: Port, slot: u8) -> Option<&ControllerPort> {
+        match slot {
+            0 => Some(&self.controllers[port as usize]),
+            _ => self.multitaps[port as usize].as_ref()?.slots.get(usize::from(slot) - 1),
+        }
+    }
+
+    fn slot_controller_mut(&mut self, port: Port, slot: u8) -> Option<&mut ControllerPort> {
+        match slot {
+            0 => Some(&mut self.controllers[port as usize]),
+            _ => self.multitaps[port as usize].as_mut()?.slots.get_mut(usize::from(slot) - 1),
+        }
+    }
+
+    fn slot_memory_card(&self, port: Port, slot: u8) -> Option<&MemoryCard> {
+        match (slot, port) {
+            (0, Port::One) => self.memory_card_1.as_ref(),
+            (0, Port::Two) => self.memory_card_2.as_ref(),
+            _ => self.multitap_memory_cards[port as usize].get(usize::from(slot) - 1)?.as_ref(),
+        }
+    }
+
+    fn slot_memory_card_mut(&mut self, port: Port, slot: u8) -> Option<&mut MemoryCard> {
+        match slot {
+            0 => self.memory_card(port),
+            _ => self.multitap_memory_cards[port as usize].get_mut(usize::from(slot) - 1)?.as_mut(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{ControllerType, Ps1Inputs};
+    use crate::sio::{MultitapMemoryCards, SerialDevices};
+
+    fn transfer(devices: &mut Sio0Devices, address: u8, tx: &[u8]) -> Vec<u8> {
+        let mut rx = RxFifo::new();
+        let mut device = devices.connect(address, Port::One);
+        let mut responses = Vec::new();
+        for &tx in tx {
+            let Some(current) = device.take() else { break };
+            device = devices.process_tx_write(current, tx, Port::One, &mut rx);
+            responses.push(rx.pop());
+        }
+
+        responses
+    }
+
+    #[test]
+    fn transfers_all_slots_after_request() {
+        let mut devices = Sio0Devices::new(None, None, Default::default());
+        let mut slots = [ControllerState::default_p2(); MULTITAP_SLOTS - 1];
+        slots[1].controller_type = ControllerType::Digital;
+        slots[1].digital.set_cross(true);
+        devices.set_multitap_inputs(Port::One, Some(slots), |_, _| unreachable!());
+
+        // Single slot reads pass through to the addressed slot
+        assert_eq!(
+            transfer(&mut devices, 0x03, &[0x42, 0x00, 0x00, 0x00]),
+            [0x41, 0x5A, 0xFF, 0xBF]
+        );
+        assert_eq!(transfer(&mut devices, 0x02, &[0x42]), []);
+
+        // Third byte $01 requests a multitap read next time
+        assert_eq!(
+            transfer(&mut devices, 0x01, &[0x42, 0x01, 0x00, 0x00]),
+            [0x41, 0x5A, 0xFF, 0xFF]
+        );
+
+        let mut tx = vec![0x42, 0x00];
+        for _ in 0..MULTITAP_SLOTS {
+            tx.extend([0x42, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        }
+        let responses = transfer(&mut devices, 0x01, &tx);
+        assert_eq!(responses.len(), 2 + 8 * MULTITAP_SLOTS);
+        assert_eq!(responses[..2], [0x80, 0x5A]);
+        assert_eq!(responses[2..10], [0x41, 0x5A, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(responses[10..18], [0xFF; 8]);
+        assert_eq!(responses[18..26], [0x41, 0x5A, 0xFF, 0xBF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(responses[26..34], [0xFF; 8]);
+
+        // Third byte was $00, so the next read is back to a single slot
+        assert_eq!(transfer(&mut devices, 0x01, &[0x42])[0], 0x41);
+
+        // The multitap transfer can also be started with command $43
+        transfer(&mut devices, 0x01, &[0x42, 0x01, 0x00, 0x00]);
+        tx[0] = 0x43;
+        let responses = transfer(&mut devices, 0x01, &tx);
+        assert_eq!(responses[..2], [0x80, 0x5A]);
+        assert_eq!(responses[18..26], [0x41, 0x5A, 0xFF, 0xBF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn passes_memory_card_transfers_through_to_slots() {
+        let mut multitap_cards: MultitapMemoryCards = Default::default();
+        multitap_cards[0][1] = Some(MemoryCard::new(None));
+
+        let mut devices = Sio0Devices::new(Some(MemoryCard::new(None)), None, multitap_cards);
+
+        // Cards in slots B-D are not connected without a multitap
+        assert_eq!(transfer(&mut devices, 0x83, &[0x52]), []);
+
+        devices.set_multitap_inputs(
+            Port::One,
+            Some(Ps1Inputs::empty_multitap()),
+            |_, _| unreachable!(),
+        );
+
+        // Read sector 0 from the card in slot C; the first frame starts with "MC"
+        let read_sector_0 = [0x52, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(
+            transfer(&mut devices, 0x83, &read_sector_0),
+            [0x08, 0x5A, 0x5D, 0x00, 0x00, 0x5C, 0x5D, 0x00, 0x00, b'M', b'C']
+        );
+
+        // Slot A is still the port's regular card, and slots without a card do not respond
+        assert_eq!(transfer(&mut devices, 0x81, &[0x52, 0x00]), [0x08, 0x5A]);
+        assert_eq!(transfer(&mut devices, 0x82, &[0x52]), []);
+        assert_eq!(transfer(&mut devices, 0x84, &[0x52]), []);
+    }
+}
@@ -0,0 +1,542 @@
+//! SIO1 serial port, which is an asynchronous UART with RTS/CTS and DTR/DSR handshaking lines
+//!
+//! The only known use of SIO1 is the link cable, which is emulated by bridging the port to another
+//! emulator instance over a [`LinkCable`]. Transmitted bytes are sent when the transfer completes
+//! at the programmed baudrate, and received bytes are delivered at most one per character time.
+//! The cable is polled using the `Sio1Irq` scheduler event: once per character time while RX is
+//! enabled or a transmit is pending, and roughly once per frame otherwise.
+
+use crate::interrupts::{InterruptRegisters, InterruptType};
+use crate::num::U32Ext;
+use crate::savestate;
+use crate::scheduler::{Scheduler, SchedulerEvent, SchedulerEventType};
+use crate::sio::link::{LinkCable, RemoteLines};
+use crate::sio::rxfifo::RxFifo;
+use crate::sio::{BaudrateTimer, Port, SerialDevices, SerialPort, TxFifoState};
+use bincode::de::Decoder;
+use bincode::enc::Encoder;
+use bincode::error::{DecodeError, EncodeError};
+use bincode::{Decode, Encode};
+use std::cmp;
+
+const RX_FIFO_LEN: u8 = 8;
+
+// Polling interval while the port is idle, which only needs to pick up connections and line changes
+const IDLE_POLL_CYCLES: u64 = 33_868_800 / 60;
+
+/// Link cable attached to SIO1. The connection is not part of save states, and clones of the
+/// port (e.g. in save states) do not have a cable attached.
+#[derive(Debug, Default)]
+struct AttachedCable(Option<LinkCable>);
+
+impl Clone for AttachedCable {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+impl Encode for AttachedCable {
+    fn encode<E: Encoder>(&self, _encoder: &mut E) -> Result<(), EncodeError> {
+        Ok(())
+    }
+}
+
+impl Decode for AttachedCable {
+    fn decode<D: Decoder>(_decoder: &mut D) -> Result<Self, DecodeError> {
+        Ok(Self(None))
+    }
+}
+
+bincode::impl_borrow_decode!(AttachedCable);
+
+#[derive(Debug, Clone, Encode)]
+pub struct SerialPort1 {
+    cable: AttachedCable,
+    last_update_cycles: u64,
+    next_poll_cycles: u64,
+    baudrate_timer: BaudrateTimer,
+    mode: u32,
+    tx_fifo: TxFifoState,
+    rx_fifo: RxFifo,
+    rx_fifo_len: u8,
+    tx_enabled: bool,
+    dtr_on: bool,
+    rts_on: bool,
+    rx_enabled: bool,
+    rx_interrupt_bytes: u8,
+    tx_interrupt_enabled: bool,
+    rx_interrupt_enabled: bool,
+    dsr_interrupt_enabled: bool,
+    rx_overrun: bool,
+    remote_dsr: bool,
+    remote_cts: bool,
+    irq: bool,
+}
+
+impl Decode for SerialPort1 {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        // SIO1 had its own implementation starting in version 10
+        if savestate::decoding_format_version() < 10 {
+            let legacy: SerialPort<LegacySio1Devices> = Decode::decode(decoder)?;
+            return Ok(Self::from_legacy(&legacy));
+        }
+
+        Ok(Self {
+            cable: Decode::decode(decoder)?,
+            last_update_cycles: Decode::decode(decoder)?,
+            next_poll_cycles: Decode::decode(decoder)?,
+            baudrate_timer: Decode::decode(decoder)?,
+            mode: Decode::decode(decoder)?,
+            tx_fifo: Decode::decode(decoder)?,
+            rx_fifo: Decode::decode(decoder)?,
+            rx_fifo_len: Decode::decode(decoder)?,
+            tx_enabled: Decode::decode(decoder)?,
+            dtr_on: Decode::decode(decoder)?,
+            rts_on: Decode::decode(decoder)?,
+            rx_enabled: Decode::decode(decoder)?,
+            rx_interrupt_bytes: Decode::decode(decoder)?,
+            tx_interrupt_enabled: Decode::decode(decoder)?,
+            rx_interrupt_enabled: Decode::decode(decoder)?,
+            dsr_interrupt_enabled: Decode::decode(decoder)?,
+            rx_overrun: Decode::decode(decoder)?,
+            remote_dsr: Decode::decode(decoder)?,
+            remote_cts: Decode::decode(decoder)?,
+            irq: Decode::decode(decoder)?,
+        })
+    }
+}
+
+bincode::impl_borrow_decode!(SerialPort1);
+
+impl SerialPort1 {
+    pub fn new() -> Self {
+        Self {
+            cable: AttachedCable::default(),
+            last_update_cycles: 0,
+            next_poll_cycles: 0,
+            baudrate_timer: BaudrateTimer::new(),
+            mode: 0,
+            tx_fifo: TxFifoState::Empty,
+            rx_fifo: RxFifo::new(),
+            rx_fifo_len: 0,
+            tx_enabled: false,
+            dtr_on: false,
+            rts_on: false,
+            rx_enabled: false,
+            rx_interrupt_bytes: 1,
+            tx_interrupt_enabled: false,
+            rx_interrupt_enabled: false,
+            dsr_interrupt_enabled: false,
+            rx_overrun: false,
+            remote_dsr: false,
+            remote_cts: false,
+            irq: false,
+        }
+    }
+
+    // Before version 10, SIO1 shared the SIO0 implementation with no devices attached
+    fn from_legacy(legacy: &SerialPort<LegacySio1Devices>) -> Self {
+        Self {
+            last_update_cycles: legacy.last_update_cycles,
+            baudrate_timer: legacy.baudrate_timer,
+            tx_enabled: legacy.tx_enabled,
+            dtr_on: legacy.dtr_on,
+            rx_enabled: legacy.rx_enabled,
+            rx_interrupt_bytes: legacy.rx_interrupt_bytes,
+            tx_interrupt_enabled: legacy.tx_interrupt_enabled,
+            rx_interrupt_enabled: legacy.rx_interrupt_enabled,
+            dsr_interrupt_enabled: legacy.dsr_interrupt_enabled,
+            irq: legacy.irq,
+            ..Self::new()
+        }
+    }
+
+    /// Attach or detach the link cable. Detaching returns the previously attached cable.
+    pub fn set_link_cable(
+        &mut self,
+        cable: Option<LinkCable>,
+        scheduler: &mut Scheduler,
+    ) -> Option<LinkCable> {
+        let previous = std::mem::replace(&mut self.cable.0, cable);
+
+        match &mut self.cable.0 {
+            Some(cable) => {
+                cable.set_output_lines(self.dtr_on, self.rts_on);
+                self.next_poll_cycles = scheduler.cpu_cycle_counter();
+                scheduler.update_or_push_event(SchedulerEvent {
+                    event_type: SchedulerEventType::Sio1Irq,
+                    cpu_cycles: self.next_poll_cycles,
+                });
+            }
+            None => {
+                self.set_remote_lines(RemoteLines::default());
+            }
+        }
+
+        previous
+    }
+
+    pub fn take_link_cable(&mut self) -> Option<LinkCable> {
+        self.cable.0.take()
+    }
+
+    pub fn link_cable(&self) -> Option<&LinkCable> {
+        self.cable.0.as_ref()
+    }
+
+    pub fn catch_up(
+        &mut self,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) {
+        let now = scheduler.cpu_cycle_counter();
+        let cycles_elapsed = u32::try_from(now - self.last_update_cycles).unwrap_or(u32::MAX);
+        self.last_update_cycles = now;
+
+        self.baudrate_timer.tick(cycles_elapsed);
+
+        match self.tx_fifo {
+            TxFifoState::Empty => {}
+            TxFifoState::Queued(value) => {
+                if self.tx_enabled {
+                    self.start_transfer(value, None, scheduler);
+                }
+            }
+            TxFifoState::Transferring { value, cycles_remaining, next } => {
+                let cycles_remaining = cycles_remaining.saturating_sub(cycles_elapsed);
+                if cycles_remaining == 0 {
+                    self.finish_transfer(value, interrupt_registers);
+
+                    self.tx_fifo = TxFifoState::Empty;
+                    match (next, self.tx_enabled) {
+                        (Some(next), true) => self.start_transfer(next, None, scheduler),
+                        (Some(next), false) => self.tx_fifo = TxFifoState::Queued(next),
+                        (None, _) => {}
+                    }
+                } else {
+                    self.tx_fifo = TxFifoState::Transferring { value, cycles_remaining, next };
+                }
+            }
+        }
+
+        if self.cable.0.is_some() && now >= self.next_poll_cycles {
+            self.poll_cable(interrupt_registers);
+
+            self.next_poll_cycles = now + self.poll_interval();
+            scheduler.update_or_push_event(SchedulerEvent {
+                event_type: SchedulerEventType::Sio1Irq,
+                cpu_cycles: self.next_poll_cycles,
+            });
+        }
+    }
+
+    fn poll_interval(&self) -> u64 {
+        let active = self.rx_enabled
+            || self.tx_fifo != TxFifoState::Empty
+            || self.cable.0.as_ref().is_some_and(LinkCable::has_pending_output);
+        if active { u64::from(self.character_cycles()) } else { IDLE_POLL_CYCLES }
+    }
+
+    // Bring the next poll forward if the port just became active while polling at the idle rate
+    fn reschedule_poll(&mut self, scheduler: &mut Scheduler) {
+        if self.cable.0.is_none() {
+            return;
+        }
+
+        let next_poll_cycles = scheduler.cpu_cycle_counter() + self.poll_interval();
+        if next_poll_cycles < self.next_poll_cycles {
+            self.next_poll_cycles = next_poll_cycles;
+            scheduler.update_or_push_event(SchedulerEvent {
+                event_type: SchedulerEventType::Sio1Irq,
+                cpu_cycles: self.next_poll_cycles,
+            });
+        }
+    }
+
+    fn start_transfer(&mut self, value: u8, next: Option<u8>, scheduler: &mut Scheduler) {
+        let cycles = self.character_cycles();
+        self.tx_fifo = TxFifoState::Transferring { value, cycles_remaining: cycles, next };
+        scheduler.update_or_push_event(SchedulerEvent {
+            event_type: SchedulerEventType::Sio1Tx,
+            cpu_cycles: scheduler.cpu_cycle_counter() + u64::from(cycles),
+        });
+    }
+
+    fn finish_transfer(&mut self, value: u8, interrupt_registers: &mut InterruptRegisters) {
+        let value = value & self.data_mask();
+        log::debug!("SIO1 transmitted {value:02X}");
+
+        if let Some(cable) = &mut self.cable.0 {
+            cable.send_byte(value);
+        }
+
+        if self.tx_interrupt_enabled {
+            self.raise_irq(interrupt_registers);
+        }
+    }
+
+    fn poll_cable(&mut self, interrupt_registers: &mut InterruptRegisters) {
+        let Some(cable) = &mut self.cable.0 else { return };
+
+        let mut received = cable.receive();
+        if !self.rx_enabled {
+            // Bytes that arrive while RX is disabled are lost; drain everything that arrived since
+            // the last poll, which may have been up to a frame ago
+            while let Some(value) = received {
+                log::debug!("SIO1 received {value:02X} with RX disabled");
+                received = cable.receive();
+            }
+        }
+        let remote_lines = cable.remote_lines();
+
+        let dsr_rising = remote_lines.dsr && !self.remote_dsr;
+        self.set_remote_lines(remote_lines);
+        if dsr_rising && self.dsr_interrupt_enabled {
+            self.raise_irq(interrupt_registers);
+        }
+
+        let Some(value) = received else { return };
+        log::debug!("SIO1 received {value:02X}");
+
+        if self.rx_fifo_len == RX_FIFO_LEN {
+            self.rx_overrun = true;
+        } else {
+            self.rx_fifo_len += 1;
+        }
+        self.rx_fifo.push(value & self.data_mask());
+
+        if self.rx_interrupt_enabled && self.rx_fifo_len >= self.rx_interrupt_bytes {
+            self.raise_irq(interrupt_registers);
+        }
+    }
+
+    fn set_remote_lines(&mut self, remote_lines: RemoteLines) {
+        self.remote_dsr = remote_lines.dsr;
+        self.remote_cts = remote_lines.cts;
+    }
+
+    fn raise_irq(&mut self, interrupt_registers: &mut InterruptRegisters) {
+        if !self.irq {
+            interrupt_registers.set_interrupt_flag(InterruptType::Sio);
+        }
+        self.irq = true;
+    }
+
+    fn data_bits(&self) -> u32 {
+        5 + ((self.mode >> 2) & 3)
+    }
+
+    fn data_mask(&self) -> u8 {
+        ((1_u16 << self.data_bits()) - 1) as u8
+    }
+
+    // CPU cycles to transfer one character, including the start, parity, and stop bits
+    fn character_cycles(&self) -> u32 {
+        let factor = match self.mode & 3 {
+            0 | 1 => 1,
+            2 => 16,
+            3 => 64,
+            _ => unreachable!("value & 3 is always <= 3"),
+        };
+        let bit_cycles = cmp::max((self.baudrate_timer.raw_reload_value * factor) & !1, factor);
+
+        let parity_bits = u32::from(self.mode.bit(4));
+        // 1, 1.5, or 2 stop bits; 0 is reserved and treated as 1
+        let stop_half_bits = match (self.mode >> 6) & 3 {
+            0 | 1 => 2,
+            2 => 3,
+            3 => 4,
+            _ => unreachable!("value & 3 is always <= 3"),
+        };
+        let half_bits = 2 * (1 + self.data_bits() + parity_bits) + stop_half_bits;
+
+        bit_cycles * half_bits / 2
+    }
+
+    pub fn write_tx_data(
+        &mut self,
+        tx_data: u32,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) {
+        self.catch_up(scheduler, interrupt_registers);
+
+        let tx_data = tx_data as u8;
+        log::debug!("SIO1_TX_DATA write: {tx_data:02X}");
+
+        match self.tx_fifo {
+            TxFifoState::Empty | TxFifoState::Queued(_) => {
+                if self.tx_enabled {
+                    self.start_transfer(tx_data, None, scheduler);
+                } else {
+                    self.tx_fifo = TxFifoState::Queued(tx_data);
+                }
+            }
+            TxFifoState::Transferring { value, cycles_remaining, .. } => {
+                self.tx_fifo =
+                    TxFifoState::Transferring { value, cycles_remaining, next: Some(tx_data) };
+            }
+        }
+
+        self.reschedule_poll(scheduler);
+    }
+
+    pub fn read_rx_data(&mut self) -> u32 {
+        let value = self.rx_fifo.pop();
+        self.rx_fifo_len = self.rx_fifo_len.saturating_sub(1);
+        log::debug!("SIO1_RX_DATA read: {value:02X}");
+        value.into()
+    }
+
+    // $1F801054: SIO1_STAT
+    pub fn read_status(
+        &mut self,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) -> u32 {
+        self.catch_up(scheduler, interrupt_registers);
+
+        // Bit 6 is the RX input level, which is always high (idle) between received characters
+        let value = u32::from(self.tx_fifo.ready_for_new_byte())
+            | (u32::from(self.rx_fifo_len != 0) << 1)
+            | (u32::from(self.tx_fifo == TxFifoState::Empty) << 2)
+            | (u32::from(self.rx_overrun) << 4)
+            | (1 << 6)
+            | (u32::from(self.remote_dsr) << 7)
+            | (u32::from(self.remote_cts) << 8)
+            | (u32::from(self.irq) << 9)
+            | (self.baudrate_timer.timer << 11);
+
+        log::debug!("SIO1_STAT read: {value:08X}");
+        value
+    }
+
+    // $1F801058: SIO1_MODE
+    pub fn write_mode(
+        &mut self,
+        value: u32,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) {
+        self.catch_up(scheduler, interrupt_registers);
+
+        self.mode = value & 0x1FF;
+        self.baudrate_timer.update_reload_factor(value);
+
+        log::debug!("SIO1_MODE write: {value:04X}");
+    }
+
+    // $1F80105A: SIO1_CTRL
+    pub fn read_control(&self) -> u32 {
+        let rx_mode = match self.rx_interrupt_bytes {
+            1 => 0,
+            2 => 1,
+            4 => 2,
+            8 => 3,
+            _ => panic!("Unexpected RX IRQ FIFO length: {}", self.rx_interrupt_bytes),
+        };
+
+        let value = u32::from(self.tx_enabled)
+            | (u32::from(self.dtr_on) << 1)
+            | (u32::from(self.rx_enabled) << 2)
+            | (u32::from(self.rts_on) << 5)
+            | (rx_mode << 8)
+            | (u32::from(self.tx_interrupt_enabled) << 10)
+            | (u32::from(self.rx_interrupt_enabled) << 11)
+            | (u32::from(self.dsr_interrupt_enabled) << 12);
+
+        log::debug!("SIO1_CTRL read: {value:04X}");
+        value
+    }
+
+    // $1F80105A: SIO1_CTRL
+    pub fn write_control(
+        &mut self,
+        value: u32,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) {
+        self.catch_up(scheduler, interrupt_registers);
+
+        if value.bit(6) {
+            // Reset bit
+            log::debug!("SIO1 reset");
+            self.write_mode(0, scheduler, interrupt_registers);
+            self.write_control(0, scheduler, interrupt_registers);
+            self.tx_fifo = TxFifoState::Empty;
+            self.rx_fifo.clear();
+            self.rx_fifo_len = 0;
+            self.rx_overrun = false;
+            self.irq = false;
+            return;
+        }
+
+        self.tx_enabled = value.bit(0);
+        self.dtr_on = value.bit(1);
+        self.rx_enabled = value.bit(2);
+        self.rts_on = value.bit(5);
+        self.rx_interrupt_bytes = 1 << ((value >> 8) & 3);
+        self.tx_interrupt_enabled = value.bit(10);
+        self.rx_interrupt_enabled = value.bit(11);
+        self.dsr_interrupt_enabled = value.bit(12);
+
+        if value.bit(4) {
+            // Acknowledge IRQ and clear error flags
+            self.irq = false;
+            self.rx_overrun = false;
+        }
+
+        if let Some(cable) = &mut self.cable.0 {
+            cable.set_output_lines(self.dtr_on, self.rts_on);
+        }
+
+        if self.tx_enabled {
+            if let TxFifoState::Queued(tx) = self.tx_fifo {
+                self.start_transfer(tx, None, scheduler);
+            }
+        }
+
+        self.reschedule_poll(scheduler);
+
+        log::debug!("SIO1_CTRL write: {value:04X}");
+    }
+
+    // $1F80105E: SIO1_BAUD (Baudrate timer reload value)
+    pub fn write_baudrate_reload(
+        &mut self,
+        value: u32,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) {
+        self.catch_up(scheduler, interrupt_registers);
+
+        self.baudrate_timer.update_reload_value(value & 0xFFFF);
+
+        log::debug!("SIO1 Baudrate timer reload value: {value:04X}");
+    }
+
+    pub fn read_baudrate_reload(&self) -> u32 {
+        self.baudrate_timer.raw_reload_value
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+struct LegacySio1Devices;
+
+impl SerialDevices for LegacySio1Devices {
+    type Device = ();
+
+    fn connect(&self, _tx: u8, _port: Port) -> Option<Self::Device> {
+        None
+    }
+
+    fn process_tx_write(
+        &mut self,
+        _device: Self::Device,
+        _tx: u8,
+        _port: Port,
+        _rx: &mut RxFifo,
+    ) -> Option<Self::Device> {
+        None
+    }
+}
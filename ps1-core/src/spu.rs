@@ -286,6 +286,10 @@ impl Spu {
         }
     }
 
+    pub fn sound_ram_mut(&mut self) -> &mut [u8] {
+        self.sound_ram.ram.as_mut_slice()
+    }
+
     pub fn clock(
         &mut self,
         cd_controller: &CdController,
@@ -27,10 +27,13 @@
 #[cfg(test)]
 mod tests;
 
-use crate::gpu::VideoMode;
+use crate::gpu::{BeamPosition, VideoMode};
 use crate::interrupts::{InterruptRegisters, InterruptType};
 use crate::num::U32Ext;
+use crate::savestate;
 use crate::scheduler::{Scheduler, SchedulerEvent, SchedulerEventType};
+use bincode::de::Decoder;
+use bincode::error::DecodeError;
 use bincode::{Decode, Encode};
 use std::cmp;
 use std::cmp::Ordering;
@@ -416,7 +419,7 @@ const PAL_GPU_CLOCK: u64 = 53_203_425;
 const CPU_CLOCK: u64 = 33_868_800;
 
 impl VideoMode {
-    const fn gpu_clock(self) -> u64 {
+    pub const fn gpu_clock(self) -> u64 {
         match self {
             Self::Ntsc => NTSC_GPU_CLOCK,
             Self::Pal => PAL_GPU_CLOCK,
@@ -432,7 +435,7 @@ impl VideoMode {
     }
 }
 
-#[derive(Debug, Clone, Encode, Decode)]
+#[derive(Debug, Clone, Encode)]
 struct GpuTimer {
     cycle_product: u64,
     line: u16,
@@ -445,8 +448,41 @@ struct GpuTimer {
     dot_clock_divider: u64,
     interlaced: bool,
     odd_frame: bool,
+    // Determines the GPU clock rate; this comes from the console's video crystal rather than from
+    // the video mode that software selects through GP1
+    clock_mode: VideoMode,
+}
+
+impl Decode for GpuTimer {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Self, DecodeError> {
+        let mut timer = Self {
+            cycle_product: Decode::decode(decoder)?,
+            line: Decode::decode(decoder)?,
+            line_cycle: Decode::decode(decoder)?,
+            x1: Decode::decode(decoder)?,
+            x2: Decode::decode(decoder)?,
+            y1: Decode::decode(decoder)?,
+            y2: Decode::decode(decoder)?,
+            video_mode: Decode::decode(decoder)?,
+            dot_clock_divider: Decode::decode(decoder)?,
+            interlaced: Decode::decode(decoder)?,
+            odd_frame: Decode::decode(decoder)?,
+            clock_mode: VideoMode::default(),
+        };
+
+        // The GPU clock followed the GP1 video mode before version 13
+        timer.clock_mode = if savestate::decoding_format_version() < 13 {
+            timer.video_mode
+        } else {
+            Decode::decode(decoder)?
+        };
+
+        Ok(timer)
+    }
 }
 
+bincode::impl_borrow_decode!(GpuTimer);
+
 impl GpuTimer {
     fn new() -> Self {
         Self {
@@ -461,6 +497,7 @@ impl GpuTimer {
             dot_clock_divider: 10,
             interlaced: false,
             odd_frame: false,
+            clock_mode: VideoMode::default(),
         }
     }
 
@@ -665,7 +702,7 @@ impl Timers {
         cpu_elapsed: u64,
         interrupt_registers: &mut InterruptRegisters,
     ) {
-        self.gpu.cycle_product += cpu_elapsed * self.gpu.video_mode.gpu_clock();
+        self.gpu.cycle_product += cpu_elapsed * self.gpu.clock_mode.gpu_clock();
         let mut gpu_elapsed = self.gpu.cycle_product / CPU_CLOCK;
         self.gpu.cycle_product %= CPU_CLOCK;
 
@@ -722,7 +759,7 @@ impl Timers {
         cpu_elapsed: u64,
         interrupt_registers: &mut InterruptRegisters,
     ) {
-        self.gpu.cycle_product += cpu_elapsed * self.gpu.video_mode.gpu_clock();
+        self.gpu.cycle_product += cpu_elapsed * self.gpu.clock_mode.gpu_clock();
         let mut gpu_elapsed = self.gpu.cycle_product / CPU_CLOCK;
         self.gpu.cycle_product %= CPU_CLOCK;
 
@@ -788,7 +825,7 @@ impl Timers {
                                 self.timer_0_clock_source.clocks(
                                     dot_clocks,
                                     timer_0_gpu_clocks,
-                                    self.gpu.video_mode,
+                                    self.gpu.clock_mode,
                                 ),
                                 interrupt_registers,
                             );
@@ -842,7 +879,7 @@ impl Timers {
                                 self.timer_1_clock_source.clocks(
                                     h_retrace_clocks,
                                     timer_1_gpu_clocks,
-                                    self.gpu.video_mode,
+                                    self.gpu.clock_mode,
                                 ),
                                 interrupt_registers,
                             );
@@ -862,14 +899,14 @@ impl Timers {
         }
 
         self.timers[0].clock(
-            self.timer_0_clock_source.clocks(dot_clocks, timer_0_gpu_clocks, self.gpu.video_mode),
+            self.timer_0_clock_source.clocks(dot_clocks, timer_0_gpu_clocks, self.gpu.clock_mode),
             interrupt_registers,
         );
         self.timers[1].clock(
             self.timer_1_clock_source.clocks(
                 h_retrace_clocks,
                 timer_1_gpu_clocks,
-                self.gpu.video_mode,
+                self.gpu.clock_mode,
             ),
             interrupt_registers,
         );
@@ -948,6 +985,23 @@ impl Timers {
         }
     }
 
+    /// Set the video crystal that drives the GPU clock, which depends on the console region
+    pub fn set_gpu_clock_mode(
+        &mut self,
+        clock_mode: VideoMode,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) {
+        if clock_mode == self.gpu.clock_mode {
+            return;
+        }
+
+        self.catch_up(scheduler, interrupt_registers);
+        self.gpu.clock_mode = clock_mode;
+        self.schedule_next_vblank(scheduler, interrupt_registers);
+        self.schedule_timer_events(scheduler, interrupt_registers);
+    }
+
     pub fn schedule_timer_events(
         &mut self,
         scheduler: &mut Scheduler,
@@ -963,7 +1017,7 @@ impl Timers {
                         // This is not exactly right (will underestimate the number of GPU clocks),
                         // but should be close enough
                         let gpu_cycles = clocks * self.gpu.dot_clock_divider;
-                        gpu_cycles * CPU_CLOCK / self.gpu.video_mode.gpu_clock() + 1
+                        gpu_cycles * CPU_CLOCK / self.gpu.clock_mode.gpu_clock() + 1
                     }
                 };
                 scheduler.update_or_push_event(SchedulerEvent {
@@ -984,7 +1038,7 @@ impl Timers {
                         // This not exactly right but should be close enough
                         // 6825 == 3412.5 * 2
                         let gpu_cycles = clocks * 6825 / 2;
-                        gpu_cycles * CPU_CLOCK / self.gpu.video_mode.gpu_clock() + 1
+                        gpu_cycles * CPU_CLOCK / self.gpu.clock_mode.gpu_clock() + 1
                     }
                 };
                 scheduler.update_or_push_event(SchedulerEvent {
@@ -1051,12 +1105,37 @@ impl Timers {
                 .sum::<u64>();
         }
 
-        let cpu_cycles = gpu_cycles * CPU_CLOCK / self.gpu.video_mode.gpu_clock() + 1;
+        let cpu_cycles = gpu_cycles * CPU_CLOCK / self.gpu.clock_mode.gpu_clock() + 1;
         scheduler.update_or_push_event(SchedulerEvent::vblank(
             scheduler.cpu_cycle_counter() + cpu_cycles,
         ));
     }
 
+    /// Number of CPU cycles until the beam next reaches the given position. Like vblank
+    /// scheduling, this treats every line as having the same length, which is close enough.
+    pub fn cpu_cycles_until_beam(
+        &mut self,
+        target: BeamPosition,
+        scheduler: &mut Scheduler,
+        interrupt_registers: &mut InterruptRegisters,
+    ) -> u64 {
+        self.catch_up(scheduler, interrupt_registers);
+
+        let cycles_per_line = match self.gpu.video_mode {
+            VideoMode::Ntsc => NTSC_CYCLES_PER_LINE,
+            VideoMode::Pal => PAL_CYCLES_PER_LINE,
+        };
+        let frame_cycles =
+            u64::from(self.gpu.video_mode.lines_per_frame(self.gpu.interlaced)) * cycles_per_line;
+
+        let current = u64::from(self.gpu.line) * cycles_per_line + self.gpu.line_cycle;
+        let target = u64::from(target.line) * cycles_per_line + u64::from(target.line_cycle);
+        let gpu_cycles =
+            if target > current { target - current } else { target + frame_cycles - current };
+
+        gpu_cycles * CPU_CLOCK / self.gpu.clock_mode.gpu_clock() + 1
+    }
+
     pub fn read_register(
         &mut self,
         address: u32,
@@ -1,20 +1,30 @@
+use crate::config::bindings::{AxisDirection, InputBindings, InputSource};
 use crate::config::{
-    AppConfig, AspectRatio, FilterMode, FiltersConfig, Rasterizer, VSyncMode, WgpuBackend,
+    AnalogConfig, AppConfig, AspectRatio, DeadzoneShape, FilterMode, FiltersConfig, InputPort,
+    LinkCableMode, MemoryCardMode, Rasterizer, VSyncMode, WgpuBackend,
 };
+use crate::emustate::Hotkey;
+use crate::emuthread::{Ps1AnalogInput, Ps1Button};
+use crate::memcards::MemoryCardManager;
+use crate::savestate::{self, SAVE_STATE_SLOTS};
 use crate::{OpenFileType, UserEvent, config};
 use egui::{
-    Align, Button, CentralPanel, Color32, Context, Key, KeyboardShortcut, Layout, Modifiers,
-    Slider, TextEdit, TopBottomPanel, Ui, Vec2, Window,
+    Align, Button, CentralPanel, CollapsingHeader, Color32, ColorImage, Context, Grid, Key,
+    KeyboardShortcut, Layout, Modifiers, Slider, TextEdit, TextureHandle, TextureOptions,
+    TopBottomPanel, Ui, Vec2, Window,
 };
 use egui_extras::{Column, TableBuilder};
-use ps1_core::input::ControllerType;
+use ps1_core::api::Region;
+use ps1_core::input::{ControllerType, DualShockConfig};
 use std::collections::HashSet;
 use std::ffi::OsStr;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::str::FromStr;
+use std::time::SystemTime;
 use winit::event_loop::EventLoopProxy;
+use winit::keyboard::KeyCode;
 
 struct NumericText {
     value: String,
@@ -47,13 +57,63 @@ impl NumericText {
     }
 }
 
+#[derive(Default)]
+struct SaveStateSlot {
+    modified: Option<SystemTime>,
+    description: String,
+    thumbnail: Option<TextureHandle>,
+}
+
+impl SaveStateSlot {
+    fn load(ctx: &Context, path: &Path, modified: Option<SystemTime>) -> Self {
+        if modified.is_none() {
+            return Self { modified, description: "Empty".into(), thumbnail: None };
+        }
+
+        let header = match savestate::read_header(path) {
+            Ok(header) => header,
+            Err(err) => {
+                log::error!("Error reading save state header from '{}': {err}", path.display());
+                return Self { modified, description: "Invalid".into(), thumbnail: None };
+            }
+        };
+
+        let description = format!(
+            "{}\n{} (v{})",
+            humantime::format_rfc3339_seconds(header.system_time()),
+            header.game_id,
+            header.emulator_version
+        );
+
+        let thumbnail = &header.thumbnail;
+        let thumbnail = (thumbnail.width != 0 && thumbnail.height != 0).then(|| {
+            let image = ColorImage::from_rgba_unmultiplied(
+                [thumbnail.width as usize, thumbnail.height as usize],
+                &thumbnail.rgba,
+            );
+            ctx.load_texture(path.to_string_lossy(), image, TextureOptions::LINEAR)
+        });
+
+        Self { modified, description, thumbnail }
+    }
+}
+
 struct AppState {
     video_window_open: bool,
     graphics_window_open: bool,
     audio_window_open: bool,
     input_window_open: bool,
+    rebinding: Option<BindingTarget>,
+    memory_cards_window_open: bool,
+    link_cable_window_open: bool,
     paths_window_open: bool,
     debug_window_open: bool,
+    save_states_window_open: bool,
+    memory_card_manager_open: bool,
+    memory_card_manager: MemoryCardManager,
+    // Empty until a game or the BIOS has been launched
+    save_state_paths: Vec<PathBuf>,
+    save_state_slots: Vec<SaveStateSlot>,
     audio_sync_threshold: NumericText,
     audio_device_queue_size: NumericText,
     internal_audio_buffer_size: NumericText,
@@ -62,6 +122,7 @@ struct AppState {
     filter_by_title: String,
     filter_by_title_lower: String,
     last_filter_by_title: String,
+    dualshock_game_id: String,
 }
 
 impl AppState {
@@ -78,8 +139,16 @@ impl AppState {
             graphics_window_open: false,
             audio_window_open: false,
             input_window_open: false,
+            rebinding: None,
+            memory_cards_window_open: false,
+            link_cable_window_open: false,
             paths_window_open: false,
             debug_window_open: false,
+            save_states_window_open: false,
+            memory_card_manager_open: false,
+            memory_card_manager: MemoryCardManager::default(),
+            save_state_paths: Vec::new(),
+            save_state_slots: Vec::new(),
             audio_sync_threshold: NumericText::new(config.audio.sync_threshold),
             audio_device_queue_size: NumericText::new(config.audio.device_queue_size),
             internal_audio_buffer_size: NumericText::new(config.audio.internal_buffer_size),
@@ -88,6 +157,7 @@ impl AppState {
             filter_by_title: String::new(),
             filter_by_title_lower: String::new(),
             last_filter_by_title: String::new(),
+            dualshock_game_id: String::new(),
         }
     }
 }
@@ -123,10 +193,35 @@ impl App {
             UserEvent::FileOpened(OpenFileType::SearchDir, Some(path)) => {
                 self.config.paths.search.push(path.clone());
             }
+            UserEvent::FileOpened(OpenFileType::Open, Some(path)) => {
+                self.set_save_state_paths(Some(path));
+            }
+            UserEvent::RunBios => {
+                self.set_save_state_paths(None);
+            }
+            UserEvent::GamepadInputPressed(source) => {
+                self.capture_binding(*source);
+            }
+            UserEvent::FileOpened(
+                file_type @ (OpenFileType::ImportSave
+                | OpenFileType::ExportSave
+                | OpenFileType::ImportCard
+                | OpenFileType::ExportCard),
+                Some(path),
+            ) => {
+                self.state.memory_card_manager.handle_file_opened(*file_type, path);
+            }
             _ => {}
         }
     }
 
+    fn set_save_state_paths(&mut self, file_path: Option<&Path>) {
+        self.state.save_state_paths = (0..SAVE_STATE_SLOTS)
+            .filter_map(|slot| savestate::slot_path(file_path, slot).ok())
+            .collect();
+        self.state.save_state_slots.clear();
+    }
+
     #[allow(clippy::missing_panics_doc)]
     pub fn render(&mut self, ctx: &Context, proxy: &EventLoopProxy<UserEvent>) {
         self.render_menu(ctx, proxy);
@@ -148,6 +243,14 @@ impl App {
             self.render_input_window(ctx);
         }
 
+        if self.state.memory_cards_window_open {
+            self.render_memory_cards_window(ctx);
+        }
+
+        if self.state.link_cable_window_open {
+            self.render_link_cable_window(ctx);
+        }
+
         if self.state.paths_window_open {
             self.render_paths_window(ctx, proxy);
         }
@@ -156,6 +259,18 @@ impl App {
             self.render_debug_window(ctx);
         }
 
+        if self.state.save_states_window_open {
+            self.render_save_states_window(ctx, proxy);
+        }
+
+        if self.state.memory_card_manager_open {
+            self.state.memory_card_manager.render(
+                ctx,
+                &mut self.state.memory_card_manager_open,
+                proxy,
+            );
+        }
+
         if self.config != self.state.last_serialized_config {
             if let Err(err) = self.serialize_config() {
                 log::error!(
@@ -227,6 +342,28 @@ impl App {
                     }
                 });
 
+                ui.menu_button("Emulation", |ui| {
+                    if ui.button("Save States").clicked() {
+                        self.state.save_states_window_open = true;
+                        ui.close_menu();
+                    }
+
+                    if ui
+                        .button("Next Disc")
+                        .on_hover_text("Switch discs in an M3U playlist")
+                        .clicked()
+                    {
+                        proxy.send_event(UserEvent::NextDisc).unwrap();
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Memory Card Manager").clicked() {
+                        self.state.memory_card_manager.refresh_card_list();
+                        self.state.memory_card_manager_open = true;
+                        ui.close_menu();
+                    }
+                });
+
                 ui.menu_button("Settings", |ui| {
                     if ui.button("Video").clicked() {
                         self.state.video_window_open = true;
@@ -248,6 +385,16 @@ impl App {
                         ui.close_menu();
                     }
 
+                    if ui.button("Memory Cards").clicked() {
+                        self.state.memory_cards_window_open = true;
+                        ui.close_menu();
+                    }
+
+                    if ui.button("Link Cable").clicked() {
+                        self.state.link_cable_window_open = true;
+                        ui.close_menu();
+                    }
+
                     if ui.button("Paths").clicked() {
                         self.state.paths_window_open = true;
                         ui.close_menu();
@@ -331,6 +478,22 @@ impl App {
                     "Crop vertical overscan",
                 )
                 .on_hover_text("Crop vertical display to 224px NTSC / 268px PAL");
+
+                ui.group(|ui| {
+                    ui.label("Console region");
+
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.config.video.console_region, None, "Auto")
+                            .on_hover_text("Detect from the disc, falling back to the BIOS");
+                        for region in Region::ALL {
+                            ui.radio_value(
+                                &mut self.config.video.console_region,
+                                Some(region),
+                                region.to_string(),
+                            );
+                        }
+                    });
+                });
             });
     }
 
@@ -531,28 +694,367 @@ impl App {
             .open(&mut self.state.input_window_open)
             .resizable(false)
             .show(ctx, |ui| {
+                let input = &mut self.config.input;
+                let multitaps = input.multitap.iter_mut().zip(&mut input.multitap_devices);
+                for (i, (device, (multitap, slot_devices))) in
+                    [&mut input.p1_device, &mut input.p2_device].into_iter().zip(multitaps).enumerate()
+                {
+                    ui.group(|ui| {
+                        ui.label(format!("P{} device", i + 1));
+
+                        ui.horizontal_wrapped(|ui| {
+                            ui.radio_value(device, ControllerType::None, "None");
+                            ui.radio_value(device, ControllerType::Digital, "Digital controller");
+                            ui.radio_value(device, ControllerType::DualShock, "DualShock");
+                            ui.radio_value(device, ControllerType::Mouse, "Mouse");
+                            ui.radio_value(device, ControllerType::GunCon, "GunCon");
+                            ui.radio_value(device, ControllerType::Justifier, "Justifier");
+                            ui.radio_value(device, ControllerType::NeGcon, "NeGcon");
+                            ui.radio_value(
+                                device,
+                                ControllerType::AnalogJoystick,
+                                "Analog joystick",
+                            );
+                        });
+
+                        ui.checkbox(multitap, "Multitap")
+                            .on_hover_text("The device above is connected to multitap slot A");
+                        if *multitap {
+                            for (slot, device) in ['B', 'C', 'D'].into_iter().zip(slot_devices) {
+                                ui.horizontal_wrapped(|ui| {
+                                    ui.label(format!("Slot {slot}:"));
+                                    ui.radio_value(device, ControllerType::None, "None");
+                                    ui.radio_value(device, ControllerType::Digital, "Digital");
+                                    ui.radio_value(device, ControllerType::DualShock, "DualShock");
+                                    ui.radio_value(device, ControllerType::NeGcon, "NeGcon");
+                                    ui.radio_value(
+                                        device,
+                                        ControllerType::AnalogJoystick,
+                                        "Analog joystick",
+                                    );
+                                });
+                            }
+                        }
+                    });
+                }
+
                 ui.group(|ui| {
-                    ui.label("P1 device");
+                    ui.label("Input devices");
 
+                    Grid::new("input_ports_grid").show(ui, |ui| {
+                        let multitap = self.config.input.multitap;
+                        let [primary, secondary] = &mut self.config.input.keyboard_ports;
+                        input_port_row(ui, "Keyboard mapping 1", primary, multitap);
+                        input_port_row(ui, "Keyboard mapping 2", secondary, multitap);
+
+                        for (i, port) in self.config.input.gamepad_ports.iter_mut().enumerate() {
+                            input_port_row(ui, &format!("Gamepad {}", i + 1), port, multitap);
+                        }
+                    });
+
+                    ui.label("Gamepads are numbered in the order they were connected");
+                });
+
+                ui.checkbox(&mut self.config.input.rumble_enabled, "Rumble enabled")
+                    .on_hover_text("Forward DualShock vibration to connected gamepads");
+
+                ui.add_enabled_ui(self.config.input.mouse_connected(), |ui| {
                     ui.horizontal(|ui| {
-                        ui.radio_value(
-                            &mut self.config.input.p1_device,
-                            ControllerType::None,
-                            "None",
+                        ui.label("Mouse sensitivity:");
+                        ui.add(
+                            Slider::new(&mut self.config.input.mouse_sensitivity, 10..=400)
+                                .suffix("%"),
                         );
-                        ui.radio_value(
-                            &mut self.config.input.p1_device,
-                            ControllerType::Digital,
-                            "Digital controller",
-                        );
-                        ui.radio_value(
-                            &mut self.config.input.p1_device,
-                            ControllerType::DualShock,
-                            "DualShock",
+                    });
+                    ui.label("Click in the emulator window to capture the mouse pointer");
+                });
+
+                CollapsingHeader::new("Analog sticks").show(ui, |ui| {
+                    let ports = ["P1", "P2"].into_iter().zip(&mut self.config.input.analog);
+                    for (label, analog) in ports {
+                        ui.group(|ui| {
+                            ui.label(label);
+                            analog_settings(ui, analog);
+                        });
+                    }
+
+                    ui.label(
+                        "Settings apply to every controller in the port, including multitap slots",
+                    );
+                });
+
+                CollapsingHeader::new("DualShock analog mode").show(ui, |ui| {
+                    ui.group(|ui| {
+                        ui.label("All games");
+                        dualshock_settings(ui, &mut self.config.input.dualshock);
+                    });
+
+                    let per_game = &mut self.config.input.dualshock_per_game;
+                    let mut removed = None;
+                    for (game_id, dualshock) in per_game.iter_mut() {
+                        ui.group(|ui| {
+                            ui.horizontal(|ui| {
+                                ui.label(game_id.as_str());
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(game_id.clone());
+                                }
+                            });
+                            dualshock_settings(ui, dualshock);
+                        });
+                    }
+                    if let Some(game_id) = removed {
+                        per_game.remove(&game_id);
+                    }
+
+                    ui.horizontal(|ui| {
+                        ui.add(
+                            TextEdit::singleline(&mut self.state.dualshock_game_id)
+                                .hint_text("SCUS-94423")
+                                .desired_width(100.0),
                         );
+                        let game_id = self.state.dualshock_game_id.trim().to_uppercase();
+                        if ui
+                            .add_enabled(!game_id.is_empty(), Button::new("Add game"))
+                            .on_hover_text("Override the settings above for one game by its serial")
+                            .clicked()
+                        {
+                            per_game.entry(game_id).or_insert(self.config.input.dualshock);
+                            self.state.dualshock_game_id.clear();
+                        }
                     });
+
+                    ui.label(
+                        "Starting in analog mode takes effect the next time a game is launched",
+                    );
+                });
+
+                if self.config.input.light_gun_connected() {
+                    ui.label(
+                        "Light guns aim at the host mouse pointer; left click to fire, right and \
+                         middle click for the A/B buttons. Aim outside of the game screen to reload.",
+                    );
+                }
+
+                let bindings = &mut self.config.input.bindings;
+                let rebinding = &mut self.state.rebinding;
+
+                CollapsingHeader::new("Keyboard bindings").show(ui, |ui| {
+                    Grid::new("keyboard_bindings_grid").show(ui, |ui| {
+                        ui.label("");
+                        ui.label("Mapping 1");
+                        ui.label("Mapping 2");
+                        ui.end_row();
+
+                        for button in Ps1Button::ALL {
+                            ui.label(button.label());
+                            for (mapping, bindings) in bindings.keyboard.iter_mut().enumerate() {
+                                binding_button(
+                                    ui,
+                                    bindings.entry(button).or_insert(InputSource::None),
+                                    BindingTarget::Keyboard(mapping, button),
+                                    rebinding,
+                                );
+                            }
+                            ui.end_row();
+                        }
+
+                        for input in Ps1AnalogInput::ALL {
+                            for &direction in analog_key_directions(input) {
+                                ui.label(analog_key_label(input, direction));
+                                for (mapping, bindings) in
+                                    bindings.keyboard_analog.iter_mut().enumerate()
+                                {
+                                    binding_button(
+                                        ui,
+                                        bindings.entry(input).or_default().get_mut(direction),
+                                        BindingTarget::KeyboardAnalog(mapping, input, direction),
+                                        rebinding,
+                                    );
+                                }
+                                ui.end_row();
+                            }
+                        }
+                    });
+
+                    ui.label(
+                        "Keys bound to analog inputs push them fully while held; + is right for X \
+                         axes and down for Y axes",
+                    );
+                });
+
+                CollapsingHeader::new("Gamepad bindings").show(ui, |ui| {
+                    Grid::new("gamepad_bindings_grid").show(ui, |ui| {
+                        for button in Ps1Button::ALL {
+                            ui.label(button.label());
+                            binding_button(
+                                ui,
+                                bindings.gamepad_buttons.entry(button).or_insert(InputSource::None),
+                                BindingTarget::GamepadButton(button),
+                                rebinding,
+                            );
+                            ui.end_row();
+                        }
+
+                        for input in Ps1AnalogInput::ALL {
+                            ui.label(input.label());
+                            binding_button(
+                                ui,
+                                bindings.gamepad_analog.entry(input).or_insert(InputSource::None),
+                                BindingTarget::GamepadAnalog(input),
+                                rebinding,
+                            );
+                            ui.end_row();
+                        }
+                    });
+
+                    ui.label("Bind sticks by pushing right for X axes and down for Y axes");
+                    ui.label(
+                        "NeGcon I, II, and L are also pressed fully by the X, Square, and L1 \
+                         bindings",
+                    );
+                });
+
+                CollapsingHeader::new("Hotkeys").show(ui, |ui| {
+                    Grid::new("hotkey_bindings_grid").show(ui, |ui| {
+                        for hotkey in Hotkey::ALL {
+                            ui.label(hotkey.label());
+                            binding_button(
+                                ui,
+                                bindings.hotkeys.entry(hotkey).or_insert(InputSource::None),
+                                BindingTarget::Hotkey(hotkey),
+                                rebinding,
+                            );
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Reset bindings to defaults").clicked() {
+                        *bindings = InputBindings::default();
+                        *rebinding = None;
+                    }
+
+                    ui.label("Right-click a binding to clear it");
                 });
             });
+
+        if !self.state.input_window_open {
+            self.state.rebinding = None;
+        }
+    }
+
+    /// Bind the input that is waiting to be rebound, if any, to the given key. Returns whether the
+    /// key was captured, in which case it should not be handled as normal GUI input.
+    pub fn capture_binding_key(&mut self, key: KeyCode) -> bool {
+        self.capture_binding(InputSource::Key(key))
+    }
+
+    fn capture_binding(&mut self, source: InputSource) -> bool {
+        let Some(target) = self.state.rebinding else { return false };
+        if !target.accepts(source) {
+            return false;
+        }
+
+        let bindings = &mut self.config.input.bindings;
+        let binding = match target {
+            BindingTarget::Keyboard(mapping, button) => {
+                bindings.keyboard[mapping].entry(button).or_insert(InputSource::None)
+            }
+            BindingTarget::KeyboardAnalog(mapping, input, direction) => {
+                bindings.keyboard_analog[mapping].entry(input).or_default().get_mut(direction)
+            }
+            BindingTarget::GamepadButton(button) => {
+                bindings.gamepad_buttons.entry(button).or_insert(InputSource::None)
+            }
+            BindingTarget::GamepadAnalog(input) => {
+                bindings.gamepad_analog.entry(input).or_insert(InputSource::None)
+            }
+            BindingTarget::Hotkey(hotkey) => {
+                bindings.hotkeys.entry(hotkey).or_insert(InputSource::None)
+            }
+        };
+        *binding = source;
+        self.state.rebinding = None;
+
+        true
+    }
+
+    fn render_memory_cards_window(&mut self, ctx: &Context) {
+        Window::new("Memory Card Settings")
+            .open(&mut self.state.memory_cards_window_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                for (label, mode) in [
+                    ("Slot 1", &mut self.config.memory_cards.slot_1),
+                    ("Slot 2", &mut self.config.memory_cards.slot_2),
+                ] {
+                    ui.group(|ui| {
+                        ui.label(label);
+
+                        ui.horizontal(|ui| {
+                            ui.radio_value(mode, MemoryCardMode::PerGame, "Per-game card")
+                                .on_hover_text("Each game gets its own memory card");
+                            ui.radio_value(mode, MemoryCardMode::Shared, "Shared card")
+                                .on_hover_text("All games share the same memory card");
+                            ui.radio_value(mode, MemoryCardMode::None, "No card");
+                        });
+                    });
+                }
+
+                let multitap_slots = self.config.memory_cards.multitap_slots.iter_mut();
+                for (port, (modes, connected)) in
+                    multitap_slots.zip(self.config.input.multitap).enumerate()
+                {
+                    if !connected {
+                        continue;
+                    }
+
+                    for (slot, mode) in ["B", "C", "D"].into_iter().zip(modes) {
+                        ui.group(|ui| {
+                            ui.label(format!("Port {} multitap slot {slot}", port + 1));
+
+                            ui.horizontal(|ui| {
+                                ui.radio_value(mode, MemoryCardMode::PerGame, "Per-game card");
+                                ui.radio_value(mode, MemoryCardMode::Shared, "Shared card");
+                                ui.radio_value(mode, MemoryCardMode::None, "No card");
+                            });
+                        });
+                    }
+                }
+
+                ui.label("Changes take effect the next time a game is launched");
+            });
+    }
+
+    fn render_link_cable_window(&mut self, ctx: &Context) {
+        Window::new("Link Cable Settings")
+            .open(&mut self.state.link_cable_window_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let link_cable = &mut self.config.link_cable;
+
+                ui.group(|ui| {
+                    ui.label("Mode");
+
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut link_cable.mode, LinkCableMode::Disabled, "Disabled");
+                        ui.radio_value(&mut link_cable.mode, LinkCableMode::Listen, "Listen")
+                            .on_hover_text("Wait for another instance to connect");
+                        ui.radio_value(&mut link_cable.mode, LinkCableMode::Connect, "Connect")
+                            .on_hover_text("Connect to another instance that is listening");
+                    });
+                });
+
+                ui.horizontal(|ui| {
+                    ui.add(TextEdit::singleline(&mut link_cable.address).desired_width(200.0));
+                    ui.label("Address").on_hover_text(
+                        "host:port for TCP, or unix:<path> for a Unix domain socket",
+                    );
+                });
+
+                ui.label("Changes take effect the next time a game is launched");
+            });
     }
 
     fn render_paths_window(&mut self, ctx: &Context, proxy: &EventLoopProxy<UserEvent>) {
@@ -629,6 +1131,69 @@ impl App {
             });
     }
 
+    fn render_save_states_window(&mut self, ctx: &Context, proxy: &EventLoopProxy<UserEvent>) {
+        const COLUMNS: usize = 5;
+        const THUMBNAIL_SIZE: Vec2 = Vec2::new(160.0, 120.0);
+
+        Window::new("Save States")
+            .open(&mut self.state.save_states_window_open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                if self.state.save_state_paths.is_empty() {
+                    ui.label("No game running");
+                    return;
+                }
+
+                let paths = &self.state.save_state_paths;
+                self.state.save_state_slots.resize_with(paths.len(), SaveStateSlot::default);
+
+                Grid::new("save_state_slots").spacing(Vec2::new(10.0, 10.0)).show(ui, |ui| {
+                    for (i, (slot, path)) in
+                        self.state.save_state_slots.iter_mut().zip(paths).enumerate()
+                    {
+                        // Reload the header whenever the slot file changes on disk
+                        let modified =
+                            fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+                        if modified != slot.modified || slot.description.is_empty() {
+                            *slot = SaveStateSlot::load(ctx, path, modified);
+                        }
+
+                        ui.vertical(|ui| {
+                            ui.label(format!("Slot {}", i + 1));
+
+                            match &slot.thumbnail {
+                                Some(texture) => {
+                                    ui.image((texture.id(), THUMBNAIL_SIZE));
+                                }
+                                None => {
+                                    ui.allocate_space(THUMBNAIL_SIZE);
+                                }
+                            }
+
+                            ui.small(&slot.description);
+
+                            ui.horizontal(|ui| {
+                                if ui.button("Save").clicked() {
+                                    proxy.send_event(UserEvent::SaveState { slot: i }).unwrap();
+                                }
+
+                                if ui
+                                    .add_enabled(slot.modified.is_some(), Button::new("Load"))
+                                    .clicked()
+                                {
+                                    proxy.send_event(UserEvent::LoadState { slot: i }).unwrap();
+                                }
+                            });
+                        });
+
+                        if i % COLUMNS == COLUMNS - 1 {
+                            ui.end_row();
+                        }
+                    }
+                });
+            });
+    }
+
     fn render_central_panel(&mut self, ctx: &Context, proxy: &EventLoopProxy<UserEvent>) {
         CentralPanel::default().show(ctx, |ui| {
             let bios_path_configured = self.config.paths.bios.is_some();
@@ -673,6 +1238,10 @@ impl App {
                 ui.checkbox(&mut self.config.filters.exe, "EXE");
                 ui.checkbox(&mut self.config.filters.cue, "CUE");
                 ui.checkbox(&mut self.config.filters.chd, "CHD");
+                ui.checkbox(&mut self.config.filters.iso, "ISO");
+                ui.checkbox(&mut self.config.filters.ecm, "ECM");
+                ui.checkbox(&mut self.config.filters.pbp, "PBP");
+                ui.checkbox(&mut self.config.filters.m3u, "M3U");
             });
 
             ui.add_space(15.0);
@@ -765,6 +1334,10 @@ enum FileExtension {
     Exe,
     Cue,
     Chd,
+    Iso,
+    Ecm,
+    Pbp,
+    M3u,
 }
 
 impl FileExtension {
@@ -773,6 +1346,10 @@ impl FileExtension {
             Self::Exe => "EXE",
             Self::Cue => "CUE",
             Self::Chd => "CHD",
+            Self::Iso => "ISO",
+            Self::Ecm => "ECM",
+            Self::Pbp => "PBP",
+            Self::M3u => "M3U",
         }
     }
 }
@@ -800,6 +1377,10 @@ fn do_file_search(
         (metadata.extension == FileExtension::Exe && file_filters.exe)
             || (metadata.extension == FileExtension::Cue && file_filters.cue)
             || (metadata.extension == FileExtension::Chd && file_filters.chd)
+            || (metadata.extension == FileExtension::Iso && file_filters.iso)
+            || (metadata.extension == FileExtension::Ecm && file_filters.ecm)
+            || (metadata.extension == FileExtension::Pbp && file_filters.pbp)
+            || (metadata.extension == FileExtension::M3u && file_filters.m3u)
     });
 
     files.sort_by(|a, b| a.file_name_no_ext.cmp(&b.file_name_no_ext));
@@ -840,7 +1421,8 @@ fn do_file_search_inner(
 
             let Some(extension) = entry_path.extension().and_then(OsStr::to_str) else { continue };
             let ext_lower = extension.to_lowercase();
-            if matches!(ext_lower.as_str(), "exe" | "cue" | "chd") {
+            // BIN files are not listed because they are usually referenced by a CUE file
+            if matches!(ext_lower.as_str(), "exe" | "cue" | "chd" | "iso" | "ecm" | "pbp" | "m3u") {
                 // TODO check that EXE is a PS1 executable
                 out.push(FileMetadata {
                     file_name_no_ext: file_name_no_ext.into(),
@@ -848,6 +1430,10 @@ fn do_file_search_inner(
                         "exe" => FileExtension::Exe,
                         "cue" => FileExtension::Cue,
                         "chd" => FileExtension::Chd,
+                        "iso" => FileExtension::Iso,
+                        "ecm" => FileExtension::Ecm,
+                        "pbp" => FileExtension::Pbp,
+                        "m3u" => FileExtension::M3u,
                         _ => unreachable!("nested match expressions"),
                     },
                     full_path: entry_path,
@@ -856,3 +1442,126 @@ fn do_file_search_inner(
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BindingTarget {
+    Keyboard(usize, Ps1Button),
+    KeyboardAnalog(usize, Ps1AnalogInput, AxisDirection),
+    GamepadButton(Ps1Button),
+    GamepadAnalog(Ps1AnalogInput),
+    Hotkey(Hotkey),
+}
+
+impl BindingTarget {
+    fn accepts(self, source: InputSource) -> bool {
+        match self {
+            Self::Keyboard(..) | Self::KeyboardAnalog(..) => source.is_key(),
+            Self::GamepadButton(_) => source.is_gamepad(),
+            Self::GamepadAnalog(_) => matches!(source, InputSource::GamepadAxis(..)),
+            Self::Hotkey(_) => true,
+        }
+    }
+
+    fn prompt(self) -> &'static str {
+        match self {
+            Self::Keyboard(..) | Self::KeyboardAnalog(..) => "Press a key...",
+            Self::GamepadButton(_) => "Press a button...",
+            Self::GamepadAnalog(_) => "Move a stick...",
+            Self::Hotkey(_) => "Press a key or button...",
+        }
+    }
+}
+
+// Pressure-sensitive NeGcon buttons only use the positive half of the axis
+fn analog_key_directions(input: Ps1AnalogInput) -> &'static [AxisDirection] {
+    match input {
+        Ps1AnalogInput::NeGconI | Ps1AnalogInput::NeGconII | Ps1AnalogInput::NeGconL => {
+            &[AxisDirection::Positive]
+        }
+        _ => &[AxisDirection::Negative, AxisDirection::Positive],
+    }
+}
+
+fn analog_key_label(input: Ps1AnalogInput, direction: AxisDirection) -> String {
+    match (input, direction) {
+        (Ps1AnalogInput::NeGconI | Ps1AnalogInput::NeGconII | Ps1AnalogInput::NeGconL, _) => {
+            input.label().into()
+        }
+        (_, AxisDirection::Negative) => format!("{}-", input.label()),
+        (_, AxisDirection::Positive) => format!("{}+", input.label()),
+    }
+}
+
+fn binding_button(
+    ui: &mut Ui,
+    binding: &mut InputSource,
+    target: BindingTarget,
+    rebinding: &mut Option<BindingTarget>,
+) {
+    let waiting = *rebinding == Some(target);
+    let text = if waiting { target.prompt().into() } else { binding.label() };
+
+    let response = ui
+        .add(Button::new(text).selected(waiting).min_size(Vec2::new(120.0, 0.0)))
+        .on_hover_text("Click to rebind, right-click to clear");
+    if response.clicked() {
+        *rebinding = if waiting { None } else { Some(target) };
+    } else if response.secondary_clicked() {
+        *binding = InputSource::None;
+        *rebinding = None;
+    }
+}
+
+fn analog_settings(ui: &mut Ui, analog: &mut AnalogConfig) {
+    ui.horizontal(|ui| {
+        ui.label("Deadzone:");
+        ui.add(Slider::new(&mut analog.deadzone, 0..=50).suffix("%"));
+        ui.radio_value(&mut analog.deadzone_shape, DeadzoneShape::Radial, "Radial")
+            .on_hover_text("Deadzone applies to the stick's distance from center");
+        ui.radio_value(&mut analog.deadzone_shape, DeadzoneShape::Axial, "Axial")
+            .on_hover_text("Deadzone applies to each axis separately");
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Sensitivity:");
+        ui.add(Slider::new(&mut analog.sensitivity, 50..=200).suffix("%"));
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Invert:");
+        ui.checkbox(&mut analog.invert_left_x, "Left X");
+        ui.checkbox(&mut analog.invert_left_y, "Left Y");
+        ui.checkbox(&mut analog.invert_right_x, "Right X");
+        ui.checkbox(&mut analog.invert_right_y, "Right Y");
+    });
+
+    ui.checkbox(&mut analog.left_stick_to_dpad, "Left stick presses D-pad");
+    ui.checkbox(&mut analog.dpad_to_left_stick, "D-pad moves left stick")
+        .on_hover_text("Allows controlling the left stick with keys or gamepad buttons");
+}
+
+fn dualshock_settings(ui: &mut Ui, dualshock: &mut DualShockConfig) {
+    ui.checkbox(&mut dualshock.start_in_analog, "Start in analog mode")
+        .on_hover_text("For games that need analog mode but never turn it on, e.g. Ape Escape");
+    ui.checkbox(&mut dualshock.ignore_mode_lock, "Ignore mode lock")
+        .on_hover_text("The Analog button always works, even if the game tries to lock it");
+    ui.checkbox(&mut dualshock.force_digital, "Force digital mode")
+        .on_hover_text("Ignore the Analog button and game requests to switch to analog mode");
+}
+
+fn input_port_row(ui: &mut Ui, label: &str, port: &mut InputPort, multitap: [bool; 2]) {
+    ui.label(label);
+    ui.horizontal_wrapped(|ui| {
+        let ports = [InputPort::P1, InputPort::P2].into_iter().zip(InputPort::MULTITAP);
+        for ((main_port, slots), connected) in ports.zip(multitap) {
+            ui.radio_value(port, main_port, main_port.label());
+            if connected {
+                for slot in slots {
+                    ui.radio_value(port, slot, slot.label());
+                }
+            }
+        }
+        ui.radio_value(port, InputPort::None, InputPort::None.label());
+    });
+    ui.end_row();
+}
@@ -1,11 +1,15 @@
+use crate::config::bindings::InputBindings;
 use cfg_if::cfg_if;
 use ps1_core::RasterizerType;
-use ps1_core::api::{DisplayConfig, PgxpConfig, Ps1EmulatorConfig};
-use ps1_core::input::ControllerType;
+use ps1_core::api::{DisplayConfig, PgxpConfig, Ps1EmulatorConfig, Region};
+use ps1_core::input::{ControllerType, DualShockConfig};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::num::NonZeroU32;
 use std::path::PathBuf;
 
+pub mod bindings;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum VSyncMode {
     #[default]
@@ -93,6 +97,9 @@ pub struct VideoConfig {
     pub window_width: u32,
     #[serde(default = "default_window_height")]
     pub window_height: u32,
+    /// `None` to detect the region from the disc and BIOS
+    #[serde(default)]
+    pub console_region: Option<Region>,
 }
 
 fn true_fn() -> bool {
@@ -200,12 +207,185 @@ impl Default for AudioConfig {
     }
 }
 
+/// Controller port that a host input device is routed to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputPort {
+    P1,
+    P2,
+    /// Device is ignored
+    None,
+    /// Multitap slots B-D; `P1` and `P2` are slot A when a multitap is connected
+    P1B,
+    P1C,
+    P1D,
+    P2B,
+    P2C,
+    P2D,
+}
+
+impl InputPort {
+    pub const MULTITAP: [[Self; 3]; 2] =
+        [[Self::P1B, Self::P1C, Self::P1D], [Self::P2B, Self::P2C, Self::P2D]];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::P1 => "P1",
+            Self::P2 => "P2",
+            Self::None => "Disabled",
+            Self::P1B => "P1-B",
+            Self::P1C => "P1-C",
+            Self::P1D => "P1-D",
+            Self::P2B => "P2-B",
+            Self::P2C => "P2-C",
+            Self::P2D => "P2-D",
+        }
+    }
+
+    /// Port index and multitap slot (0 = slot A), or `None` if the device is ignored
+    #[must_use]
+    pub fn port_and_slot(self) -> Option<(usize, usize)> {
+        match self {
+            Self::P1 => Some((0, 0)),
+            Self::P1B => Some((0, 1)),
+            Self::P1C => Some((0, 2)),
+            Self::P1D => Some((0, 3)),
+            Self::P2 => Some((1, 0)),
+            Self::P2B => Some((1, 1)),
+            Self::P2C => Some((1, 2)),
+            Self::P2D => Some((1, 3)),
+            Self::None => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DeadzoneShape {
+    /// Deadzone applies to the stick's distance from center, which preserves its direction
+    #[default]
+    Radial,
+    /// Deadzone applies to each axis separately
+    Axial,
+}
+
+/// Analog stick processing for the controllers in one port, including any multitap slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalogConfig {
+    #[serde(default)]
+    pub deadzone_shape: DeadzoneShape,
+    /// Percentage of stick travel around the center that is ignored
+    #[serde(default)]
+    pub deadzone: u32,
+    /// Percentage applied to stick travel outside of the deadzone; above 100, the stick reaches
+    /// full deflection before the host stick does
+    #[serde(default = "default_analog_sensitivity")]
+    pub sensitivity: u32,
+    #[serde(default)]
+    pub invert_left_x: bool,
+    #[serde(default)]
+    pub invert_left_y: bool,
+    #[serde(default)]
+    pub invert_right_x: bool,
+    #[serde(default)]
+    pub invert_right_y: bool,
+    /// Left stick also presses the D-pad when pushed at least halfway
+    #[serde(default)]
+    pub left_stick_to_dpad: bool,
+    /// D-pad also pushes the left stick to full deflection, which allows driving it from keys
+    #[serde(default)]
+    pub dpad_to_left_stick: bool,
+}
+
+fn default_analog_sensitivity() -> u32 {
+    100
+}
+
+impl Default for AnalogConfig {
+    fn default() -> Self {
+        toml::from_str("").unwrap()
+    }
+}
+
+/// Number of gamepads that can be assigned to a port; gamepads are numbered in the order they were
+/// connected
+pub const GAMEPAD_SLOTS: usize = 4;
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct InputConfig {
     #[serde(default = "default_p1_input_device")]
     pub p1_device: ControllerType,
     #[serde(default = "default_p2_input_device")]
     pub p2_device: ControllerType,
+    /// Whether a multitap is connected to each port
+    #[serde(default)]
+    pub multitap: [bool; 2],
+    /// Devices in multitap slots B-D of each port
+    #[serde(default = "default_multitap_devices")]
+    pub multitap_devices: [[ControllerType; 3]; 2],
+    /// Ports for the primary and secondary keyboard mappings
+    #[serde(default = "default_keyboard_ports")]
+    pub keyboard_ports: [InputPort; 2],
+    #[serde(default = "default_gamepad_ports")]
+    pub gamepad_ports: [InputPort; GAMEPAD_SLOTS],
+    #[serde(default = "true_fn")]
+    pub rumble_enabled: bool,
+    /// Percentage applied to host mouse motion before it is sent to an emulated mouse
+    #[serde(default = "default_mouse_sensitivity")]
+    pub mouse_sensitivity: u32,
+    /// Indexed by port
+    #[serde(default)]
+    pub analog: [AnalogConfig; 2],
+    /// `DualShock` mode options for games without an entry in `dualshock_per_game`
+    #[serde(default)]
+    pub dualshock: DualShockConfig,
+    /// `DualShock` mode options by game ID (the disc's serial number, e.g. `SCUS-94423`)
+    #[serde(default)]
+    pub dualshock_per_game: BTreeMap<String, DualShockConfig>,
+    #[serde(default)]
+    pub bindings: InputBindings,
+}
+
+impl InputConfig {
+    #[must_use]
+    pub fn mouse_connected(&self) -> bool {
+        self.connected_devices().any(|device| device == ControllerType::Mouse)
+    }
+
+    #[must_use]
+    pub fn light_gun_connected(&self) -> bool {
+        self.connected_devices().any(ControllerType::is_light_gun)
+    }
+
+    // Devices in both ports, including multitap slots B-D of ports with a multitap connected
+    fn connected_devices(&self) -> impl Iterator<Item = ControllerType> + '_ {
+        let multitap_devices = self
+            .multitap_devices
+            .iter()
+            .zip(self.multitap)
+            .filter(|&(_, connected)| connected)
+            .flat_map(|(devices, _)| devices.iter().copied());
+        [self.p1_device, self.p2_device].into_iter().chain(multitap_devices)
+    }
+
+    #[must_use]
+    pub fn dualshock_config(&self, game_id: Option<&str>) -> DualShockConfig {
+        game_id
+            .and_then(|game_id| self.dualshock_per_game.get(game_id))
+            .copied()
+            .unwrap_or(self.dualshock)
+    }
+}
+
+fn default_mouse_sensitivity() -> u32 {
+    100
+}
+
+fn default_keyboard_ports() -> [InputPort; 2] {
+    [InputPort::P1, InputPort::P2]
+}
+
+fn default_gamepad_ports() -> [InputPort; GAMEPAD_SLOTS] {
+    [InputPort::P1, InputPort::P2, InputPort::None, InputPort::None]
 }
 
 fn default_p1_input_device() -> ControllerType {
@@ -216,12 +396,84 @@ fn default_p2_input_device() -> ControllerType {
     ControllerType::None
 }
 
+fn default_multitap_devices() -> [[ControllerType; 3]; 2] {
+    [[ControllerType::Digital; 3]; 2]
+}
+
 impl Default for InputConfig {
     fn default() -> Self {
         toml::from_str("").unwrap()
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryCardMode {
+    /// Each game gets its own card, `memcards/<game>_<slot>.mcd`
+    PerGame,
+    /// Every game uses the same card, `memcards/global_<slot>.mcd`
+    Shared,
+    /// Slot is empty
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryCardsConfig {
+    #[serde(default = "default_memory_card_1_mode")]
+    pub slot_1: MemoryCardMode,
+    #[serde(default = "default_memory_card_2_mode")]
+    pub slot_2: MemoryCardMode,
+    /// Multitap slots B-D of each port; `<slot>` in the card's file name is e.g. `1B`
+    #[serde(default = "default_multitap_memory_card_modes")]
+    pub multitap_slots: [[MemoryCardMode; 3]; 2],
+}
+
+fn default_memory_card_1_mode() -> MemoryCardMode {
+    MemoryCardMode::PerGame
+}
+
+fn default_memory_card_2_mode() -> MemoryCardMode {
+    MemoryCardMode::None
+}
+
+fn default_multitap_memory_card_modes() -> [[MemoryCardMode; 3]; 2] {
+    [[MemoryCardMode::None; 3]; 2]
+}
+
+impl Default for MemoryCardsConfig {
+    fn default() -> Self {
+        toml::from_str("").unwrap()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LinkCableMode {
+    #[default]
+    Disabled,
+    /// Wait for another instance to connect to the address
+    Listen,
+    /// Connect to another instance that is listening on the address
+    Connect,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkCableConfig {
+    #[serde(default)]
+    pub mode: LinkCableMode,
+    /// TCP `host:port`, or `unix:<path>` for a Unix domain socket
+    #[serde(default = "default_link_cable_address")]
+    pub address: String,
+}
+
+fn default_link_cable_address() -> String {
+    "127.0.0.1:7654".into()
+}
+
+impl Default for LinkCableConfig {
+    fn default() -> Self {
+        toml::from_str("").unwrap()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PathsConfig {
     pub bios: Option<PathBuf>,
@@ -245,6 +497,14 @@ pub struct FiltersConfig {
     pub cue: bool,
     #[serde(default = "true_fn")]
     pub chd: bool,
+    #[serde(default = "true_fn")]
+    pub iso: bool,
+    #[serde(default = "true_fn")]
+    pub ecm: bool,
+    #[serde(default = "true_fn")]
+    pub pbp: bool,
+    #[serde(default = "true_fn")]
+    pub m3u: bool,
 }
 
 impl Default for FiltersConfig {
@@ -278,6 +538,10 @@ pub struct AppConfig {
     #[serde(default)]
     pub input: InputConfig,
     #[serde(default)]
+    pub memory_cards: MemoryCardsConfig,
+    #[serde(default)]
+    pub link_cable: LinkCableConfig,
+    #[serde(default)]
     pub paths: PathsConfig,
     #[serde(default)]
     pub filters: FiltersConfig,
@@ -292,8 +556,9 @@ impl Default for AppConfig {
 }
 
 impl AppConfig {
+    /// `game_id` selects per-game settings, if the running game is known
     #[must_use]
-    pub fn to_emulator_config(&self) -> Ps1EmulatorConfig {
+    pub fn to_emulator_config(&self, game_id: Option<&str>) -> Ps1EmulatorConfig {
         let rasterizer_type = self.graphics.rasterizer_type();
 
         Ps1EmulatorConfig {
@@ -319,6 +584,8 @@ impl AppConfig {
             },
             internal_audio_buffer_size: self.audio.internal_buffer_size,
             tty_enabled: self.debug.tty_enabled,
+            dualshock: self.input.dualshock_config(game_id),
+            forced_region: self.video.console_region,
         }
     }
 }
@@ -331,4 +598,24 @@ mod tests {
     fn gui_config_default_does_not_panic() {
         let _ = AppConfig::default();
     }
+
+    #[test]
+    fn input_bindings_round_trip() {
+        let mut config = AppConfig::default();
+        config.input.bindings.hotkeys.insert(
+            crate::emustate::Hotkey::Pause,
+            bindings::InputSource::GamepadButton(sdl2::controller::Button::Guide),
+        );
+        config.input.bindings.keyboard_analog[1].insert(
+            crate::emuthread::Ps1AnalogInput::RightStickX,
+            bindings::KeyAxisBinding {
+                negative: bindings::InputSource::Key(winit::keyboard::KeyCode::KeyB),
+                positive: bindings::InputSource::None,
+            },
+        );
+
+        let serialized = toml::to_string(&config).unwrap();
+        let deserialized: AppConfig = toml::from_str(&serialized).unwrap();
+        assert_eq!(config.input.bindings, deserialized.input.bindings);
+    }
 }
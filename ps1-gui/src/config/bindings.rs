@@ -0,0 +1,369 @@
+//! Keyboard and gamepad bindings for controller buttons, analog sticks, and hotkeys
+
+use crate::emustate::Hotkey;
+use crate::emuthread::{Ps1AnalogInput, Ps1Button};
+use sdl2::controller::{Axis as SdlAxis, Button as SdlButton};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use winit::keyboard::KeyCode;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+impl AxisDirection {
+    #[must_use]
+    pub fn of(value: i16) -> Self {
+        if value < 0 { Self::Negative } else { Self::Positive }
+    }
+}
+
+/// A host input that can be bound to a controller input or a hotkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InputSource {
+    None,
+    Key(KeyCode),
+    GamepadButton(#[serde(with = "sdl_button")] SdlButton),
+    /// When bound to a button or hotkey, the input is pressed once the axis is at least halfway
+    /// toward the given direction. When bound to an analog input, the full axis is used and
+    /// `Negative` inverts it.
+    GamepadAxis(#[serde(with = "sdl_axis")] SdlAxis, AxisDirection),
+}
+
+impl InputSource {
+    #[must_use]
+    pub fn label(self) -> String {
+        match self {
+            Self::None => "<None>".into(),
+            Self::Key(key) => format!("{key:?}"),
+            Self::GamepadButton(button) => format!("Gamepad {}", button.string()),
+            Self::GamepadAxis(axis, AxisDirection::Positive) => {
+                format!("Gamepad {}+", axis.string())
+            }
+            Self::GamepadAxis(axis, AxisDirection::Negative) => {
+                format!("Gamepad {}-", axis.string())
+            }
+        }
+    }
+
+    #[must_use]
+    pub fn is_key(self) -> bool {
+        matches!(self, Self::Key(_))
+    }
+
+    #[must_use]
+    pub fn is_gamepad(self) -> bool {
+        matches!(self, Self::GamepadButton(_) | Self::GamepadAxis(..))
+    }
+}
+
+/// Keys that push an analog input fully toward either end of its range while held
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyAxisBinding {
+    pub negative: InputSource,
+    pub positive: InputSource,
+}
+
+impl Default for KeyAxisBinding {
+    fn default() -> Self {
+        Self { negative: InputSource::None, positive: InputSource::None }
+    }
+}
+
+impl KeyAxisBinding {
+    pub fn get_mut(&mut self, direction: AxisDirection) -> &mut InputSource {
+        match direction {
+            AxisDirection::Negative => &mut self.negative,
+            AxisDirection::Positive => &mut self.positive,
+        }
+    }
+
+    /// Returns the axis value given which keys are currently held; holding both keys or neither
+    /// centers the axis
+    #[must_use]
+    pub fn value(self, is_held: impl Fn(KeyCode) -> bool) -> i16 {
+        let held = |source| matches!(source, InputSource::Key(key) if is_held(key));
+        match (held(self.negative), held(self.positive)) {
+            (true, false) => i16::MIN,
+            (false, true) => i16::MAX,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputBindings {
+    /// Primary and secondary keyboard mappings, routed to ports by `InputConfig::keyboard_ports`
+    #[serde(default = "default_keyboard", deserialize_with = "deserialize_keyboard")]
+    pub keyboard: [BTreeMap<Ps1Button, InputSource>; 2],
+    /// Analog inputs for each keyboard mapping; unbound by default
+    #[serde(default)]
+    pub keyboard_analog: [BTreeMap<Ps1AnalogInput, KeyAxisBinding>; 2],
+    /// Shared by every gamepad, routed to ports by `InputConfig::gamepad_ports`
+    #[serde(default = "default_gamepad_buttons", deserialize_with = "deserialize_gamepad_buttons")]
+    pub gamepad_buttons: BTreeMap<Ps1Button, InputSource>,
+    #[serde(default = "default_gamepad_analog", deserialize_with = "deserialize_gamepad_analog")]
+    pub gamepad_analog: BTreeMap<Ps1AnalogInput, InputSource>,
+    #[serde(default = "default_hotkeys", deserialize_with = "deserialize_hotkeys")]
+    pub hotkeys: BTreeMap<Hotkey, InputSource>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self {
+            keyboard: default_keyboard(),
+            keyboard_analog: Default::default(),
+            gamepad_buttons: default_gamepad_buttons(),
+            gamepad_analog: default_gamepad_analog(),
+            hotkeys: default_hotkeys(),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Returns the keyboard mapping index and button for every binding to the given key
+    pub fn keyboard_buttons(&self, key: KeyCode) -> impl Iterator<Item = (usize, Ps1Button)> + '_ {
+        self.keyboard.iter().enumerate().flat_map(move |(mapping, bindings)| {
+            find_bound(bindings, InputSource::Key(key)).map(move |button| (mapping, button))
+        })
+    }
+
+    /// Returns the keyboard mapping index, analog input, and binding for every analog binding that
+    /// uses the given key in either direction
+    pub fn keyboard_analog(
+        &self,
+        key: KeyCode,
+    ) -> impl Iterator<Item = (usize, Ps1AnalogInput, KeyAxisBinding)> + '_ {
+        let source = InputSource::Key(key);
+        self.keyboard_analog.iter().enumerate().flat_map(move |(mapping, bindings)| {
+            bindings
+                .iter()
+                .filter(move |&(_, binding)| {
+                    binding.negative == source || binding.positive == source
+                })
+                .map(move |(&input, &binding)| (mapping, input, binding))
+        })
+    }
+
+    pub fn gamepad_buttons(&self, source: InputSource) -> impl Iterator<Item = Ps1Button> + '_ {
+        find_bound(&self.gamepad_buttons, source)
+    }
+
+    /// Returns every analog input bound to the given axis, along with the direction it is bound in
+    pub fn gamepad_analog(
+        &self,
+        axis: SdlAxis,
+    ) -> impl Iterator<Item = (Ps1AnalogInput, AxisDirection)> + '_ {
+        self.gamepad_analog.iter().filter_map(move |(&input, &source)| match source {
+            InputSource::GamepadAxis(bound_axis, direction) if bound_axis == axis => {
+                Some((input, direction))
+            }
+            _ => None,
+        })
+    }
+
+    pub fn hotkeys(&self, source: InputSource) -> impl Iterator<Item = Hotkey> + '_ {
+        find_bound(&self.hotkeys, source)
+    }
+}
+
+fn find_bound<K: Copy>(
+    bindings: &BTreeMap<K, InputSource>,
+    source: InputSource,
+) -> impl Iterator<Item = K> + '_ {
+    bindings.iter().filter(move |&(_, &bound)| bound == source).map(|(&key, _)| key)
+}
+
+fn default_keyboard() -> [BTreeMap<Ps1Button, InputSource>; 2] {
+    use KeyCode as K;
+    use Ps1Button as B;
+
+    let primary = [
+        (B::Up, K::ArrowUp),
+        (B::Down, K::ArrowDown),
+        (B::Left, K::ArrowLeft),
+        (B::Right, K::ArrowRight),
+        (B::Cross, K::KeyX),
+        (B::Circle, K::KeyS),
+        (B::Square, K::KeyZ),
+        (B::Triangle, K::KeyA),
+        (B::L1, K::KeyW),
+        (B::L2, K::KeyQ),
+        (B::R1, K::KeyE),
+        (B::R2, K::KeyR),
+        (B::Start, K::Enter),
+        (B::Select, K::ShiftRight),
+        (B::Analog, K::KeyY),
+    ];
+    let secondary = [
+        (B::Up, K::KeyT),
+        (B::Down, K::KeyG),
+        (B::Left, K::KeyF),
+        (B::Right, K::KeyH),
+        (B::Cross, K::KeyK),
+        (B::Circle, K::KeyL),
+        (B::Square, K::KeyJ),
+        (B::Triangle, K::KeyI),
+        (B::L1, K::KeyU),
+        (B::L2, K::Digit7),
+        (B::R1, K::KeyO),
+        (B::R2, K::Digit9),
+        (B::Start, K::Space),
+        (B::Select, K::KeyV),
+        (B::Analog, K::KeyM),
+    ];
+
+    [primary, secondary]
+        .map(|keys| keys.into_iter().map(|(button, key)| (button, InputSource::Key(key))).collect())
+}
+
+fn default_gamepad_buttons() -> BTreeMap<Ps1Button, InputSource> {
+    use Ps1Button as B;
+    use SdlButton as S;
+
+    let buttons = [
+        (B::Up, S::DPadUp),
+        (B::Down, S::DPadDown),
+        (B::Left, S::DPadLeft),
+        (B::Right, S::DPadRight),
+        (B::Cross, S::A),
+        (B::Circle, S::B),
+        (B::Square, S::X),
+        (B::Triangle, S::Y),
+        (B::L1, S::LeftShoulder),
+        (B::R1, S::RightShoulder),
+        (B::Start, S::Start),
+        (B::Select, S::Back),
+        (B::Analog, S::Guide),
+    ];
+
+    buttons
+        .into_iter()
+        .map(|(button, sdl_button)| (button, InputSource::GamepadButton(sdl_button)))
+        .chain([
+            (B::L2, InputSource::GamepadAxis(SdlAxis::TriggerLeft, AxisDirection::Positive)),
+            (B::R2, InputSource::GamepadAxis(SdlAxis::TriggerRight, AxisDirection::Positive)),
+        ])
+        .collect()
+}
+
+fn default_gamepad_analog() -> BTreeMap<Ps1AnalogInput, InputSource> {
+    [
+        (Ps1AnalogInput::LeftStickX, SdlAxis::LeftX),
+        (Ps1AnalogInput::LeftStickY, SdlAxis::LeftY),
+        (Ps1AnalogInput::RightStickX, SdlAxis::RightX),
+        (Ps1AnalogInput::RightStickY, SdlAxis::RightY),
+        (Ps1AnalogInput::NeGconTwist, SdlAxis::LeftX),
+        (Ps1AnalogInput::NeGconI, SdlAxis::TriggerRight),
+        (Ps1AnalogInput::NeGconII, SdlAxis::TriggerLeft),
+    ]
+    .into_iter()
+    .map(|(input, axis)| (input, InputSource::GamepadAxis(axis, AxisDirection::Positive)))
+    .chain([(Ps1AnalogInput::NeGconL, InputSource::None)])
+    .collect()
+}
+
+fn default_hotkeys() -> BTreeMap<Hotkey, InputSource> {
+    use Hotkey as H;
+    use KeyCode as K;
+
+    [
+        (H::Quit, K::Escape),
+        (H::ToggleFullscreen, K::F9),
+        (H::ToggleVramDisplay, K::Quote),
+        (H::EnableHardwareRasterizer, K::Digit0),
+        (H::EnableSoftwareRasterizer, K::Minus),
+        (H::DecreaseResolutionScale, K::BracketLeft),
+        (H::IncreaseResolutionScale, K::BracketRight),
+        (H::NextDisc, K::F2),
+        (H::PreviousSaveStateSlot, K::F3),
+        (H::NextSaveStateSlot, K::F4),
+        (H::SaveState, K::F5),
+        (H::LoadState, K::F6),
+        (H::RecordMovieFromPowerOn, K::F7),
+        (H::RecordMovieFromState, K::F8),
+        (H::PlayMovie, K::F10),
+        (H::StopMovie, K::F11),
+        (H::Pause, K::KeyP),
+        (H::StepFrame, K::KeyN),
+        (H::FastForward, K::Tab),
+        (H::Rewind, K::Backspace),
+        (H::ReleaseMouse, K::F12),
+    ]
+    .into_iter()
+    .map(|(hotkey, key)| (hotkey, InputSource::Key(key)))
+    .collect()
+}
+
+// Bindings that are missing from the config file keep their defaults, so that inputs added in
+// newer versions are bound without needing to reset the whole table
+fn overlay_defaults<'de, D, K>(
+    deserializer: D,
+    mut defaults: BTreeMap<K, InputSource>,
+) -> Result<BTreeMap<K, InputSource>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Ord + Deserialize<'de>,
+{
+    let overrides: BTreeMap<K, InputSource> = Deserialize::deserialize(deserializer)?;
+    defaults.extend(overrides);
+
+    Ok(defaults)
+}
+
+fn deserialize_keyboard<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<[BTreeMap<Ps1Button, InputSource>; 2], D::Error> {
+    let overrides: Vec<BTreeMap<Ps1Button, InputSource>> = Deserialize::deserialize(deserializer)?;
+
+    let mut bindings = default_keyboard();
+    for (bindings, overrides) in bindings.iter_mut().zip(overrides) {
+        bindings.extend(overrides);
+    }
+
+    Ok(bindings)
+}
+
+fn deserialize_gamepad_buttons<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Ps1Button, InputSource>, D::Error> {
+    overlay_defaults(deserializer, default_gamepad_buttons())
+}
+
+fn deserialize_gamepad_analog<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Ps1AnalogInput, InputSource>, D::Error> {
+    overlay_defaults(deserializer, default_gamepad_analog())
+}
+
+fn deserialize_hotkeys<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<Hotkey, InputSource>, D::Error> {
+    overlay_defaults(deserializer, default_hotkeys())
+}
+
+// SDL game controller inputs are stored using SDL's mapping string names, e.g. "dpup" or "leftx"
+macro_rules! impl_sdl_serde {
+    ($module:ident, $t:ty, $name:literal) => {
+        mod $module {
+            use serde::de::Error;
+            use serde::{Deserialize, Deserializer, Serializer};
+
+            #[allow(clippy::trivially_copy_pass_by_ref)]
+            pub fn serialize<S: Serializer>(value: &$t, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&value.string())
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<$t, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                <$t>::from_string(&s)
+                    .ok_or_else(|| D::Error::custom(format!("invalid {} '{s}'", $name)))
+            }
+        }
+    };
+}
+
+impl_sdl_serde!(sdl_button, sdl2::controller::Button, "gamepad button");
+impl_sdl_serde!(sdl_axis, sdl2::controller::Axis, "gamepad axis");
@@ -1,23 +1,33 @@
-use crate::config::{AppConfig, Rasterizer, VSyncMode, VideoConfig};
-use crate::emuthread::{EmulationThreadHandle, EmulatorThreadCommand, Ps1AnalogInput, Ps1Button};
+use crate::config::bindings::{AxisDirection, InputSource};
+use crate::config::{
+    AppConfig, GAMEPAD_SLOTS, InputConfig, InputPort, Rasterizer, VSyncMode, VideoConfig,
+};
+use crate::emuthread::{
+    EmulationThreadHandle, EmulatorThreadCommand, MovieRecordStart, Ps1LightGunButton,
+    Ps1MouseButton,
+};
+use crate::savestate::SAVE_STATE_SLOTS;
 use crate::{OpenFileType, UserEvent};
 use anyhow::anyhow;
+use ps1_core::input::{Ps1Rumble, RumbleState};
 use sdl2::controller::Axis as SdlAxis;
 use sdl2::controller::Button as SdlButton;
 use sdl2::controller::GameController;
 use sdl2::event::Event as SdlEvent;
 use sdl2::{EventPump, GameControllerSubsystem, Sdl};
+use serde::{Deserialize, Serialize};
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use wgpu::PresentMode;
 use winit::dpi::LogicalSize;
-use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
+use winit::event::{DeviceEvent, ElementState, Event, KeyEvent, MouseButton, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoopProxy};
 use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::window::{Fullscreen, Window, WindowAttributes};
+use winit::window::{CursorGrabMode, Fullscreen, Window, WindowAttributes};
 
 #[derive(Debug)]
 struct EmulatorWindow {
@@ -25,6 +35,11 @@ struct EmulatorWindow {
     surface_config: wgpu::SurfaceConfiguration,
     vsync_mode: VSyncMode,
     fast_forwarding: bool,
+    mouse_captured: bool,
+    // Fractional mouse motion left over after applying mouse sensitivity
+    mouse_remainder: (f64, f64),
+    // Keyboard keys that are currently held, for analog inputs bound to a pair of keys
+    held_keys: HashSet<KeyCode>,
     supported_present_modes: Vec<wgpu::PresentMode>,
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
@@ -134,6 +149,9 @@ impl EmulatorWindow {
             surface_config,
             vsync_mode,
             fast_forwarding: false,
+            mouse_captured: false,
+            mouse_remainder: (0.0, 0.0),
+            held_keys: HashSet::new(),
             supported_present_modes: surface_capabilities.present_modes,
             device: Arc::new(device),
             queue: Arc::new(queue),
@@ -179,6 +197,47 @@ impl EmulatorWindow {
         };
         self.window.set_fullscreen(new_fullscreen);
     }
+
+    fn set_mouse_captured(&mut self, captured: bool) {
+        if captured == self.mouse_captured {
+            return;
+        }
+
+        if captured {
+            // Not every platform supports locking the cursor in place
+            if let Err(err) = self
+                .window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+            {
+                log::error!("Unable to capture mouse pointer: {err}");
+                return;
+            }
+            log::info!("Captured mouse pointer");
+        } else {
+            if let Err(err) = self.window.set_cursor_grab(CursorGrabMode::None) {
+                log::error!("Unable to release mouse pointer: {err}");
+            }
+            log::info!("Released mouse pointer");
+        }
+
+        self.mouse_captured = captured;
+        self.mouse_remainder = (0.0, 0.0);
+        self.update_cursor_visibility();
+    }
+
+    fn update_cursor_visibility(&self) {
+        self.window.set_cursor_visible(!self.mouse_captured && self.window.fullscreen().is_none());
+    }
+
+    fn scale_mouse_motion(&mut self, (dx, dy): (f64, f64), sensitivity: u32) -> (i16, i16) {
+        let scale = f64::from(sensitivity) / 100.0;
+        let x = dx * scale + self.mouse_remainder.0;
+        let y = dy * scale + self.mouse_remainder.1;
+        self.mouse_remainder = (x.fract(), y.fract());
+
+        (x.trunc() as i16, y.trunc() as i16)
+    }
 }
 
 struct RunningState {
@@ -186,84 +245,190 @@ struct RunningState {
     emu_thread: EmulationThreadHandle,
 }
 
+// SDL stops rumble after a fixed duration, so active rumble is periodically re-sent to keep the
+// motors running for as long as the game wants them on
+const RUMBLE_DURATION_MS: u32 = 1000;
+const RUMBLE_REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
 struct Controllers {
     subsystem: GameControllerSubsystem,
     controllers: HashMap<u32, GameController>,
     instance_id_to_device_id: HashMap<u32, u32>,
+    // Instance ID of the gamepad in each slot; gamepads take the first free slot when connected
+    slots: [Option<u32>; GAMEPAD_SLOTS],
+    slot_rumble: [RumbleState; GAMEPAD_SLOTS],
+    last_rumble_refresh: Instant,
+    axis_values: HashMap<(u32, SdlAxis), i16>,
 }
 
 impl Controllers {
     fn new(subsystem: GameControllerSubsystem) -> Self {
-        Self { subsystem, controllers: HashMap::new(), instance_id_to_device_id: HashMap::new() }
+        Self {
+            subsystem,
+            controllers: HashMap::new(),
+            instance_id_to_device_id: HashMap::new(),
+            slots: [None; GAMEPAD_SLOTS],
+            slot_rumble: [RumbleState::default(); GAMEPAD_SLOTS],
+            last_rumble_refresh: Instant::now(),
+            axis_values: HashMap::new(),
+        }
+    }
+
+    fn port(&self, instance_id: u32, input_config: &InputConfig) -> InputPort {
+        self.slots
+            .iter()
+            .position(|&slot| slot == Some(instance_id))
+            .map_or(InputPort::None, |slot| input_config.gamepad_ports[slot])
+    }
+
+    fn update_rumble(&mut self, rumble: Ps1Rumble, input_config: &InputConfig) {
+        let now = Instant::now();
+        let refresh = now.duration_since(self.last_rumble_refresh) >= RUMBLE_REFRESH_INTERVAL;
+        if refresh {
+            self.last_rumble_refresh = now;
+        }
+
+        for (slot, &instance_id) in self.slots.iter().enumerate() {
+            let Some(instance_id) = instance_id else { continue };
+
+            let slot_rumble = input_config.gamepad_ports[slot]
+                .port_and_slot()
+                .map_or_else(RumbleState::default, |(port, slot)| rumble.slot(port, slot));
+            if slot_rumble == self.slot_rumble[slot] && !(refresh && slot_rumble.is_active()) {
+                continue;
+            }
+            self.slot_rumble[slot] = slot_rumble;
+
+            let Some(controller) = self
+                .instance_id_to_device_id
+                .get(&instance_id)
+                .and_then(|device_id| self.controllers.get_mut(device_id))
+            else {
+                continue;
+            };
+
+            // The DualShock's large motor is the low-frequency motor and the small motor is the
+            // high-frequency motor
+            let low_frequency = u16::from(slot_rumble.large_motor) * 257;
+            let high_frequency = if slot_rumble.small_motor { u16::MAX } else { 0 };
+            if let Err(err) =
+                controller.set_rumble(low_frequency, high_frequency, RUMBLE_DURATION_MS)
+            {
+                log::debug!("Unable to set rumble on controller '{}': {err}", controller.name());
+            }
+        }
     }
 
     fn handle_device_added(&mut self, which: u32) -> anyhow::Result<()> {
         let controller = self.subsystem.open(which)?;
+        let instance_id = controller.instance_id();
 
-        log::info!("Controller added: '{}'", controller.name());
+        match self.slots.iter().position(Option::is_none) {
+            Some(slot) => {
+                log::info!("Controller added as gamepad {}: '{}'", slot + 1, controller.name());
+                self.slots[slot] = Some(instance_id);
+                self.slot_rumble[slot] = RumbleState::default();
+            }
+            None => {
+                log::info!(
+                    "Controller added, but all gamepad slots are in use: '{}'",
+                    controller.name()
+                );
+            }
+        }
 
-        self.instance_id_to_device_id.insert(controller.instance_id(), which);
+        self.instance_id_to_device_id.insert(instance_id, which);
         self.controllers.insert(which, controller);
 
         Ok(())
     }
 
     fn handle_device_removed(&mut self, which: u32) {
+        for slot in &mut self.slots {
+            if *slot == Some(which) {
+                *slot = None;
+            }
+        }
+        self.axis_values.retain(|&(instance_id, _), _| instance_id != which);
+
         let Some(device_id) = self.instance_id_to_device_id.remove(&which) else { return };
         let Some(controller) = self.controllers.remove(&device_id) else { return };
 
         log::info!("Controller removed: '{}'", controller.name());
     }
 
-    #[allow(clippy::unused_self)]
     fn handle_button_press(
         &self,
+        which: u32,
         button: SdlButton,
         pressed: bool,
+        input_config: &InputConfig,
         proxy: &EventLoopProxy<UserEvent>,
     ) {
-        let ps1_button = match button {
-            SdlButton::DPadUp => Ps1Button::Up,
-            SdlButton::DPadLeft => Ps1Button::Left,
-            SdlButton::DPadRight => Ps1Button::Right,
-            SdlButton::DPadDown => Ps1Button::Down,
-            SdlButton::A => Ps1Button::Cross,
-            SdlButton::B => Ps1Button::Circle,
-            SdlButton::X => Ps1Button::Square,
-            SdlButton::Y => Ps1Button::Triangle,
-            SdlButton::LeftShoulder => Ps1Button::L1,
-            SdlButton::RightShoulder => Ps1Button::R1,
-            SdlButton::Start => Ps1Button::Start,
-            SdlButton::Back => Ps1Button::Select,
-            SdlButton::Guide => Ps1Button::Analog,
-            _ => return,
-        };
+        self.handle_input(which, InputSource::GamepadButton(button), pressed, input_config, proxy);
+    }
+
+    fn handle_axis_motion(
+        &mut self,
+        which: u32,
+        axis: SdlAxis,
+        value: i16,
+        input_config: &InputConfig,
+        proxy: &EventLoopProxy<UserEvent>,
+    ) {
+        let port = self.port(which, input_config);
+        if port != InputPort::None {
+            for (input, direction) in input_config.bindings.gamepad_analog(axis) {
+                let value = match direction {
+                    AxisDirection::Positive => value,
+                    AxisDirection::Negative => value.saturating_neg(),
+                };
+                proxy.send_event(UserEvent::ControllerAnalog { port, input, value }).unwrap();
+            }
+        }
 
-        proxy.send_event(UserEvent::ControllerButton { button: ps1_button, pressed }).unwrap();
+        // Each half of the axis also acts as a button
+        let previous = self.axis_values.insert((which, axis), value).unwrap_or(0);
+        for direction in [AxisDirection::Positive, AxisDirection::Negative] {
+            let pressed = axis_half_pressed(value, direction);
+            if pressed != axis_half_pressed(previous, direction) {
+                let source = InputSource::GamepadAxis(axis, direction);
+                self.handle_input(which, source, pressed, input_config, proxy);
+            }
+        }
     }
 
-    #[allow(clippy::unused_self)]
-    fn handle_axis_motion(&self, axis: SdlAxis, value: i16, proxy: &EventLoopProxy<UserEvent>) {
-        let ps1_axis = match axis {
-            SdlAxis::LeftX => Some(Ps1AnalogInput::LeftStickX),
-            SdlAxis::LeftY => Some(Ps1AnalogInput::LeftStickY),
-            SdlAxis::RightX => Some(Ps1AnalogInput::RightStickX),
-            SdlAxis::RightY => Some(Ps1AnalogInput::RightStickY),
-            _ => None,
-        };
-        if let Some(ps1_axis) = ps1_axis {
-            proxy.send_event(UserEvent::ControllerAnalog { input: ps1_axis, value }).unwrap();
+    fn handle_input(
+        &self,
+        which: u32,
+        source: InputSource,
+        pressed: bool,
+        input_config: &InputConfig,
+        proxy: &EventLoopProxy<UserEvent>,
+    ) {
+        if pressed {
+            proxy.send_event(UserEvent::GamepadInputPressed(source)).unwrap();
+        }
+
+        for hotkey in input_config.bindings.hotkeys(source) {
+            proxy.send_event(UserEvent::Hotkey { hotkey, pressed }).unwrap();
+        }
+
+        let port = self.port(which, input_config);
+        if port == InputPort::None {
             return;
         }
 
-        let button = match axis {
-            SdlAxis::TriggerLeft => Ps1Button::L2,
-            SdlAxis::TriggerRight => Ps1Button::R2,
-            _ => return,
-        };
+        for button in input_config.bindings.gamepad_buttons(source) {
+            proxy.send_event(UserEvent::ControllerButton { port, button, pressed }).unwrap();
+        }
+    }
+}
 
-        let pressed = value >= i16::MAX / 2;
-        proxy.send_event(UserEvent::ControllerButton { button, pressed }).unwrap();
+fn axis_half_pressed(value: i16, direction: AxisDirection) -> bool {
+    match direction {
+        AxisDirection::Positive => value >= i16::MAX / 2,
+        AxisDirection::Negative => value <= i16::MIN / 2,
     }
 }
 
@@ -272,6 +437,7 @@ pub struct EmulatorState {
     sdl_ctx: Sdl,
     sdl_event_pump: EventPump,
     controllers: Controllers,
+    save_state_slot: usize,
 }
 
 impl EmulatorState {
@@ -287,7 +453,7 @@ impl EmulatorState {
             .map_err(|err| anyhow!("Error initializing SDL2 game controller subsystem: {err}"))?;
         let controllers = Controllers::new(controller_subsystem);
 
-        Ok(Self { running: None, sdl_ctx, sdl_event_pump, controllers })
+        Ok(Self { running: None, sdl_ctx, sdl_event_pump, controllers, save_state_slot: 0 })
     }
 
     #[allow(clippy::missing_errors_doc)]
@@ -306,23 +472,52 @@ impl EmulatorState {
                 return self.start_emulator(None, elwt, app_config);
             }
             Event::AboutToWait => {
-                self.process_sdl_events(proxy)?;
+                self.process_sdl_events(&app_config.input, proxy)?;
+
+                let rumble = match &self.running {
+                    Some(RunningState { emu_thread, .. }) if app_config.input.rumble_enabled => {
+                        emu_thread.rumble()
+                    }
+                    _ => Ps1Rumble::default(),
+                };
+                self.controllers.update_rumble(rumble, &app_config.input);
             }
             _ => {}
         }
 
         let Some(RunningState { window, emu_thread }) = &mut self.running else { return Ok(()) };
 
+        let mut hotkeys = Vec::new();
         match event {
             Event::UserEvent(UserEvent::AppConfigChanged) => {
                 window.update_config(&app_config.video);
                 emu_thread.handle_config_change(app_config)?;
+
+                if !app_config.input.mouse_connected() {
+                    window.set_mouse_captured(false);
+                }
             }
-            &Event::UserEvent(UserEvent::ControllerButton { button, pressed }) => {
-                emu_thread.send_command(EmulatorThreadCommand::DigitalInput { button, pressed });
+            &Event::UserEvent(UserEvent::ControllerButton { port, button, pressed }) => {
+                emu_thread.send_command(EmulatorThreadCommand::DigitalInput {
+                    port,
+                    button,
+                    pressed,
+                });
             }
-            &Event::UserEvent(UserEvent::ControllerAnalog { input, value }) => {
-                emu_thread.send_command(EmulatorThreadCommand::AnalogInput { input, value });
+            &Event::UserEvent(UserEvent::ControllerAnalog { port, input, value }) => {
+                emu_thread.send_command(EmulatorThreadCommand::AnalogInput { port, input, value });
+            }
+            &Event::UserEvent(UserEvent::SaveState { slot }) => {
+                emu_thread.send_command(EmulatorThreadCommand::SaveState { slot });
+            }
+            &Event::UserEvent(UserEvent::LoadState { slot }) => {
+                emu_thread.send_command(EmulatorThreadCommand::LoadState { slot });
+            }
+            Event::UserEvent(UserEvent::NextDisc) => {
+                emu_thread.send_command(EmulatorThreadCommand::NextDisc);
+            }
+            &Event::UserEvent(UserEvent::Hotkey { hotkey, pressed }) => {
+                hotkeys.push((hotkey, pressed));
             }
             Event::WindowEvent { event: win_event, window_id }
                 if *window_id == window.window.id() =>
@@ -337,113 +532,250 @@ impl EmulatorState {
                         window.surface_config.height = size.height;
                         window.surface.configure(&window.device, &window.surface_config);
 
-                        match window.window.fullscreen() {
-                            Some(_) => {
-                                window.window.set_cursor_visible(false);
-                            }
-                            None => {
-                                let logical_size = size.to_logical(window.window.scale_factor());
-                                app_config.video.window_width = logical_size.width;
-                                app_config.video.window_height = logical_size.height;
-
-                                window.window.set_cursor_visible(true);
-                            }
+                        if window.window.fullscreen().is_none() {
+                            let logical_size = size.to_logical(window.window.scale_factor());
+                            app_config.video.window_width = logical_size.width;
+                            app_config.video.window_height = logical_size.height;
                         }
+                        window.update_cursor_visibility();
 
                         emu_thread.handle_resize(*size);
                     }
                     &WindowEvent::KeyboardInput {
-                        event: KeyEvent { physical_key, state, .. },
+                        event: KeyEvent { physical_key: PhysicalKey::Code(keycode), state, .. },
                         ..
                     } => {
-                        if let Some(command) = key_input_command(physical_key, state) {
-                            emu_thread.send_command(command);
+                        let pressed = state == ElementState::Pressed;
+                        let input_config = &app_config.input;
+
+                        for (mapping, button) in input_config.bindings.keyboard_buttons(keycode) {
+                            let port = input_config.keyboard_ports[mapping];
+                            if port != InputPort::None {
+                                emu_thread.send_command(EmulatorThreadCommand::DigitalInput {
+                                    port,
+                                    button,
+                                    pressed,
+                                });
+                            }
                         }
 
-                        let hotkey = check_hotkey(physical_key, state);
-                        match hotkey {
-                            Some(Hotkey::Quit) => {
-                                emu_thread.send_command(EmulatorThreadCommand::Stop);
-                                self.running = None;
-                            }
-                            Some(Hotkey::ToggleFullscreen) => {
-                                window.toggle_fullscreen();
-                            }
-                            Some(Hotkey::ToggleVramDisplay) => {
-                                app_config.debug.vram_display = !app_config.debug.vram_display;
-                                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone(),
-                                ));
-                            }
-                            Some(Hotkey::EnableHardwareRasterizer) => {
-                                app_config.graphics.rasterizer = Rasterizer::Hardware;
-                                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone(),
-                                ));
-                                log::info!(
-                                    "Using hardware rasterizer with resolution scale {}",
-                                    app_config.graphics.hardware_resolution_scale
-                                );
-                            }
-                            Some(Hotkey::EnableSoftwareRasterizer) => {
-                                app_config.graphics.rasterizer = Rasterizer::Software;
-                                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone(),
-                                ));
-                                log::info!("Using software rasterizer");
-                            }
-                            Some(Hotkey::DecreaseResolutionScale) => {
-                                let scale =
-                                    cmp::max(1, app_config.graphics.hardware_resolution_scale - 1);
-                                app_config.graphics.hardware_resolution_scale = scale;
-                                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone(),
-                                ));
-                                log::info!("Set resolution scale to {scale}");
-                            }
-                            Some(Hotkey::IncreaseResolutionScale) => {
-                                let scale =
-                                    cmp::min(16, app_config.graphics.hardware_resolution_scale + 1);
-                                app_config.graphics.hardware_resolution_scale = scale;
-                                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(
-                                    app_config.clone(),
-                                ));
-                                log::info!("Set resolution scale to {scale}");
-                            }
-                            Some(Hotkey::SaveState) => {
-                                emu_thread.send_command(EmulatorThreadCommand::SaveState);
-                            }
-                            Some(Hotkey::LoadState) => {
-                                emu_thread.send_command(EmulatorThreadCommand::LoadState);
+                        if pressed {
+                            window.held_keys.insert(keycode);
+                        } else {
+                            window.held_keys.remove(&keycode);
+                        }
+                        for (mapping, input, binding) in
+                            input_config.bindings.keyboard_analog(keycode)
+                        {
+                            let port = input_config.keyboard_ports[mapping];
+                            if port != InputPort::None {
+                                let value = binding.value(|key| window.held_keys.contains(&key));
+                                emu_thread.send_command(EmulatorThreadCommand::AnalogInput {
+                                    port,
+                                    input,
+                                    value,
+                                });
                             }
-                            Some(Hotkey::Pause) => {
-                                emu_thread.send_command(EmulatorThreadCommand::TogglePause);
+                        }
+
+                        hotkeys.extend(
+                            input_config
+                                .bindings
+                                .hotkeys(InputSource::Key(keycode))
+                                .map(|hotkey| (hotkey, pressed)),
+                        );
+                    }
+                    &WindowEvent::MouseInput { state, button, .. } => {
+                        let pressed = state == ElementState::Pressed;
+                        if app_config.input.light_gun_connected() && !window.mouse_captured {
+                            let button = match button {
+                                MouseButton::Left => Some(Ps1LightGunButton::Trigger),
+                                MouseButton::Right => Some(Ps1LightGunButton::A),
+                                MouseButton::Middle => Some(Ps1LightGunButton::B),
+                                _ => None,
+                            };
+                            if let Some(button) = button {
+                                emu_thread.send_command(EmulatorThreadCommand::LightGunButton {
+                                    button,
+                                    pressed,
+                                });
                             }
-                            Some(Hotkey::StepFrame) => {
-                                emu_thread.send_command(EmulatorThreadCommand::StepFrame);
+                        } else if !window.mouse_captured {
+                            // Clicking in the window captures the pointer, and the click is not
+                            // sent to the emulated mouse
+                            if pressed
+                                && button == MouseButton::Left
+                                && app_config.input.mouse_connected()
+                            {
+                                window.set_mouse_captured(true);
                             }
-                            Some(Hotkey::FastForward) => {
-                                let enabled = state == ElementState::Pressed;
-                                emu_thread
-                                    .send_command(EmulatorThreadCommand::FastForward { enabled });
-                                window.set_fast_forwarding(enabled);
+                        } else {
+                            let button = match button {
+                                MouseButton::Left => Some(Ps1MouseButton::Left),
+                                MouseButton::Right => Some(Ps1MouseButton::Right),
+                                _ => None,
+                            };
+                            if let Some(button) = button {
+                                emu_thread.send_command(EmulatorThreadCommand::MouseButton {
+                                    button,
+                                    pressed,
+                                });
                             }
-                            None => {}
                         }
                     }
+                    &WindowEvent::CursorMoved { position, .. }
+                        if app_config.input.light_gun_connected() =>
+                    {
+                        // Pointing outside of the emulated frame aims the gun off-screen
+                        let aim = emu_thread.window_to_frame_position(position);
+                        emu_thread.send_command(EmulatorThreadCommand::LightGunAim { aim });
+                    }
+                    WindowEvent::CursorLeft { .. } if app_config.input.light_gun_connected() => {
+                        emu_thread.send_command(EmulatorThreadCommand::LightGunAim { aim: None });
+                    }
+                    WindowEvent::Focused(false) => {
+                        window.set_mouse_captured(false);
+                    }
                     _ => {}
                 }
             }
+            &Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta }, .. }
+                if window.mouse_captured =>
+            {
+                let (dx, dy) = window.scale_mouse_motion(delta, app_config.input.mouse_sensitivity);
+                if dx != 0 || dy != 0 {
+                    emu_thread.send_command(EmulatorThreadCommand::MouseMotion { dx, dy });
+                }
+            }
             Event::AboutToWait => {
                 emu_thread.render_frame_if_available(&window.surface)?;
             }
             _ => {}
         }
 
+        for (hotkey, pressed) in hotkeys {
+            self.handle_hotkey(hotkey, pressed, app_config);
+        }
+
         Ok(())
     }
 
-    fn process_sdl_events(&mut self, proxy: &EventLoopProxy<UserEvent>) -> anyhow::Result<()> {
+    fn handle_hotkey(&mut self, hotkey: Hotkey, pressed: bool, app_config: &mut AppConfig) {
+        if !pressed && !hotkey.is_held() {
+            return;
+        }
+
+        let Some(RunningState { window, emu_thread }) = &mut self.running else { return };
+
+        match hotkey {
+            Hotkey::ReleaseMouse => {
+                window.set_mouse_captured(false);
+            }
+            Hotkey::Quit => {
+                emu_thread.send_command(EmulatorThreadCommand::Stop);
+                self.running = None;
+            }
+            Hotkey::ToggleFullscreen => {
+                window.toggle_fullscreen();
+            }
+            Hotkey::ToggleVramDisplay => {
+                app_config.debug.vram_display = !app_config.debug.vram_display;
+                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(Box::new(
+                    app_config.clone(),
+                )));
+            }
+            Hotkey::EnableHardwareRasterizer => {
+                app_config.graphics.rasterizer = Rasterizer::Hardware;
+                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(Box::new(
+                    app_config.clone(),
+                )));
+                log::info!(
+                    "Using hardware rasterizer with resolution scale {}",
+                    app_config.graphics.hardware_resolution_scale
+                );
+            }
+            Hotkey::EnableSoftwareRasterizer => {
+                app_config.graphics.rasterizer = Rasterizer::Software;
+                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(Box::new(
+                    app_config.clone(),
+                )));
+                log::info!("Using software rasterizer");
+            }
+            Hotkey::DecreaseResolutionScale => {
+                let scale = cmp::max(1, app_config.graphics.hardware_resolution_scale - 1);
+                app_config.graphics.hardware_resolution_scale = scale;
+                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(Box::new(
+                    app_config.clone(),
+                )));
+                log::info!("Set resolution scale to {scale}");
+            }
+            Hotkey::IncreaseResolutionScale => {
+                let scale = cmp::min(16, app_config.graphics.hardware_resolution_scale + 1);
+                app_config.graphics.hardware_resolution_scale = scale;
+                emu_thread.send_command(EmulatorThreadCommand::UpdateConfig(Box::new(
+                    app_config.clone(),
+                )));
+                log::info!("Set resolution scale to {scale}");
+            }
+            Hotkey::SaveState => {
+                emu_thread
+                    .send_command(EmulatorThreadCommand::SaveState { slot: self.save_state_slot });
+            }
+            Hotkey::LoadState => {
+                emu_thread
+                    .send_command(EmulatorThreadCommand::LoadState { slot: self.save_state_slot });
+            }
+            Hotkey::PreviousSaveStateSlot => {
+                self.save_state_slot =
+                    (self.save_state_slot + SAVE_STATE_SLOTS - 1) % SAVE_STATE_SLOTS;
+                log::info!("Selected save state slot {}", self.save_state_slot + 1);
+            }
+            Hotkey::NextSaveStateSlot => {
+                self.save_state_slot = (self.save_state_slot + 1) % SAVE_STATE_SLOTS;
+                log::info!("Selected save state slot {}", self.save_state_slot + 1);
+            }
+            Hotkey::Rewind => {
+                let enabled = pressed;
+                emu_thread.send_command(EmulatorThreadCommand::Rewind { enabled });
+            }
+            Hotkey::RecordMovieFromPowerOn => {
+                emu_thread.send_command(EmulatorThreadCommand::RecordMovie {
+                    start: MovieRecordStart::PowerOn,
+                });
+            }
+            Hotkey::RecordMovieFromState => {
+                emu_thread.send_command(EmulatorThreadCommand::RecordMovie {
+                    start: MovieRecordStart::CurrentState,
+                });
+            }
+            Hotkey::PlayMovie => {
+                emu_thread.send_command(EmulatorThreadCommand::PlayMovie);
+            }
+            Hotkey::StopMovie => {
+                emu_thread.send_command(EmulatorThreadCommand::StopMovie);
+            }
+            Hotkey::NextDisc => {
+                emu_thread.send_command(EmulatorThreadCommand::NextDisc);
+            }
+            Hotkey::Pause => {
+                emu_thread.send_command(EmulatorThreadCommand::TogglePause);
+            }
+            Hotkey::StepFrame => {
+                emu_thread.send_command(EmulatorThreadCommand::StepFrame);
+            }
+            Hotkey::FastForward => {
+                let enabled = pressed;
+                emu_thread.send_command(EmulatorThreadCommand::FastForward { enabled });
+                window.set_fast_forwarding(enabled);
+            }
+        }
+    }
+
+    fn process_sdl_events(
+        &mut self,
+        input_config: &InputConfig,
+        proxy: &EventLoopProxy<UserEvent>,
+    ) -> anyhow::Result<()> {
         for event in self.sdl_event_pump.poll_iter() {
             match event {
                 SdlEvent::ControllerDeviceAdded { which, .. } => {
@@ -452,14 +784,14 @@ impl EmulatorState {
                 SdlEvent::ControllerDeviceRemoved { which, .. } => {
                     self.controllers.handle_device_removed(which);
                 }
-                SdlEvent::ControllerButtonDown { button, .. } => {
-                    self.controllers.handle_button_press(button, true, proxy);
+                SdlEvent::ControllerButtonDown { which, button, .. } => {
+                    self.controllers.handle_button_press(which, button, true, input_config, proxy);
                 }
-                SdlEvent::ControllerButtonUp { button, .. } => {
-                    self.controllers.handle_button_press(button, false, proxy);
+                SdlEvent::ControllerButtonUp { which, button, .. } => {
+                    self.controllers.handle_button_press(which, button, false, input_config, proxy);
                 }
-                SdlEvent::ControllerAxisMotion { axis, value, .. } => {
-                    self.controllers.handle_axis_motion(axis, value, proxy);
+                SdlEvent::ControllerAxisMotion { which, axis, value, .. } => {
+                    self.controllers.handle_axis_motion(which, axis, value, input_config, proxy);
                 }
                 _ => {}
             }
@@ -499,35 +831,8 @@ impl EmulatorState {
     }
 }
 
-fn key_input_command(key: PhysicalKey, state: ElementState) -> Option<EmulatorThreadCommand> {
-    let PhysicalKey::Code(keycode) = key else { return None };
-    let pressed = state == ElementState::Pressed;
-
-    // TODO configurable
-    let button = match keycode {
-        KeyCode::ArrowUp => Ps1Button::Up,
-        KeyCode::ArrowDown => Ps1Button::Down,
-        KeyCode::ArrowLeft => Ps1Button::Left,
-        KeyCode::ArrowRight => Ps1Button::Right,
-        KeyCode::KeyX => Ps1Button::Cross,
-        KeyCode::KeyS => Ps1Button::Circle,
-        KeyCode::KeyZ => Ps1Button::Square,
-        KeyCode::KeyA => Ps1Button::Triangle,
-        KeyCode::KeyW => Ps1Button::L1,
-        KeyCode::KeyQ => Ps1Button::L2,
-        KeyCode::KeyE => Ps1Button::R1,
-        KeyCode::KeyR => Ps1Button::R2,
-        KeyCode::Enter => Ps1Button::Start,
-        KeyCode::ShiftRight => Ps1Button::Select,
-        KeyCode::KeyY => Ps1Button::Analog,
-        _ => return None,
-    };
-
-    Some(EmulatorThreadCommand::DigitalInput { button, pressed })
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Hotkey {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Hotkey {
     Quit,
     ToggleFullscreen,
     ToggleVramDisplay,
@@ -537,29 +842,74 @@ enum Hotkey {
     IncreaseResolutionScale,
     SaveState,
     LoadState,
+    PreviousSaveStateSlot,
+    NextSaveStateSlot,
+    RecordMovieFromPowerOn,
+    RecordMovieFromState,
+    PlayMovie,
+    StopMovie,
+    NextDisc,
     Pause,
     StepFrame,
     FastForward,
+    Rewind,
+    ReleaseMouse,
 }
 
-fn check_hotkey(key: PhysicalKey, state: ElementState) -> Option<Hotkey> {
-    let PhysicalKey::Code(keycode) = key else { return None };
-    let pressed = state == ElementState::Pressed;
-
-    // TODO configurable
-    match keycode {
-        KeyCode::Escape if pressed => Some(Hotkey::Quit),
-        KeyCode::F9 if pressed => Some(Hotkey::ToggleFullscreen),
-        KeyCode::Quote if pressed => Some(Hotkey::ToggleVramDisplay),
-        KeyCode::Digit0 if pressed => Some(Hotkey::EnableHardwareRasterizer),
-        KeyCode::Minus if pressed => Some(Hotkey::EnableSoftwareRasterizer),
-        KeyCode::BracketLeft if pressed => Some(Hotkey::DecreaseResolutionScale),
-        KeyCode::BracketRight if pressed => Some(Hotkey::IncreaseResolutionScale),
-        KeyCode::F5 if pressed => Some(Hotkey::SaveState),
-        KeyCode::F6 if pressed => Some(Hotkey::LoadState),
-        KeyCode::KeyP if pressed => Some(Hotkey::Pause),
-        KeyCode::KeyN if pressed => Some(Hotkey::StepFrame),
-        KeyCode::Tab => Some(Hotkey::FastForward),
-        _ => None,
+impl Hotkey {
+    pub const ALL: [Self; 21] = [
+        Self::Quit,
+        Self::ToggleFullscreen,
+        Self::ToggleVramDisplay,
+        Self::EnableHardwareRasterizer,
+        Self::EnableSoftwareRasterizer,
+        Self::DecreaseResolutionScale,
+        Self::IncreaseResolutionScale,
+        Self::SaveState,
+        Self::LoadState,
+        Self::PreviousSaveStateSlot,
+        Self::NextSaveStateSlot,
+        Self::RecordMovieFromPowerOn,
+        Self::RecordMovieFromState,
+        Self::PlayMovie,
+        Self::StopMovie,
+        Self::NextDisc,
+        Self::Pause,
+        Self::StepFrame,
+        Self::FastForward,
+        Self::Rewind,
+        Self::ReleaseMouse,
+    ];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Quit => "Quit",
+            Self::ToggleFullscreen => "Toggle fullscreen",
+            Self::ToggleVramDisplay => "Toggle VRAM display",
+            Self::EnableHardwareRasterizer => "Use hardware rasterizer",
+            Self::EnableSoftwareRasterizer => "Use software rasterizer",
+            Self::DecreaseResolutionScale => "Decrease resolution scale",
+            Self::IncreaseResolutionScale => "Increase resolution scale",
+            Self::SaveState => "Save state",
+            Self::LoadState => "Load state",
+            Self::PreviousSaveStateSlot => "Previous save state slot",
+            Self::NextSaveStateSlot => "Next save state slot",
+            Self::RecordMovieFromPowerOn => "Record movie from power-on",
+            Self::RecordMovieFromState => "Record movie from current state",
+            Self::PlayMovie => "Play movie",
+            Self::StopMovie => "Stop movie",
+            Self::NextDisc => "Next disc",
+            Self::Pause => "Pause",
+            Self::StepFrame => "Step frame",
+            Self::FastForward => "Fast forward (hold)",
+            Self::Rewind => "Rewind (hold)",
+            Self::ReleaseMouse => "Release captured mouse",
+        }
+    }
+
+    // Held hotkeys are active for as long as their input is held; all others trigger on press
+    fn is_held(self) -> bool {
+        matches!(self, Self::FastForward | Self::Rewind)
     }
 }
@@ -1,33 +1,51 @@
 use crate::Never;
-use crate::config::{AppConfig, GraphicsConfig};
+use crate::config::{
+    AnalogConfig, AppConfig, GraphicsConfig, InputPort, LinkCableConfig, LinkCableMode,
+    MemoryCardMode, MemoryCardsConfig,
+};
+use crate::emuthread::analog::HostAnalogState;
 use crate::emuthread::audio::{AudioQueue, QueueAudioCallback, QueueAudioOutput};
+use crate::emuthread::movie::MovieState;
+use crate::emuthread::playlist::Playlist;
 use crate::emuthread::renderer::{SurfaceRenderer, SwapChainRenderer};
+use crate::emuthread::rewind::RewindBuffer;
+use crate::savestate::{self, SaveStateHeader};
 use anyhow::{Context, anyhow};
 use cdrom::reader::{CdRom, CdRomFileFormat};
 use cfg_if::cfg_if;
 use ps1_core::api::{
-    Ps1Emulator, Ps1EmulatorBuilder, Ps1EmulatorState, SaveWriter, TickEffect, TickError,
+    Ps1Emulator, Ps1EmulatorBuilder, RumbleOutput, SaveWriter, TickEffect, TickError,
+};
+use ps1_core::input::{
+    AnalogJoypadState, ControllerState, ControllerType, DigitalJoypadState, LightGunState,
+    MULTITAP_SLOTS, NeGconState, Ps1Inputs, Ps1Rumble,
 };
-use ps1_core::input::{AnalogJoypadState, DigitalJoypadState, Ps1Inputs};
+use ps1_core::link::{LinkAddress, LinkCable};
+use ps1_core::movie::{Movie, MovieStart};
 use regex::Regex;
 use sdl2::audio::AudioDevice;
 use sdl2::{AudioSubsystem, Sdl};
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 use std::ffi::OsStr;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex, OnceLock, mpsc};
 use std::time::Duration;
 use std::{fs, io, thread};
-use winit::dpi::PhysicalSize;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 
+mod analog;
 mod audio;
+mod movie;
+mod playlist;
 mod renderer;
+mod rewind;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Ps1Button {
     Up,
     Down,
@@ -46,25 +64,156 @@ pub enum Ps1Button {
     Analog,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+impl Ps1Button {
+    pub const ALL: [Self; 15] = [
+        Self::Up,
+        Self::Down,
+        Self::Left,
+        Self::Right,
+        Self::Cross,
+        Self::Circle,
+        Self::Square,
+        Self::Triangle,
+        Self::L1,
+        Self::L2,
+        Self::R1,
+        Self::R2,
+        Self::Start,
+        Self::Select,
+        Self::Analog,
+    ];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Up => "Up",
+            Self::Down => "Down",
+            Self::Left => "Left",
+            Self::Right => "Right",
+            Self::Cross => "X",
+            Self::Circle => "O",
+            Self::Square => "Square",
+            Self::Triangle => "Triangle",
+            Self::L1 => "L1",
+            Self::L2 => "L2",
+            Self::R1 => "R1",
+            Self::R2 => "R2",
+            Self::Start => "Start",
+            Self::Select => "Select",
+            Self::Analog => "Analog",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Ps1AnalogInput {
     LeftStickX,
     LeftStickY,
     RightStickX,
     RightStickY,
+    NeGconTwist,
+    NeGconI,
+    NeGconII,
+    NeGconL,
+}
+
+impl Ps1AnalogInput {
+    pub const ALL: [Self; 8] = [
+        Self::LeftStickX,
+        Self::LeftStickY,
+        Self::RightStickX,
+        Self::RightStickY,
+        Self::NeGconTwist,
+        Self::NeGconI,
+        Self::NeGconII,
+        Self::NeGconL,
+    ];
+
+    #[must_use]
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::LeftStickX => "Left stick X",
+            Self::LeftStickY => "Left stick Y",
+            Self::RightStickX => "Right stick X",
+            Self::RightStickY => "Right stick Y",
+            Self::NeGconTwist => "NeGcon twist",
+            Self::NeGconI => "NeGcon I",
+            Self::NeGconII => "NeGcon II",
+            Self::NeGconL => "NeGcon L",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps1MouseButton {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ps1LightGunButton {
+    Trigger,
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovieRecordStart {
+    PowerOn,
+    CurrentState,
 }
 
 #[derive(Debug)]
 pub enum EmulatorThreadCommand {
     Stop,
-    DigitalInput { button: Ps1Button, pressed: bool },
-    AnalogInput { input: Ps1AnalogInput, value: i16 },
-    UpdateConfig(AppConfig),
-    SaveState,
-    LoadState,
+    DigitalInput {
+        port: InputPort,
+        button: Ps1Button,
+        pressed: bool,
+    },
+    AnalogInput {
+        port: InputPort,
+        input: Ps1AnalogInput,
+        value: i16,
+    },
+    /// Mouse inputs go to every port that has a mouse connected
+    MouseMotion {
+        dx: i16,
+        dy: i16,
+    },
+    MouseButton {
+        button: Ps1MouseButton,
+        pressed: bool,
+    },
+    /// Light gun inputs go to every port that has a light gun connected
+    LightGunAim {
+        aim: Option<(u16, u16)>,
+    },
+    LightGunButton {
+        button: Ps1LightGunButton,
+        pressed: bool,
+    },
+    UpdateConfig(Box<AppConfig>),
+    SaveState {
+        slot: usize,
+    },
+    LoadState {
+        slot: usize,
+    },
     TogglePause,
     StepFrame,
-    FastForward { enabled: bool },
+    FastForward {
+        enabled: bool,
+    },
+    Rewind {
+        enabled: bool,
+    },
+    RecordMovie {
+        start: MovieRecordStart,
+    },
+    PlayMovie,
+    StopMovie,
+    NextDisc,
 }
 
 #[derive(Debug)]
@@ -130,6 +279,7 @@ pub struct EmulationThreadHandle {
     audio_subsystem: AudioSubsystem,
     audio_queue: AudioQueue,
     audio_device: AudioDevice<QueueAudioCallback>,
+    rumble: Arc<Mutex<Ps1Rumble>>,
     command_sender: Sender<EmulatorThreadCommand>,
 }
 
@@ -150,36 +300,75 @@ impl EmulationThreadHandle {
         let bios = fs::read(bios_path)
             .with_context(|| format!("Failed to read BIOS from '{}'", bios_path.display()))?;
 
-        let emulator_config = config.to_emulator_config();
+        // Per-game settings are applied once the game ID is known, before the first frame
+        let emulator_config = config.to_emulator_config(None);
 
-        let save_writer = FsSaveWriter::from_path(file_path.unwrap_or(&PathBuf::from("global")))?;
+        let save_writer = FsSaveWriter::new(file_path, &config.memory_cards)?;
 
         let mut builder = Ps1EmulatorBuilder::new(bios, Arc::clone(&device), Arc::clone(&queue))
             .with_config(emulator_config);
 
-        if let Ok(card_data) = fs::read(&save_writer.card_1_path) {
+        builder = builder.with_memory_cards_inserted(
+            save_writer.card_1_path.is_some(),
+            save_writer.card_2_path.is_some(),
+        );
+        if let Some(card_data) =
+            save_writer.card_1_path.as_ref().and_then(|path| fs::read(path).ok())
+        {
             builder = builder.with_memory_card_1(card_data);
         }
+        if let Some(card_data) =
+            save_writer.card_2_path.as_ref().and_then(|path| fs::read(path).ok())
+        {
+            builder = builder.with_memory_card_2(card_data);
+        }
+        for (port, paths) in save_writer.multitap_card_paths.iter().enumerate() {
+            for (slot, path) in paths.iter().enumerate() {
+                if let Some(path) = path {
+                    builder = builder.with_multitap_memory_card(port, slot, fs::read(path).ok());
+                }
+            }
+        }
 
-        let emulator = match file_path {
-            Some(file_path) => match file_path.extension().and_then(OsStr::to_str) {
-                Some(extension @ ("cue" | "chd")) => {
-                    let format = match extension {
-                        "cue" => CdRomFileFormat::CueBin,
-                        "chd" => CdRomFileFormat::Chd,
-                        _ => unreachable!("nested match expressions"),
-                    };
+        let mut exe = None;
+        let mut playlist = None;
+        let mut emulator = match file_path {
+            Some(file_path) => match file_path
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(str::to_ascii_lowercase)
+                .as_deref()
+            {
+                Some("cue" | "chd" | "iso" | "bin" | "ecm") => {
+                    let disc = open_disc(file_path, 0)?;
+                    builder.with_disc(disc).build()?
+                }
+                Some("m3u") => {
+                    let m3u = Playlist::read_m3u(file_path)?;
+                    let current = m3u.current();
+                    let disc = open_disc(&current.path, current.disc_index)?;
+                    log::info!("Loaded playlist with {} discs", m3u.len());
+                    playlist = Some(m3u);
+
+                    builder.with_disc(disc).build()?
+                }
+                Some("pbp") => {
+                    playlist = Playlist::read_pbp(file_path)?;
+                    if let Some(playlist) = &playlist {
+                        log::info!("Loaded PBP file with {} discs", playlist.len());
+                    }
 
-                    let disc = CdRom::open(file_path, format)?;
+                    let disc = open_disc(file_path, 0)?;
                     builder.with_disc(disc).build()?
                 }
                 Some("exe") => {
-                    let exe = fs::read(file_path).with_context(|| {
+                    let exe_bytes = fs::read(file_path).with_context(|| {
                         format!("Failed to read EXE from path {}", file_path.display())
                     })?;
 
                     let mut emulator = builder.build()?;
-                    emulator.run_until_exe_sideloaded(&exe)?;
+                    emulator.run_until_exe_sideloaded(&exe_bytes)?;
+                    exe = Some(exe_bytes);
 
                     emulator
                 }
@@ -196,6 +385,16 @@ impl EmulationThreadHandle {
             None => builder.build()?,
         };
 
+        let game_id = match emulator.game_id()? {
+            Some(game_id) => game_id,
+            None => {
+                file_path.and_then(Path::file_stem).and_then(OsStr::to_str).unwrap_or("BIOS").into()
+            }
+        };
+
+        emulator.update_config(config.to_emulator_config(Some(&game_id)));
+        emulator.set_link_cable(open_link_cable(&config.link_cable)?);
+
         let swap_chain = EmulatorSwapChain::new(&config.graphics);
         let swap_chain_renderer =
             SwapChainRenderer::new(Arc::clone(&device), Arc::clone(&queue), swap_chain.clone());
@@ -214,9 +413,22 @@ impl EmulationThreadHandle {
 
         let audio_output = QueueAudioOutput::new(Arc::clone(&audio_queue));
 
+        let rumble_output = SharedRumbleOutput::new();
+        let rumble = Arc::clone(&rumble_output.rumble);
+
         let (command_sender, command_receiver) = mpsc::channel();
 
-        let save_state_path = determine_save_state_path(file_path)?;
+        let save_state_paths = (0..savestate::SAVE_STATE_SLOTS)
+            .map(|slot| savestate::slot_path(file_path, slot))
+            .collect::<anyhow::Result<_>>()?;
+        let legacy_save_state_path = savestate::legacy_path(file_path)?;
+        if legacy_save_state_path.exists() {
+            log::warn!(
+                "Found a save state from before numbered slots at '{}'; this version cannot load it, so it was left in place",
+                legacy_save_state_path.display()
+            );
+        }
+        let movie_path = determine_path(file_path, MOVIES_DIRECTORY, "ps1m")?;
 
         let mut inputs = Ps1Inputs::default();
         update_input_config(config, &mut inputs);
@@ -227,8 +439,18 @@ impl EmulationThreadHandle {
             audio_output,
             audio_sync_threshold: config.audio.sync_threshold,
             save_writer,
+            rumble_output,
             inputs,
-            save_state_path,
+            analog_config: config.input.analog,
+            host_analog: Default::default(),
+            exe,
+            playlist,
+            lid_close_frames_remaining: None,
+            game_id,
+            save_state_paths,
+            movie_path,
+            movie: MovieState::default(),
+            rewind: RewindBuffer::new(),
             command_receiver,
         });
 
@@ -246,6 +468,7 @@ impl EmulationThreadHandle {
             audio_subsystem,
             audio_queue,
             audio_device,
+            rumble,
             command_sender,
         })
     }
@@ -254,6 +477,12 @@ impl EmulationThreadHandle {
         self.surface_renderer.handle_resize(size);
     }
 
+    /// See [`SurfaceRenderer::window_to_frame_position`]
+    #[must_use]
+    pub fn window_to_frame_position(&self, position: PhysicalPosition<f64>) -> Option<(u16, u16)> {
+        self.surface_renderer.window_to_frame_position(position)
+    }
+
     #[allow(clippy::missing_errors_doc)]
     pub fn handle_config_change(&mut self, config: &AppConfig) -> anyhow::Result<()> {
         self.swap_chain.update_config(&config.graphics);
@@ -271,7 +500,7 @@ impl EmulationThreadHandle {
             self.audio_device.resume();
         }
 
-        self.send_command(EmulatorThreadCommand::UpdateConfig(config.clone()));
+        self.send_command(EmulatorThreadCommand::UpdateConfig(Box::new(config.clone())));
 
         Ok(())
     }
@@ -286,6 +515,13 @@ impl EmulationThreadHandle {
         }
     }
 
+    /// Controller motor state as of the last frame the emulator rendered
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn rumble(&self) -> Ps1Rumble {
+        *self.rumble.lock().unwrap()
+    }
+
     pub fn swap_chain(&mut self) -> &mut EmulatorSwapChain {
         &mut self.swap_chain
     }
@@ -302,21 +538,161 @@ struct EmulatorRunner {
     audio_output: QueueAudioOutput,
     audio_sync_threshold: u32,
     save_writer: FsSaveWriter,
+    rumble_output: SharedRumbleOutput,
     inputs: Ps1Inputs,
-    save_state_path: PathBuf,
+    analog_config: [AnalogConfig; 2],
+    // Indexed by port and multitap slot
+    host_analog: [[HostAnalogState; MULTITAP_SLOTS]; 2],
+    exe: Option<Vec<u8>>,
+    playlist: Option<Playlist>,
+    lid_close_frames_remaining: Option<u32>,
+    game_id: String,
+    save_state_paths: Vec<PathBuf>,
+    movie_path: PathBuf,
+    movie: MovieState,
+    rewind: RewindBuffer,
     command_receiver: Receiver<EmulatorThreadCommand>,
 }
 
 impl EmulatorRunner {
-    fn process_next_frame(&mut self) -> Result<(), TickError<Never, Never, io::Error>> {
+    // The D-pad and sticks are recomputed after every change because a radial deadzone and D-pad
+    // emulation each depend on more than one host input
+    fn update_host_analog(&mut self, port: InputPort, update: impl FnOnce(&mut HostAnalogState)) {
+        let Some((port_idx, slot)) = port.port_and_slot() else { return };
+        let state = &mut self.host_analog[port_idx][slot];
+        update(state);
+
+        if let Some(inputs) = port_inputs(&mut self.inputs, port) {
+            state.apply(&self.analog_config[port_idx], inputs);
+        }
+    }
+
+    fn process_next_frame(&mut self) -> Result<(), TickError<Never, Never, io::Error, Never>> {
+        let inputs = self.movie.next_frame_inputs(self.inputs);
+
         while self.emulator.tick(
-            self.inputs,
+            inputs,
             &mut self.renderer,
             &mut self.audio_output,
             &mut self.save_writer,
+            &mut self.rumble_output,
         )? != TickEffect::FrameRendered
         {}
 
+        if let Some(frames_remaining) = &mut self.lid_close_frames_remaining {
+            *frames_remaining -= 1;
+            if *frames_remaining == 0 {
+                self.emulator.close_cd_lid();
+                self.lid_close_frames_remaining = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn next_disc(&mut self) -> anyhow::Result<()> {
+        let Some(playlist) = &mut self.playlist else {
+            return Err(anyhow!("Not running a multi-disc playlist"));
+        };
+
+        // Only advance once the next disc has opened successfully so that the playlist position
+        // still matches the disc in the drive if it fails
+        let next = playlist.next_disc();
+        let disc = open_disc(&next.path, next.disc_index)?;
+        playlist.advance();
+
+        let current = playlist.current();
+        log::info!(
+            "Inserting disc {} of {}: '{}'",
+            playlist.current_index() + 1,
+            playlist.len(),
+            current.path.display()
+        );
+
+        // Leave the lid open long enough for software to notice that the disc was changed
+        self.emulator.insert_disc(disc);
+        self.lid_close_frames_remaining = Some(LID_OPEN_FRAMES);
+        self.rewind.clear();
+
+        Ok(())
+    }
+
+    fn rewind_step(&mut self) -> anyhow::Result<()> {
+        if let Some(state) = self.rewind.step_back()? {
+            self.emulator =
+                Ps1Emulator::from_state(state, self.emulator.take_unserialized_fields());
+        }
+
+        Ok(())
+    }
+
+    fn save_state(&mut self, slot: usize) -> anyhow::Result<()> {
+        let header = SaveStateHeader::new(self.game_id.clone(), &self.emulator.screenshot());
+        let identity = self.emulator.content_identity()?;
+        savestate::write(
+            &self.save_state_paths[slot],
+            &header,
+            identity,
+            &self.emulator.save_state(),
+        )
+    }
+
+    fn load_state(&mut self, slot: usize) -> anyhow::Result<()> {
+        let identity = self.emulator.content_identity()?;
+        let state = savestate::read(&self.save_state_paths[slot], identity)?;
+
+        self.emulator = Ps1Emulator::from_state(state, self.emulator.take_unserialized_fields());
+        self.rewind.clear();
+
+        Ok(())
+    }
+
+    fn power_cycle(&mut self, ram_seed: u64) -> anyhow::Result<()> {
+        self.emulator.power_cycle(ram_seed);
+        self.rewind.clear();
+        if let Some(exe) = &self.exe {
+            self.emulator.run_until_exe_sideloaded(exe)?;
+        }
+
+        Ok(())
+    }
+
+    fn start_movie_recording(&mut self, start: MovieRecordStart) -> anyhow::Result<()> {
+        self.movie.stop(&self.movie_path)?;
+
+        let start = match start {
+            MovieRecordStart::PowerOn => {
+                let ram_seed = rand::random();
+                self.power_cycle(ram_seed)?;
+                MovieStart::PowerOn { ram_seed }
+            }
+            MovieRecordStart::CurrentState => {
+                MovieStart::SaveState(encode_state(&mut self.emulator)?)
+            }
+        };
+
+        let identity = self.emulator.content_identity()?;
+        self.movie = MovieState::Recording(Movie::new(identity, start));
+        self.rewind.clear();
+
+        Ok(())
+    }
+
+    fn start_movie_playback(&mut self) -> anyhow::Result<()> {
+        self.movie.stop(&self.movie_path)?;
+
+        let movie = Movie::read(&mut BufReader::new(File::open(&self.movie_path)?))?;
+        movie.check_identity(self.emulator.content_identity()?)?;
+
+        match movie.start() {
+            &MovieStart::PowerOn { ram_seed } => self.power_cycle(ram_seed)?,
+            MovieStart::SaveState(state) => decode_state(&mut self.emulator, state)?,
+        }
+
+        log::info!("Playing {}-frame movie", movie.len());
+        self.movie = MovieState::Playing { movie, next_frame: 0 };
+        self.rewind.clear();
+
         Ok(())
     }
 }
@@ -326,16 +702,29 @@ fn spawn_emu_thread(mut runner: EmulatorRunner) {
         let mut paused = false;
         let mut step_frame = false;
         let mut fast_forward = false;
+        let mut rewinding = false;
 
         loop {
             if (!paused || step_frame)
                 && (fast_forward
                     || (runner.audio_output.samples_len() as u32) < runner.audio_sync_threshold)
             {
+                if rewinding {
+                    if let Err(err) = runner.rewind_step() {
+                        log::error!("Error loading rewind snapshot: {err:?}");
+                    }
+                }
+
                 if let Err(err) = runner.process_next_frame() {
                     log::error!("Video/audio/save write error: {err:?}");
                 }
 
+                if !rewinding {
+                    if let Err(err) = runner.rewind.record_frame(&mut runner.emulator) {
+                        log::error!("Error recording rewind snapshot: {err:?}");
+                    }
+                }
+
                 step_frame = false;
             }
 
@@ -349,50 +738,95 @@ fn spawn_emu_thread(mut runner: EmulatorRunner) {
                 match command {
                     EmulatorThreadCommand::Stop => {
                         log::info!("Stopping emulator thread");
+                        runner.rumble_output.stop();
+                        if let Err(err) = runner.movie.stop(&runner.movie_path) {
+                            log::error!("Error saving movie: {err:?}");
+                        }
                         return;
                     }
-                    EmulatorThreadCommand::DigitalInput { button, pressed } => {
-                        update_digital_inputs(&mut runner.inputs, button, pressed);
+                    EmulatorThreadCommand::DigitalInput { port, button, pressed } => {
+                        if let Some(inputs) = port_inputs(&mut runner.inputs, port) {
+                            update_digital_inputs(inputs, button, pressed);
+                        }
+                        runner.update_host_analog(port, |state| state.set_dpad(button, pressed));
+                    }
+                    EmulatorThreadCommand::AnalogInput { port, input, value } => {
+                        if let Some(inputs) = port_inputs(&mut runner.inputs, port) {
+                            update_analog_inputs(inputs, input, value);
+                        }
+                        runner.update_host_analog(port, |state| state.set_axis(input, value));
+                    }
+                    EmulatorThreadCommand::MouseMotion { dx, dy } => {
+                        for inputs in mouse_inputs(&mut runner.inputs) {
+                            inputs.mouse.add_motion(dx, dy);
+                        }
+                    }
+                    EmulatorThreadCommand::MouseButton { button, pressed } => {
+                        for inputs in mouse_inputs(&mut runner.inputs) {
+                            match button {
+                                Ps1MouseButton::Left => inputs.mouse.left_button = pressed,
+                                Ps1MouseButton::Right => inputs.mouse.right_button = pressed,
+                            }
+                        }
                     }
-                    EmulatorThreadCommand::AnalogInput { input, value } => {
-                        update_analog_inputs(&mut runner.inputs, input, value);
+                    EmulatorThreadCommand::LightGunAim { aim } => {
+                        for inputs in light_gun_inputs(&mut runner.inputs) {
+                            inputs.light_gun.aim = aim;
+                        }
+                    }
+                    EmulatorThreadCommand::LightGunButton { button, pressed } => {
+                        for inputs in light_gun_inputs(&mut runner.inputs) {
+                            match button {
+                                Ps1LightGunButton::Trigger => inputs.light_gun.trigger = pressed,
+                                Ps1LightGunButton::A => inputs.light_gun.button_a = pressed,
+                                Ps1LightGunButton::B => inputs.light_gun.button_b = pressed,
+                            }
+                        }
                     }
                     EmulatorThreadCommand::UpdateConfig(config) => {
-                        runner.emulator.update_config(config.to_emulator_config());
+                        runner
+                            .emulator
+                            .update_config(config.to_emulator_config(Some(&runner.game_id)));
                         runner.audio_sync_threshold = config.audio.sync_threshold;
                         update_input_config(&config, &mut runner.inputs);
+                        runner.analog_config = config.input.analog;
+                        runner.host_analog = Default::default();
                     }
-                    EmulatorThreadCommand::SaveState => {
-                        match save_state(&mut runner.emulator, &runner.save_state_path) {
+                    EmulatorThreadCommand::SaveState { slot } => {
+                        let path = runner.save_state_paths[slot].clone();
+                        match runner.save_state(slot) {
                             Ok(()) => {
-                                log::info!("Saved state to '{}'", runner.save_state_path.display());
+                                log::info!("Saved state to '{}'", path.display());
                             }
                             Err(err) => {
-                                log::error!(
-                                    "Error saving state to '{}': {err}",
-                                    runner.save_state_path.display()
-                                );
+                                log::error!("Error saving state to '{}': {err}", path.display());
                             }
                         }
                     }
-                    EmulatorThreadCommand::LoadState => {
-                        match load_state(&mut runner.emulator, &runner.save_state_path) {
+                    EmulatorThreadCommand::LoadState { slot } => {
+                        // Loading a state would desync any recording or playback in progress
+                        if let Err(err) = runner.movie.stop(&runner.movie_path) {
+                            log::error!("Error saving movie: {err:?}");
+                        }
+
+                        let path = runner.save_state_paths[slot].clone();
+                        match runner.load_state(slot) {
                             Ok(()) => {
-                                log::info!(
-                                    "Loaded state from '{}'",
-                                    runner.save_state_path.display()
-                                );
+                                log::info!("Loaded state from '{}'", path.display());
                             }
                             Err(err) => {
-                                log::error!(
-                                    "Error loading state from '{}': {err}",
-                                    runner.save_state_path.display()
-                                );
+                                log::error!("Error loading state from '{}': {err}", path.display());
                             }
                         }
                     }
                     EmulatorThreadCommand::TogglePause => {
                         paused = !paused;
+
+                        // Don't leave controllers vibrating while paused; the game's next poll after
+                        // unpausing will restore the motor state
+                        if paused {
+                            runner.rumble_output.stop();
+                        }
                     }
                     EmulatorThreadCommand::StepFrame => {
                         step_frame = true;
@@ -406,6 +840,49 @@ fn spawn_emu_thread(mut runner: EmulatorRunner) {
                             runner.renderer.clear_swap_chain();
                         }
                     }
+                    EmulatorThreadCommand::Rewind { enabled } => {
+                        rewinding = enabled;
+
+                        // Rewinding would desync any recording or playback in progress
+                        if rewinding {
+                            if let Err(err) = runner.movie.stop(&runner.movie_path) {
+                                log::error!("Error saving movie: {err:?}");
+                            }
+                        }
+                    }
+                    EmulatorThreadCommand::RecordMovie { start } => {
+                        match runner.start_movie_recording(start) {
+                            Ok(()) => {
+                                log::info!("Started recording movie from {start:?}");
+                            }
+                            Err(err) => {
+                                log::error!("Error starting movie recording: {err:?}");
+                            }
+                        }
+                    }
+                    EmulatorThreadCommand::PlayMovie => {
+                        if let Err(err) = runner.start_movie_playback() {
+                            log::error!(
+                                "Error playing movie from '{}': {err:?}",
+                                runner.movie_path.display()
+                            );
+                        }
+                    }
+                    EmulatorThreadCommand::NextDisc => {
+                        // Changing discs would desync any recording or playback in progress
+                        if let Err(err) = runner.movie.stop(&runner.movie_path) {
+                            log::error!("Error saving movie: {err:?}");
+                        }
+
+                        if let Err(err) = runner.next_disc() {
+                            log::error!("Error changing disc: {err:?}");
+                        }
+                    }
+                    EmulatorThreadCommand::StopMovie => {
+                        if let Err(err) = runner.movie.stop(&runner.movie_path) {
+                            log::error!("Error saving movie: {err:?}");
+                        }
+                    }
                 }
             }
 
@@ -419,11 +896,36 @@ fn spawn_emu_thread(mut runner: EmulatorRunner) {
 fn update_input_config(config: &AppConfig, inputs: &mut Ps1Inputs) {
     inputs.p1.controller_type = config.input.p1_device;
     inputs.p2.controller_type = config.input.p2_device;
+    inputs.p1_multitap =
+        multitap_inputs(config.input.multitap[0], config.input.multitap_devices[0]);
+    inputs.p2_multitap =
+        multitap_inputs(config.input.multitap[1], config.input.multitap_devices[1]);
 
     inputs.p1.digital = DigitalJoypadState::default();
     inputs.p2.digital = DigitalJoypadState::default();
     inputs.p1.analog = AnalogJoypadState::default();
     inputs.p2.analog = AnalogJoypadState::default();
+    inputs.p1.negcon = NeGconState::default();
+    inputs.p2.negcon = NeGconState::default();
+
+    for inputs in [&mut inputs.p1, &mut inputs.p2] {
+        inputs.mouse.left_button = false;
+        inputs.mouse.right_button = false;
+        inputs.light_gun = LightGunState { aim: inputs.light_gun.aim, ..LightGunState::default() };
+    }
+}
+
+fn multitap_inputs(
+    connected: bool,
+    devices: [ControllerType; MULTITAP_SLOTS - 1],
+) -> Option<[ControllerState; MULTITAP_SLOTS - 1]> {
+    connected.then(|| {
+        let mut slots = Ps1Inputs::empty_multitap();
+        for (slot, device) in slots.iter_mut().zip(devices) {
+            slot.controller_type = device;
+        }
+        slots
+    })
 }
 
 macro_rules! impl_update_digital_inputs {
@@ -437,8 +939,36 @@ macro_rules! impl_update_digital_inputs {
     }
 }
 
-fn update_digital_inputs(inputs: &mut Ps1Inputs, button: Ps1Button, pressed: bool) {
-    impl_update_digital_inputs!(inputs.p1, button, pressed, [
+fn port_inputs(inputs: &mut Ps1Inputs, port: InputPort) -> Option<&mut ControllerState> {
+    match port {
+        InputPort::P1 => Some(&mut inputs.p1),
+        InputPort::P2 => Some(&mut inputs.p2),
+        InputPort::P1B => inputs.p1_multitap.as_mut().map(|slots| &mut slots[0]),
+        InputPort::P1C => inputs.p1_multitap.as_mut().map(|slots| &mut slots[1]),
+        InputPort::P1D => inputs.p1_multitap.as_mut().map(|slots| &mut slots[2]),
+        InputPort::P2B => inputs.p2_multitap.as_mut().map(|slots| &mut slots[0]),
+        InputPort::P2C => inputs.p2_multitap.as_mut().map(|slots| &mut slots[1]),
+        InputPort::P2D => inputs.p2_multitap.as_mut().map(|slots| &mut slots[2]),
+        InputPort::None => None,
+    }
+}
+
+// Every controller in both ports, including multitap slots B-D
+fn all_inputs(inputs: &mut Ps1Inputs) -> impl Iterator<Item = &mut ControllerState> {
+    let multitap_slots = inputs.p1_multitap.iter_mut().chain(&mut inputs.p2_multitap).flatten();
+    [&mut inputs.p1, &mut inputs.p2].into_iter().chain(multitap_slots)
+}
+
+fn mouse_inputs(inputs: &mut Ps1Inputs) -> impl Iterator<Item = &mut ControllerState> {
+    all_inputs(inputs).filter(|inputs| inputs.controller_type == ControllerType::Mouse)
+}
+
+fn light_gun_inputs(inputs: &mut Ps1Inputs) -> impl Iterator<Item = &mut ControllerState> {
+    all_inputs(inputs).filter(|inputs| inputs.controller_type.is_light_gun())
+}
+
+fn update_digital_inputs(inputs: &mut ControllerState, button: Ps1Button, pressed: bool) {
+    impl_update_digital_inputs!(inputs, button, pressed, [
         Up => set_up,
         Down => set_down,
         Left => set_left,
@@ -454,46 +984,85 @@ fn update_digital_inputs(inputs: &mut Ps1Inputs, button: Ps1Button, pressed: boo
         Start => set_start,
         Select => set_select,
     ]);
+
+    // The NeGcon's I, II, and L buttons are where Cross, Square, and L1 would be on a standard
+    // controller, so those buttons press them fully for keyboard and digital-only bindings
+    if inputs.controller_type == ControllerType::NeGcon {
+        let value = if pressed { 0xFF } else { 0x00 };
+        match button {
+            Ps1Button::Cross => inputs.negcon.button_i = value,
+            Ps1Button::Square => inputs.negcon.button_ii = value,
+            Ps1Button::L1 => inputs.negcon.button_l = value,
+            _ => {}
+        }
+    }
 }
 
-fn update_analog_inputs(inputs: &mut Ps1Inputs, input: Ps1AnalogInput, value: i16) {
+fn update_analog_inputs(inputs: &mut ControllerState, input: Ps1AnalogInput, value: i16) {
     // Map from [-32768, 32767] to [0, 255]
     let converted_value = ((i32::from(value) + 0x8000) >> 8) as u8;
     match input {
-        Ps1AnalogInput::LeftStickX => inputs.p1.analog.left_x = converted_value,
-        Ps1AnalogInput::LeftStickY => inputs.p1.analog.left_y = converted_value,
-        Ps1AnalogInput::RightStickX => inputs.p1.analog.right_x = converted_value,
-        Ps1AnalogInput::RightStickY => inputs.p1.analog.right_y = converted_value,
+        // Sticks go through deadzone and sensitivity processing in `HostAnalogState`
+        Ps1AnalogInput::LeftStickX
+        | Ps1AnalogInput::LeftStickY
+        | Ps1AnalogInput::RightStickX
+        | Ps1AnalogInput::RightStickY => {}
+        Ps1AnalogInput::NeGconTwist => inputs.negcon.twist = converted_value,
+        // Pressure-sensitive buttons only use the positive half of the axis, which covers the full
+        // range of a trigger
+        Ps1AnalogInput::NeGconI => inputs.negcon.button_i = pressure(value),
+        Ps1AnalogInput::NeGconII => inputs.negcon.button_ii = pressure(value),
+        Ps1AnalogInput::NeGconL => inputs.negcon.button_l = pressure(value),
     }
 }
 
-macro_rules! bincode_config {
-    () => {
-        bincode::config::standard()
-            .with_little_endian()
-            .with_fixed_int_encoding()
-            .with_limit::<1_000_000_000>()
-    };
+fn pressure(value: i16) -> u8 {
+    (value.max(0) >> 7) as u8
 }
 
-fn save_state(emulator: &mut Ps1Emulator, path: &Path) -> anyhow::Result<()> {
-    let file = File::create(path)?;
-    let mut writer = BufWriter::new(file);
-    bincode::encode_into_std_write(emulator.save_state(), &mut writer, bincode_config!())?;
+fn encode_state(emulator: &mut Ps1Emulator) -> anyhow::Result<Vec<u8>> {
+    let identity = emulator.content_identity()?;
 
-    Ok(())
+    let mut bytes = Vec::new();
+    ps1_core::savestate::write(&mut bytes, identity, &emulator.save_state())?;
+
+    Ok(bytes)
 }
 
-fn load_state(emulator: &mut Ps1Emulator, path: &Path) -> anyhow::Result<()> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let state: Ps1EmulatorState = bincode::decode_from_std_read(&mut reader, bincode_config!())?;
+fn decode_state(emulator: &mut Ps1Emulator, mut bytes: &[u8]) -> anyhow::Result<()> {
+    let identity = emulator.content_identity()?;
+    let state = ps1_core::savestate::read(&mut bytes, identity)?;
 
     *emulator = Ps1Emulator::from_state(state, emulator.take_unserialized_fields());
 
     Ok(())
 }
 
+// `disc_index` selects a disc from a multi-disc PBP file and is ignored for other formats
+fn open_disc(path: &Path, disc_index: usize) -> anyhow::Result<CdRom> {
+    let format = match CdRomFileFormat::from_file_path(path) {
+        Some(CdRomFileFormat::Pbp { .. }) => CdRomFileFormat::Pbp { disc_index },
+        Some(format) => format,
+        None => return Err(anyhow!("Unsupported disc image format: '{}'", path.display())),
+    };
+
+    CdRom::open(path, format)
+        .with_context(|| format!("Failed to open disc image '{}'", path.display()))
+}
+
+fn open_link_cable(config: &LinkCableConfig) -> anyhow::Result<Option<LinkCable>> {
+    let address = LinkAddress::parse(&config.address);
+    let link_cable = match config.mode {
+        LinkCableMode::Disabled => return Ok(None),
+        LinkCableMode::Listen => LinkCable::listen(address.clone()),
+        LinkCableMode::Connect => LinkCable::connect(address.clone()),
+    };
+
+    link_cable
+        .map(Some)
+        .with_context(|| format!("Failed to open link cable connection on '{address}'"))
+}
+
 fn sleep(duration: Duration) {
     cfg_if! {
         if #[cfg(target_os = "windows")] {
@@ -508,34 +1077,69 @@ fn sleep(duration: Duration) {
     }
 }
 
-const MEMORY_CARDS_DIRECTORY: &str = "memcards";
-const SAVE_STATES_DIRECTORY: &str = "states";
+// Roughly 2 seconds
+const LID_OPEN_FRAMES: u32 = 120;
+
+pub const MEMORY_CARDS_DIRECTORY: &str = "memcards";
+const MOVIES_DIRECTORY: &str = "movies";
 
 struct FsSaveWriter {
-    card_1_path: PathBuf,
+    // None if the slot is empty
+    card_1_path: Option<PathBuf>,
+    card_2_path: Option<PathBuf>,
+    // Multitap slots B-D of each port
+    multitap_card_paths: [[Option<PathBuf>; MULTITAP_SLOTS - 1]; 2],
 }
 
 impl FsSaveWriter {
-    fn from_path(path: &Path) -> anyhow::Result<Self> {
-        static DISC_REV_REGEX: OnceLock<Regex> = OnceLock::new();
+    fn new(file_path: Option<&Path>, config: &MemoryCardsConfig) -> anyhow::Result<Self> {
+        let mut multitap_card_paths: [[Option<PathBuf>; MULTITAP_SLOTS - 1]; 2] =
+            Default::default();
+        for (port, (paths, modes)) in
+            multitap_card_paths.iter_mut().zip(config.multitap_slots).enumerate()
+        {
+            for ((path, mode), slot) in paths.iter_mut().zip(modes).zip(['B', 'C', 'D']) {
+                *path = memory_card_path(file_path, mode, &format!("{}{slot}", port + 1))?;
+            }
+        }
+
+        Ok(Self {
+            card_1_path: memory_card_path(file_path, config.slot_1, "1")?,
+            card_2_path: memory_card_path(file_path, config.slot_2, "2")?,
+            multitap_card_paths,
+        })
+    }
+}
 
-        let path_no_ext = path.with_extension("");
-        let file_name_no_ext =
-            path_no_ext.file_name().and_then(OsStr::to_str).ok_or_else(|| {
-                anyhow!("Unable to determine file extension for path: {}", path.display())
-            })?;
+fn memory_card_path(
+    file_path: Option<&Path>,
+    mode: MemoryCardMode,
+    slot: &str,
+) -> anyhow::Result<Option<PathBuf>> {
+    static DISC_REV_REGEX: OnceLock<Regex> = OnceLock::new();
+
+    let path = match (mode, file_path) {
+        (MemoryCardMode::None, _) => return Ok(None),
+        (MemoryCardMode::PerGame, Some(file_path)) => file_path,
+        // Running the BIOS without a game always uses the shared cards
+        (MemoryCardMode::Shared, _) | (MemoryCardMode::PerGame, None) => Path::new("global"),
+    };
 
-        let disc_rev_regex = DISC_REV_REGEX
-            .get_or_init(|| Regex::new(r"( \(Disc [1-9]\))?( \(Rev [1-9]\))?$").unwrap());
+    let path_no_ext = path.with_extension("");
+    let file_name_no_ext = path_no_ext.file_name().and_then(OsStr::to_str).ok_or_else(|| {
+        anyhow!("Unable to determine file extension for path: {}", path.display())
+    })?;
 
-        let file_name_no_disc = disc_rev_regex.replace(file_name_no_ext, "");
-        let card_1_file_name = format!("{file_name_no_disc}_1.mcd");
-        let card_1_path = PathBuf::from(MEMORY_CARDS_DIRECTORY).join(card_1_file_name);
+    let disc_rev_regex =
+        DISC_REV_REGEX.get_or_init(|| Regex::new(r"( \(Disc [1-9]\))?( \(Rev [1-9]\))?$").unwrap());
 
-        ensure_parent_dir_exists(&card_1_path)?;
+    let file_name_no_disc = disc_rev_regex.replace(file_name_no_ext, "");
+    let card_file_name = format!("{file_name_no_disc}_{slot}.mcd");
+    let card_path = PathBuf::from(MEMORY_CARDS_DIRECTORY).join(card_file_name);
 
-        Ok(Self { card_1_path })
-    }
+    ensure_parent_dir_exists(&card_path)?;
+
+    Ok(Some(card_path))
 }
 
 fn ensure_parent_dir_exists(path: &Path) -> anyhow::Result<()> {
@@ -552,22 +1156,69 @@ impl SaveWriter for FsSaveWriter {
     type Err = io::Error;
 
     fn save_memory_card_1(&mut self, card_data: &[u8]) -> Result<(), Self::Err> {
-        fs::write(&self.card_1_path, card_data)?;
-        log::debug!("Saved memory card 1 to {}", self.card_1_path.display());
+        let Some(path) = &self.card_1_path else { return Ok(()) };
+        fs::write(path, card_data)?;
+        log::debug!("Saved memory card 1 to {}", path.display());
+        Ok(())
+    }
+
+    fn save_memory_card_2(&mut self, card_data: &[u8]) -> Result<(), Self::Err> {
+        let Some(path) = &self.card_2_path else { return Ok(()) };
+        fs::write(path, card_data)?;
+        log::debug!("Saved memory card 2 to {}", path.display());
+        Ok(())
+    }
+
+    fn save_multitap_memory_card(
+        &mut self,
+        port: usize,
+        slot: usize,
+        card_data: &[u8],
+    ) -> Result<(), Self::Err> {
+        let Some(path) = &self.multitap_card_paths[port][slot] else { return Ok(()) };
+        fs::write(path, card_data)?;
+        log::debug!("Saved multitap memory card to {}", path.display());
+        Ok(())
+    }
+}
+
+struct SharedRumbleOutput {
+    rumble: Arc<Mutex<Ps1Rumble>>,
+}
+
+impl SharedRumbleOutput {
+    fn new() -> Self {
+        Self { rumble: Arc::new(Mutex::new(Ps1Rumble::default())) }
+    }
+
+    fn stop(&mut self) {
+        *self.rumble.lock().unwrap() = Ps1Rumble::default();
+    }
+}
+
+impl RumbleOutput for SharedRumbleOutput {
+    type Err = Never;
+
+    fn set_rumble(&mut self, rumble: Ps1Rumble) -> Result<(), Self::Err> {
+        *self.rumble.lock().unwrap() = rumble;
         Ok(())
     }
 }
 
-fn determine_save_state_path(file_path: Option<&Path>) -> anyhow::Result<PathBuf> {
+fn determine_path(
+    file_path: Option<&Path>,
+    directory: &str,
+    extension: &str,
+) -> anyhow::Result<PathBuf> {
     let path_no_ext = file_path.unwrap_or(&PathBuf::from("bios")).with_extension("");
     let file_name_no_ext = path_no_ext.file_name().and_then(OsStr::to_str).ok_or_else(|| {
         anyhow!("Unable to determine file extension for path: {}", path_no_ext.display())
     })?;
 
-    let state_file_name = format!("{file_name_no_ext}.sst");
-    let state_path = PathBuf::from(SAVE_STATES_DIRECTORY).join(state_file_name);
+    let file_name = format!("{file_name_no_ext}.{extension}");
+    let path = PathBuf::from(directory).join(file_name);
 
-    ensure_parent_dir_exists(&state_path)?;
+    ensure_parent_dir_exists(&path)?;
 
-    Ok(state_path)
+    Ok(path)
 }
@@ -0,0 +1,156 @@
+//! Host analog stick processing: deadzone, sensitivity, inversion, and stick/D-pad emulation
+//!
+//! Stick axes arrive one at a time, but a radial deadzone depends on both axes of a stick, so the
+//! unprocessed host state is kept per controller and the emulated sticks and D-pad are recomputed
+//! from it on every change.
+
+use crate::config::{AnalogConfig, DeadzoneShape};
+use crate::emuthread::{Ps1AnalogInput, Ps1Button};
+use ps1_core::input::ControllerState;
+
+// Fraction of full deflection at which the left stick presses a D-pad direction
+const STICK_TO_DPAD_THRESHOLD: f64 = 0.5;
+
+/// Unprocessed host stick positions and D-pad presses for one controller
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HostAnalogState {
+    left_stick: (i16, i16),
+    right_stick: (i16, i16),
+    up: bool,
+    down: bool,
+    left: bool,
+    right: bool,
+}
+
+impl HostAnalogState {
+    /// Record a stick axis; inputs other than stick axes are ignored
+    pub fn set_axis(&mut self, input: Ps1AnalogInput, value: i16) {
+        match input {
+            Ps1AnalogInput::LeftStickX => self.left_stick.0 = value,
+            Ps1AnalogInput::LeftStickY => self.left_stick.1 = value,
+            Ps1AnalogInput::RightStickX => self.right_stick.0 = value,
+            Ps1AnalogInput::RightStickY => self.right_stick.1 = value,
+            Ps1AnalogInput::NeGconTwist
+            | Ps1AnalogInput::NeGconI
+            | Ps1AnalogInput::NeGconII
+            | Ps1AnalogInput::NeGconL => {}
+        }
+    }
+
+    /// Record a D-pad press; other buttons are ignored
+    pub fn set_dpad(&mut self, button: Ps1Button, pressed: bool) {
+        match button {
+            Ps1Button::Up => self.up = pressed,
+            Ps1Button::Down => self.down = pressed,
+            Ps1Button::Left => self.left = pressed,
+            Ps1Button::Right => self.right = pressed,
+            _ => {}
+        }
+    }
+
+    /// Update the emulated sticks and D-pad in `inputs`
+    pub fn apply(&self, config: &AnalogConfig, inputs: &mut ControllerState) {
+        let mut left_stick =
+            process_stick(config, self.left_stick, (config.invert_left_x, config.invert_left_y));
+        let right_stick =
+            process_stick(config, self.right_stick, (config.invert_right_x, config.invert_right_y));
+
+        let (mut up, mut down, mut left, mut right) = (self.up, self.down, self.left, self.right);
+
+        if config.left_stick_to_dpad {
+            up |= left_stick.1 <= -STICK_TO_DPAD_THRESHOLD;
+            down |= left_stick.1 >= STICK_TO_DPAD_THRESHOLD;
+            left |= left_stick.0 <= -STICK_TO_DPAD_THRESHOLD;
+            right |= left_stick.0 >= STICK_TO_DPAD_THRESHOLD;
+        }
+
+        if config.dpad_to_left_stick && (self.up || self.down || self.left || self.right) {
+            left_stick = (
+                f64::from(i8::from(self.right) - i8::from(self.left)),
+                f64::from(i8::from(self.down) - i8::from(self.up)),
+            );
+        }
+
+        inputs.analog.left_x = to_axis_value(left_stick.0);
+        inputs.analog.left_y = to_axis_value(left_stick.1);
+        inputs.analog.right_x = to_axis_value(right_stick.0);
+        inputs.analog.right_y = to_axis_value(right_stick.1);
+
+        inputs.digital.set_up(up);
+        inputs.digital.set_down(down);
+        inputs.digital.set_left(left);
+        inputs.digital.set_right(right);
+    }
+}
+
+// Returns the stick position in [-1, 1] on each axis
+fn process_stick(
+    config: &AnalogConfig,
+    (x, y): (i16, i16),
+    (invert_x, invert_y): (bool, bool),
+) -> (f64, f64) {
+    let normalize = |value: i16, invert: bool| {
+        let value = (f64::from(value) / f64::from(i16::MAX)).clamp(-1.0, 1.0);
+        if invert { -value } else { value }
+    };
+    let (x, y) = (normalize(x, invert_x), normalize(y, invert_y));
+
+    let deadzone = f64::from(config.deadzone.min(99)) / 100.0;
+    let sensitivity = f64::from(config.sensitivity) / 100.0;
+
+    // Travel outside of the deadzone is rescaled to start from 0
+    let scale = |distance: f64| (distance - deadzone).max(0.0) / (1.0 - deadzone) * sensitivity;
+
+    match config.deadzone_shape {
+        DeadzoneShape::Radial => {
+            let distance = x.hypot(y);
+            if distance <= deadzone {
+                return (0.0, 0.0);
+            }
+
+            let factor = scale(distance) / distance;
+            ((x * factor).clamp(-1.0, 1.0), (y * factor).clamp(-1.0, 1.0))
+        }
+        DeadzoneShape::Axial => {
+            let axis = |value: f64| (value.signum() * scale(value.abs())).clamp(-1.0, 1.0);
+            (axis(x), axis(y))
+        }
+    }
+}
+
+// Map from [-1, 1] to [0, 255], with 0 at the center value of $80
+fn to_axis_value(value: f64) -> u8 {
+    (128.0 + value * 128.0).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ps1_core::input::Ps1Inputs;
+
+    #[test]
+    fn radial_deadzone_and_stick_to_dpad() {
+        let config =
+            AnalogConfig { deadzone: 20, left_stick_to_dpad: true, ..AnalogConfig::default() };
+        let mut state = HostAnalogState::default();
+        let mut inputs = Ps1Inputs::default().p1;
+
+        // Inside the radial deadzone
+        state.set_axis(Ps1AnalogInput::LeftStickX, 4000);
+        state.set_axis(Ps1AnalogInput::LeftStickY, 4000);
+        state.apply(&config, &mut inputs);
+        assert_eq!((inputs.analog.left_x, inputs.analog.left_y), (0x80, 0x80));
+
+        state.set_axis(Ps1AnalogInput::LeftStickX, i16::MIN);
+        state.set_axis(Ps1AnalogInput::LeftStickY, 0);
+        state.apply(&config, &mut inputs);
+        assert_eq!((inputs.analog.left_x, inputs.analog.left_y), (0x00, 0x80));
+        assert!(inputs.digital.left() && !inputs.digital.right() && !inputs.digital.up());
+
+        // D-pad presses are kept while the stick is centered
+        state.set_axis(Ps1AnalogInput::LeftStickX, 0);
+        state.set_dpad(Ps1Button::Up, true);
+        state.apply(&config, &mut inputs);
+        assert!(!inputs.digital.left() && inputs.digital.up());
+    }
+}
@@ -0,0 +1,61 @@
+use ps1_core::input::Ps1Inputs;
+use ps1_core::movie::Movie;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+#[derive(Debug, Default)]
+pub enum MovieState {
+    #[default]
+    None,
+    Recording(Movie),
+    Playing {
+        movie: Movie,
+        next_frame: usize,
+    },
+}
+
+impl MovieState {
+    /// Returns the inputs to use for the next frame. Live inputs are recorded while recording and
+    /// replaced by the movie's inputs during playback.
+    pub fn next_frame_inputs(&mut self, live_inputs: Ps1Inputs) -> Ps1Inputs {
+        match self {
+            Self::None => live_inputs,
+            Self::Recording(movie) => {
+                movie.push_frame(live_inputs);
+                live_inputs
+            }
+            Self::Playing { movie, next_frame } => match movie.frame_inputs(*next_frame) {
+                Some(inputs) => {
+                    *next_frame += 1;
+                    inputs
+                }
+                None => {
+                    log::info!("Movie playback finished after {next_frame} frames");
+                    *self = Self::None;
+                    live_inputs
+                }
+            },
+        }
+    }
+
+    /// Stop any recording or playback in progress, writing the movie to the given path if it was
+    /// being recorded.
+    pub fn stop(&mut self, path: &Path) -> anyhow::Result<()> {
+        match std::mem::take(self) {
+            Self::None => {}
+            Self::Recording(movie) => {
+                let mut writer = BufWriter::new(File::create(path)?);
+                movie.write(&mut writer)?;
+                writer.flush()?;
+
+                log::info!("Saved {}-frame movie to '{}'", movie.len(), path.display());
+            }
+            Self::Playing { next_frame, .. } => {
+                log::info!("Stopped movie playback at frame {next_frame}");
+            }
+        }
+
+        Ok(())
+    }
+}
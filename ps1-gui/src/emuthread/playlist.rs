@@ -0,0 +1,115 @@
+use anyhow::{Context, anyhow};
+use cdrom::reader::CdRom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A disc image in a playlist. Multi-disc PBP files contain several discs in one file, selected
+/// by `disc_index`; it is always 0 for other formats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistDisc {
+    pub path: PathBuf,
+    pub disc_index: usize,
+}
+
+/// The discs of a multi-disc game, in order.
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    discs: Vec<PlaylistDisc>,
+    current: usize,
+}
+
+impl Playlist {
+    /// Read an M3U playlist, which lists one disc image path per line. Relative paths are relative
+    /// to the directory containing the playlist, and lines starting with `#` are comments.
+    pub fn read_m3u(path: &Path) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let discs = parse_m3u(&contents, path.parent().unwrap_or(Path::new("")));
+        if discs.is_empty() {
+            return Err(anyhow!("M3U playlist '{}' does not list any discs", path.display()));
+        }
+
+        Ok(Self { discs, current: 0 })
+    }
+
+    /// Read the disc list from a PSP eboot PBP file. Returns `None` if the file contains only one
+    /// disc.
+    pub fn read_pbp(path: &Path) -> anyhow::Result<Option<Self>> {
+        let disc_count = CdRom::pbp_disc_count(path)
+            .with_context(|| format!("Failed to read PBP file '{}'", path.display()))?;
+        if disc_count <= 1 {
+            return Ok(None);
+        }
+
+        let discs = (0..disc_count)
+            .map(|disc_index| PlaylistDisc { path: path.into(), disc_index })
+            .collect();
+
+        Ok(Some(Self { discs, current: 0 }))
+    }
+
+    pub fn current(&self) -> &PlaylistDisc {
+        &self.discs[self.current]
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn len(&self) -> usize {
+        self.discs.len()
+    }
+
+    /// The disc after the current one, wrapping around to the first disc after the last one.
+    pub fn next_disc(&self) -> &PlaylistDisc {
+        &self.discs[self.next_index()]
+    }
+
+    /// Advance to the disc returned by [`Self::next_disc`].
+    pub fn advance(&mut self) {
+        self.current = self.next_index();
+    }
+
+    fn next_index(&self) -> usize {
+        (self.current + 1) % self.discs.len()
+    }
+}
+
+fn parse_m3u(contents: &str, base_dir: &Path) -> Vec<PlaylistDisc> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| PlaylistDisc { path: base_dir.join(line), disc_index: 0 })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn m3u_paths_relative_to_playlist() {
+        let contents =
+            "#EXTM3U\r\nGame (Disc 1).cue\r\n\r\n# comment\r\n/abs/Game (Disc 2).chd\r\n";
+        let discs = parse_m3u(contents, Path::new("/games"));
+        assert_eq!(
+            discs.into_iter().map(|disc| disc.path).collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/games/Game (Disc 1).cue"),
+                PathBuf::from("/abs/Game (Disc 2).chd"),
+            ]
+        );
+    }
+
+    #[test]
+    fn next_disc_does_not_advance() {
+        let mut playlist =
+            Playlist { discs: parse_m3u("a.cue\nb.cue\n", Path::new("")), current: 0 };
+        assert_eq!(playlist.next_disc().path, PathBuf::from("b.cue"));
+        assert_eq!(playlist.current_index(), 0);
+
+        playlist.advance();
+        assert_eq!(playlist.current_index(), 1);
+        assert_eq!(playlist.next_disc().path, PathBuf::from("a.cue"));
+    }
+}
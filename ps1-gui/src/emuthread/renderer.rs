@@ -1,12 +1,13 @@
 use crate::config::{AspectRatio, VideoConfig};
 use crate::emuthread::{EmulatorSwapChain, QueuedFrame};
 use crate::{Never, emuthread};
-use ps1_core::api::Renderer;
+use ps1_core::api::{CpuFrame, Renderer};
+use std::collections::HashMap;
 use std::iter;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
 use wgpu::PipelineCompilationOptions;
-use winit::dpi::PhysicalSize;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct FrameSize {
@@ -14,17 +15,12 @@ struct FrameSize {
     height: u32,
 }
 
-impl From<&wgpu::Texture> for FrameSize {
-    fn from(value: &wgpu::Texture) -> Self {
-        Self { width: value.width(), height: value.height() }
-    }
-}
-
 pub struct SwapChainRenderer {
     device: Arc<wgpu::Device>,
     queue: Arc<wgpu::Queue>,
     swap_chain: EmulatorSwapChain,
     in_progress_renders: Arc<AtomicU32>,
+    cpu_frame_textures: HashMap<FrameSize, wgpu::Texture>,
 }
 
 impl SwapChainRenderer {
@@ -33,7 +29,13 @@ impl SwapChainRenderer {
         queue: Arc<wgpu::Queue>,
         swap_chain: EmulatorSwapChain,
     ) -> Self {
-        Self { device, queue, swap_chain, in_progress_renders: Arc::new(AtomicU32::new(0)) }
+        Self {
+            device,
+            queue,
+            swap_chain,
+            in_progress_renders: Arc::new(AtomicU32::new(0)),
+            cpu_frame_textures: HashMap::new(),
+        }
     }
 
     pub fn clear_swap_chain(&self) {
@@ -89,6 +91,47 @@ impl Renderer for SwapChainRenderer {
 
         Ok(())
     }
+
+    fn render_cpu_frame(
+        &mut self,
+        frame: CpuFrame<'_>,
+        pixel_aspect_ratio: f64,
+    ) -> Result<(), Self::Err> {
+        let frame_size = FrameSize { width: frame.width, height: frame.height };
+        // Temporarily take the texture out of the map so that it can be passed to render_frame()
+        let texture = self.cpu_frame_textures.remove(&frame_size).unwrap_or_else(|| {
+            self.device.create_texture(&wgpu::TextureDescriptor {
+                label: "cpu_frame_texture".into(),
+                size: wgpu::Extent3d {
+                    width: frame.width,
+                    height: frame.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+        });
+
+        self.queue.write_texture(
+            texture.as_image_copy(),
+            frame.rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * frame.width),
+                rows_per_image: None,
+            },
+            texture.size(),
+        );
+
+        let result = self.render_frame(iter::empty(), &texture, pixel_aspect_ratio);
+        self.cpu_frame_textures.insert(frame_size, texture);
+
+        result
+    }
 }
 
 pub struct SurfaceRenderer {
@@ -101,6 +144,8 @@ pub struct SurfaceRenderer {
     frame_bind_group_layout: wgpu::BindGroupLayout,
     pipeline: wgpu::RenderPipeline,
     aspect_ratio: AspectRatio,
+    // Size and pixel aspect ratio of the most recently displayed frame
+    last_frame: Option<(wgpu::Extent3d, f64)>,
 }
 
 impl SurfaceRenderer {
@@ -196,6 +241,7 @@ impl SurfaceRenderer {
             frame_bind_group_layout,
             pipeline,
             aspect_ratio: config.aspect_ratio,
+            last_frame: None,
         }
     }
 
@@ -230,8 +276,9 @@ impl SurfaceRenderer {
             }],
         });
 
-        let viewport = determine_viewport(frame.size, self.surface_size, frame.pixel_aspect_ratio);
+        let viewport = self.viewport(frame.size, frame.pixel_aspect_ratio);
         log::trace!("Rendering to viewport {viewport:?}");
+        self.last_frame = Some((frame.size, frame.pixel_aspect_ratio));
 
         let mut encoder =
             self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
@@ -250,16 +297,14 @@ impl SurfaceRenderer {
                 ..wgpu::RenderPassDescriptor::default()
             });
 
-            if self.aspect_ratio == AspectRatio::Native {
-                render_pass.set_viewport(
-                    viewport.x,
-                    viewport.y,
-                    viewport.width,
-                    viewport.height,
-                    0.0,
-                    1.0,
-                );
-            }
+            render_pass.set_viewport(
+                viewport.x,
+                viewport.y,
+                viewport.width,
+                viewport.height,
+                0.0,
+                1.0,
+            );
 
             render_pass.set_pipeline(&self.pipeline);
             render_pass.set_bind_group(0, &self.sampler_bind_group, &[]);
@@ -273,6 +318,31 @@ impl SurfaceRenderer {
 
         Ok(())
     }
+
+    /// Convert a position in the window to a position within the most recently displayed frame,
+    /// scaled so that (0, 0) is the top left corner of the frame and (0xFFFF, 0xFFFF) is the bottom
+    /// right corner. Returns `None` if the position is outside of the frame or no frame has been
+    /// displayed yet.
+    pub fn window_to_frame_position(&self, position: PhysicalPosition<f64>) -> Option<(u16, u16)> {
+        let (frame_size, pixel_aspect_ratio) = self.last_frame?;
+        let (x, y) = self.viewport(frame_size, pixel_aspect_ratio).to_frame(position)?;
+
+        Some(((x * 65536.0) as u16, (y * 65536.0) as u16))
+    }
+
+    fn viewport(&self, frame_size: wgpu::Extent3d, pixel_aspect_ratio: f64) -> Viewport {
+        match self.aspect_ratio {
+            AspectRatio::Native => {
+                determine_viewport(frame_size, self.surface_size, pixel_aspect_ratio)
+            }
+            AspectRatio::Stretched => Viewport {
+                x: 0.0,
+                y: 0.0,
+                width: self.surface_size.width as f32,
+                height: self.surface_size.height as f32,
+            },
+        }
+    }
 }
 
 fn create_sampler_bind_group(
@@ -306,6 +376,17 @@ struct Viewport {
     height: f32,
 }
 
+impl Viewport {
+    // Inverse of the viewport transform: maps a window position to a position in [0, 1) within
+    // the frame
+    fn to_frame(&self, position: PhysicalPosition<f64>) -> Option<(f64, f64)> {
+        let x = (position.x - f64::from(self.x)) / f64::from(self.width);
+        let y = (position.y - f64::from(self.y)) / f64::from(self.height);
+
+        ((0.0..1.0).contains(&x) && (0.0..1.0).contains(&y)).then_some((x, y))
+    }
+}
+
 fn determine_viewport(
     frame_size: wgpu::Extent3d,
     surface_size: wgpu::Extent3d,
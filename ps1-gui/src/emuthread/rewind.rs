@@ -0,0 +1,107 @@
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use ps1_core::api::{Ps1Emulator, Ps1EmulatorState};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+
+// Take a snapshot every 4 frames, or 15 per second for NTSC games
+const SNAPSHOT_INTERVAL_FRAMES: u32 = 4;
+
+// Oldest snapshots are dropped once compressed snapshots exceed this size; at typical compressed
+// sizes this holds several minutes of history
+const MAX_BUFFER_BYTES: usize = 256 * 1024 * 1024;
+
+pub struct RewindBuffer {
+    // Compressed snapshots, oldest first. The large buffers in each snapshot are XORed against the
+    // next newer snapshot, so a snapshot can only be decoded after every newer snapshot has been
+    // decoded, and the oldest snapshot can be dropped at any time
+    deltas: VecDeque<Vec<u8>>,
+    deltas_len: usize,
+    // Most recent snapshot, kept uncompressed so that the next snapshot can be delta-encoded
+    // against it and the previous snapshot can be decoded from it
+    latest: Option<Ps1EmulatorState>,
+    frames_since_snapshot: u32,
+}
+
+impl RewindBuffer {
+    pub fn new() -> Self {
+        Self { deltas: VecDeque::new(), deltas_len: 0, latest: None, frames_since_snapshot: 0 }
+    }
+
+    /// Discard all snapshots. Should be called whenever the emulator jumps to a state that does not
+    /// follow from the last recorded frame, e.g. after loading a state or changing discs, since
+    /// rewinding past that point would pair old RAM and VRAM contents with the current disc.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Should be called after every frame that the emulator runs forwards.
+    pub fn record_frame(&mut self, emulator: &mut Ps1Emulator) -> anyhow::Result<()> {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < SNAPSHOT_INTERVAL_FRAMES {
+            return Ok(());
+        }
+        self.frames_since_snapshot = 0;
+
+        let mut state = emulator.save_state();
+        if let Some(mut previous) = self.latest.take() {
+            xor_large_buffers(&mut previous, &mut state);
+
+            let delta = compress(previous)?;
+            self.deltas_len += delta.len();
+            self.deltas.push_back(delta);
+
+            while self.deltas_len > MAX_BUFFER_BYTES {
+                let Some(oldest) = self.deltas.pop_front() else { break };
+                self.deltas_len -= oldest.len();
+            }
+        }
+        self.latest = Some(state);
+
+        Ok(())
+    }
+
+    /// Step back to the previous snapshot and return it, or return the oldest snapshot if there
+    /// is no more history. Returns `None` if no snapshots have been taken.
+    pub fn step_back(&mut self) -> anyhow::Result<Option<Ps1EmulatorState>> {
+        let Some(latest) = &mut self.latest else { return Ok(None) };
+
+        if let Some(delta) = self.deltas.pop_back() {
+            self.deltas_len -= delta.len();
+
+            let mut previous = decompress(&delta)?;
+            xor_large_buffers(&mut previous, latest);
+            *latest = previous;
+        }
+        self.frames_since_snapshot = 0;
+
+        Ok(Some(latest.clone()))
+    }
+}
+
+fn xor_large_buffers(target: &mut Ps1EmulatorState, other: &mut Ps1EmulatorState) {
+    for (target, other) in target.large_buffers_mut().into_iter().zip(other.large_buffers_mut()) {
+        for (a, b) in target.iter_mut().zip(other.iter()) {
+            *a ^= b;
+        }
+    }
+}
+
+// Serialize to a buffer first rather than streaming through the compressor; bincode performs many
+// small writes and reads, which are very slow when each one goes through flate2
+fn compress(state: Ps1EmulatorState) -> anyhow::Result<Vec<u8>> {
+    let serialized = bincode::encode_to_vec(state, bincode_config!())?;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(&serialized)?;
+    Ok(encoder.finish()?)
+}
+
+fn decompress(bytes: &[u8]) -> anyhow::Result<Ps1EmulatorState> {
+    let mut serialized = Vec::new();
+    DeflateDecoder::new(bytes).read_to_end(&mut serialized)?;
+
+    let (state, _) = bincode::decode_from_slice(&serialized, bincode_config!())?;
+    Ok(state)
+}
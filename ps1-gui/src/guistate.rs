@@ -11,8 +11,9 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::{iter, thread};
 use winit::dpi::LogicalSize;
-use winit::event::{Event, WindowEvent};
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, EventLoop, EventLoopProxy};
+use winit::keyboard::PhysicalKey;
 use winit::window::{Window, WindowAttributes};
 
 pub struct GuiState {
@@ -149,12 +150,31 @@ impl GuiState {
     ) {
         if let Event::UserEvent(user_event) = event {
             self.app.handle_event(user_event);
+            if matches!(user_event, UserEvent::GamepadInputPressed(_)) {
+                self.egui_event_repaint = true;
+            }
         }
 
         match event {
             Event::WindowEvent { event: win_event, window_id }
                 if *window_id == self.window.id() =>
             {
+                if let WindowEvent::KeyboardInput {
+                    event:
+                        KeyEvent {
+                            physical_key: PhysicalKey::Code(keycode),
+                            state: ElementState::Pressed,
+                            ..
+                        },
+                    ..
+                } = win_event
+                {
+                    if self.app.capture_binding_key(*keycode) {
+                        self.egui_event_repaint = true;
+                        return;
+                    }
+                }
+
                 let egui_response = self.egui_state.on_window_event(&self.window, win_event);
                 self.egui_event_repaint |= egui_response.repaint;
 
@@ -206,6 +226,11 @@ impl GuiState {
 
                 async_open_file_dialog(*file_type, initial_dir.as_ref(), proxy);
             }
+            Event::UserEvent(UserEvent::SaveFile { file_type, file_name }) => {
+                self.file_dialog_open = true;
+
+                async_save_file_dialog(*file_type, file_name.clone(), proxy);
+            }
             Event::UserEvent(UserEvent::FileOpened(..)) => {
                 self.file_dialog_open = false;
             }
@@ -299,8 +324,20 @@ fn async_open_file_dialog(
     proxy: &EventLoopProxy<UserEvent>,
 ) {
     let (name, extensions): (_, &[_]) = match file_type {
-        OpenFileType::Open => ("PS1", &["cue", "chd", "exe"]),
+        // File dialog filters can be case-sensitive, so list upper-case extensions as well
+        OpenFileType::Open => (
+            "PS1",
+            &[
+                "cue", "CUE", "chd", "CHD", "iso", "ISO", "bin", "BIN", "ecm", "ECM", "pbp", "PBP",
+                "m3u", "M3U", "exe", "EXE",
+            ],
+        ),
         OpenFileType::BiosPath => ("BIOS", &["bin", "BIN"]),
+        // Raw saves have no extension
+        OpenFileType::ImportSave | OpenFileType::ExportSave => ("Save", &["*", "mcs", "psx"]),
+        OpenFileType::ImportCard | OpenFileType::ExportCard => {
+            ("Memory card", &["mcd", "mcr", "mc", "gme", "vgs", "mem", "vmp"])
+        }
         OpenFileType::SearchDir => {
             let proxy = proxy.clone();
             thread::spawn(move || {
@@ -324,3 +361,15 @@ fn async_open_file_dialog(
         proxy.send_event(UserEvent::FileOpened(file_type, path)).unwrap();
     });
 }
+
+fn async_save_file_dialog(
+    file_type: OpenFileType,
+    file_name: String,
+    proxy: &EventLoopProxy<UserEvent>,
+) {
+    let proxy = proxy.clone();
+    thread::spawn(move || {
+        let path = FileDialog::new().set_file_name(file_name).save_file();
+        proxy.send_event(UserEvent::FileOpened(file_type, path)).unwrap();
+    });
+}
@@ -1,28 +1,75 @@
+use crate::config::InputPort;
+use crate::config::bindings::InputSource;
+use crate::emustate::Hotkey;
 use crate::emuthread::{Ps1AnalogInput, Ps1Button};
 use std::path::PathBuf;
 
+macro_rules! bincode_config {
+    () => {
+        bincode::config::standard()
+            .with_little_endian()
+            .with_fixed_int_encoding()
+            .with_limit::<1_000_000_000>()
+    };
+}
+
 pub mod app;
 pub mod config;
 pub mod emustate;
 pub mod emuthread;
 pub mod guistate;
+pub mod memcards;
+pub mod savestate;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpenFileType {
     Open,
     BiosPath,
     SearchDir,
+    ImportSave,
+    ExportSave,
+    ImportCard,
+    ExportCard,
 }
 
 #[derive(Debug)]
 pub enum UserEvent {
-    OpenFile { file_type: OpenFileType, initial_dir: Option<PathBuf> },
+    OpenFile {
+        file_type: OpenFileType,
+        initial_dir: Option<PathBuf>,
+    },
+    SaveFile {
+        file_type: OpenFileType,
+        file_name: String,
+    },
     FileOpened(OpenFileType, Option<PathBuf>),
     RunBios,
     AppConfigChanged,
     Close,
-    ControllerButton { button: Ps1Button, pressed: bool },
-    ControllerAnalog { input: Ps1AnalogInput, value: i16 },
+    ControllerButton {
+        port: InputPort,
+        button: Ps1Button,
+        pressed: bool,
+    },
+    ControllerAnalog {
+        port: InputPort,
+        input: Ps1AnalogInput,
+        value: i16,
+    },
+    Hotkey {
+        hotkey: Hotkey,
+        pressed: bool,
+    },
+    /// Sent whenever any gamepad button is pressed or axis is pushed halfway, regardless of which
+    /// port the gamepad is assigned to
+    GamepadInputPressed(InputSource),
+    SaveState {
+        slot: usize,
+    },
+    LoadState {
+        slot: usize,
+    },
+    NextDisc,
 }
 
 // Enum with no variants cannot be instantiated
@@ -0,0 +1,410 @@
+//! Memory card manager window, for moving individual saves between `.mcd` card images
+
+use crate::emuthread::MEMORY_CARDS_DIRECTORY;
+use crate::{OpenFileType, UserEvent};
+use egui::{
+    Button, Color32, ColorImage, ComboBox, Context, Grid, ScrollArea, TextureHandle,
+    TextureOptions, Ui, Vec2, Window,
+};
+use ps1_core::memcard::MemoryCardData;
+use ps1_core::memcard::filesystem::{self, MemoryCardFsError, RawSave, Region, SaveEntry};
+use ps1_core::memcard::formats::{self, CardFormat, SaveFormat};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use winit::event_loop::EventLoopProxy;
+
+const ICON_SIZE: Vec2 = Vec2::new(32.0, 32.0);
+
+struct LoadedCard {
+    path: PathBuf,
+    data: Box<MemoryCardData>,
+    saves: Vec<SaveEntry>,
+    icons: Vec<TextureHandle>,
+}
+
+impl LoadedCard {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let (_, data) = formats::read_card(&fs::read(path)?)?;
+
+        let mut card = Self { path: path.into(), data, saves: Vec::new(), icons: Vec::new() };
+        card.refresh()?;
+        Ok(card)
+    }
+
+    fn refresh(&mut self) -> Result<(), MemoryCardFsError> {
+        self.saves = filesystem::list_saves(&self.data)?;
+        self.icons.clear();
+        Ok(())
+    }
+
+    // Icon textures are created lazily because they can only be created during rendering
+    fn load_icons(&mut self, ctx: &Context) {
+        if self.icons.len() == self.saves.len() {
+            return;
+        }
+
+        self.icons = self
+            .saves
+            .iter()
+            .map(|save| {
+                let image = ColorImage::from_rgba_unmultiplied(
+                    [filesystem::ICON_WIDTH, filesystem::ICON_HEIGHT],
+                    &save.icon.frames[0][..],
+                );
+                let name = format!("{}#{}", self.path.display(), save.first_block);
+                ctx.load_texture(name, image, TextureOptions::NEAREST)
+            })
+            .collect();
+    }
+
+    fn write(&mut self) -> anyhow::Result<()> {
+        fs::write(&self.path, self.data.as_slice())?;
+        self.refresh()?;
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct CardPane {
+    card: Option<LoadedCard>,
+    selected: Option<usize>,
+}
+
+impl CardPane {
+    fn selected_save(&self) -> Option<usize> {
+        self.card.as_ref().and(self.selected)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingExport {
+    pane: usize,
+    first_block: usize,
+}
+
+#[derive(Default)]
+pub struct MemoryCardManager {
+    card_paths: Vec<PathBuf>,
+    panes: [CardPane; 2],
+    pending_import: Option<usize>,
+    pending_export: Option<PendingExport>,
+    pending_card_export: Option<usize>,
+    status: Option<Result<String, String>>,
+}
+
+impl MemoryCardManager {
+    pub fn refresh_card_list(&mut self) {
+        self.card_paths = fs::read_dir(MEMORY_CARDS_DIRECTORY)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().and_then(OsStr::to_str) == Some("mcd"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.card_paths.sort();
+    }
+
+    pub fn handle_file_opened(&mut self, file_type: OpenFileType, path: &Path) {
+        let result = match file_type {
+            OpenFileType::ImportSave => match self.pending_import.take() {
+                Some(pane) => self.import_save(pane, path),
+                None => return,
+            },
+            OpenFileType::ExportSave => match self.pending_export.take() {
+                Some(export) => self.export_save(export, path),
+                None => return,
+            },
+            OpenFileType::ImportCard => self.import_card(path),
+            OpenFileType::ExportCard => match self.pending_card_export.take() {
+                Some(pane) => self.export_card(pane, path),
+                None => return,
+            },
+            _ => return,
+        };
+        self.set_status(result);
+    }
+
+    #[allow(clippy::missing_panics_doc)]
+    pub fn render(&mut self, ctx: &Context, open: &mut bool, proxy: &EventLoopProxy<UserEvent>) {
+        Window::new("Memory Card Manager").open(open).resizable(false).show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Refresh").clicked() {
+                    self.refresh_card_list();
+                }
+
+                if ui
+                    .button("Import Card...")
+                    .on_hover_text("Convert a card from another emulator into a new .mcd card")
+                    .clicked()
+                {
+                    proxy
+                        .send_event(UserEvent::OpenFile {
+                            file_type: OpenFileType::ImportCard,
+                            initial_dir: None,
+                        })
+                        .unwrap();
+                }
+
+                ui.label("Saves in cards used by a running game may be overwritten by the game");
+            });
+
+            ui.columns(2, |columns| {
+                for (i, column) in columns.iter_mut().enumerate() {
+                    self.render_pane(column, i, proxy);
+                }
+            });
+
+            match &self.status {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(message)) => {
+                    ui.colored_label(Color32::RED, message);
+                }
+                None => {}
+            }
+        });
+    }
+
+    fn render_pane(&mut self, ui: &mut Ui, i: usize, proxy: &EventLoopProxy<UserEvent>) {
+        let selected_text = self.panes[i]
+            .card
+            .as_ref()
+            .and_then(|card| card.path.file_name())
+            .map_or("<Select card>".into(), |name| name.to_string_lossy().into_owned());
+
+        let mut load_path = None;
+        ComboBox::from_id_salt(("memory_card_pane", i))
+            .width(250.0)
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                for path in &self.card_paths {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    if ui.selectable_label(false, name).clicked() {
+                        load_path = Some(path.clone());
+                    }
+                }
+            });
+
+        if let Some(path) = load_path {
+            self.panes[i].selected = None;
+            match LoadedCard::load(&path) {
+                Ok(card) => self.panes[i].card = Some(card),
+                Err(err) => {
+                    self.panes[i].card = None;
+                    self.status = Some(Err(format!("Error loading '{}': {err}", path.display())));
+                }
+            }
+        }
+
+        let pane = &mut self.panes[i];
+        let Some(card) = &mut pane.card else { return };
+        card.load_icons(ui.ctx());
+
+        ui.label(format!("{} free blocks", filesystem::free_blocks(&card.data)));
+
+        ScrollArea::vertical().id_salt(("memory_card_saves", i)).max_height(400.0).show(ui, |ui| {
+            Grid::new(("memory_card_save_grid", i)).striped(true).show(ui, |ui| {
+                for (save, icon) in card.saves.iter().zip(&card.icons) {
+                    ui.image((icon.id(), ICON_SIZE));
+
+                    let text = format!(
+                        "{}\n{} ({}) - {} block{}",
+                        save.title,
+                        save.product_code,
+                        region_str(save.region),
+                        save.blocks.len(),
+                        if save.blocks.len() == 1 { "" } else { "s" }
+                    );
+                    let is_selected = pane.selected == Some(save.first_block);
+                    if ui.selectable_label(is_selected, text).clicked() {
+                        pane.selected = Some(save.first_block);
+                    }
+
+                    ui.end_row();
+                }
+            });
+        });
+
+        let selected = pane.selected_save();
+        let other = 1 - i;
+        let arrow = if i == 0 { "Copy >" } else { "< Copy" };
+
+        ui.horizontal(|ui| {
+            let can_copy = selected.is_some() && self.panes[other].card.is_some();
+            if ui.add_enabled(can_copy, Button::new(arrow)).clicked() {
+                let result = self.copy_save(i, selected.unwrap());
+                self.set_status(result);
+            }
+
+            if ui.add_enabled(selected.is_some(), Button::new("Delete")).clicked() {
+                let result = self.delete_save(i, selected.unwrap());
+                self.set_status(result);
+            }
+
+            if ui.add_enabled(selected.is_some(), Button::new("Export...")).clicked() {
+                let first_block = selected.unwrap();
+                self.pending_export = Some(PendingExport { pane: i, first_block });
+
+                // Default to the directory entry name so that the save can be imported again
+                let file_name = self.panes[i]
+                    .card
+                    .as_ref()
+                    .and_then(|card| card.saves.iter().find(|save| save.first_block == first_block))
+                    .map(|save| save.file_name.clone())
+                    .unwrap_or_default();
+                proxy
+                    .send_event(UserEvent::SaveFile {
+                        file_type: OpenFileType::ExportSave,
+                        file_name,
+                    })
+                    .unwrap();
+            }
+
+            if ui.button("Import...").clicked() {
+                self.pending_import = Some(i);
+                proxy
+                    .send_event(UserEvent::OpenFile {
+                        file_type: OpenFileType::ImportSave,
+                        initial_dir: None,
+                    })
+                    .unwrap();
+            }
+
+            if ui
+                .button("Export Card...")
+                .on_hover_text("Save a copy of this card in another emulator's format")
+                .clicked()
+            {
+                self.pending_card_export = Some(i);
+                let file_name = self.panes[i]
+                    .card
+                    .as_ref()
+                    .and_then(|card| card.path.file_name())
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                proxy
+                    .send_event(UserEvent::SaveFile {
+                        file_type: OpenFileType::ExportCard,
+                        file_name,
+                    })
+                    .unwrap();
+            }
+        });
+    }
+
+    fn copy_save(&mut self, from: usize, first_block: usize) -> anyhow::Result<String> {
+        let [first, second] = &mut self.panes;
+        let (source, dest) = if from == 0 { (first, second) } else { (second, first) };
+        let (Some(source), Some(dest)) = (&source.card, &mut dest.card) else {
+            return Err(anyhow::anyhow!("Select a card on both sides to copy saves"));
+        };
+
+        filesystem::copy_save(&source.data, first_block, &mut dest.data)?;
+        dest.write()?;
+
+        Ok(format!("Copied save to '{}'", dest.path.display()))
+    }
+
+    fn delete_save(&mut self, pane: usize, first_block: usize) -> anyhow::Result<String> {
+        let pane = &mut self.panes[pane];
+        let Some(card) = &mut pane.card else { return Err(anyhow::anyhow!("No card selected")) };
+
+        filesystem::delete_save(&mut card.data, first_block)?;
+        card.write()?;
+        pane.selected = None;
+
+        Ok(format!("Deleted save from '{}'", card.path.display()))
+    }
+
+    fn import_save(&mut self, pane: usize, path: &Path) -> anyhow::Result<String> {
+        let Some(card) = &mut self.panes[pane].card else {
+            return Err(anyhow::anyhow!("No card selected"));
+        };
+
+        let bytes = fs::read(path)?;
+        let save =
+            match path.extension().and_then(OsStr::to_str).and_then(SaveFormat::from_extension) {
+                Some(format) => formats::read_save(&bytes, format)?,
+                None => {
+                    // Raw saves are named after their directory entry
+                    let file_name =
+                        path.file_name().and_then(OsStr::to_str).unwrap_or_default().to_string();
+                    RawSave { file_name, data: bytes }
+                }
+            };
+
+        filesystem::import_save(&mut card.data, &save)?;
+        card.write()?;
+
+        Ok(format!("Imported '{}' into '{}'", path.display(), card.path.display()))
+    }
+
+    fn export_save(&self, export: PendingExport, path: &Path) -> anyhow::Result<String> {
+        let Some(card) = &self.panes[export.pane].card else {
+            return Err(anyhow::anyhow!("No card selected"));
+        };
+
+        let save = filesystem::export_save(&card.data, export.first_block)?;
+        match path.extension().and_then(OsStr::to_str).and_then(SaveFormat::from_extension) {
+            Some(format) => fs::write(path, formats::write_save(&save, format)?)?,
+            None => fs::write(path, &save.data)?,
+        }
+
+        Ok(format!("Exported '{}' to '{}'", save.file_name, path.display()))
+    }
+
+    fn import_card(&mut self, path: &Path) -> anyhow::Result<String> {
+        let (format, data) = formats::read_card(&fs::read(path)?)?;
+
+        let file_name = path.with_extension("mcd").file_name().map(ToOwned::to_owned);
+        let Some(file_name) = file_name else {
+            return Err(anyhow::anyhow!("Invalid card path '{}'", path.display()));
+        };
+        let card_path = Path::new(MEMORY_CARDS_DIRECTORY).join(file_name);
+        if card_path.exists() {
+            return Err(anyhow::anyhow!("'{}' already exists", card_path.display()));
+        }
+
+        fs::create_dir_all(MEMORY_CARDS_DIRECTORY)?;
+        fs::write(&card_path, data.as_slice())?;
+        self.refresh_card_list();
+
+        Ok(format!("Imported {format:?} card '{}' as '{}'", path.display(), card_path.display()))
+    }
+
+    fn export_card(&self, pane: usize, path: &Path) -> anyhow::Result<String> {
+        let Some(card) = &self.panes[pane].card else {
+            return Err(anyhow::anyhow!("No card selected"));
+        };
+
+        let extension = path.extension().and_then(OsStr::to_str).unwrap_or_default();
+        let Some(format) = CardFormat::from_extension(extension) else {
+            return Err(anyhow::anyhow!(
+                "Unknown card format '{extension}'; use .mcd, .mcr, .gme, .vgs, .mem, or .vmp"
+            ));
+        };
+
+        fs::write(path, formats::write_card(&card.data, format))?;
+
+        Ok(format!("Exported '{}' to '{}'", card.path.display(), path.display()))
+    }
+
+    fn set_status(&mut self, result: anyhow::Result<String>) {
+        self.status = Some(result.map_err(|err| {
+            log::error!("Memory card manager error: {err}");
+            err.to_string()
+        }));
+    }
+}
+
+fn region_str(region: Region) -> String {
+    match region {
+        Region::Japan => "Japan".into(),
+        Region::America => "America".into(),
+        Region::Europe => "Europe".into(),
+        Region::Unknown(prefix) => String::from_utf8_lossy(&prefix).into_owned(),
+    }
+}
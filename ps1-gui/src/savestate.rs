@@ -0,0 +1,207 @@
+//! Numbered save state slots
+//!
+//! Each game has [`SAVE_STATE_SLOTS`] slots, stored as `states/<game>_<slot>.sst`. A slot file
+//! contains the 4-byte magic `PS1S`, followed by a bincode-encoded [`SaveStateHeader`], followed
+//! by the emulator state in the container format from [`ps1_core::savestate`]. The header can be
+//! read without decoding the state.
+
+use bincode::{Decode, Encode};
+use ps1_core::api::{ContentIdentity, Ps1EmulatorState, Screenshot};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+pub const SAVE_STATE_SLOTS: usize = 10;
+
+pub const SAVE_STATES_DIRECTORY: &str = "states";
+
+const MAGIC: [u8; 4] = *b"PS1S";
+
+const THUMBNAIL_MAX_WIDTH: u32 = 160;
+const THUMBNAIL_MAX_HEIGHT: u32 = 120;
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Thumbnail {
+    /// Downscale a screenshot by averaging blocks of pixels until it fits in 160x120.
+    #[must_use]
+    pub fn from_screenshot(screenshot: &Screenshot) -> Self {
+        let factor = screenshot
+            .width
+            .div_ceil(THUMBNAIL_MAX_WIDTH)
+            .max(screenshot.height.div_ceil(THUMBNAIL_MAX_HEIGHT))
+            .max(1);
+        let width = screenshot.width / factor;
+        let height = screenshot.height / factor;
+
+        let mut rgba = Vec::with_capacity((4 * width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sums = [0_u32; 4];
+                for dy in 0..factor {
+                    let row = (y * factor + dy) * screenshot.width;
+                    for dx in 0..factor {
+                        let i = (4 * (row + x * factor + dx)) as usize;
+                        for (sum, &component) in sums.iter_mut().zip(&screenshot.rgba[i..i + 4]) {
+                            *sum += u32::from(component);
+                        }
+                    }
+                }
+
+                rgba.extend(sums.map(|sum| (sum / (factor * factor)) as u8));
+            }
+        }
+
+        Self { width, height, rgba }
+    }
+}
+
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct SaveStateHeader {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub emulator_version: String,
+    /// Disc serial number if available (e.g. `SLUS-00594`), otherwise the file name
+    pub game_id: String,
+    pub thumbnail: Thumbnail,
+}
+
+impl SaveStateHeader {
+    #[must_use]
+    pub fn new(game_id: String, screenshot: &Screenshot) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+
+        Self {
+            timestamp,
+            emulator_version: env!("CARGO_PKG_VERSION").into(),
+            game_id,
+            thumbnail: Thumbnail::from_screenshot(screenshot),
+        }
+    }
+
+    #[must_use]
+    pub fn system_time(&self) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(self.timestamp)
+    }
+}
+
+/// Returns the path of the given slot for the given file, or for the BIOS if no file is given.
+/// Slots are 0-based but numbered from 1 in file names.
+///
+/// # Errors
+///
+/// Returns an error if a file name cannot be determined from the path.
+pub fn slot_path(file_path: Option<&Path>, slot: usize) -> anyhow::Result<PathBuf> {
+    let file_name = format!("{}_{}.sst", file_name_no_ext(file_path)?, slot + 1);
+    Ok(PathBuf::from(SAVE_STATES_DIRECTORY).join(file_name))
+}
+
+/// Returns the path of the single save state file that versions before numbered slots used,
+/// `states/<game>.sst`. These files have no header and no container, so they cannot be loaded.
+///
+/// # Errors
+///
+/// Returns an error if a file name cannot be determined from the path.
+pub fn legacy_path(file_path: Option<&Path>) -> anyhow::Result<PathBuf> {
+    let file_name = format!("{}.sst", file_name_no_ext(file_path)?);
+    Ok(PathBuf::from(SAVE_STATES_DIRECTORY).join(file_name))
+}
+
+fn file_name_no_ext(file_path: Option<&Path>) -> anyhow::Result<String> {
+    let path_no_ext = file_path.unwrap_or(Path::new("bios")).with_extension("");
+    let file_name_no_ext = path_no_ext.file_name().and_then(OsStr::to_str).ok_or_else(|| {
+        anyhow::anyhow!("Unable to determine file name for path: {}", path_no_ext.display())
+    })?;
+
+    Ok(file_name_no_ext.into())
+}
+
+/// Write a slot file containing the given header and state.
+///
+/// # Errors
+///
+/// Propagates any I/O or encoding errors.
+pub fn write(
+    path: &Path,
+    header: &SaveStateHeader,
+    identity: ContentIdentity,
+    state: &Ps1EmulatorState,
+) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(&MAGIC)?;
+    bincode::encode_into_std_write(header, &mut writer, bincode_config!())?;
+    ps1_core::savestate::write(&mut writer, identity, state)?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Read only the header from a slot file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or is not a valid slot file.
+pub fn read_header(path: &Path) -> anyhow::Result<SaveStateHeader> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_header_from(&mut reader)
+}
+
+/// Read the state from a slot file.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read, is not a valid slot file, or contains a state
+/// that was created with a different BIOS or disc.
+pub fn read(path: &Path, identity: ContentIdentity) -> anyhow::Result<Ps1EmulatorState> {
+    let mut reader = BufReader::new(File::open(path)?);
+    read_header_from(&mut reader)?;
+
+    Ok(ps1_core::savestate::read(&mut reader, identity)?)
+}
+
+fn read_header_from<R: Read>(reader: &mut R) -> anyhow::Result<SaveStateHeader> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a save state slot file").into());
+    }
+
+    Ok(bincode::decode_from_std_read(reader, bincode_config!())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thumbnail_averages_blocks() {
+        // 320x240 image where each 2x2 block contains one white pixel
+        let mut rgba = vec![0; 4 * 320 * 240];
+        for y in (0..240).step_by(2) {
+            for x in (0..320).step_by(2) {
+                let i = 4 * (y * 320 + x);
+                rgba[i..i + 4].copy_from_slice(&[255, 255, 255, 255]);
+            }
+        }
+        let screenshot = Screenshot { width: 320, height: 240, rgba };
+
+        let thumbnail = Thumbnail::from_screenshot(&screenshot);
+        assert_eq!((thumbnail.width, thumbnail.height), (160, 120));
+        assert_eq!(thumbnail.rgba.len(), 4 * 160 * 120);
+        assert!(thumbnail.rgba.chunks(4).all(|pixel| pixel == [63, 63, 63, 63]));
+    }
+}
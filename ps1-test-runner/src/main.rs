@@ -0,0 +1,320 @@
+//! Headless test runner
+//!
+//! Boots a BIOS plus an optional disc image or EXE without a window or a graphics adapter, runs a
+//! fixed number of frames with scripted inputs, and then writes out the final frame as a PNG and a
+//! hash of every audio sample that was output. If a reference directory is given, the results are
+//! compared against the reference and the process exits with a nonzero status on any difference.
+
+mod output;
+mod script;
+
+use crate::output::{
+    AudioHash, AudioHasher, CapturedFrame, DiscardSaveWriter, FrameCapture, IgnoreRumble,
+};
+use crate::script::InputScript;
+use anyhow::{Context, anyhow};
+use cdrom::reader::{CdRom, CdRomFileFormat};
+use clap::{Parser, ValueEnum};
+use env_logger::Env;
+use ps1_core::RasterizerType;
+use ps1_core::api::{
+    DisplayConfig, Ps1Emulator, Ps1EmulatorBuilder, Ps1EmulatorConfig, Region, TickEffect,
+};
+use ps1_core::input::{ControllerType, DualShockConfig, Ps1Inputs};
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+const FRAME_FILE_NAME: &str = "frame.png";
+const AUDIO_HASH_FILE_NAME: &str = "audio.txt";
+
+// Enum with no variants cannot be instantiated
+#[derive(Debug, Clone, Copy)]
+pub enum Never {}
+
+impl Display for Never {
+    fn fmt(&self, _f: &mut Formatter<'_>) -> std::fmt::Result {
+        match *self {}
+    }
+}
+
+impl Error for Never {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Rasterizer {
+    Naive,
+    Simd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Controller {
+    None,
+    Digital,
+    DualShock,
+}
+
+impl Controller {
+    fn to_controller_type(self) -> ControllerType {
+        match self {
+            Self::None => ControllerType::None,
+            Self::Digital => ControllerType::Digital,
+            Self::DualShock => ControllerType::DualShock,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConsoleRegion {
+    Auto,
+    NtscU,
+    NtscJ,
+    Pal,
+}
+
+impl ConsoleRegion {
+    fn to_forced_region(self) -> Option<Region> {
+        match self {
+            Self::Auto => None,
+            Self::NtscU => Some(Region::NtscU),
+            Self::NtscJ => Some(Region::NtscJ),
+            Self::Pal => Some(Region::Pal),
+        }
+    }
+}
+
+/// Exits with status 1 if the output does not match the reference, or status 2 on any other error
+#[derive(Debug, Parser)]
+struct Args {
+    /// BIOS ROM path
+    #[arg(long, short = 'b')]
+    bios: PathBuf,
+
+    /// Disc image (CUE/CHD/ISO/BIN/ECM/PBP) or EXE to run. Will run the BIOS shell if not set
+    #[arg(long, short = 'f')]
+    file: Option<PathBuf>,
+
+    /// Number of frames to run
+    #[arg(long, short = 'n')]
+    frames: u64,
+
+    /// Input script path; see the test runner's script module docs for the format
+    #[arg(long, short = 'i')]
+    inputs: Option<PathBuf>,
+
+    /// Directory to write the final frame and the audio hash to
+    #[arg(long, short = 'o', default_value = ".")]
+    output_dir: PathBuf,
+
+    /// Directory containing reference output to compare against
+    #[arg(long, short = 'r')]
+    reference_dir: Option<PathBuf>,
+
+    /// Software rasterizer to use. The SIMD rasterizer requires AVX2
+    #[arg(long, value_enum, default_value_t = Rasterizer::Naive)]
+    rasterizer: Rasterizer,
+
+    /// Controller type in port 1
+    #[arg(long, value_enum, default_value_t = Controller::Digital)]
+    p1: Controller,
+
+    /// Controller type in port 2
+    #[arg(long, value_enum, default_value_t = Controller::None)]
+    p2: Controller,
+
+    /// Memory card image to load into port 1, in any supported format. Writes are never persisted
+    #[arg(long)]
+    memory_card_1: Option<PathBuf>,
+
+    /// Memory card image to load into port 2, in any supported format. Writes are never persisted
+    #[arg(long)]
+    memory_card_2: Option<PathBuf>,
+
+    /// Start `DualShock` controllers in analog mode
+    #[arg(long)]
+    dualshock_start_in_analog: bool,
+
+    /// Console region; auto detects it from the disc, falling back to the BIOS
+    #[arg(long, value_enum, default_value_t = ConsoleRegion::Auto)]
+    region: ConsoleRegion,
+}
+
+fn main() -> ExitCode {
+    env_logger::Builder::from_env(Env::default().default_filter_or("warn")).init();
+
+    let args = Args::parse();
+
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            log::error!("{err:#}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns whether the output matched the reference (always true if there is no reference).
+fn run(args: &Args) -> anyhow::Result<bool> {
+    let script = match &args.inputs {
+        Some(path) => {
+            let script = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read input script '{}'", path.display()))?;
+            InputScript::parse(&script)?
+        }
+        None => InputScript::default(),
+    };
+
+    let mut emulator = create_emulator(args)?;
+
+    let mut inputs = Ps1Inputs::default();
+    inputs.p1.controller_type = args.p1.to_controller_type();
+    inputs.p2.controller_type = args.p2.to_controller_type();
+
+    let mut frame_capture = FrameCapture::default();
+    let mut audio_hasher = AudioHasher::new();
+    for frame in 0..args.frames {
+        script.apply(frame, &mut inputs);
+
+        while emulator.tick(
+            inputs,
+            &mut frame_capture,
+            &mut audio_hasher,
+            &mut DiscardSaveWriter,
+            &mut IgnoreRumble,
+        )? != TickEffect::FrameRendered
+        {}
+    }
+
+    let frame = frame_capture.last_frame().ok_or_else(|| anyhow!("No frames were rendered"))?;
+    let audio_hash = audio_hasher.hash();
+
+    fs::create_dir_all(&args.output_dir)?;
+    frame.write_png(&args.output_dir.join(FRAME_FILE_NAME))?;
+    fs::write(args.output_dir.join(AUDIO_HASH_FILE_NAME), format!("{audio_hash}\n"))?;
+
+    println!("Ran {} frames; audio {audio_hash}", args.frames);
+
+    match &args.reference_dir {
+        Some(reference_dir) => compare_to_reference(reference_dir, frame, audio_hash),
+        None => Ok(true),
+    }
+}
+
+fn create_emulator(args: &Args) -> anyhow::Result<Ps1Emulator> {
+    let bios = fs::read(&args.bios)
+        .with_context(|| format!("Failed to read BIOS from '{}'", args.bios.display()))?;
+
+    let config = Ps1EmulatorConfig {
+        display: DisplayConfig {
+            rasterizer_type: match args.rasterizer {
+                Rasterizer::Naive => RasterizerType::NaiveSoftware,
+                Rasterizer::Simd => RasterizerType::SimdSoftware,
+            },
+            ..DisplayConfig::default()
+        },
+        dualshock: DualShockConfig {
+            start_in_analog: args.dualshock_start_in_analog,
+            ..DualShockConfig::default()
+        },
+        forced_region: args.region.to_forced_region(),
+        ..Ps1EmulatorConfig::default()
+    };
+
+    // Fixed RAM seed so that runs are repeatable
+    let mut builder = Ps1EmulatorBuilder::new_software(bios).with_config(config).with_ram_seed(0);
+
+    if let Some(path) = &args.memory_card_1 {
+        let card_data = fs::read(path)
+            .with_context(|| format!("Failed to read memory card from '{}'", path.display()))?;
+        builder = builder.with_memory_card_1(card_data);
+    }
+
+    if let Some(path) = &args.memory_card_2 {
+        let card_data = fs::read(path)
+            .with_context(|| format!("Failed to read memory card from '{}'", path.display()))?;
+        builder = builder.with_memory_card_2(card_data);
+    }
+
+    let Some(file_path) = &args.file else {
+        return Ok(builder.build()?);
+    };
+
+    let extension = file_path.extension().and_then(OsStr::to_str).map(str::to_ascii_lowercase);
+    let emulator = match extension.as_deref() {
+        Some(extension @ ("cue" | "chd" | "iso" | "bin" | "ecm" | "pbp")) => {
+            let format = match extension {
+                "cue" => CdRomFileFormat::CueBin,
+                "chd" => CdRomFileFormat::Chd,
+                "iso" => CdRomFileFormat::Iso,
+                "bin" => CdRomFileFormat::Bin,
+                "ecm" => CdRomFileFormat::Ecm,
+                // Multi-disc PBP files always start from the first disc
+                "pbp" => CdRomFileFormat::Pbp { disc_index: 0 },
+                _ => unreachable!("nested match expressions"),
+            };
+
+            let disc = CdRom::open(file_path, format)?;
+            builder.with_disc(disc).build()?
+        }
+        Some("exe") => {
+            let exe = fs::read(file_path)
+                .with_context(|| format!("Failed to read EXE from path {}", file_path.display()))?;
+
+            let mut emulator = builder.build()?;
+            emulator.run_until_exe_sideloaded(&exe)?;
+
+            emulator
+        }
+        Some(extension) => return Err(anyhow!("Unsupported file extension {extension}")),
+        None => {
+            return Err(anyhow!("Unable to determine file extension of '{}'", file_path.display()));
+        }
+    };
+
+    Ok(emulator)
+}
+
+fn compare_to_reference(
+    reference_dir: &Path,
+    frame: &CapturedFrame,
+    audio_hash: AudioHash,
+) -> anyhow::Result<bool> {
+    let mut matches = true;
+
+    let reference_frame = CapturedFrame::read_png(&reference_dir.join(FRAME_FILE_NAME))
+        .context("Failed to read reference frame")?;
+    match frame.count_differing_pixels(&reference_frame) {
+        Some(0) => {}
+        Some(differing_pixels) => {
+            log::error!("Frame differs from reference in {differing_pixels} pixels");
+            matches = false;
+        }
+        None => {
+            log::error!(
+                "Frame size {}x{} does not match reference size {}x{}",
+                frame.width,
+                frame.height,
+                reference_frame.width,
+                reference_frame.height
+            );
+            matches = false;
+        }
+    }
+
+    let audio_hash_path = reference_dir.join(AUDIO_HASH_FILE_NAME);
+    let reference_audio_hash = fs::read_to_string(&audio_hash_path).with_context(|| {
+        format!("Failed to read reference audio hash from '{}'", audio_hash_path.display())
+    })?;
+    if reference_audio_hash.trim() != audio_hash.to_string() {
+        log::error!(
+            "Audio hash '{audio_hash}' does not match reference '{}'",
+            reference_audio_hash.trim()
+        );
+        matches = false;
+    }
+
+    Ok(matches)
+}
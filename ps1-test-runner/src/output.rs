@@ -0,0 +1,202 @@
+//! Emulator output sinks that capture the final frame and hash the audio stream
+
+use crate::Never;
+use crc::{Crc, Digest};
+use ps1_core::api::{AudioOutput, CpuFrame, Renderer, RumbleOutput, SaveWriter};
+use ps1_core::input::Ps1Rumble;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+const AUDIO_CRC: Crc<u64> = Crc::<u64>::new(&crc::CRC_64_XZ);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedFrame {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl CapturedFrame {
+    /// # Errors
+    ///
+    /// Propagates any I/O or PNG encoding errors.
+    pub fn write_png(&self, path: &Path) -> anyhow::Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// Propagates any I/O or PNG decoding errors, or returns an error if the PNG is not 8-bit RGBA.
+    pub fn read_png(path: &Path) -> anyhow::Result<Self> {
+        let decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+        let mut reader = decoder.read_info()?;
+
+        let mut rgba = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut rgba)?;
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            anyhow::bail!(
+                "Expected 8-bit RGBA PNG at '{}', was {:?} {:?}",
+                path.display(),
+                info.bit_depth,
+                info.color_type
+            );
+        }
+        rgba.truncate(info.buffer_size());
+
+        Ok(Self { width: info.width, height: info.height, rgba })
+    }
+
+    /// Returns the number of pixels that differ between the two frames, or None if the frames are
+    /// not the same size.
+    #[must_use]
+    pub fn count_differing_pixels(&self, other: &Self) -> Option<usize> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+
+        Some(
+            self.rgba
+                .chunks_exact(4)
+                .zip(other.rgba.chunks_exact(4))
+                .filter(|(a, b)| a != b)
+                .count(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioHash {
+    pub samples: u64,
+    pub crc64: u64,
+}
+
+impl Display for AudioHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "samples={} crc64={:016X}", self.samples, self.crc64)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FrameCapture {
+    last_frame: Option<CapturedFrame>,
+}
+
+impl FrameCapture {
+    pub fn last_frame(&self) -> Option<&CapturedFrame> {
+        self.last_frame.as_ref()
+    }
+}
+
+impl Renderer for FrameCapture {
+    type Err = Never;
+
+    fn render_frame(
+        &mut self,
+        _command_buffers: impl Iterator<Item = wgpu::CommandBuffer>,
+        _frame: &wgpu::Texture,
+        _pixel_aspect_ratio: f64,
+    ) -> Result<(), Self::Err> {
+        unreachable!("test runner always builds the emulator without a wgpu device")
+    }
+
+    fn render_cpu_frame(
+        &mut self,
+        frame: CpuFrame<'_>,
+        _pixel_aspect_ratio: f64,
+    ) -> Result<(), Self::Err> {
+        match &mut self.last_frame {
+            Some(last_frame) => {
+                last_frame.width = frame.width;
+                last_frame.height = frame.height;
+                last_frame.rgba.clear();
+                last_frame.rgba.extend_from_slice(frame.rgba);
+            }
+            None => {
+                self.last_frame = Some(CapturedFrame {
+                    width: frame.width,
+                    height: frame.height,
+                    rgba: frame.rgba.to_vec(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct AudioHasher {
+    digest: Digest<'static, u64>,
+    samples: u64,
+}
+
+impl AudioHasher {
+    pub fn new() -> Self {
+        Self { digest: AUDIO_CRC.digest(), samples: 0 }
+    }
+
+    pub fn hash(&self) -> AudioHash {
+        AudioHash { samples: self.samples, crc64: self.digest.clone().finalize() }
+    }
+}
+
+impl AudioOutput for AudioHasher {
+    type Err = Never;
+
+    fn queue_samples(&mut self, samples: &[(i16, i16)]) -> Result<(), Self::Err> {
+        for &(l, r) in samples {
+            self.digest.update(&l.to_le_bytes());
+            self.digest.update(&r.to_le_bytes());
+        }
+        self.samples += samples.len() as u64;
+
+        Ok(())
+    }
+}
+
+// Never persists memory card writes so that test runs are repeatable
+pub struct DiscardSaveWriter;
+
+impl SaveWriter for DiscardSaveWriter {
+    type Err = Never;
+
+    fn save_memory_card_1(&mut self, _card_data: &[u8]) -> Result<(), Self::Err> {
+        Ok(())
+    }
+
+    fn save_memory_card_2(&mut self, _card_data: &[u8]) -> Result<(), Self::Err> {
+        Ok(())
+    }
+
+    fn save_multitap_memory_card(
+        &mut self,
+        _port: usize,
+        _slot: usize,
+        _card_data: &[u8],
+    ) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
+
+// Test runs have no controllers to vibrate
+pub struct IgnoreRumble;
+
+impl RumbleOutput for IgnoreRumble {
+    type Err = Never;
+
+    fn set_rumble(&mut self, _rumble: Ps1Rumble) -> Result<(), Self::Err> {
+        Ok(())
+    }
+}
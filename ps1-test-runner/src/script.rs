@@ -0,0 +1,194 @@
+//! Input script parsing
+//!
+//! Input scripts are plain text files where each non-empty line has the form:
+//!
+//! ```text
+//! <frame> <port> [input]...
+//! ```
+//!
+//! Each line replaces the complete input state for the given port (`p1` or `p2`) starting on the
+//! given frame (0-based), and that state is held until a later line for the same port changes it.
+//! Inputs are either button names (`up`, `down`, `left`, `right`, `cross`, `circle`, `square`,
+//! `triangle`, `l1`, `l2`, `r1`, `r2`, `start`, `select`, `analog`) or analog stick positions in
+//! the form `lx=<value>`, `ly=<value>`, `rx=<value>`, or `ry=<value>` where value is in [0, 255].
+//! Sticks that are not listed are centered. Text following a `#` is a comment.
+//!
+//! Example that presses Start for 2 frames on frame 120 and then holds Cross:
+//!
+//! ```text
+//! 120 p1 start
+//! 122 p1
+//! 300 p1 cross
+//! ```
+
+use ps1_core::input::{AnalogJoypadState, ControllerState, DigitalJoypadState, Ps1Inputs};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("Line {line}: expected '<frame> <port> [input]...'")]
+    MissingFields { line: usize },
+    #[error("Line {line}: invalid frame number '{value}'")]
+    InvalidFrame { line: usize, value: String },
+    #[error("Line {line}: invalid port '{value}', expected 'p1' or 'p2'")]
+    InvalidPort { line: usize, value: String },
+    #[error("Line {line}: unknown input '{value}'")]
+    InvalidInput { line: usize, value: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Port {
+    P1,
+    P2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PortInputs {
+    digital: DigitalJoypadState,
+    analog: AnalogJoypadState,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InputScript {
+    // Keyed by frame number; entries for the same frame are applied in file order
+    changes: BTreeMap<u64, Vec<(Port, PortInputs)>>,
+}
+
+impl InputScript {
+    /// # Errors
+    ///
+    /// Will return an error if any line in the script is malformed.
+    pub fn parse(script: &str) -> Result<Self, ScriptError> {
+        let mut changes: BTreeMap<u64, Vec<(Port, PortInputs)>> = BTreeMap::new();
+
+        for (i, line) in script.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let (Some(frame), Some(port)) = (fields.next(), fields.next()) else {
+                return Err(ScriptError::MissingFields { line: line_number });
+            };
+
+            let frame: u64 = frame.parse().map_err(|_| ScriptError::InvalidFrame {
+                line: line_number,
+                value: frame.into(),
+            })?;
+
+            let port = match port.to_ascii_lowercase().as_str() {
+                "p1" => Port::P1,
+                "p2" => Port::P2,
+                _ => {
+                    return Err(ScriptError::InvalidPort { line: line_number, value: port.into() });
+                }
+            };
+
+            let mut inputs = PortInputs {
+                digital: DigitalJoypadState::default(),
+                analog: AnalogJoypadState::default(),
+            };
+            for input in fields {
+                if !apply_input(&mut inputs, &input.to_ascii_lowercase()) {
+                    return Err(ScriptError::InvalidInput {
+                        line: line_number,
+                        value: input.into(),
+                    });
+                }
+            }
+
+            changes.entry(frame).or_default().push((port, inputs));
+        }
+
+        Ok(Self { changes })
+    }
+
+    /// Apply any input changes that take effect on the given frame.
+    pub fn apply(&self, frame: u64, inputs: &mut Ps1Inputs) {
+        let Some(changes) = self.changes.get(&frame) else { return };
+
+        for &(port, port_inputs) in changes {
+            let controller: &mut ControllerState = match port {
+                Port::P1 => &mut inputs.p1,
+                Port::P2 => &mut inputs.p2,
+            };
+            controller.digital = port_inputs.digital;
+            controller.analog = port_inputs.analog;
+        }
+    }
+}
+
+fn apply_input(inputs: &mut PortInputs, input: &str) -> bool {
+    if let Some((axis, value)) = input.split_once('=') {
+        let Ok(value) = value.parse::<u8>() else { return false };
+        match axis {
+            "lx" => inputs.analog.left_x = value,
+            "ly" => inputs.analog.left_y = value,
+            "rx" => inputs.analog.right_x = value,
+            "ry" => inputs.analog.right_y = value,
+            _ => return false,
+        }
+        return true;
+    }
+
+    let digital = &mut inputs.digital;
+    match input {
+        "up" => digital.set_up(true),
+        "down" => digital.set_down(true),
+        "left" => digital.set_left(true),
+        "right" => digital.set_right(true),
+        "cross" => digital.set_cross(true),
+        "circle" => digital.set_circle(true),
+        "square" => digital.set_square(true),
+        "triangle" => digital.set_triangle(true),
+        "l1" => digital.set_l1(true),
+        "l2" => digital.set_l2(true),
+        "r1" => digital.set_r1(true),
+        "r2" => digital.set_r2(true),
+        "start" => digital.set_start(true),
+        "select" => digital.set_select(true),
+        "analog" => inputs.analog.analog_button = true,
+        _ => return false,
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_held_until_next_change() {
+        let script = InputScript::parse("# comment\n10 p1 start lx=0\n\n12 P1 cross # hold\n")
+            .expect("valid script");
+
+        let mut inputs = Ps1Inputs::default();
+        for frame in 0..=11 {
+            script.apply(frame, &mut inputs);
+        }
+        assert!(inputs.p1.digital.start());
+        assert_eq!(inputs.p1.analog.left_x, 0);
+
+        script.apply(12, &mut inputs);
+        assert!(!inputs.p1.digital.start());
+        assert!(inputs.p1.digital.cross());
+        assert_eq!(inputs.p1.analog.left_x, 0x80);
+        assert_eq!(inputs.p2.digital, DigitalJoypadState::default());
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        assert!(matches!(InputScript::parse("10"), Err(ScriptError::MissingFields { line: 1 })));
+        assert!(matches!(InputScript::parse("x p1"), Err(ScriptError::InvalidFrame { .. })));
+        assert!(matches!(InputScript::parse("0 p3"), Err(ScriptError::InvalidPort { .. })));
+        assert!(matches!(
+            InputScript::parse("0 p1\n1 p1 jump"),
+            Err(ScriptError::InvalidInput { line: 2, .. })
+        ));
+        assert!(matches!(InputScript::parse("0 p1 lx=256"), Err(ScriptError::InvalidInput { .. })));
+    }
+}